@@ -1,4 +1,4 @@
-use reqwest::Client;
+use reqwest::{Client, Response};
 use secp256k1::{ecdsa::Signature, PublicKey};
 use serde::{de::DeserializeOwned, Serialize};
 use url::Url;
@@ -7,6 +7,7 @@ use zksync_prover_interface::{
     api::{
         RegisterTeeAttestationRequest, RegisterTeeAttestationResponse, SubmitTeeProofRequest,
         SubmitTeeProofResponse, TeeProofGenerationDataRequest, TeeProofGenerationDataResponse,
+        IDEMPOTENCY_KEY_HEADER, TEE_PENDING_BATCHES_HEADER,
     },
     inputs::TeeVerifierInput,
     outputs::L1BatchTeeProofForL1,
@@ -24,11 +25,15 @@ pub(crate) struct TeeApiClient {
 }
 
 impl TeeApiClient {
-    pub fn new(api_base_url: Url) -> Self {
-        TeeApiClient {
-            api_base_url,
-            http_client: Client::new(),
+    pub fn new(api_base_url: Url, proxy: Option<reqwest::Proxy>) -> reqwest::Result<Self> {
+        let mut http_client = Client::builder();
+        if let Some(proxy) = proxy {
+            http_client = http_client.proxy(proxy);
         }
+        Ok(TeeApiClient {
+            api_base_url,
+            http_client: http_client.build()?,
+        })
     }
 
     async fn post<Req, Resp, S>(&self, endpoint: S, request: Req) -> Result<Resp, reqwest::Error>
@@ -36,19 +41,33 @@ impl TeeApiClient {
         Req: Serialize + std::fmt::Debug,
         Resp: DeserializeOwned,
         S: AsRef<str>,
+    {
+        self.send_post(endpoint, request, &[])
+            .await?
+            .json::<Resp>()
+            .await
+    }
+
+    /// Sends a POST request with additional headers and returns the successful response without reading its body.
+    async fn send_post<Req, S>(
+        &self,
+        endpoint: S,
+        request: Req,
+        headers: &[(&str, &str)],
+    ) -> Result<Response, reqwest::Error>
+    where
+        Req: Serialize + std::fmt::Debug,
+        S: AsRef<str>,
     {
         let url = self.api_base_url.join(endpoint.as_ref()).unwrap();
 
         tracing::trace!("Sending POST request to {}: {:?}", url, request);
 
-        self.http_client
-            .post(url)
-            .json(&request)
-            .send()
-            .await?
-            .error_for_status()?
-            .json::<Resp>()
-            .await
+        let mut request_builder = self.http_client.post(url).json(&request);
+        for &(name, value) in headers {
+            request_builder = request_builder.header(name, value);
+        }
+        request_builder.send().await?.error_for_status()
     }
 
     /// Registers the attestation quote with the TEE prover interface API, effectively proving that
@@ -79,15 +98,23 @@ impl TeeApiClient {
         tee_type: TeeType,
     ) -> Result<Option<Box<TeeVerifierInput>>, TeeProverError> {
         let request = TeeProofGenerationDataRequest { tee_type };
-        let response = self
-            .post::<_, TeeProofGenerationDataResponse, _>("/tee/proof_inputs", request)
-            .await?;
+        let response = self.send_post("/tee/proof_inputs", request, &[]).await?;
+        // The header is optional, e.g. it's not returned by older servers.
+        let pending_batches = response
+            .headers()
+            .get(TEE_PENDING_BATCHES_HEADER)
+            .and_then(|value| value.to_str().ok()?.parse::<u64>().ok());
+        if let Some(pending_batches) = pending_batches {
+            METRICS.pending_batches.set(pending_batches);
+        }
+        let response = response.json::<TeeProofGenerationDataResponse>().await?;
         Ok(response.0)
     }
 
-    /// Submits the successfully verified proof to the TEE prover interface API.
+    /// Submits the successfully verified proof to the TEE prover interface API using the specified endpoint.
     pub async fn submit_proof(
         &self,
+        endpoint: &str,
         batch_number: L1BatchNumber,
         signature: Signature,
         pubkey: &PublicKey,
@@ -100,11 +127,16 @@ impl TeeApiClient {
             proof: root_hash.as_bytes().into(),
             tee_type,
         }));
+        // The key is deterministic, so it's reused if the same proof is re-submitted after a failure.
+        let idempotency_key = request.idempotency_key(batch_number);
         let observer = METRICS.proof_submitting_time.start();
-        self.post::<_, SubmitTeeProofResponse, _>(
-            format!("/tee/submit_proofs/{batch_number}").as_str(),
+        self.send_post(
+            format!("{endpoint}/{batch_number}"),
             request,
+            &[(IDEMPOTENCY_KEY_HEADER, &idempotency_key)],
         )
+        .await?
+        .json::<SubmitTeeProofResponse>()
         .await?;
         observer.observe();
         tracing::info!(
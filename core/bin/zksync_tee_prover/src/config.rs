@@ -1,16 +1,28 @@
-use std::{path::PathBuf, time::Duration};
+use std::{collections::HashMap, path::PathBuf, time::Duration};
 
+use anyhow::Context as _;
 use secp256k1::SecretKey;
-use serde::Deserialize;
+use secrecy::{ExposeSecret, SecretString};
+use serde::{de, Deserialize, Deserializer};
 use url::Url;
 use zksync_env_config::FromEnv;
-use zksync_types::tee_types::TeeType;
+use zksync_types::{tee_types::TeeType, ProtocolVersionId};
+
+/// Endpoint (relative to [`TeeProverConfig::api_url`]) to submit proofs to if there's no override for the batch protocol version.
+const DEFAULT_SUBMIT_PROOF_ENDPOINT: &str = "/tee/submit_proofs";
 
 /// Configuration for the TEE prover.
 #[derive(Debug, Clone, Deserialize)]
 pub(crate) struct TeeProverConfig {
-    /// The private key used to sign the proofs.
+    /// The primary private key used to sign the proofs.
     pub signing_key: SecretKey,
+    /// Additional private keys used for key rotation, in the order they are tried if the server rejects a proof
+    /// signed with the primary key as signed by an unknown key. Attestations are registered for all keys.
+    #[serde(default)]
+    pub additional_signing_keys: Vec<SecretKey>,
+    /// Determines which key is used to sign proofs after a fallback to an additional key.
+    #[serde(default)]
+    pub key_rotation_policy: KeyRotationPolicy,
     /// The path to the file containing the TEE quote.
     pub attestation_quote_file_path: PathBuf,
     /// Attestation quote file.
@@ -27,9 +39,65 @@ pub(crate) struct TeeProverConfig {
     pub retry_backoff_multiplier: f32,
     /// Maximum back-off interval when retrying recovery on a retriable error.
     pub max_backoff_sec: u64,
+    /// Multiplier for the poll interval while there are no pending batches. The interval starts from
+    /// [`Self::initial_retry_backoff_sec`] and is reset once a batch is received. By default, the interval is constant.
+    #[serde(default = "TeeProverConfig::default_idle_backoff_multiplier")]
+    pub idle_backoff_multiplier: f32,
+    /// Maximum poll interval while there are no pending batches. If not set, [`Self::max_backoff_sec`] is used.
+    #[serde(default)]
+    pub max_idle_backoff_sec: Option<u64>,
+    /// Overrides for the proof submission endpoint keyed by the batch protocol version, specified as a comma-separated list
+    /// of `<protocol version>=<endpoint>` entries (e.g., `24=/tee/v24/submit_proofs`). Batches with other protocol versions
+    /// are submitted to the default endpoint.
+    #[serde(default, deserialize_with = "deserialize_submit_proof_endpoints")]
+    pub submit_proof_endpoints: HashMap<ProtocolVersionId, String>,
+    /// URL of the HTTP / HTTPS proxy to send API requests through. If not set, requests are sent directly.
+    #[serde(default)]
+    pub proxy_url: Option<Url>,
+    /// Username for the proxy basic authentication. Ignored if [`Self::proxy_url`] is not set.
+    #[serde(default)]
+    pub proxy_username: Option<String>,
+    /// Password for the proxy basic authentication. Ignored if [`Self::proxy_username`] is not set.
+    #[serde(default)]
+    pub proxy_password: Option<SecretString>,
+    /// If set, batches are fetched, verified and signed as usual, but signed proofs are only logged instead of being
+    /// submitted, and attestations are not registered. Useful to validate a deployment end-to-end.
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Maximum number of batch verification results cached to avoid re-verifying batches that are served again
+    /// (e.g., after a transient proof submission failure). Set to 0 to disable caching.
+    #[serde(default = "TeeProverConfig::default_verification_cache_size")]
+    pub verification_cache_size: u64,
+}
+
+/// Policy of switching between [signing keys](TeeProverConfig::additional_signing_keys) on rejected proofs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum KeyRotationPolicy {
+    /// Each proof is signed with the primary key first; other keys are only used for the rejected proof.
+    #[default]
+    PrimaryFirst,
+    /// Once a proof signed with a key is accepted, this key is used to sign all following proofs.
+    Sticky,
+}
+
+impl KeyRotationPolicy {
+    /// Returns the index of the key used to sign the next proof after a proof signed with the key
+    /// at `accepted_key_idx` was accepted.
+    pub fn next_active_key_idx(self, active_key_idx: usize, accepted_key_idx: usize) -> usize {
+        match self {
+            Self::PrimaryFirst => active_key_idx,
+            Self::Sticky => accepted_key_idx,
+        }
+    }
 }
 
 impl TeeProverConfig {
+    /// Returns all signing keys: the primary key followed by additional keys.
+    pub fn signing_keys(&self) -> impl Iterator<Item = &SecretKey> + '_ {
+        std::iter::once(&self.signing_key).chain(&self.additional_signing_keys)
+    }
+
     pub fn initial_retry_backoff(&self) -> Duration {
         Duration::from_secs(self.initial_retry_backoff_sec)
     }
@@ -37,6 +105,76 @@ impl TeeProverConfig {
     pub fn max_backoff(&self) -> Duration {
         Duration::from_secs(self.max_backoff_sec)
     }
+
+    const fn default_idle_backoff_multiplier() -> f32 {
+        1.0
+    }
+
+    const fn default_verification_cache_size() -> u64 {
+        16
+    }
+
+    pub fn max_idle_backoff(&self) -> Duration {
+        Duration::from_secs(self.max_idle_backoff_sec.unwrap_or(self.max_backoff_sec))
+    }
+
+    /// Returns the poll interval following `current` while there are no pending batches.
+    pub fn next_idle_backoff(&self, current: Duration) -> Duration {
+        std::cmp::min(
+            current.mul_f32(self.idle_backoff_multiplier),
+            self.max_idle_backoff(),
+        )
+    }
+
+    /// Returns the proxy to send API requests through, if one is configured.
+    pub fn proxy(&self) -> anyhow::Result<Option<reqwest::Proxy>> {
+        let Some(proxy_url) = &self.proxy_url else {
+            return Ok(None);
+        };
+        let mut proxy = reqwest::Proxy::all(proxy_url.clone())
+            .with_context(|| format!("invalid proxy URL `{proxy_url}`"))?;
+        if let Some(username) = &self.proxy_username {
+            let password = self
+                .proxy_password
+                .as_ref()
+                .map_or("", |password| password.expose_secret());
+            proxy = proxy.basic_auth(username, password);
+        }
+        Ok(Some(proxy))
+    }
+
+    /// Returns the endpoint to submit proofs to for a batch with the specified protocol version.
+    pub fn submit_proof_endpoint(&self, protocol_version: Option<ProtocolVersionId>) -> &str {
+        protocol_version
+            .and_then(|version| self.submit_proof_endpoints.get(&version))
+            .map_or(DEFAULT_SUBMIT_PROOF_ENDPOINT, String::as_str)
+    }
+}
+
+fn deserialize_submit_proof_endpoints<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<HashMap<ProtocolVersionId, String>, D::Error> {
+    let raw = String::deserialize(deserializer)?;
+    parse_submit_proof_endpoints(&raw).map_err(|err| de::Error::custom(format!("{err:#}")))
+}
+
+fn parse_submit_proof_endpoints(raw: &str) -> anyhow::Result<HashMap<ProtocolVersionId, String>> {
+    raw.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (version, endpoint) = entry.split_once('=').with_context(|| {
+                format!("invalid entry `{entry}`; expected `<protocol version>=<endpoint>`")
+            })?;
+            let version: u16 = version
+                .trim()
+                .parse()
+                .with_context(|| format!("invalid protocol version in entry `{entry}`"))?;
+            let version = ProtocolVersionId::try_from(version)
+                .with_context(|| format!("unknown protocol version in entry `{entry}`"))?;
+            Ok((version, endpoint.trim().to_owned()))
+        })
+        .collect()
 }
 
 impl FromEnv for TeeProverConfig {
@@ -45,6 +183,8 @@ impl FromEnv for TeeProverConfig {
     /// Example usage of environment variables for tests:
     /// ```
     /// export TEE_PROVER_SIGNING_KEY="b50b38c8d396c88728fc032ece558ebda96907a0b1a9340289715eef7bf29deb"
+    /// export TEE_PROVER_ADDITIONAL_SIGNING_KEYS="a50b38c8d396c88728fc032ece558ebda96907a0b1a9340289715eef7bf29deb"  # optional, comma-separated
+    /// export TEE_PROVER_KEY_ROTATION_POLICY="primary_first"  # optional, `primary_first` or `sticky`
     /// export TEE_PROVER_ATTESTATION_QUOTE_FILE_PATH="/tmp/test"  # run `echo test > /tmp/test` beforehand
     /// export TEE_PROVER_TEE_TYPE="sgx"
     /// export TEE_PROVER_API_URL="http://127.0.0.1:3320"
@@ -52,9 +192,156 @@ impl FromEnv for TeeProverConfig {
     /// export TEE_PROVER_INITIAL_RETRY_BACKOFF_SEC=1
     /// export TEE_PROVER_RETRY_BACKOFF_MULTIPLIER=2.0
     /// export TEE_PROVER_MAX_BACKOFF_SEC=128
+    /// export TEE_PROVER_IDLE_BACKOFF_MULTIPLIER=1.5  # optional
+    /// export TEE_PROVER_MAX_IDLE_BACKOFF_SEC=60  # optional
+    /// export TEE_PROVER_SUBMIT_PROOF_ENDPOINTS="24=/tee/v24/submit_proofs"  # optional
+    /// export TEE_PROVER_PROXY_URL="http://proxy.example.com:3128"  # optional
+    /// export TEE_PROVER_PROXY_USERNAME="user"  # optional
+    /// export TEE_PROVER_PROXY_PASSWORD="password"  # optional
+    /// export TEE_PROVER_DRY_RUN=true  # optional
+    /// export TEE_PROVER_VERIFICATION_CACHE_SIZE=16  # optional, 0 disables caching
     /// ```
     fn from_env() -> anyhow::Result<Self> {
         let config: Self = envy::prefixed("TEE_PROVER_").from_env()?;
         Ok(config)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mock_config() -> TeeProverConfig {
+        TeeProverConfig {
+            signing_key: SecretKey::from_slice(&[1; 32]).unwrap(),
+            additional_signing_keys: vec![],
+            key_rotation_policy: KeyRotationPolicy::default(),
+            attestation_quote_file_path: "/tmp/test".into(),
+            tee_type: TeeType::Sgx,
+            api_url: "http://127.0.0.1:3320".parse().unwrap(),
+            max_retries: 10,
+            initial_retry_backoff_sec: 1,
+            retry_backoff_multiplier: 2.0,
+            max_backoff_sec: 128,
+            idle_backoff_multiplier: TeeProverConfig::default_idle_backoff_multiplier(),
+            max_idle_backoff_sec: None,
+            submit_proof_endpoints: HashMap::new(),
+            proxy_url: None,
+            proxy_username: None,
+            proxy_password: None,
+            dry_run: false,
+            verification_cache_size: TeeProverConfig::default_verification_cache_size(),
+        }
+    }
+
+    #[test]
+    fn parsing_submit_proof_endpoints() {
+        assert!(parse_submit_proof_endpoints("").unwrap().is_empty());
+        assert!(parse_submit_proof_endpoints(" , ").unwrap().is_empty());
+
+        let endpoints =
+            parse_submit_proof_endpoints("24=/tee/v24/submit_proofs, 25 = /tee/v25/submit_proofs,")
+                .unwrap();
+        assert_eq!(endpoints.len(), 2);
+        assert_eq!(
+            endpoints[&ProtocolVersionId::Version24],
+            "/tee/v24/submit_proofs"
+        );
+        assert_eq!(
+            endpoints[&ProtocolVersionId::Version25],
+            "/tee/v25/submit_proofs"
+        );
+    }
+
+    #[test]
+    fn parsing_invalid_submit_proof_endpoints() {
+        let err = parse_submit_proof_endpoints("/tee/submit_proofs").unwrap_err();
+        assert!(err.to_string().contains("invalid entry"), "{err:#}");
+        let err = parse_submit_proof_endpoints("v24=/tee/submit_proofs").unwrap_err();
+        assert!(
+            err.to_string().contains("invalid protocol version"),
+            "{err:#}"
+        );
+        let err = parse_submit_proof_endpoints("999=/tee/submit_proofs").unwrap_err();
+        assert!(
+            err.to_string().contains("unknown protocol version"),
+            "{err:#}"
+        );
+    }
+
+    #[test]
+    fn selecting_submit_proof_endpoint() {
+        let mut config = mock_config();
+        config.submit_proof_endpoints =
+            parse_submit_proof_endpoints("24=/tee/v24/submit_proofs").unwrap();
+
+        assert_eq!(
+            config.submit_proof_endpoint(Some(ProtocolVersionId::Version24)),
+            "/tee/v24/submit_proofs"
+        );
+        assert_eq!(
+            config.submit_proof_endpoint(Some(ProtocolVersionId::Version25)),
+            DEFAULT_SUBMIT_PROOF_ENDPOINT
+        );
+        assert_eq!(
+            config.submit_proof_endpoint(None),
+            DEFAULT_SUBMIT_PROOF_ENDPOINT
+        );
+    }
+
+    #[test]
+    fn signing_keys_order() {
+        let mut config = mock_config();
+        assert_eq!(config.signing_keys().count(), 1);
+
+        let additional_keys = [
+            SecretKey::from_slice(&[2; 32]).unwrap(),
+            SecretKey::from_slice(&[3; 32]).unwrap(),
+        ];
+        config.additional_signing_keys = additional_keys.to_vec();
+        let keys: Vec<_> = config.signing_keys().copied().collect();
+        assert_eq!(
+            keys,
+            [config.signing_key, additional_keys[0], additional_keys[1]]
+        );
+    }
+
+    #[test]
+    fn key_rotation() {
+        let policy = KeyRotationPolicy::PrimaryFirst;
+        assert_eq!(policy.next_active_key_idx(0, 0), 0);
+        assert_eq!(policy.next_active_key_idx(0, 2), 0);
+
+        let policy = KeyRotationPolicy::Sticky;
+        assert_eq!(policy.next_active_key_idx(0, 0), 0);
+        assert_eq!(policy.next_active_key_idx(0, 2), 2);
+        assert_eq!(policy.next_active_key_idx(2, 2), 2);
+    }
+
+    #[test]
+    fn constant_idle_backoff_by_default() {
+        let config = mock_config();
+        let backoff = config.initial_retry_backoff();
+        assert_eq!(config.next_idle_backoff(backoff), backoff);
+    }
+
+    #[test]
+    fn growing_idle_backoff() {
+        let mut config = mock_config();
+        config.idle_backoff_multiplier = 2.0;
+        config.max_idle_backoff_sec = Some(5);
+
+        let mut backoff = config.initial_retry_backoff();
+        let mut backoffs = vec![];
+        for _ in 0..5 {
+            backoff = config.next_idle_backoff(backoff);
+            backoffs.push(backoff.as_secs());
+        }
+        assert_eq!(backoffs, [2, 4, 5, 5, 5]);
+
+        // Falls back to the max retry backoff if the max idle backoff is not set.
+        config.max_idle_backoff_sec = None;
+        let backoff = config.next_idle_backoff(Duration::from_secs(100));
+        assert_eq!(backoff, config.max_backoff());
+    }
+}
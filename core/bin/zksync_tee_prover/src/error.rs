@@ -17,6 +17,18 @@ impl TeeProverError {
             _ => false,
         }
     }
+
+    /// Checks whether the server has rejected a request because it doesn't recognize the signing key
+    /// (i.e., responded with 401 Unauthorized or 403 Forbidden).
+    pub fn is_unknown_key(&self) -> bool {
+        match self {
+            Self::Request(err) => matches!(
+                err.status(),
+                Some(StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN)
+            ),
+            _ => false,
+        }
+    }
 }
 
 fn is_retriable_http_error(err: &reqwest::Error) -> bool {
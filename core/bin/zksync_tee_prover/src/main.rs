@@ -16,6 +16,7 @@ mod config;
 mod error;
 mod metrics;
 mod tee_prover;
+mod verification_cache;
 
 /// This application serves as a TEE verifier, a.k.a. a TEE prover.
 ///
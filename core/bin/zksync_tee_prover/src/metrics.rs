@@ -2,7 +2,16 @@
 
 use std::time::Duration;
 
-use vise::{Buckets, Gauge, Histogram, Metrics, Unit};
+use vise::{
+    Buckets, Counter, EncodeLabelSet, EncodeLabelValue, Family, Gauge, Histogram, Metrics, Unit,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EncodeLabelValue, EncodeLabelSet)]
+#[metrics(label = "outcome", rename_all = "snake_case")]
+pub(crate) enum VerificationOutcome {
+    Success,
+    Failure,
+}
 
 #[derive(Debug, Metrics)]
 #[metrics(prefix = "tee_prover")]
@@ -11,10 +20,24 @@ pub(crate) struct TeeProverMetrics {
     pub job_waiting_time: Histogram<Duration>,
     #[metrics(buckets = Buckets::LATENCIES, unit = Unit::Seconds)]
     pub proof_generation_time: Histogram<Duration>,
+    /// Latency of verifying a batch (i.e., re-executing it in the VM), excluding signing and network requests.
+    #[metrics(buckets = Buckets::LATENCIES, unit = Unit::Seconds)]
+    pub verification_time: Family<VerificationOutcome, Histogram<Duration>>,
+    /// Latency of signing a verified root hash, excluding verification and network requests.
+    #[metrics(buckets = Buckets::LATENCIES, unit = Unit::Seconds)]
+    pub signing_time: Histogram<Duration>,
     #[metrics(buckets = Buckets::LATENCIES, unit = Unit::Seconds)]
     pub proof_submitting_time: Histogram<Duration>,
     pub network_errors_counter: Gauge<u64>,
+    /// Number of proofs re-signed with the next signing key after being rejected due to an unknown key.
+    pub signing_key_fallbacks: Counter,
+    /// Index of the signing key in the configured key list that was used for the last accepted proof.
+    pub active_signing_key: Gauge<u64>,
     pub last_batch_number_processed: Gauge<u64>,
+    /// Number of batches for which verification was skipped because the result was cached.
+    pub verification_cache_hits: Counter,
+    /// Number of batches waiting to be proven, as reported by the proof data handler.
+    pub pending_batches: Gauge<u64>,
 }
 
 #[vise::register]
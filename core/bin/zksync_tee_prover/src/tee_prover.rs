@@ -1,6 +1,14 @@
-use std::fmt;
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
 
-use secp256k1::{ecdsa::Signature, Message, PublicKey, Secp256k1};
+use anyhow::Context as _;
+use secp256k1::{ecdsa::Signature, Message, PublicKey, Secp256k1, SecretKey};
 use zksync_basic_types::H256;
 use zksync_node_framework::{
     service::StopReceiver,
@@ -13,7 +21,11 @@ use zksync_tee_verifier::Verify;
 use zksync_types::L1BatchNumber;
 
 use crate::{
-    api_client::TeeApiClient, config::TeeProverConfig, error::TeeProverError, metrics::METRICS,
+    api_client::TeeApiClient,
+    config::TeeProverConfig,
+    error::TeeProverError,
+    metrics::{VerificationOutcome, METRICS},
+    verification_cache::VerificationCache,
 };
 
 /// Wiring layer for `TeeProver`
@@ -45,9 +57,24 @@ impl WiringLayer for TeeProverLayer {
 
     async fn wire(self, _input: Self::Input) -> Result<Self::Output, WiringError> {
         let api_url = self.config.api_url.clone();
+        let proxy = self.config.proxy().map_err(WiringError::internal)?;
+        let api_client = TeeApiClient::new(api_url, proxy)
+            .context("failed building HTTP client")
+            .map_err(WiringError::internal)?;
+        let secp = Secp256k1::signing_only();
+        let signing_keys = self
+            .config
+            .signing_keys()
+            .map(|&secret_key| (secret_key, secret_key.public_key(&secp)))
+            .collect();
+        let verification_cache = (self.config.verification_cache_size > 0)
+            .then(|| Arc::new(VerificationCache::new(self.config.verification_cache_size)));
         let tee_prover = TeeProver {
             config: self.config,
-            api_client: TeeApiClient::new(api_url),
+            api_client,
+            signing_keys,
+            active_key_idx: AtomicUsize::new(0),
+            verification_cache,
         };
         Ok(LayerOutput { tee_prover })
     }
@@ -56,6 +83,11 @@ impl WiringLayer for TeeProverLayer {
 pub(crate) struct TeeProver {
     config: TeeProverConfig,
     api_client: TeeApiClient,
+    /// Signing keys in the order they are tried, starting from the primary key.
+    signing_keys: Vec<(SecretKey, PublicKey)>,
+    /// Index of the key in `signing_keys` used to sign the next proof.
+    active_key_idx: AtomicUsize,
+    verification_cache: Option<Arc<VerificationCache>>,
 }
 
 impl fmt::Debug for TeeProver {
@@ -67,21 +99,60 @@ impl fmt::Debug for TeeProver {
 }
 
 impl TeeProver {
+    /// Digest signed during the [self-test](Self::self_test()).
+    const SELF_TEST_DIGEST: [u8; 32] = *b"zksync-tee-prover-self-test-dgst";
+
+    /// Signs a known digest and verifies the signature against the provided public key. This allows to fail fast
+    /// if the key material is misconfigured, rather than producing invalid signatures for real batches.
+    fn self_test(signing_key: &SecretKey, public_key: &PublicKey) -> anyhow::Result<()> {
+        let msg_to_sign = Message::from_slice(&Self::SELF_TEST_DIGEST)?;
+        let signature = signing_key.sign_ecdsa(msg_to_sign);
+        Secp256k1::verification_only()
+            .verify_ecdsa(&msg_to_sign, &signature, public_key)
+            .context("signature self-test failed; the signing key is likely misconfigured")?;
+        tracing::info!("Signature self-test passed for the public key {public_key}");
+        Ok(())
+    }
+
+    /// Verifies the batch and signs its root hash. This is CPU-intensive, so it should be run on a blocking thread
+    /// (see [`Self::verify_in_background()`]). If a `cache` is provided and contains the result for the same input,
+    /// verification is skipped, and the cached root hash is signed.
     fn verify(
-        &self,
+        signing_key: &SecretKey,
         tvi: TeeVerifierInput,
+        cache: Option<&VerificationCache>,
     ) -> Result<(Signature, L1BatchNumber, H256), TeeProverError> {
         match tvi {
             TeeVerifierInput::V1(tvi) => {
                 let observer = METRICS.proof_generation_time.start();
-                let verification_result = tvi.verify().map_err(TeeProverError::Verification)?;
-                let root_hash_bytes = verification_result.value_hash.as_bytes();
-                let batch_number = verification_result.batch_number;
-                let msg_to_sign = Message::from_slice(root_hash_bytes)
-                    .map_err(|e| TeeProverError::Verification(e.into()))?;
-                let signature = self.config.signing_key.sign_ecdsa(msg_to_sign);
+                let batch_number = tvi.l1_batch_env.number;
+                let cache_key = cache.and_then(|_| VerificationCache::input_key(&tvi));
+                let cached_root_hash = cache.zip(cache_key).and_then(|(cache, key)| cache.get(key));
+
+                let root_hash = if let Some(root_hash) = cached_root_hash {
+                    tracing::info!(
+                        "Batch #{batch_number} was already verified with root hash {root_hash:?}; skipping verification"
+                    );
+                    root_hash
+                } else {
+                    let verification_started_at = Instant::now();
+                    let verification_result = tvi.verify();
+                    let outcome = if verification_result.is_ok() {
+                        VerificationOutcome::Success
+                    } else {
+                        VerificationOutcome::Failure
+                    };
+                    METRICS.verification_time[&outcome].observe(verification_started_at.elapsed());
+                    let verification_result =
+                        verification_result.map_err(TeeProverError::Verification)?;
+                    if let (Some(cache), Some(key)) = (cache, cache_key) {
+                        cache.insert(key, verification_result.value_hash);
+                    }
+                    verification_result.value_hash
+                };
+                let signature = Self::sign(signing_key, root_hash)?;
                 observer.observe();
-                Ok((signature, batch_number, verification_result.value_hash))
+                Ok((signature, batch_number, root_hash))
             }
             _ => Err(TeeProverError::Verification(anyhow::anyhow!(
                 "Only TeeVerifierInput::V1 verification supported."
@@ -89,19 +160,116 @@ impl TeeProver {
         }
     }
 
-    async fn step(&self, public_key: &PublicKey) -> Result<Option<L1BatchNumber>, TeeProverError> {
+    fn sign(signing_key: &SecretKey, root_hash: H256) -> Result<Signature, TeeProverError> {
+        let msg_to_sign = Message::from_slice(root_hash.as_bytes())
+            .map_err(|e| TeeProverError::Verification(e.into()))?;
+        let latency = METRICS.signing_time.start();
+        let signature = signing_key.sign_ecdsa(msg_to_sign);
+        let elapsed = latency.observe();
+        tracing::debug!("Signed root hash {root_hash:?} in {elapsed:?}");
+        Ok(signature)
+    }
+
+    /// Runs [`Self::verify()`] on a blocking thread, so that the async runtime stays responsive
+    /// (e.g., to stop signals) while the batch is being verified.
+    async fn verify_in_background(
+        &self,
+        signing_key: SecretKey,
+        tvi: TeeVerifierInput,
+    ) -> Result<(Signature, L1BatchNumber, H256), TeeProverError> {
+        let cache = self.verification_cache.clone();
+        tokio::task::spawn_blocking(move || Self::verify(&signing_key, tvi, cache.as_deref()))
+            .await
+            .map_err(|err| {
+                TeeProverError::Verification(
+                    anyhow::Error::new(err).context("verification task failed"),
+                )
+            })?
+    }
+
+    /// Submits a proof signed with the key at `key_idx`. If the server rejects the proof because it doesn't recognize
+    /// the key, the proof is re-signed and submitted with the following keys. Returns the index of the accepted key.
+    async fn submit_proof(
+        &self,
+        endpoint: &str,
+        batch_number: L1BatchNumber,
+        root_hash: H256,
+        mut key_idx: usize,
+        mut signature: Signature,
+    ) -> Result<usize, TeeProverError> {
+        loop {
+            let public_key = &self.signing_keys[key_idx].1;
+            // The signing key is intentionally not logged.
+            tracing::debug!(
+                l1_batch_number = batch_number.0,
+                root_hash = hex::encode(root_hash.as_bytes()),
+                public_key = %public_key,
+                signature = hex::encode(signature.serialize_compact()),
+                endpoint,
+                "Submitting signed TEE proof"
+            );
+            let result = self
+                .api_client
+                .submit_proof(
+                    endpoint,
+                    batch_number,
+                    signature,
+                    public_key,
+                    root_hash,
+                    self.config.tee_type,
+                )
+                .await;
+            let err = match result {
+                Ok(()) => return Ok(key_idx),
+                Err(err) => err,
+            };
+            let Some(next_key_idx) = fallback_key_idx(&err, key_idx, self.signing_keys.len())
+            else {
+                return Err(err);
+            };
+            tracing::warn!(
+                %err,
+                "Proof for batch #{batch_number} signed by the public key {public_key} was rejected; \
+                 retrying with the next signing key"
+            );
+            METRICS.signing_key_fallbacks.inc();
+            key_idx = next_key_idx;
+            signature = Self::sign(&self.signing_keys[key_idx].0, root_hash)?;
+        }
+    }
+
+    async fn step(&self) -> Result<Option<L1BatchNumber>, TeeProverError> {
         match self.api_client.get_job(self.config.tee_type).await? {
             Some(job) => {
-                let (signature, batch_number, root_hash) = self.verify(*job)?;
-                self.api_client
-                    .submit_proof(
-                        batch_number,
-                        signature,
-                        public_key,
-                        root_hash,
-                        self.config.tee_type,
-                    )
+                let protocol_version = match &*job {
+                    TeeVerifierInput::V1(tvi) => Some(tvi.system_env.version),
+                    _ => None,
+                };
+                let key_idx = self.active_key_idx.load(Ordering::Relaxed);
+                let signing_key = self.signing_keys[key_idx].0;
+                let (signature, batch_number, root_hash) =
+                    self.verify_in_background(signing_key, *job).await?;
+                let endpoint = self.config.submit_proof_endpoint(protocol_version);
+                if self.config.dry_run {
+                    tracing::info!(
+                        l1_batch_number = batch_number.0,
+                        root_hash = hex::encode(root_hash.as_bytes()),
+                        public_key = %self.signing_keys[key_idx].1,
+                        signature = hex::encode(signature.serialize_compact()),
+                        endpoint,
+                        "Dry run: not submitting signed TEE proof"
+                    );
+                    return Ok(Some(batch_number));
+                }
+                let accepted_key_idx = self
+                    .submit_proof(endpoint, batch_number, root_hash, key_idx, signature)
                     .await?;
+                METRICS.active_signing_key.set(accepted_key_idx as u64);
+                let next_key_idx = self
+                    .config
+                    .key_rotation_policy
+                    .next_active_key_idx(key_idx, accepted_key_idx);
+                self.active_key_idx.store(next_key_idx, Ordering::Relaxed);
                 Ok(Some(batch_number))
             }
             None => {
@@ -112,6 +280,12 @@ impl TeeProver {
     }
 }
 
+/// Returns the index of the signing key to re-sign a rejected proof with, or `None` if the proof wasn't rejected
+/// because of an unknown key, or if there are no more keys to try.
+fn fallback_key_idx(err: &TeeProverError, key_idx: usize, key_count: usize) -> Option<usize> {
+    (err.is_unknown_key() && key_idx + 1 < key_count).then_some(key_idx + 1)
+}
+
 #[async_trait::async_trait]
 impl Task for TeeProver {
     fn id(&self) -> TaskId {
@@ -123,13 +297,24 @@ impl Task for TeeProver {
 
         let config = &self.config;
         let attestation_quote_bytes = std::fs::read(&config.attestation_quote_file_path)?;
-        let public_key = config.signing_key.public_key(&Secp256k1::new());
-        self.api_client
-            .register_attestation(attestation_quote_bytes, &public_key)
-            .await?;
+        if config.dry_run {
+            tracing::warn!(
+                "Running in dry-run mode; attestations and proofs will not be submitted"
+            );
+        }
+        for (signing_key, public_key) in &self.signing_keys {
+            Self::self_test(signing_key, public_key)?;
+            if config.dry_run {
+                continue;
+            }
+            self.api_client
+                .register_attestation(attestation_quote_bytes.clone(), public_key)
+                .await?;
+        }
 
         let mut retries = 1;
         let mut backoff = config.initial_retry_backoff();
+        let mut idle_backoff = config.initial_retry_backoff();
         let mut observer = METRICS.job_waiting_time.start();
 
         loop {
@@ -137,20 +322,23 @@ impl Task for TeeProver {
                 tracing::info!("Stop signal received, shutting down TEE Prover component");
                 return Ok(());
             }
-            let result = self.step(&public_key).await;
-            let need_to_sleep = match result {
+            let result = self.step().await;
+            let sleep_duration = match result {
                 Ok(batch_number) => {
                     retries = 1;
                     backoff = config.initial_retry_backoff();
                     if let Some(batch_number) = batch_number {
+                        idle_backoff = config.initial_retry_backoff();
                         observer.observe();
                         observer = METRICS.job_waiting_time.start();
                         METRICS
                             .last_batch_number_processed
                             .set(batch_number.0 as u64);
-                        false
+                        None
                     } else {
-                        true
+                        let sleep_duration = idle_backoff;
+                        idle_backoff = config.next_idle_backoff(idle_backoff);
+                        Some(sleep_duration)
                     }
                 }
                 Err(err) => {
@@ -164,14 +352,62 @@ impl Task for TeeProver {
                         backoff.mul_f32(config.retry_backoff_multiplier),
                         config.max_backoff(),
                     );
-                    true
+                    Some(backoff)
                 }
             };
-            if need_to_sleep {
-                tokio::time::timeout(backoff, stop_receiver.0.changed())
+            if let Some(sleep_duration) = sleep_duration {
+                tokio::time::timeout(sleep_duration, stop_receiver.0.changed())
                     .await
                     .ok();
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use reqwest::StatusCode;
+
+    use super::*;
+
+    fn http_error(status: StatusCode) -> TeeProverError {
+        let response = http::Response::builder()
+            .status(status)
+            .body(Vec::<u8>::new())
+            .unwrap();
+        reqwest::Response::from(response)
+            .error_for_status()
+            .unwrap_err()
+            .into()
+    }
+
+    #[test]
+    fn falling_back_to_next_signing_key() {
+        for status in [StatusCode::UNAUTHORIZED, StatusCode::FORBIDDEN] {
+            let err = http_error(status);
+            assert!(err.is_unknown_key());
+            assert_eq!(fallback_key_idx(&err, 0, 3), Some(1));
+            assert_eq!(fallback_key_idx(&err, 1, 3), Some(2));
+            // All keys are exhausted.
+            assert_eq!(fallback_key_idx(&err, 2, 3), None);
+            assert_eq!(fallback_key_idx(&err, 0, 1), None);
+        }
+
+        let err = http_error(StatusCode::BAD_GATEWAY);
+        assert!(!err.is_unknown_key());
+        assert_eq!(fallback_key_idx(&err, 0, 3), None);
+        let err = TeeProverError::Verification(anyhow::anyhow!("invalid batch"));
+        assert_eq!(fallback_key_idx(&err, 0, 3), None);
+    }
+
+    #[test]
+    fn signing_self_test() {
+        let secp = Secp256k1::signing_only();
+        let signing_key = SecretKey::from_slice(&[1; 32]).unwrap();
+        let other_key = SecretKey::from_slice(&[2; 32]).unwrap();
+
+        TeeProver::self_test(&signing_key, &signing_key.public_key(&secp)).unwrap();
+        let err = TeeProver::self_test(&signing_key, &other_key.public_key(&secp)).unwrap_err();
+        assert!(err.to_string().contains("self-test"), "{err}");
+    }
+}
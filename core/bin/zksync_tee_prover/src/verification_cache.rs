@@ -0,0 +1,86 @@
+//! Cache of batch verification results.
+
+use zksync_basic_types::H256;
+use zksync_prover_interface::inputs::V1TeeVerifierInput;
+use zksync_types::L1BatchNumber;
+
+use crate::metrics::METRICS;
+
+/// Bounded LRU cache of verified root hashes keyed by the batch number and the expected root hash from the input
+/// metadata. Allows skipping re-verification if the proof data handler serves the same batch again (e.g., after
+/// a transient proof submission failure). Since the key includes the expected root hash, a batch re-served
+/// with different Merkle tree metadata is always verified.
+#[derive(Debug)]
+pub(crate) struct VerificationCache {
+    inner: mini_moka::sync::Cache<(L1BatchNumber, H256), H256>,
+}
+
+impl VerificationCache {
+    /// Creates a cache holding up to `capacity` verification results.
+    pub fn new(capacity: u64) -> Self {
+        tracing::info!("Configured verification cache with capacity {capacity}");
+        let inner = mini_moka::sync::Cache::builder()
+            .max_capacity(capacity)
+            .build();
+        Self { inner }
+    }
+
+    /// Returns the cache key for the verifier input: the batch number and the root hash after the last storage log
+    /// in the input metadata. If the batch has no storage logs, the root hash of the previous batch is used instead.
+    pub fn input_key(tvi: &V1TeeVerifierInput) -> Option<(L1BatchNumber, H256)> {
+        let root_hash = tvi
+            .witness_input_merkle_paths
+            .root_hash()
+            .or(tvi.l1_batch_env.previous_batch_hash)?;
+        Some((tvi.l1_batch_env.number, root_hash))
+    }
+
+    /// Returns the cached root hash for the batch input, if any.
+    pub fn get(&self, key: (L1BatchNumber, H256)) -> Option<H256> {
+        let root_hash = self.inner.get(&key);
+        if root_hash.is_some() {
+            METRICS.verification_cache_hits.inc();
+        }
+        root_hash
+    }
+
+    pub fn insert(&self, key: (L1BatchNumber, H256), root_hash: H256) {
+        self.inner.insert(key, root_hash);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mini_moka::sync::ConcurrentCacheExt;
+
+    use super::*;
+
+    #[test]
+    fn caching_root_hashes() {
+        let cache = VerificationCache::new(16);
+        let key = (L1BatchNumber(1), H256::repeat_byte(1));
+        assert_eq!(cache.get(key), None);
+
+        cache.insert(key, H256::repeat_byte(0xff));
+        assert_eq!(cache.get(key), Some(H256::repeat_byte(0xff)));
+        // A batch with different Merkle tree metadata is not a hit.
+        assert_eq!(cache.get((L1BatchNumber(1), H256::repeat_byte(2))), None);
+        assert_eq!(cache.get((L1BatchNumber(2), H256::repeat_byte(1))), None);
+    }
+
+    #[test]
+    fn cache_eviction() {
+        let cache = VerificationCache::new(2);
+        let keys: Vec<_> = (0..10)
+            .map(|i| (L1BatchNumber(i), H256::repeat_byte(i as u8)))
+            .collect();
+        for &key in &keys {
+            cache.insert(key, H256::zero());
+        }
+        cache.inner.sync();
+
+        assert!(cache.inner.entry_count() <= 2);
+        let cached_count = keys.iter().filter(|&&key| cache.get(key).is_some()).count();
+        assert!(cached_count <= 2, "{cached_count}");
+    }
+}
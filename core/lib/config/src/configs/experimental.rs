@@ -1,6 +1,6 @@
 //! Experimental part of configuration.
 
-use std::num::NonZeroU32;
+use std::{num::NonZeroU32, ops::RangeInclusive};
 
 use serde::Deserialize;
 use zksync_basic_types::{vm::FastVmMode, L1BatchNumber};
@@ -76,6 +76,12 @@ pub struct ExperimentalVmPlaygroundConfig {
     /// require to drop the RocksDB cache.
     #[serde(default)]
     pub reset: bool,
+    /// Format of VM dumps uploaded on divergences.
+    #[serde(default)]
+    pub dumps_format: VmPlaygroundDumpFormat,
+    /// `zstd` compression level for VM dumps uploaded on divergences. Lower (incl. negative) levels favor speed,
+    /// higher ones (up to 22) favor compression ratio. If not set, a balanced default level is used.
+    pub dumps_compression_level: Option<i32>,
 }
 
 impl Default for ExperimentalVmPlaygroundConfig {
@@ -86,14 +92,42 @@ impl Default for ExperimentalVmPlaygroundConfig {
             first_processed_batch: L1BatchNumber(0),
             window_size: Self::default_window_size(),
             reset: false,
+            dumps_format: VmPlaygroundDumpFormat::default(),
+            dumps_compression_level: None,
         }
     }
 }
 
+/// Format of VM dumps produced by the VM playground.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VmPlaygroundDumpFormat {
+    /// Human-readable JSON.
+    #[default]
+    Json,
+    /// Compact binary format based on `bincode`.
+    Bincode,
+}
+
 impl ExperimentalVmPlaygroundConfig {
+    /// Range of supported [`Self::dumps_compression_level`] values; matches the range supported by `zstd`.
+    pub const DUMPS_COMPRESSION_LEVELS: RangeInclusive<i32> = -(1 << 17)..=22;
+
     pub fn default_window_size() -> NonZeroU32 {
         NonZeroU32::new(1).unwrap()
     }
+
+    /// Checks that [`Self::dumps_compression_level`] (if set) is within [`Self::DUMPS_COMPRESSION_LEVELS`].
+    pub fn validate_dumps_compression_level(&self) -> anyhow::Result<()> {
+        if let Some(level) = self.dumps_compression_level {
+            anyhow::ensure!(
+                Self::DUMPS_COMPRESSION_LEVELS.contains(&level),
+                "dumps_compression_level {level} is outside the supported range {:?}",
+                Self::DUMPS_COMPRESSION_LEVELS
+            );
+        }
+        Ok(())
+    }
 }
 
 /// Experimental VM configuration options.
@@ -10,7 +10,10 @@ pub use self::{
     database::{DBConfig, PostgresConfig},
     eth_sender::{EthConfig, GasAdjusterConfig},
     eth_watch::EthWatchConfig,
-    experimental::{ExperimentalDBConfig, ExperimentalVmConfig, ExperimentalVmPlaygroundConfig},
+    experimental::{
+        ExperimentalDBConfig, ExperimentalVmConfig, ExperimentalVmPlaygroundConfig,
+        VmPlaygroundDumpFormat,
+    },
     external_price_api_client::ExternalPriceApiClientConfig,
     external_proof_integration_api::ExternalProofIntegrationApiConfig,
     fri_proof_compressor::FriProofCompressorConfig,
@@ -306,6 +306,13 @@ impl Distribution<configs::ExperimentalVmPlaygroundConfig> for EncodeDist {
             first_processed_batch: L1BatchNumber(rng.gen()),
             window_size: rng.gen(),
             reset: self.sample(rng),
+            dumps_format: match rng.gen_range(0..2) {
+                0 => configs::VmPlaygroundDumpFormat::Json,
+                _ => configs::VmPlaygroundDumpFormat::Bincode,
+            },
+            dumps_compression_level: self.sample_opt(|| {
+                rng.gen_range(configs::ExperimentalVmPlaygroundConfig::DUMPS_COMPRESSION_LEVELS)
+            }),
         }
     }
 }
@@ -0,0 +1 @@
+ALTER TABLE tee_verifier_input_producer_jobs DROP COLUMN IF EXISTS priority;
@@ -0,0 +1 @@
+ALTER TABLE tee_verifier_input_producer_jobs ADD COLUMN IF NOT EXISTS priority INT NOT NULL DEFAULT 0;
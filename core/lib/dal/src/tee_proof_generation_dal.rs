@@ -267,6 +267,34 @@ impl TeeProofGenerationDal<'_, '_> {
         Ok(proofs)
     }
 
+    /// Returns the number of batches with TEE verifier inputs that are not picked by provers of the specified type.
+    pub async fn count_unpicked_batches(&mut self, tee_type: TeeType) -> DalResult<u64> {
+        let query = sqlx::query!(
+            r#"
+            SELECT
+                COUNT(*) AS "count!"
+            FROM
+                tee_proof_generation_details AS proofs
+                JOIN tee_verifier_input_producer_jobs AS inputs ON proofs.l1_batch_number = inputs.l1_batch_number
+            WHERE
+                inputs.status = $1
+                AND proofs.status = $2
+                AND proofs.tee_type = $3
+            "#,
+            TeeVerifierInputProducerJobStatus::Successful as TeeVerifierInputProducerJobStatus,
+            TeeProofGenerationJobStatus::Unpicked.to_string(),
+            tee_type.to_string(),
+        );
+        let count = Instrumented::new("count_unpicked_batches")
+            .with_arg("tee_type", &tee_type)
+            .with(query)
+            .fetch_one(self.storage)
+            .await?
+            .count;
+
+        Ok(count as u64)
+    }
+
     pub async fn get_oldest_unpicked_batch(&mut self) -> DalResult<Option<L1BatchNumber>> {
         let query = sqlx::query!(
             r#"
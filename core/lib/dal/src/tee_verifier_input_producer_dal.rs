@@ -47,6 +47,32 @@ pub enum TeeVerifierInputProducerJobStatus {
     Failed,
 }
 
+/// Order in which [`TeeVerifierInputProducerDal::get_next_tee_verifier_input_producer_job_in_order()`] picks jobs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TeeVerifierInputProducerJobOrder {
+    /// Jobs for older L1 batches are picked first.
+    #[default]
+    OldestFirst,
+    /// Jobs for newer L1 batches are picked first.
+    NewestFirst,
+    /// Jobs with higher priority (set via [`TeeVerifierInputProducerDal::set_job_priority()`]) are picked first;
+    /// jobs with the same priority are picked oldest first.
+    Priority,
+}
+
+/// Outcome of [claiming](TeeVerifierInputProducerDal::claim_job()) the job for a specific L1 batch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TeeVerifierInputProducerJobClaim {
+    /// The job was marked as in progress; the caller is responsible for completing it.
+    Claimed,
+    /// There is no job for the L1 batch.
+    Missing,
+    /// The job is being processed by another worker.
+    InProgress,
+    /// The job was already processed successfully.
+    Successful,
+}
+
 impl TeeVerifierInputProducerDal<'_, '_> {
     pub async fn create_tee_verifier_input_producer_job(
         &mut self,
@@ -74,6 +100,17 @@ impl TeeVerifierInputProducerDal<'_, '_> {
 
     pub async fn get_next_tee_verifier_input_producer_job(
         &mut self,
+    ) -> DalResult<Option<L1BatchNumber>> {
+        self.get_next_tee_verifier_input_producer_job_in_order(
+            TeeVerifierInputProducerJobOrder::OldestFirst,
+        )
+        .await
+    }
+
+    /// Same as [`Self::get_next_tee_verifier_input_producer_job()`], but picks jobs in the specified `order`.
+    pub async fn get_next_tee_verifier_input_producer_job_in_order(
+        &mut self,
+        order: TeeVerifierInputProducerJobOrder,
     ) -> DalResult<Option<L1BatchNumber>> {
         let l1_batch_number = sqlx::query!(
             r#"
@@ -100,6 +137,14 @@ impl TeeVerifierInputProducerDal<'_, '_> {
                             AND attempts < $5
                         )
                     ORDER BY
+                        CASE
+                            WHEN $6 THEN priority
+                            ELSE 0
+                        END DESC,
+                        CASE
+                            WHEN $7 THEN l1_batch_number
+                            ELSE 0
+                        END DESC,
                         l1_batch_number ASC
                     LIMIT
                         1
@@ -114,8 +159,11 @@ impl TeeVerifierInputProducerDal<'_, '_> {
             TeeVerifierInputProducerJobStatus::Failed as TeeVerifierInputProducerJobStatus,
             &JOB_PROCESSING_TIMEOUT,
             JOB_MAX_ATTEMPT,
+            matches!(order, TeeVerifierInputProducerJobOrder::Priority),
+            matches!(order, TeeVerifierInputProducerJobOrder::NewestFirst),
         )
-        .instrument("get_next_tee_verifier_input_producer_job")
+        .instrument("get_next_tee_verifier_input_producer_job_in_order")
+        .with_arg("order", &order)
         .report_latency()
         .fetch_optional(self.storage)
         .await?
@@ -124,6 +172,180 @@ impl TeeVerifierInputProducerDal<'_, '_> {
         Ok(l1_batch_number)
     }
 
+    /// Sets the priority of the job for the specified L1 batch. Jobs with higher priority are picked first
+    /// if jobs are picked in the [priority order](TeeVerifierInputProducerJobOrder::Priority). Returns `false`
+    /// if there is no job for the batch.
+    pub async fn set_job_priority(
+        &mut self,
+        l1_batch_number: L1BatchNumber,
+        priority: i32,
+    ) -> DalResult<bool> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE tee_verifier_input_producer_jobs
+            SET
+                priority = $1,
+                updated_at = NOW()
+            WHERE
+                l1_batch_number = $2
+            "#,
+            priority,
+            i64::from(l1_batch_number.0),
+        )
+        .instrument("set_job_priority")
+        .with_arg("l1_batch_number", &l1_batch_number)
+        .with_arg("priority", &priority)
+        .report_latency()
+        .execute(self.storage)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Same as [`Self::get_next_tee_verifier_input_producer_job()`], but only considers jobs for the `window_size`
+    /// latest L1 batches, prioritizing the most recent ones. If `reverify_after` is specified, jobs for batches
+    /// in the window that were successfully processed longer than this interval ago are picked up again once
+    /// there are no unprocessed jobs in the window.
+    pub async fn get_next_tee_verifier_input_producer_job_in_window(
+        &mut self,
+        window_size: u32,
+        reverify_after: Option<Duration>,
+    ) -> DalResult<Option<L1BatchNumber>> {
+        let l1_batch_number = sqlx::query!(
+            r#"
+            UPDATE tee_verifier_input_producer_jobs
+            SET
+                status = $1,
+                attempts = CASE
+                    WHEN status = $6 THEN 1
+                    ELSE attempts + 1
+                END,
+                updated_at = NOW(),
+                processing_started_at = NOW()
+            WHERE
+                l1_batch_number = (
+                    SELECT
+                        l1_batch_number
+                    FROM
+                        tee_verifier_input_producer_jobs
+                    WHERE
+                        l1_batch_number > (
+                            SELECT
+                                MAX(l1_batch_number)
+                            FROM
+                                tee_verifier_input_producer_jobs
+                        ) - $7
+                        AND (
+                            status = $2
+                            OR (
+                                status = $1
+                                AND processing_started_at < NOW() - $4::INTERVAL
+                            )
+                            OR (
+                                status = $3
+                                AND attempts < $5
+                            )
+                            OR (
+                                status = $6
+                                AND updated_at < NOW() - $8::INTERVAL
+                            )
+                        )
+                    ORDER BY
+                        status = $6 ASC,
+                        l1_batch_number DESC
+                    LIMIT
+                        1
+                    FOR UPDATE
+                        SKIP LOCKED
+                )
+            RETURNING
+                tee_verifier_input_producer_jobs.l1_batch_number
+            "#,
+            TeeVerifierInputProducerJobStatus::InProgress as TeeVerifierInputProducerJobStatus,
+            TeeVerifierInputProducerJobStatus::Queued as TeeVerifierInputProducerJobStatus,
+            TeeVerifierInputProducerJobStatus::Failed as TeeVerifierInputProducerJobStatus,
+            &JOB_PROCESSING_TIMEOUT,
+            JOB_MAX_ATTEMPT,
+            TeeVerifierInputProducerJobStatus::Successful as TeeVerifierInputProducerJobStatus,
+            i64::from(window_size),
+            reverify_after.map(pg_interval_from_duration) as Option<PgInterval>,
+        )
+        .instrument("get_next_tee_verifier_input_producer_job_in_window")
+        .with_arg("window_size", &window_size)
+        .report_latency()
+        .fetch_optional(self.storage)
+        .await?
+        .map(|job| L1BatchNumber(job.l1_batch_number as u32));
+
+        Ok(l1_batch_number)
+    }
+
+    /// Marks the job for the specified L1 batch as in progress, unless it's being processed by another worker
+    /// or was already processed successfully. Unlike [`Self::get_next_tee_verifier_input_producer_job()`], this allows
+    /// processing a specific batch (e.g., as a part of a backfill) without racing with workers processing the queue.
+    /// Similar to picking jobs from the queue, jobs stuck in progress for longer than the processing timeout
+    /// can be claimed.
+    pub async fn claim_job(
+        &mut self,
+        l1_batch_number: L1BatchNumber,
+    ) -> DalResult<TeeVerifierInputProducerJobClaim> {
+        let claimed = sqlx::query!(
+            r#"
+            UPDATE tee_verifier_input_producer_jobs
+            SET
+                status = $1,
+                attempts = attempts + 1,
+                updated_at = NOW(),
+                processing_started_at = NOW()
+            WHERE
+                l1_batch_number = $2
+                AND status != $3
+                AND (
+                    status != $1
+                    OR processing_started_at < NOW() - $4::INTERVAL
+                )
+            RETURNING
+                tee_verifier_input_producer_jobs.l1_batch_number
+            "#,
+            TeeVerifierInputProducerJobStatus::InProgress as TeeVerifierInputProducerJobStatus,
+            i64::from(l1_batch_number.0),
+            TeeVerifierInputProducerJobStatus::Successful as TeeVerifierInputProducerJobStatus,
+            &JOB_PROCESSING_TIMEOUT,
+        )
+        .instrument("claim_job")
+        .with_arg("l1_batch_number", &l1_batch_number)
+        .report_latency()
+        .fetch_optional(self.storage)
+        .await?
+        .is_some();
+        if claimed {
+            return Ok(TeeVerifierInputProducerJobClaim::Claimed);
+        }
+
+        let row = sqlx::query!(
+            r#"
+            SELECT
+                status = $2 AS "is_successful!"
+            FROM
+                tee_verifier_input_producer_jobs
+            WHERE
+                l1_batch_number = $1
+            "#,
+            i64::from(l1_batch_number.0),
+            TeeVerifierInputProducerJobStatus::Successful as TeeVerifierInputProducerJobStatus,
+        )
+        .instrument("claim_job#get_status")
+        .with_arg("l1_batch_number", &l1_batch_number)
+        .fetch_optional(self.storage)
+        .await?;
+
+        Ok(match row {
+            None => TeeVerifierInputProducerJobClaim::Missing,
+            Some(row) if row.is_successful => TeeVerifierInputProducerJobClaim::Successful,
+            Some(_) => TeeVerifierInputProducerJobClaim::InProgress,
+        })
+    }
+
     pub async fn get_tee_verifier_input_producer_job_attempts(
         &mut self,
         l1_batch_number: L1BatchNumber,
@@ -148,13 +370,15 @@ impl TeeVerifierInputProducerDal<'_, '_> {
         Ok(attempts)
     }
 
+    /// Marks the job as successful. This method is idempotent: if the job is already successful (e.g., because it was
+    /// processed by another worker), it is not updated, and `false` is returned.
     pub async fn mark_job_as_successful(
         &mut self,
         l1_batch_number: L1BatchNumber,
         started_at: Instant,
         object_path: &str,
-    ) -> DalResult<()> {
-        sqlx::query!(
+    ) -> DalResult<bool> {
+        let result = sqlx::query!(
             r#"
             UPDATE tee_verifier_input_producer_jobs
             SET
@@ -164,6 +388,7 @@ impl TeeVerifierInputProducerDal<'_, '_> {
                 input_blob_url = $4
             WHERE
                 l1_batch_number = $2
+                AND status != $1
             "#,
             TeeVerifierInputProducerJobStatus::Successful as TeeVerifierInputProducerJobStatus,
             i64::from(l1_batch_number.0),
@@ -176,9 +401,66 @@ impl TeeVerifierInputProducerDal<'_, '_> {
         .execute(self.storage)
         .await?;
 
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Returns the path to the input blob for the specified L1 batch if its job is successful.
+    pub async fn get_successful_job_input_blob_url(
+        &mut self,
+        l1_batch_number: L1BatchNumber,
+    ) -> DalResult<Option<String>> {
+        let row = sqlx::query!(
+            r#"
+            SELECT
+                input_blob_url
+            FROM
+                tee_verifier_input_producer_jobs
+            WHERE
+                l1_batch_number = $1
+                AND status = $2
+            "#,
+            i64::from(l1_batch_number.0),
+            TeeVerifierInputProducerJobStatus::Successful as TeeVerifierInputProducerJobStatus,
+        )
+        .instrument("get_successful_job_input_blob_url")
+        .with_arg("l1_batch_number", &l1_batch_number)
+        .fetch_optional(self.storage)
+        .await?;
+
+        Ok(row.and_then(|row| row.input_blob_url))
+    }
+
+    /// Returns an in-progress job to the queue without counting the processing attempt, e.g. if processing
+    /// was cancelled on shutdown. This is a no-op if the job is not in progress.
+    pub async fn unlock_job(&mut self, l1_batch_number: L1BatchNumber) -> DalResult<()> {
+        sqlx::query!(
+            r#"
+            UPDATE tee_verifier_input_producer_jobs
+            SET
+                status = $1,
+                attempts = GREATEST(attempts - 1, 0),
+                updated_at = NOW(),
+                processing_started_at = NULL
+            WHERE
+                l1_batch_number = $2
+                AND status = $3
+            "#,
+            TeeVerifierInputProducerJobStatus::Queued as TeeVerifierInputProducerJobStatus,
+            i64::from(l1_batch_number.0),
+            TeeVerifierInputProducerJobStatus::InProgress as TeeVerifierInputProducerJobStatus,
+        )
+        .instrument("unlock_job")
+        .with_arg("l1_batch_number", &l1_batch_number)
+        .report_latency()
+        .execute(self.storage)
+        .await?;
+
         Ok(())
     }
 
+    /// Marks an in-progress job as failed and returns the number of processing attempts. If the job is not in progress
+    /// (e.g., it was processed by another worker, or [returned to the queue](Self::unlock_job())), it is not updated,
+    /// and `None` is returned.
     pub async fn mark_job_as_failed(
         &mut self,
         l1_batch_number: L1BatchNumber,
@@ -195,7 +477,7 @@ impl TeeVerifierInputProducerDal<'_, '_> {
                 error = $4
             WHERE
                 l1_batch_number = $2
-                AND status != $5
+                AND status = $5
             RETURNING
                 tee_verifier_input_producer_jobs.attempts
             "#,
@@ -203,7 +485,7 @@ impl TeeVerifierInputProducerDal<'_, '_> {
             i64::from(l1_batch_number.0),
             duration_to_naive_time(started_at.elapsed()),
             error,
-            TeeVerifierInputProducerJobStatus::Successful as TeeVerifierInputProducerJobStatus,
+            TeeVerifierInputProducerJobStatus::InProgress as TeeVerifierInputProducerJobStatus,
         )
         .instrument("mark_job_as_failed")
         .with_arg("l1_batch_number", &l1_batch_number)
@@ -230,3 +512,317 @@ impl TeeVerifierInputProducerDal<'_, '_> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ConnectionPool, CoreDal};
+
+    #[tokio::test]
+    async fn job_returned_to_queue_is_not_marked_as_failed() {
+        let pool = ConnectionPool::<Core>::test_pool().await;
+        let mut conn = pool.connection().await.unwrap();
+        let mut dal = conn.tee_verifier_input_producer_dal();
+        dal.create_tee_verifier_input_producer_job(L1BatchNumber(1))
+            .await
+            .unwrap();
+
+        let job = dal
+            .get_next_tee_verifier_input_producer_job()
+            .await
+            .unwrap();
+        assert_eq!(job, Some(L1BatchNumber(1)));
+        dal.unlock_job(L1BatchNumber(1)).await.unwrap();
+        let attempts = dal
+            .mark_job_as_failed(L1BatchNumber(1), Instant::now(), "error".to_owned())
+            .await
+            .unwrap();
+        assert_eq!(attempts, None);
+
+        // The job is still queued, and the processing attempt is not counted.
+        let attempts = dal
+            .get_tee_verifier_input_producer_job_attempts(L1BatchNumber(1))
+            .await
+            .unwrap();
+        assert_eq!(attempts, Some(0));
+        let job = dal
+            .get_next_tee_verifier_input_producer_job()
+            .await
+            .unwrap();
+        assert_eq!(job, Some(L1BatchNumber(1)));
+        let attempts = dal
+            .mark_job_as_failed(L1BatchNumber(1), Instant::now(), "error".to_owned())
+            .await
+            .unwrap();
+        assert_eq!(attempts, Some(1));
+    }
+
+    async fn create_jobs(dal: &mut TeeVerifierInputProducerDal<'_, '_>, count: u32) {
+        for number in 1..=count {
+            dal.create_tee_verifier_input_producer_job(L1BatchNumber(number))
+                .await
+                .unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn getting_jobs_in_order() {
+        let pool = ConnectionPool::<Core>::test_pool().await;
+        let mut conn = pool.connection().await.unwrap();
+        let mut dal = conn.tee_verifier_input_producer_dal();
+        create_jobs(&mut dal, 3).await;
+
+        let job = dal
+            .get_next_tee_verifier_input_producer_job_in_order(
+                TeeVerifierInputProducerJobOrder::NewestFirst,
+            )
+            .await
+            .unwrap();
+        assert_eq!(job, Some(L1BatchNumber(3)));
+        let job = dal
+            .get_next_tee_verifier_input_producer_job_in_order(
+                TeeVerifierInputProducerJobOrder::OldestFirst,
+            )
+            .await
+            .unwrap();
+        assert_eq!(job, Some(L1BatchNumber(1)));
+        let job = dal
+            .get_next_tee_verifier_input_producer_job_in_order(
+                TeeVerifierInputProducerJobOrder::NewestFirst,
+            )
+            .await
+            .unwrap();
+        assert_eq!(job, Some(L1BatchNumber(2)));
+        let job = dal
+            .get_next_tee_verifier_input_producer_job_in_order(
+                TeeVerifierInputProducerJobOrder::OldestFirst,
+            )
+            .await
+            .unwrap();
+        assert_eq!(job, None);
+    }
+
+    #[tokio::test]
+    async fn getting_jobs_in_priority_order() {
+        let pool = ConnectionPool::<Core>::test_pool().await;
+        let mut conn = pool.connection().await.unwrap();
+        let mut dal = conn.tee_verifier_input_producer_dal();
+        create_jobs(&mut dal, 4).await;
+
+        assert!(dal.set_job_priority(L1BatchNumber(3), 10).await.unwrap());
+        assert!(dal.set_job_priority(L1BatchNumber(2), 5).await.unwrap());
+        assert!(dal.set_job_priority(L1BatchNumber(4), -1).await.unwrap());
+        assert!(!dal.set_job_priority(L1BatchNumber(100), 10).await.unwrap());
+
+        let mut jobs = vec![];
+        while let Some(job) = dal
+            .get_next_tee_verifier_input_producer_job_in_order(
+                TeeVerifierInputProducerJobOrder::Priority,
+            )
+            .await
+            .unwrap()
+        {
+            jobs.push(job.0);
+        }
+        assert_eq!(jobs, [3, 2, 1, 4]);
+
+        // Priorities are ignored for other orders.
+        dal.delete_all_jobs().await.unwrap();
+        create_jobs(&mut dal, 2).await;
+        assert!(dal.set_job_priority(L1BatchNumber(2), 10).await.unwrap());
+        let job = dal
+            .get_next_tee_verifier_input_producer_job()
+            .await
+            .unwrap();
+        assert_eq!(job, Some(L1BatchNumber(1)));
+    }
+
+    #[tokio::test]
+    async fn getting_jobs_in_window() {
+        let pool = ConnectionPool::<Core>::test_pool().await;
+        let mut conn = pool.connection().await.unwrap();
+        let mut dal = conn.tee_verifier_input_producer_dal();
+        create_jobs(&mut dal, 5).await;
+
+        let job = dal
+            .get_next_tee_verifier_input_producer_job_in_window(2, None)
+            .await
+            .unwrap();
+        assert_eq!(job, Some(L1BatchNumber(5)));
+        assert!(dal
+            .mark_job_as_successful(L1BatchNumber(5), Instant::now(), "path/5")
+            .await
+            .unwrap());
+
+        // Unprocessed jobs are picked before re-verified ones.
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let job = dal
+            .get_next_tee_verifier_input_producer_job_in_window(2, Some(Duration::ZERO))
+            .await
+            .unwrap();
+        assert_eq!(job, Some(L1BatchNumber(4)));
+        assert!(dal
+            .mark_job_as_successful(L1BatchNumber(4), Instant::now(), "path/4")
+            .await
+            .unwrap());
+
+        // Jobs outside the window are not picked, and successful jobs are not re-verified by default.
+        let job = dal
+            .get_next_tee_verifier_input_producer_job_in_window(2, None)
+            .await
+            .unwrap();
+        assert_eq!(job, None);
+        let job = dal
+            .get_next_tee_verifier_input_producer_job_in_window(2, Some(Duration::from_secs(3_600)))
+            .await
+            .unwrap();
+        assert_eq!(job, None);
+
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let job = dal
+            .get_next_tee_verifier_input_producer_job_in_window(2, Some(Duration::ZERO))
+            .await
+            .unwrap();
+        assert_eq!(job, Some(L1BatchNumber(5)));
+        // Attempts are reset for re-verified jobs.
+        let attempts = dal
+            .get_tee_verifier_input_producer_job_attempts(L1BatchNumber(5))
+            .await
+            .unwrap();
+        assert_eq!(attempts, Some(1));
+
+        let job = dal
+            .get_next_tee_verifier_input_producer_job_in_window(3, None)
+            .await
+            .unwrap();
+        assert_eq!(job, Some(L1BatchNumber(3)));
+    }
+
+    #[tokio::test]
+    async fn marking_job_as_successful_is_idempotent() {
+        let pool = ConnectionPool::<Core>::test_pool().await;
+        let mut conn = pool.connection().await.unwrap();
+        let mut dal = conn.tee_verifier_input_producer_dal();
+        create_jobs(&mut dal, 1).await;
+
+        let job = dal
+            .get_next_tee_verifier_input_producer_job()
+            .await
+            .unwrap();
+        assert_eq!(job, Some(L1BatchNumber(1)));
+        let url = dal
+            .get_successful_job_input_blob_url(L1BatchNumber(1))
+            .await
+            .unwrap();
+        assert_eq!(url, None);
+
+        assert!(dal
+            .mark_job_as_successful(L1BatchNumber(1), Instant::now(), "path/1")
+            .await
+            .unwrap());
+        assert!(!dal
+            .mark_job_as_successful(L1BatchNumber(1), Instant::now(), "other/path/1")
+            .await
+            .unwrap());
+        let url = dal
+            .get_successful_job_input_blob_url(L1BatchNumber(1))
+            .await
+            .unwrap();
+        assert_eq!(url.as_deref(), Some("path/1"));
+    }
+
+    #[tokio::test]
+    async fn unlocking_job_is_noop_if_job_is_not_in_progress() {
+        let pool = ConnectionPool::<Core>::test_pool().await;
+        let mut conn = pool.connection().await.unwrap();
+        let mut dal = conn.tee_verifier_input_producer_dal();
+        create_jobs(&mut dal, 1).await;
+
+        dal.unlock_job(L1BatchNumber(1)).await.unwrap();
+        let attempts = dal
+            .get_tee_verifier_input_producer_job_attempts(L1BatchNumber(1))
+            .await
+            .unwrap();
+        assert_eq!(attempts, Some(0));
+
+        let job = dal
+            .get_next_tee_verifier_input_producer_job()
+            .await
+            .unwrap();
+        assert_eq!(job, Some(L1BatchNumber(1)));
+        assert!(dal
+            .mark_job_as_successful(L1BatchNumber(1), Instant::now(), "path/1")
+            .await
+            .unwrap());
+        dal.unlock_job(L1BatchNumber(1)).await.unwrap();
+
+        // The job is still successful.
+        let url = dal
+            .get_successful_job_input_blob_url(L1BatchNumber(1))
+            .await
+            .unwrap();
+        assert_eq!(url.as_deref(), Some("path/1"));
+        let attempts = dal
+            .get_tee_verifier_input_producer_job_attempts(L1BatchNumber(1))
+            .await
+            .unwrap();
+        assert_eq!(attempts, Some(1));
+        let job = dal
+            .get_next_tee_verifier_input_producer_job()
+            .await
+            .unwrap();
+        assert_eq!(job, None);
+    }
+
+    #[tokio::test]
+    async fn claiming_job() {
+        let pool = ConnectionPool::<Core>::test_pool().await;
+        let mut conn = pool.connection().await.unwrap();
+        let mut dal = conn.tee_verifier_input_producer_dal();
+        create_jobs(&mut dal, 2).await;
+
+        let claim = dal.claim_job(L1BatchNumber(3)).await.unwrap();
+        assert_eq!(claim, TeeVerifierInputProducerJobClaim::Missing);
+        let claim = dal.claim_job(L1BatchNumber(2)).await.unwrap();
+        assert_eq!(claim, TeeVerifierInputProducerJobClaim::Claimed);
+        let attempts = dal
+            .get_tee_verifier_input_producer_job_attempts(L1BatchNumber(2))
+            .await
+            .unwrap();
+        assert_eq!(attempts, Some(1));
+
+        // The claimed job cannot be claimed again or picked from the queue.
+        let claim = dal.claim_job(L1BatchNumber(2)).await.unwrap();
+        assert_eq!(claim, TeeVerifierInputProducerJobClaim::InProgress);
+        let job = dal
+            .get_next_tee_verifier_input_producer_job_in_order(
+                TeeVerifierInputProducerJobOrder::NewestFirst,
+            )
+            .await
+            .unwrap();
+        assert_eq!(job, Some(L1BatchNumber(1)));
+        let claim = dal.claim_job(L1BatchNumber(1)).await.unwrap();
+        assert_eq!(claim, TeeVerifierInputProducerJobClaim::InProgress);
+
+        // Failed jobs can be claimed.
+        let attempts = dal
+            .mark_job_as_failed(L1BatchNumber(2), Instant::now(), "error".to_owned())
+            .await
+            .unwrap();
+        assert_eq!(attempts, Some(1));
+        let claim = dal.claim_job(L1BatchNumber(2)).await.unwrap();
+        assert_eq!(claim, TeeVerifierInputProducerJobClaim::Claimed);
+
+        assert!(dal
+            .mark_job_as_successful(L1BatchNumber(2), Instant::now(), "path/2")
+            .await
+            .unwrap());
+        let claim = dal.claim_job(L1BatchNumber(2)).await.unwrap();
+        assert_eq!(claim, TeeVerifierInputProducerJobClaim::Successful);
+        let attempts = dal
+            .get_tee_verifier_input_producer_job_attempts(L1BatchNumber(2))
+            .await
+            .unwrap();
+        assert_eq!(attempts, Some(2));
+    }
+}
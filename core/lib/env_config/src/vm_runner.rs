@@ -18,16 +18,19 @@ impl FromEnv for BasicWitnessInputProducerConfig {
 
 impl FromEnv for ExperimentalVmConfig {
     fn from_env() -> anyhow::Result<Self> {
-        Ok(Self {
+        let config = Self {
             playground: envy_load("experimental_vm.playground", "EXPERIMENTAL_VM_PLAYGROUND_")?,
             ..envy_load("experimental_vm", "EXPERIMENTAL_VM_")?
-        })
+        };
+        config.playground.validate_dumps_compression_level()?;
+        Ok(config)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use zksync_basic_types::{vm::FastVmMode, L1BatchNumber};
+    use zksync_config::configs::VmPlaygroundDumpFormat;
 
     use super::*;
     use crate::test_utils::EnvMutex;
@@ -59,6 +62,8 @@ mod tests {
             EXPERIMENTAL_VM_PLAYGROUND_DB_PATH=/db/vm_playground
             EXPERIMENTAL_VM_PLAYGROUND_FIRST_PROCESSED_BATCH=123
             EXPERIMENTAL_VM_PLAYGROUND_RESET=true
+            EXPERIMENTAL_VM_PLAYGROUND_DUMPS_FORMAT=bincode
+            EXPERIMENTAL_VM_PLAYGROUND_DUMPS_COMPRESSION_LEVEL=5
         "#;
         lock.set_env(config);
 
@@ -68,6 +73,11 @@ mod tests {
         assert_eq!(config.playground.db_path.unwrap(), "/db/vm_playground");
         assert_eq!(config.playground.first_processed_batch, L1BatchNumber(123));
         assert!(config.playground.reset);
+        assert_eq!(
+            config.playground.dumps_format,
+            VmPlaygroundDumpFormat::Bincode
+        );
+        assert_eq!(config.playground.dumps_compression_level, Some(5));
 
         lock.remove_env(&["EXPERIMENTAL_VM_PLAYGROUND_RESET"]);
         let config = ExperimentalVmConfig::from_env().unwrap();
@@ -84,5 +94,20 @@ mod tests {
         lock.remove_env(&["EXPERIMENTAL_VM_PLAYGROUND_DB_PATH"]);
         let config = ExperimentalVmConfig::from_env().unwrap();
         assert!(config.playground.db_path.is_none());
+
+        lock.set_env("EXPERIMENTAL_VM_PLAYGROUND_DUMPS_COMPRESSION_LEVEL=23");
+        let err = ExperimentalVmConfig::from_env().unwrap_err();
+        assert!(
+            err.to_string().contains("dumps_compression_level 23"),
+            "{err:#}"
+        );
+
+        lock.remove_env(&[
+            "EXPERIMENTAL_VM_PLAYGROUND_DUMPS_FORMAT",
+            "EXPERIMENTAL_VM_PLAYGROUND_DUMPS_COMPRESSION_LEVEL",
+        ]);
+        let config = ExperimentalVmConfig::from_env().unwrap();
+        assert_eq!(config.playground.dumps_format, VmPlaygroundDumpFormat::Json);
+        assert_eq!(config.playground.dumps_compression_level, None);
     }
 }
@@ -1,12 +1,15 @@
 //! Shadow VM tests. Since there are no real VM implementations in the `vm_interface` crate where `ShadowVm` is defined,
 //! these tests are placed here.
 
+use std::sync::{Arc, Mutex};
+
 use assert_matches::assert_matches;
 use ethabi::Contract;
 use zksync_contracts::{
     get_loadnext_contract, load_contract, read_bytecode,
     test_contracts::LoadnextContractExecutionParams,
 };
+use zksync_system_constants::CONTRACT_DEPLOYER_ADDRESS;
 use zksync_test_account::{Account, TxType};
 use zksync_types::{
     block::L2BlockHasher, fee::Fee, AccountTreeId, Address, Execute, L1BatchNumber, L2BlockNumber,
@@ -17,8 +20,14 @@ use zksync_utils::bytecode::hash_bytecode;
 use crate::{
     interface::{
         storage::{InMemoryStorage, ReadStorage, StorageView},
-        utils::{ShadowVm, VmDump},
-        ExecutionResult, L1BatchEnv, L2BlockEnv, VmFactory, VmInterface, VmInterfaceExt,
+        utils::{
+            diff_dumps, testonly::DivergingVm, DivergenceCallback, DivergenceErrors,
+            DivergenceHandler, DivergenceSeverities, DivergenceSeverity, ShadowActivation,
+            ShadowActivationInput, ShadowVm, TracerComparator, VmDump, VmDumpCompression,
+            VmDumpFormat, ISOLATED_DIVERGENCE_HALT_REASON,
+        },
+        ExecutionResult, Halt, L1BatchEnv, L2BlockEnv, VmExecutionResultAndLogs, VmFactory,
+        VmInspectExecutionState, VmInterface, VmInterfaceExt, VmInterfaceHistoryEnabled,
     },
     utils::get_max_gas_per_pubdata_byte,
     versions::testonly::{
@@ -216,6 +225,29 @@ where
     (vm, harness)
 }
 
+type DivergingShadowVm = ShadowVm<InMemoryStorage, ReferenceVm, DivergingVm<ReferenceVm>>;
+
+/// Creates a shadow VM with a [`DivergingVm`] stub mutating execution results using `mutate_result`, and a harness
+/// to execute transactions on it.
+fn diverging_shadow_vm(
+    mutate_result: impl FnMut(&mut VmExecutionResultAndLogs) + 'static,
+) -> (DivergingShadowVm, Harness) {
+    let system_env = default_system_env();
+    let l1_batch_env = default_l1_batch(L1BatchNumber(1));
+    let mut storage = InMemoryStorage::with_system_contracts(hash_bytecode);
+    let harness = Harness::new(&l1_batch_env);
+    harness.setup_storage(&mut storage);
+
+    let main_storage = StorageView::new(storage.clone()).to_rc_ptr();
+    let shadow_storage = StorageView::new(storage).to_rc_ptr();
+    let shadow = DivergingVm::new(
+        ReferenceVm::new(l1_batch_env.clone(), system_env.clone(), shadow_storage),
+        mutate_result,
+    );
+    let vm = ShadowVm::with_shadow_vm(l1_batch_env, system_env, main_storage, shadow);
+    (vm, harness)
+}
+
 #[test]
 fn sanity_check_harness() {
     sanity_check_vm::<ReferenceVm>();
@@ -246,6 +278,339 @@ fn sanity_check_shadow_vm() {
     harness.execute_on_vm(&mut vm);
 }
 
+#[test]
+fn shadow_vm_with_concurrent_finish_batch_comparison() {
+    let system_env = default_system_env();
+    let l1_batch_env = default_l1_batch(L1BatchNumber(1));
+    let mut storage = InMemoryStorage::with_system_contracts(hash_bytecode);
+    let mut harness = Harness::new(&l1_batch_env);
+    harness.setup_storage(&mut storage);
+
+    let storage = StorageView::new(storage).to_rc_ptr();
+    let mut vm = ShadowedFastVm::new(l1_batch_env, system_env, storage);
+    vm.set_finish_batch_concurrency(3);
+    // The VMs must not diverge, so comparing finished batches concurrently must not panic.
+    harness.execute_on_vm(&mut vm);
+}
+
+/// Tracer comparator that always reports a divergence.
+#[derive(Debug)]
+struct DivergingTracerComparator;
+
+impl<MainTracer, ShadowTracer> TracerComparator<MainTracer, ShadowTracer>
+    for DivergingTracerComparator
+{
+    fn compare(&self, _main: &MainTracer, _shadow: &ShadowTracer, errors: &mut DivergenceErrors) {
+        errors.check_match("tracer", &"main", &"shadow");
+    }
+}
+
+#[test]
+fn shadow_vm_with_tracer_comparator() {
+    let system_env = default_system_env();
+    let l1_batch_env = default_l1_batch(L1BatchNumber(1));
+    let mut storage = InMemoryStorage::with_system_contracts(hash_bytecode);
+    let mut harness = Harness::new(&l1_batch_env);
+    harness.setup_storage(&mut storage);
+
+    let main_storage = StorageView::new(&storage).to_rc_ptr();
+    let shadow_storage = StorageView::new(&storage).to_rc_ptr();
+    let mut vm = ShadowVm::<_, ReferenceVm<_>, ReferenceVm<_>>::with_custom_shadow(
+        l1_batch_env,
+        system_env,
+        main_storage,
+        shadow_storage,
+    )
+    .with_tracer_comparator(DivergingTracerComparator);
+
+    let divergence = Arc::new(Mutex::new(None));
+    vm.set_divergence_handler(DivergenceHandler::new({
+        let divergence = divergence.clone();
+        move |err, _| {
+            *divergence.lock().unwrap() = Some(err.to_string());
+        }
+    }));
+    harness.execute_on_vm(&mut vm);
+
+    let divergence = divergence.lock().unwrap().take().expect("no divergence");
+    assert!(divergence.contains("`tracer` mismatch"), "{divergence}");
+}
+
+#[test]
+fn shadow_vm_with_diverging_stub() {
+    let (mut vm, mut harness) = diverging_shadow_vm(|result| result.statistics.gas_remaining += 1);
+
+    let dump_dir = tempfile::TempDir::new().unwrap();
+    let dump_path = dump_dir.path().join("dump.json");
+    let contexts = Arc::new(Mutex::new(vec![]));
+    vm.set_divergence_handler(DivergenceHandler::new({
+        let contexts = contexts.clone();
+        let dump_path = dump_path.clone();
+        move |err, dump| {
+            contexts
+                .lock()
+                .unwrap()
+                .extend(err.contexts().map(str::to_owned));
+            // The shadow VM only mutates the execution result, so it performs the same number of steps.
+            let steps = err.execution_steps().expect("no execution steps");
+            assert!(steps.main > 0);
+            assert_eq!(steps.main, steps.shadow);
+            // The divergence is detected on the first transaction.
+            assert_eq!(err.tx_count(), Some(1));
+            let dump = serde_json::to_string(&dump).unwrap();
+            std::fs::write(&dump_path, dump).unwrap();
+        }
+    }));
+    harness.execute_on_vm(&mut vm);
+
+    let contexts = contexts.lock().unwrap().clone();
+    assert_eq!(contexts, ["gas_remaining"]);
+    let dump = std::fs::read_to_string(&dump_path).unwrap();
+    let dump: VmDump = serde_json::from_str(&dump).unwrap();
+    assert_eq!(dump.l1_batch_number(), L1BatchNumber(1));
+    // The divergence is detected on the first transaction, so the dump contains only it.
+    let tx_count: usize = dump.l2_blocks.iter().map(|block| block.txs.len()).sum();
+    assert_eq!(tx_count, 1);
+}
+
+#[test]
+fn shadow_vm_spilling_dumps_to_disk() {
+    let formats = [
+        (VmDumpFormat::Json, VmDumpCompression::None),
+        (VmDumpFormat::Bincode, VmDumpCompression::default()),
+    ];
+    for (format, compression) in formats {
+        test_spilling_dumps_to_disk(format, compression);
+    }
+}
+
+fn test_spilling_dumps_to_disk(format: VmDumpFormat, compression: VmDumpCompression) {
+    let (mut vm, mut harness) = diverging_shadow_vm(|result| result.statistics.gas_remaining += 1);
+
+    let dump_dir = tempfile::TempDir::new().unwrap();
+    let spilled_paths = Arc::new(Mutex::new(vec![]));
+    // Any dump exceeds the zero threshold, so the in-memory handler must never be called.
+    let handler = DivergenceHandler::new(|_, _| panic!("dump should be spilled to disk"));
+    let handler = handler.with_dump_spill(0, dump_dir.path(), format, compression, {
+        let spilled_paths = spilled_paths.clone();
+        move |err, path| {
+            let path = path.unwrap_or_else(|err| {
+                panic!("failed spilling {format:?} dump with {compression:?}: {err:#}")
+            });
+            assert_eq!(err.contexts().collect::<Vec<_>>(), ["gas_remaining"]);
+            let expected_name =
+                VmDump::file_name(L1BatchNumber(1), err.stable_hash(), format, compression);
+            assert_eq!(
+                path.file_name().unwrap(),
+                expected_name.as_str(),
+                "{format:?}, {compression:?}"
+            );
+            spilled_paths.lock().unwrap().push(path.to_owned());
+        }
+    });
+    vm.set_divergence_handler(handler);
+    harness.execute_on_vm(&mut vm);
+
+    let spilled_paths = spilled_paths.lock().unwrap();
+    assert_eq!(spilled_paths.len(), 1, "{format:?}, {compression:?}");
+    assert!(spilled_paths[0].starts_with(dump_dir.path()));
+    let dump = VmDump::read_from_file(&spilled_paths[0]).unwrap();
+    assert_eq!(dump.l1_batch_number(), L1BatchNumber(1));
+    let tx_count: usize = dump.l2_blocks.iter().map(|block| block.txs.len()).sum();
+    assert_eq!(tx_count, 1, "{format:?}, {compression:?}");
+    // No temporary files should be left over.
+    let file_count = std::fs::read_dir(dump_dir.path()).unwrap().count();
+    assert_eq!(file_count, 1, "{format:?}, {compression:?}");
+}
+
+#[test]
+fn shadow_vm_reporting_divergence_if_spilling_fails() {
+    let (mut vm, mut harness) = diverging_shadow_vm(|result| result.statistics.gas_remaining += 1);
+
+    let dump_dir = tempfile::TempDir::new().unwrap();
+    let spill_errors = Arc::new(Mutex::new(vec![]));
+    let handler = DivergenceHandler::new(|_, _| panic!("dump should be spilled to disk"));
+    // Unsupported compression level makes writing the dump fail after the file is created.
+    let compression = VmDumpCompression::Zstd { level: 100 };
+    let handler = handler.with_dump_spill(0, dump_dir.path(), VmDumpFormat::Json, compression, {
+        let spill_errors = spill_errors.clone();
+        move |err, path| {
+            assert_eq!(err.contexts().collect::<Vec<_>>(), ["gas_remaining"]);
+            let spill_err = path.expect_err("spilling should fail");
+            spill_errors.lock().unwrap().push(format!("{spill_err:#}"));
+        }
+    });
+    vm.set_divergence_handler(handler);
+    harness.execute_on_vm(&mut vm);
+
+    let spill_errors = spill_errors.lock().unwrap();
+    assert_eq!(spill_errors.len(), 1);
+    assert!(
+        spill_errors[0].contains("compression level 100"),
+        "{spill_errors:?}"
+    );
+    // The partially written dump is removed.
+    let file_count = std::fs::read_dir(dump_dir.path()).unwrap().count();
+    assert_eq!(file_count, 0);
+}
+
+#[test]
+fn streamed_vm_dumps_match_in_memory_dumps() {
+    let (vm, _) = sanity_check_vm::<ShadowedFastVm>();
+    let dump = vm.dump_state();
+
+    for format in [VmDumpFormat::Json, VmDumpFormat::Bincode] {
+        let mut streamed = vec![];
+        vm.write_dump(&mut streamed, format, VmDumpCompression::None)
+            .unwrap();
+        assert!(
+            streamed == dump.to_bytes(format).unwrap(),
+            "streamed {format:?} dump differs from the in-memory one"
+        );
+
+        let mut compressed = vec![];
+        vm.write_dump(&mut compressed, format, VmDumpCompression::default())
+            .unwrap();
+        let restored_dump = VmDump::from_bytes(&compressed).unwrap();
+        assert_eq!(restored_dump, dump, "{format:?}");
+    }
+}
+
+#[test]
+fn shadow_vm_disabled_by_switch() {
+    let (mut vm, mut harness) = diverging_shadow_vm(|result| result.statistics.gas_remaining += 1);
+    // The default divergence handler panics, so the test would fail if the shadow VM was not dropped.
+    let (switch_sender, switch) = tokio::sync::watch::channel(false);
+    vm.set_shadow_switch(switch);
+    harness.execute_on_vm(&mut vm);
+
+    // Re-enabling the switch doesn't resurrect the dropped shadow VM.
+    switch_sender.send_replace(true);
+    vm.finish_batch();
+}
+
+#[test]
+fn shadow_vm_deactivated_by_predicate() {
+    let (mut vm, mut harness) = diverging_shadow_vm(|result| result.statistics.gas_remaining += 1);
+    // Only transactions deploying contracts are shadowed; the first transaction in the harness is a transfer,
+    // so the shadow VM should be dropped before it's executed. Otherwise, the default divergence handler would panic.
+    let evaluated_txs = Arc::new(Mutex::new(0));
+    vm.set_shadow_activation(ShadowActivation::new({
+        let evaluated_txs = evaluated_txs.clone();
+        move |input| match input {
+            ShadowActivationInput::BatchInit(env) => env.number == L1BatchNumber(1),
+            ShadowActivationInput::Transaction(tx) => {
+                *evaluated_txs.lock().unwrap() += 1;
+                tx.execute.contract_address == Some(CONTRACT_DEPLOYER_ADDRESS)
+            }
+            _ => true,
+        }
+    }));
+    harness.execute_on_vm(&mut vm);
+
+    // The predicate is not evaluated after the shadow VM is dropped.
+    assert_eq!(*evaluated_txs.lock().unwrap(), 1);
+}
+
+#[test]
+fn shadow_vm_with_panicking_divergence_callback() {
+    let (mut vm, mut harness) = diverging_shadow_vm(|result| result.statistics.gas_remaining += 1);
+
+    let callback_contexts = Arc::new(Mutex::new(vec![]));
+    vm.set_divergence_callback(DivergenceCallback::new({
+        let callback_contexts = callback_contexts.clone();
+        move |err| {
+            callback_contexts
+                .lock()
+                .unwrap()
+                .extend(err.contexts().map(str::to_owned));
+            panic!("callback failure");
+        }
+    }));
+    let handled_count = Arc::new(Mutex::new(0));
+    vm.set_divergence_handler(DivergenceHandler::new({
+        let handled_count = handled_count.clone();
+        move |_, _| *handled_count.lock().unwrap() += 1
+    }));
+    harness.execute_on_vm(&mut vm);
+
+    // The panic in the callback is caught, and the divergence is still passed to the handler.
+    let callback_contexts = callback_contexts.lock().unwrap().clone();
+    assert_eq!(callback_contexts, ["gas_remaining"]);
+    assert_eq!(*handled_count.lock().unwrap(), 1);
+}
+
+#[test]
+fn shadow_vm_isolating_diverging_tx() {
+    let mut call_count = 0;
+    let (mut vm, mut harness) = diverging_shadow_vm(move |result| {
+        // Only the first executed transaction diverges.
+        if call_count == 0 {
+            result.statistics.gas_remaining += 1;
+        }
+        call_count += 1;
+    });
+    vm.set_divergent_tx_isolation(true);
+    let divergences = Arc::new(Mutex::new(vec![]));
+    vm.set_divergence_handler(DivergenceHandler::new({
+        let divergences = divergences.clone();
+        move |err, _| {
+            divergences.lock().unwrap().push(err.to_string());
+        }
+    }));
+
+    let transfer_exec = Execute {
+        contract_address: Some(harness.bob.address()),
+        calldata: vec![],
+        value: 1_000_000_000.into(),
+        factory_deps: vec![],
+    };
+    let transfer = harness.alice.get_l2_tx_for_execute(transfer_exec, None);
+    vm.make_snapshot();
+    let (_, exec_result) = vm.execute_transaction_with_bytecode_compression(transfer.clone(), true);
+    assert_matches!(
+        &exec_result.result,
+        ExecutionResult::Halt { reason: Halt::TracerCustom(reason) }
+            if reason == ISOLATED_DIVERGENCE_HALT_REASON
+    );
+    vm.rollback_to_the_latest_snapshot();
+    assert_eq!(divergences.lock().unwrap().len(), 1);
+
+    // The shadow VM is kept, and its state is re-synchronized with the main VM, so the re-executed transaction
+    // doesn't diverge.
+    vm.make_snapshot();
+    let (compression_result, exec_result) =
+        vm.execute_transaction_with_bytecode_compression(transfer, true);
+    compression_result.unwrap();
+    assert!(!exec_result.result.is_failed(), "{exec_result:#?}");
+    vm.pop_snapshot_no_rollback();
+    vm.finish_batch();
+
+    let divergences = divergences.lock().unwrap();
+    assert_eq!(divergences.len(), 1, "{divergences:?}");
+    assert!(divergences[0].contains("gas_remaining"), "{divergences:?}");
+}
+
+#[test]
+fn inspecting_shadow_vm_execution_state() {
+    let (vm, _) = sanity_check_vm::<ShadowedFastVm>();
+    let shadow_state = vm.shadow_execution_state().expect("shadow VM was dropped");
+    let (reference_vm, _) = sanity_check_vm::<ReferenceVm>();
+    let reference_state = reference_vm.current_execution_state();
+
+    assert!(!shadow_state.events.is_empty());
+    assert_eq!(shadow_state.events, reference_state.events);
+    assert_eq!(shadow_state.system_logs, reference_state.system_logs);
+    assert_eq!(
+        shadow_state.user_l2_to_l1_logs,
+        reference_state.user_l2_to_l1_logs
+    );
+
+    // Getting the state compares main and shadow states; the default handler panics on divergence.
+    let main_state = vm.current_execution_state();
+    assert_eq!(main_state.events, reference_state.events);
+}
+
 #[test]
 fn shadow_vm_basics() {
     let (vm, harness) = sanity_check_vm::<ShadowedFastVm>();
@@ -274,3 +639,251 @@ fn shadow_vm_basics() {
     let new_dump = vm.dump_state();
     pretty_assertions::assert_eq!(new_dump, dump);
 }
+
+#[test]
+fn diffing_vm_dumps() {
+    let (vm, _) = sanity_check_vm::<ShadowedFastVm>();
+    let dump = vm.dump_state();
+    let errors = diff_dumps(&dump, &dump.clone());
+    assert_eq!(errors.contexts().count(), 0);
+
+    let mut tampered_dump = dump.clone();
+    tampered_dump.l1_batch_env.timestamp += 1;
+    tampered_dump.l2_blocks.last_mut().unwrap().txs.pop();
+    let errors = diff_dumps(&dump, &tampered_dump);
+    let contexts: Vec<_> = errors.contexts().collect();
+    assert_eq!(contexts, ["l1_batch_env", "l2_blocks"]);
+}
+
+/// Checks object keys and types in `value` against the (subset of) JSON schema features used in `VmDump::json_schema()`.
+fn assert_conforms_to_schema(
+    value: &serde_json::Value,
+    schema: &serde_json::Value,
+    root: &serde_json::Value,
+    path: &str,
+) {
+    if let Some(reference) = schema.get("$ref") {
+        let name = reference
+            .as_str()
+            .unwrap()
+            .strip_prefix("#/$defs/")
+            .unwrap();
+        let schema = &root["$defs"][name];
+        return assert_conforms_to_schema(value, schema, root, path);
+    }
+    if let Some(variants) = schema.get("oneOf") {
+        let mut variants = variants.as_array().unwrap().iter();
+        if value.is_null() {
+            assert!(
+                variants.any(|variant| variant["type"] == "null"),
+                "{path}: unexpected null"
+            );
+        } else {
+            let variant = variants.find(|variant| variant["type"] != "null").unwrap();
+            assert_conforms_to_schema(value, variant, root, path);
+        }
+        return;
+    }
+
+    match schema.get("type").and_then(serde_json::Value::as_str) {
+        Some("object") => {
+            let object = value
+                .as_object()
+                .unwrap_or_else(|| panic!("{path}: expected object, got {value}"));
+            for required in schema["required"].as_array().into_iter().flatten() {
+                let required = required.as_str().unwrap();
+                assert!(
+                    object.contains_key(required),
+                    "{path}: missing required field `{required}`"
+                );
+            }
+            for (key, field) in object {
+                let field_path = format!("{path}.{key}");
+                if let Some(field_schema) =
+                    schema.get("properties").and_then(|props| props.get(key))
+                {
+                    assert_conforms_to_schema(field, field_schema, root, &field_path);
+                } else if let Some(values_schema) = schema
+                    .get("additionalProperties")
+                    .filter(|val| val.is_object())
+                {
+                    assert_conforms_to_schema(field, values_schema, root, &field_path);
+                } else {
+                    assert_ne!(
+                        schema["additionalProperties"], false,
+                        "{path}: unexpected field `{key}`"
+                    );
+                }
+            }
+        }
+        Some("array") => {
+            let array = value
+                .as_array()
+                .unwrap_or_else(|| panic!("{path}: expected array, got {value}"));
+            if let Some(items_schema) = schema.get("items").filter(|items| items.is_object()) {
+                for (i, item) in array.iter().enumerate() {
+                    assert_conforms_to_schema(item, items_schema, root, &format!("{path}[{i}]"));
+                }
+            }
+        }
+        Some("string") => assert!(value.is_string(), "{path}: expected string, got {value}"),
+        Some("integer") => assert!(
+            value.is_u64() || value.is_i64(),
+            "{path}: expected integer, got {value}"
+        ),
+        Some("boolean") => assert!(value.is_boolean(), "{path}: expected boolean, got {value}"),
+        _ => { /* not checked */ }
+    }
+}
+
+#[test]
+fn vm_dump_conforms_to_json_schema() {
+    let system_env = default_system_env();
+    let l1_batch_env = default_l1_batch(L1BatchNumber(1));
+    let mut storage = InMemoryStorage::with_system_contracts(hash_bytecode);
+    let mut harness = Harness::new(&l1_batch_env);
+    harness.setup_storage(&mut storage);
+
+    let storage = StorageView::new(storage).to_rc_ptr();
+    let mut vm = ShadowedFastVm::new(l1_batch_env, system_env, storage);
+    vm.record_outputs();
+    harness.execute_on_vm(&mut vm);
+    let dump = vm.dump_state();
+    assert!(!dump.outputs.is_empty());
+    let dump = serde_json::to_value(dump).unwrap();
+
+    let schema = VmDump::json_schema();
+    assert_conforms_to_schema(&dump, &schema, &schema, "$");
+}
+
+#[test]
+fn vm_dump_serialization_formats() {
+    let system_env = default_system_env();
+    let l1_batch_env = default_l1_batch(L1BatchNumber(1));
+    let mut storage = InMemoryStorage::with_system_contracts(hash_bytecode);
+    let mut harness = Harness::new(&l1_batch_env);
+    harness.setup_storage(&mut storage);
+
+    let storage = StorageView::new(storage).to_rc_ptr();
+    let mut vm = ShadowedFastVm::new(l1_batch_env, system_env, storage);
+    vm.record_outputs();
+    harness.execute_on_vm(&mut vm);
+    let dump = vm.dump_state();
+    assert!(!dump.outputs.is_empty());
+
+    let json_bytes = dump.to_bytes(VmDumpFormat::Json).unwrap();
+    assert_eq!(VmDumpFormat::detect(&json_bytes), VmDumpFormat::Json);
+    let binary_bytes = dump.to_bytes(VmDumpFormat::Bincode).unwrap();
+    assert_eq!(VmDumpFormat::detect(&binary_bytes), VmDumpFormat::Bincode);
+    assert!(binary_bytes.len() < json_bytes.len());
+
+    let compressed_bytes = dump
+        .to_compressed_bytes(VmDumpFormat::Json, VmDumpCompression::default())
+        .unwrap();
+    assert!(compressed_bytes.len() < json_bytes.len());
+    let err = dump
+        .to_compressed_bytes(VmDumpFormat::Json, VmDumpCompression::Zstd { level: 100 })
+        .unwrap_err();
+    assert!(err.to_string().contains("compression level"), "{err}");
+
+    let dump_dir = tempfile::TempDir::new().unwrap();
+    let compressions = [
+        VmDumpCompression::None,
+        VmDumpCompression::Zstd { level: 1 },
+        VmDumpCompression::default(),
+    ];
+    for format in [VmDumpFormat::Json, VmDumpFormat::Bincode] {
+        for compression in compressions {
+            let dump_path = dump_dir.path().join(format!(
+                "dump.{}.{}",
+                format.file_extension(),
+                compression.file_extension().unwrap_or("raw")
+            ));
+            dump.dump_to_file(&dump_path, format, compression).unwrap();
+            let restored_dump = VmDump::read_from_file(&dump_path).unwrap();
+            pretty_assertions::assert_eq!(restored_dump, dump, "{format:?}, {compression:?}");
+        }
+    }
+
+    // Dumps without recorded outputs should be handled as well.
+    let mut dump = dump;
+    dump.outputs.clear();
+    let binary_bytes = dump.to_bytes(VmDumpFormat::Bincode).unwrap();
+    assert_eq!(VmDump::from_bytes(&binary_bytes).unwrap(), dump);
+}
+
+#[test]
+fn shadow_vm_with_recorded_outputs() {
+    let system_env = default_system_env();
+    let l1_batch_env = default_l1_batch(L1BatchNumber(1));
+    let mut storage = InMemoryStorage::with_system_contracts(hash_bytecode);
+    let mut harness = Harness::new(&l1_batch_env);
+    harness.setup_storage(&mut storage);
+
+    let storage = StorageView::new(storage).to_rc_ptr();
+    let mut vm = ShadowedFastVm::new(l1_batch_env, system_env, storage);
+    vm.record_outputs();
+    harness.execute_on_vm(&mut vm);
+    let dump = vm.dump_state();
+
+    let operations: Vec<_> = dump
+        .outputs
+        .iter()
+        .map(|outputs| outputs.operation.as_str())
+        .collect();
+    assert_eq!(
+        operations,
+        ["inspect_transaction"; 5]
+            .into_iter()
+            .chain(["finish_batch"])
+            .collect::<Vec<_>>()
+    );
+
+    // Playing back the dump on the reference VM shouldn't lead to divergences.
+    dump.clone()
+        .play_back_with_recorded_outputs::<ReferenceVm<_>>();
+
+    // Tamper with the recorded outputs and check that the divergence is detected.
+    let divergence = play_back_with_tampered_outputs(dump.clone(), DivergenceSeverities::default())
+        .expect("no divergence");
+    assert!(
+        divergence.contains("`gas_remaining` mismatch"),
+        "{divergence}"
+    );
+
+    // Divergences with lower severity should only be logged.
+    let severities =
+        DivergenceSeverities::default().with_context("gas_remaining", DivergenceSeverity::Warn);
+    let divergence = play_back_with_tampered_outputs(dump, severities);
+    assert_eq!(divergence, None);
+}
+
+fn play_back_with_tampered_outputs(
+    mut dump: VmDump,
+    severities: DivergenceSeverities,
+) -> Option<String> {
+    let mut outputs = std::mem::take(&mut dump.outputs);
+    *outputs[0].values.get_mut("gas_remaining").unwrap() = "0".to_owned();
+
+    let divergence = Arc::new(Mutex::new(None));
+    let divergence_handler = DivergenceHandler::new({
+        let divergence = divergence.clone();
+        move |err, _| {
+            *divergence.lock().unwrap() = Some(err.to_string());
+        }
+    });
+    dump.play_back_custom(|l1_batch_env, system_env, storage| {
+        let mut vm = ShadowVm::<_, ReferenceVm<_>, ReferenceVm<_>>::with_recorded_outputs(
+            l1_batch_env,
+            system_env,
+            storage,
+            outputs,
+        );
+        vm.set_divergence_handler(divergence_handler);
+        vm.set_divergence_severities(severities);
+        vm
+    });
+
+    let divergence = divergence.lock().unwrap().take();
+    divergence
+}
@@ -35,8 +35,8 @@ use crate::{
         BytecodeCompressionError, BytecodeCompressionResult, CurrentExecutionState,
         ExecutionResult, FinishedL1Batch, Halt, L1BatchEnv, L2BlockEnv, Refunds, SystemEnv,
         TxRevertReason, VmEvent, VmExecutionLogs, VmExecutionMode, VmExecutionResultAndLogs,
-        VmExecutionStatistics, VmFactory, VmInterface, VmInterfaceHistoryEnabled, VmMemoryMetrics,
-        VmRevertReason, VmTrackingContracts,
+        VmExecutionStatistics, VmFactory, VmInspectExecutionState, VmInterface,
+        VmInterfaceHistoryEnabled, VmMemoryMetrics, VmRevertReason, VmTrackingContracts,
     },
     utils::events::extract_l2tol1logs_from_l1_messenger,
     vm_fast::{
@@ -699,6 +699,12 @@ impl<S: ReadStorage> VmTrackingContracts for Vm<S> {
     }
 }
 
+impl<S: ReadStorage, Tr: Tracer + Default + 'static> VmInspectExecutionState for Vm<S, Tr> {
+    fn current_execution_state(&self) -> CurrentExecutionState {
+        self.get_current_execution_state()
+    }
+}
+
 impl<S: fmt::Debug, Tr: fmt::Debug> fmt::Debug for Vm<S, Tr> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Vm")
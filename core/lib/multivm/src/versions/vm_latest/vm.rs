@@ -12,8 +12,8 @@ use crate::{
         storage::{StoragePtr, WriteStorage},
         BytecodeCompressionError, BytecodeCompressionResult, CurrentExecutionState,
         FinishedL1Batch, L1BatchEnv, L2BlockEnv, SystemEnv, VmExecutionMode,
-        VmExecutionResultAndLogs, VmFactory, VmInterface, VmInterfaceHistoryEnabled,
-        VmMemoryMetrics, VmTrackingContracts,
+        VmExecutionResultAndLogs, VmFactory, VmInspectExecutionState, VmInterface,
+        VmInterfaceHistoryEnabled, VmMemoryMetrics, VmTrackingContracts,
     },
     utils::events::extract_l2tol1logs_from_l1_messenger,
     vm_latest::{
@@ -240,6 +240,12 @@ impl<S: WriteStorage> VmInterfaceHistoryEnabled for Vm<S, HistoryEnabled> {
     }
 }
 
+impl<S: WriteStorage, H: HistoryMode> VmInspectExecutionState for Vm<S, H> {
+    fn current_execution_state(&self) -> CurrentExecutionState {
+        self.get_current_execution_state()
+    }
+}
+
 impl<S: WriteStorage, H: HistoryMode> VmTrackingContracts for Vm<S, H> {
     fn used_contract_hashes(&self) -> Vec<H256> {
         self.get_used_contracts()
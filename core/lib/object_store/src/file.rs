@@ -1,9 +1,13 @@
-use std::fmt::Debug;
+use std::{fmt::Debug, io::Write as _};
 
 use async_trait::async_trait;
-use tokio::{fs, io};
+use tokio::{
+    fs, io,
+    runtime::{self, RuntimeFlavor},
+    task,
+};
 
-use crate::raw::{Bucket, ObjectStore, ObjectStoreError};
+use crate::raw::{Bucket, ObjectStore, ObjectStoreError, ValueWriter};
 
 impl From<io::Error> for ObjectStoreError {
     fn from(err: io::Error) -> Self {
@@ -73,6 +77,27 @@ impl ObjectStore for FileBackedObjectStore {
         fs::write(filename, value).await.map_err(From::from)
     }
 
+    /// Writes the value directly to a temporary file, which is then renamed to the target file. Since the value writer
+    /// is synchronous, the file is written in a blocking section, so that it doesn't stall other tasks. I/O errors
+    /// (including ones returned by the value writer) are considered retriable.
+    async fn put_raw_streaming(
+        &self,
+        bucket: Bucket,
+        key: &str,
+        write_value: &ValueWriter<'_>,
+    ) -> Result<(), ObjectStoreError> {
+        let filename = self.filename(bucket, key);
+        let tmp_filename = format!("{filename}.tmp");
+        let write_result = run_blocking(|| write_file(&tmp_filename, write_value));
+        if let Err(err) = write_result {
+            fs::remove_file(&tmp_filename).await.ok();
+            return Err(err);
+        }
+        fs::rename(tmp_filename, filename)
+            .await
+            .map_err(retriable_io_error)
+    }
+
     async fn remove_raw(&self, bucket: Bucket, key: &str) -> Result<(), ObjectStoreError> {
         let filename = self.filename(bucket, key);
         fs::remove_file(filename).await.map_err(From::from)
@@ -83,8 +108,37 @@ impl ObjectStore for FileBackedObjectStore {
     }
 }
 
+fn retriable_io_error(err: std::io::Error) -> ObjectStoreError {
+    ObjectStoreError::Other {
+        is_retriable: true,
+        source: err.into(),
+    }
+}
+
+fn write_file(path: &str, write_value: &ValueWriter<'_>) -> Result<(), ObjectStoreError> {
+    let file = std::fs::File::create(path).map_err(retriable_io_error)?;
+    let mut writer = std::io::BufWriter::new(file);
+    write_value(&mut writer).map_err(|err| match err.downcast::<std::io::Error>() {
+        Ok(err) => retriable_io_error(*err),
+        Err(err) => ObjectStoreError::Serialization(err),
+    })?;
+    writer.flush().map_err(retriable_io_error)
+}
+
+/// Runs a blocking closure without stalling other tasks on a multi-threaded runtime. A single-threaded runtime
+/// (e.g., in tests) has no other workers to hand tasks over to, so the closure is run in place.
+fn run_blocking<T>(f: impl FnOnce() -> T) -> T {
+    match runtime::Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+            task::block_in_place(f)
+        }
+        _ => f(),
+    }
+}
+
 #[cfg(test)]
 mod test {
+    use assert_matches::assert_matches;
     use tempfile::TempDir;
 
     use super::*;
@@ -106,6 +160,57 @@ mod test {
         assert_eq!(expected, bytes, "expected didn't match");
     }
 
+    #[tokio::test]
+    async fn test_put_streaming() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.into_path().into_os_string().into_string().unwrap();
+        let object_store = FileBackedObjectStore::new(path).await.unwrap();
+        let expected = vec![9, 0, 8, 9, 0, 7];
+        object_store
+            .put_raw_streaming(Bucket::ProverJobs, "test-key.bin", &|writer| {
+                writer.write_all(&expected[..3])?;
+                writer.write_all(&expected[3..])?;
+                Ok(())
+            })
+            .await
+            .unwrap();
+        let bytes = object_store
+            .get_raw(Bucket::ProverJobs, "test-key.bin")
+            .await
+            .unwrap();
+        assert_eq!(expected, bytes, "expected didn't match");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn put_streaming_errors() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.into_path().into_os_string().into_string().unwrap();
+        let object_store = FileBackedObjectStore::new(path).await.unwrap();
+
+        let err = object_store
+            .put_raw_streaming(Bucket::ProverJobs, "test-key.bin", &|_| {
+                Err(std::io::Error::new(std::io::ErrorKind::Other, "disk full").into())
+            })
+            .await
+            .unwrap_err();
+        assert!(err.is_retriable(), "{err:?}");
+
+        let err = object_store
+            .put_raw_streaming(Bucket::ProverJobs, "test-key.bin", &|_| {
+                Err("cannot serialize".into())
+            })
+            .await
+            .unwrap_err();
+        assert_matches!(err, ObjectStoreError::Serialization(_));
+
+        // Failed writes must not leave any files behind.
+        let err = object_store
+            .get_raw(Bucket::ProverJobs, "test-key.bin")
+            .await
+            .unwrap_err();
+        assert_matches!(err, ObjectStoreError::KeyNotFound(_));
+    }
+
     #[tokio::test]
     async fn test_put() {
         let dir = TempDir::new().unwrap();
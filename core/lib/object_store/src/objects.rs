@@ -1,6 +1,6 @@
 //! Stored objects.
 
-use std::io::{Read, Write};
+use std::io::{self, Read, Write};
 
 use anyhow::Context;
 use flate2::{read::GzDecoder, write::GzEncoder, Compression};
@@ -33,6 +33,17 @@ pub trait StoredObject: Sized {
     /// Returns an error if serialization fails.
     fn serialize(&self) -> Result<Vec<u8>, BoxedError>;
 
+    /// Serializes a value into the provided writer. Used for [streaming](ObjectStore::put_raw_streaming()) values
+    /// to the store. The default implementation writes the output of [`Self::serialize()`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if serialization or writing fails.
+    fn serialize_into(&self, writer: &mut dyn io::Write) -> Result<(), BoxedError> {
+        writer.write_all(&self.serialize()?)?;
+        Ok(())
+    }
+
     /// Deserializes a value from the blob.
     ///
     /// # Errors
@@ -52,6 +63,13 @@ macro_rules! serialize_using_bincode {
             $crate::bincode::serialize(self).map_err(std::convert::From::from)
         }
 
+        fn serialize_into(
+            &self,
+            writer: &mut dyn std::io::Write,
+        ) -> std::result::Result<(), $crate::_reexports::BoxedError> {
+            $crate::bincode::serialize_into(writer, self).map_err(std::convert::From::from)
+        }
+
         fn deserialize(
             bytes: std::vec::Vec<u8>,
         ) -> std::result::Result<Self, $crate::_reexports::BoxedError> {
@@ -183,6 +201,31 @@ impl dyn ObjectStore + '_ {
         Ok(key)
     }
 
+    /// Same as [`Self::put()`], but streams the serialized value to the store if the store supports this,
+    /// instead of buffering it in memory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if serialization or the insertion / replacement operation fails.
+    #[tracing::instrument(
+        name = "ObjectStore::put_streaming",
+        skip_all,
+        fields(key) // Will be recorded within the function.
+    )]
+    pub async fn put_streaming<V: StoredObject + Sync>(
+        &self,
+        key: V::Key<'_>,
+        value: &V,
+    ) -> Result<String, ObjectStoreError> {
+        let key = V::encode_key(key);
+        // Record the key for tracing.
+        tracing::Span::current().record("key", key.as_str());
+        let write_value = |writer: &mut dyn io::Write| value.serialize_into(writer);
+        self.put_raw_streaming(V::BUCKET, &key, &write_value)
+            .await?;
+        Ok(key)
+    }
+
     /// Removes a value associated with the key.
     ///
     /// # Errors
@@ -1,4 +1,4 @@
-use std::{error, fmt};
+use std::{error, fmt, io};
 
 use async_trait::async_trait;
 
@@ -54,6 +54,10 @@ impl fmt::Display for Bucket {
 /// Thread-safe boxed error.
 pub type BoxedError = Box<dyn error::Error + Send + Sync>;
 
+/// Closure writing a serialized value, used in [`ObjectStore::put_raw_streaming()`]. The closure may be called
+/// multiple times (e.g., on retries) and must write the same bytes each time.
+pub type ValueWriter<'a> = dyn Fn(&mut dyn io::Write) -> Result<(), BoxedError> + Send + Sync + 'a;
+
 /// Errors during [`ObjectStore`] operations.
 #[derive(Debug)]
 #[non_exhaustive]
@@ -152,6 +156,25 @@ pub trait ObjectStore: 'static + fmt::Debug + Send + Sync {
         value: Vec<u8>,
     ) -> Result<(), ObjectStoreError>;
 
+    /// Stores the value written by `write_value` associating it with the key into the given bucket, streaming it
+    /// to the storage if the store supports this. If the key already exists, the value is replaced.
+    ///
+    /// The default implementation buffers the value in memory and delegates to [`Self::put_raw()`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing the value or the insertion / replacement operation fails.
+    async fn put_raw_streaming(
+        &self,
+        bucket: Bucket,
+        key: &str,
+        write_value: &ValueWriter<'_>,
+    ) -> Result<(), ObjectStoreError> {
+        let mut value = vec![];
+        write_value(&mut value).map_err(ObjectStoreError::Serialization)?;
+        self.put_raw(bucket, key, value).await
+    }
+
     /// Removes the value associated with the key from the given bucket if it exists.
     ///
     /// # Errors
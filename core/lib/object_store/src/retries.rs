@@ -5,7 +5,7 @@ use rand::Rng;
 
 use crate::{
     metrics::OBJECT_STORE_METRICS,
-    raw::{Bucket, ObjectStore, ObjectStoreError},
+    raw::{Bucket, ObjectStore, ObjectStoreError, ValueWriter},
 };
 
 /// Information about request added to logs.
@@ -119,6 +119,22 @@ impl<S: ObjectStore> ObjectStore for StoreWithRetries<S> {
         result
     }
 
+    async fn put_raw_streaming(
+        &self,
+        bucket: Bucket,
+        key: &str,
+        write_value: &ValueWriter<'_>,
+    ) -> Result<(), ObjectStoreError> {
+        let latency = OBJECT_STORE_METRICS.start_store(bucket);
+        let result = Request::Put(bucket, key)
+            .retry(&self.inner, self.max_retries, || {
+                self.inner.put_raw_streaming(bucket, key, write_value)
+            })
+            .await;
+        latency.observe();
+        result
+    }
+
     async fn remove_raw(&self, bucket: Bucket, key: &str) -> Result<(), ObjectStoreError> {
         Request::Remove(bucket, key)
             .retry(&self.inner, self.max_retries, || {
@@ -69,11 +69,27 @@ impl proto::FastVmMode {
     }
 }
 
+impl proto::VmDumpFormat {
+    fn new(source: configs::VmPlaygroundDumpFormat) -> Self {
+        match source {
+            configs::VmPlaygroundDumpFormat::Json => Self::Json,
+            configs::VmPlaygroundDumpFormat::Bincode => Self::Bincode,
+        }
+    }
+
+    fn parse(&self) -> configs::VmPlaygroundDumpFormat {
+        match self {
+            Self::Json => configs::VmPlaygroundDumpFormat::Json,
+            Self::Bincode => configs::VmPlaygroundDumpFormat::Bincode,
+        }
+    }
+}
+
 impl ProtoRepr for proto::VmPlayground {
     type Type = configs::ExperimentalVmPlaygroundConfig;
 
     fn read(&self) -> anyhow::Result<Self::Type> {
-        Ok(Self::Type {
+        let config = Self::Type {
             fast_vm_mode: self
                 .fast_vm_mode
                 .map(proto::FastVmMode::try_from)
@@ -85,7 +101,18 @@ impl ProtoRepr for proto::VmPlayground {
             window_size: NonZeroU32::new(self.window_size.unwrap_or(1))
                 .context("window_size cannot be 0")?,
             reset: self.reset.unwrap_or(false),
-        })
+            dumps_format: self
+                .dumps_format
+                .map(proto::VmDumpFormat::try_from)
+                .transpose()
+                .context("dumps_format")?
+                .map_or_else(configs::VmPlaygroundDumpFormat::default, |format| {
+                    format.parse()
+                }),
+            dumps_compression_level: self.dumps_compression_level,
+        };
+        config.validate_dumps_compression_level()?;
+        Ok(config)
     }
 
     fn build(this: &Self::Type) -> Self {
@@ -95,6 +122,8 @@ impl ProtoRepr for proto::VmPlayground {
             first_processed_batch: Some(this.first_processed_batch.0),
             window_size: Some(this.window_size.get()),
             reset: Some(this.reset),
+            dumps_format: Some(proto::VmDumpFormat::new(this.dumps_format).into()),
+            dumps_compression_level: this.dumps_compression_level,
         }
     }
 }
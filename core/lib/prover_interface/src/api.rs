@@ -33,6 +33,10 @@ pub enum ProofGenerationDataResponse {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TeeProofGenerationDataResponse(pub Option<Box<TeeVerifierInput>>);
 
+/// Optional HTTP header of [`TeeProofGenerationDataResponse`]s containing the number of batches that are ready
+/// to be proven by TEE provers of the requested type, not counting the returned batch.
+pub const TEE_PENDING_BATCHES_HEADER: &str = "x-zksync-tee-pending-batches";
+
 #[derive(Debug, Serialize, Deserialize)]
 pub enum SubmitProofResponse {
     Success,
@@ -72,6 +76,29 @@ pub struct VerifyProofRequest(pub Box<L1BatchProofForL1>);
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct SubmitTeeProofRequest(pub Box<L1BatchTeeProofForL1>);
 
+/// HTTP header of [`SubmitTeeProofRequest`]s containing an [idempotency key](SubmitTeeProofRequest::idempotency_key()).
+/// If the header is present, the server checks that it matches the submitted proof and doesn't re-save a proof
+/// that was already stored by a previous submission with the same key.
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+impl SubmitTeeProofRequest {
+    /// Returns the idempotency key for submitting this proof for the specified batch. The key is derived
+    /// from the batch number, the public key of the prover and the signed proof data, so it's the same for all retries
+    /// of the same proof, but differs for proofs of the same batch signed with different keys.
+    pub fn idempotency_key(&self, l1_batch_number: L1BatchNumber) -> String {
+        fn to_hex(bytes: &[u8]) -> String {
+            bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+        }
+
+        format!(
+            "tee-proof-{}-{l1_batch_number}-{}-{}",
+            self.0.tee_type,
+            to_hex(&self.0.pubkey),
+            to_hex(&self.0.proof)
+        )
+    }
+}
+
 #[serde_as]
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct RegisterTeeAttestationRequest {
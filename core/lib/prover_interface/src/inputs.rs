@@ -1,4 +1,8 @@
-use std::{collections::HashMap, convert::TryInto, fmt::Debug};
+use std::{
+    collections::{BTreeMap, HashMap},
+    convert::TryInto,
+    fmt::{self, Debug},
+};
 
 use serde::{Deserialize, Serialize};
 use serde_with::{serde_as, Bytes};
@@ -94,6 +98,21 @@ impl WitnessInputMerklePaths {
         self.next_enumeration_index
     }
 
+    /// Returns the number of contained Merkle paths, i.e., the number of storage logs in the block.
+    pub fn len(&self) -> usize {
+        self.merkle_paths.len()
+    }
+
+    /// Checks whether this job contains no Merkle paths.
+    pub fn is_empty(&self) -> bool {
+        self.merkle_paths.is_empty()
+    }
+
+    /// Returns the tree root hash after the last contained storage log, or `None` if there are no logs.
+    pub fn root_hash(&self) -> Option<H256> {
+        self.merkle_paths.last().map(|path| H256(path.root_hash))
+    }
+
     /// Reserves additional capacity for Merkle paths.
     pub fn reserve(&mut self, additional_capacity: usize) {
         self.merkle_paths.reserve(additional_capacity);
@@ -230,6 +249,217 @@ impl TeeVerifierInput {
     pub fn new(input: V1TeeVerifierInput) -> Self {
         TeeVerifierInput::V1(input)
     }
+
+    /// Returns structural information about this input without executing the VM. Returns `None`
+    /// for the `V0` placeholder.
+    pub fn summary(&self) -> Option<TeeVerifierInputSummary> {
+        match self {
+            Self::V0 => None,
+            Self::V1(input) => Some(TeeVerifierInputSummary {
+                l1_batch_number: input.l1_batch_env.number,
+                protocol_version: input.system_env.version,
+                l2_block_count: input.l2_blocks_execution_data.len(),
+                tx_count: input
+                    .l2_blocks_execution_data
+                    .iter()
+                    .map(|block| block.txs.len())
+                    .sum(),
+                storage_log_count: input.witness_input_merkle_paths.len(),
+                factory_dep_count: input.used_contracts.len(),
+                factory_deps_size: input
+                    .used_contracts
+                    .iter()
+                    .map(|(_, bytecode)| bytecode.len())
+                    .sum(),
+            }),
+        }
+    }
+
+    /// Returns sorted and deduplicated hashes of the contracts (factory deps) included into this input.
+    /// Returns an empty list for the `V0` placeholder.
+    pub fn used_contract_hashes(&self) -> Vec<H256> {
+        let Self::V1(input) = self else {
+            return vec![];
+        };
+        let mut hashes: Vec<_> = input.used_contracts.iter().map(|(hash, _)| *hash).collect();
+        hashes.sort_unstable();
+        hashes.dedup();
+        hashes
+    }
+}
+
+impl TeeVerifierInput {
+    /// Checks whether this input is semantically equal to `other`, i.e., whether both inputs would lead to the same
+    /// verification outcome. Unlike `PartialEq`, this ignores incidental ordering of used contracts. On mismatch,
+    /// returns the first found difference.
+    pub fn semantically_eq(&self, other: &Self) -> Result<(), TeeVerifierInputMismatch> {
+        match (self, other) {
+            (Self::V0, Self::V0) => Ok(()),
+            (Self::V1(input), Self::V1(other)) => input.semantically_eq(other),
+            _ => Err(TeeVerifierInputMismatch::new(
+                "version",
+                format!("{} vs {}", self.version_name(), other.version_name()),
+            )),
+        }
+    }
+
+    fn version_name(&self) -> &'static str {
+        match self {
+            Self::V0 => "V0",
+            Self::V1(_) => "V1",
+        }
+    }
+}
+
+impl V1TeeVerifierInput {
+    fn semantically_eq(&self, other: &Self) -> Result<(), TeeVerifierInputMismatch> {
+        TeeVerifierInputMismatch::check("l1_batch_env", &self.l1_batch_env, &other.l1_batch_env)?;
+        TeeVerifierInputMismatch::check("system_env", &self.system_env, &other.system_env)?;
+
+        let (blocks, other_blocks) = (
+            &self.l2_blocks_execution_data,
+            &other.l2_blocks_execution_data,
+        );
+        TeeVerifierInputMismatch::check(
+            "l2_blocks_execution_data.len()",
+            &blocks.len(),
+            &other_blocks.len(),
+        )?;
+        for (i, (block, other_block)) in blocks.iter().zip(other_blocks).enumerate() {
+            let field = format!("l2_blocks_execution_data[{i}]");
+            TeeVerifierInputMismatch::check(
+                format!("{field}.number"),
+                &block.number,
+                &other_block.number,
+            )?;
+            TeeVerifierInputMismatch::check(
+                format!("{field}.txs.len()"),
+                &block.txs.len(),
+                &other_block.txs.len(),
+            )?;
+            for (j, (tx, other_tx)) in block.txs.iter().zip(&other_block.txs).enumerate() {
+                TeeVerifierInputMismatch::check(format!("{field}.txs[{j}]"), tx, other_tx)?;
+            }
+            TeeVerifierInputMismatch::check(field, block, other_block)?;
+        }
+
+        let (paths, other_paths) = (
+            &self.witness_input_merkle_paths,
+            &other.witness_input_merkle_paths,
+        );
+        TeeVerifierInputMismatch::check(
+            "witness_input_merkle_paths.next_enumeration_index",
+            &paths.next_enumeration_index,
+            &other_paths.next_enumeration_index,
+        )?;
+        TeeVerifierInputMismatch::check(
+            "witness_input_merkle_paths.len()",
+            &paths.len(),
+            &other_paths.len(),
+        )?;
+        // Merkle paths are compacted deterministically, so it's sufficient to compare compacted paths.
+        for (i, (path, other_path)) in paths
+            .merkle_paths
+            .iter()
+            .zip(&other_paths.merkle_paths)
+            .enumerate()
+        {
+            TeeVerifierInputMismatch::check(
+                format!("witness_input_merkle_paths[{i}]"),
+                path,
+                other_path,
+            )?;
+        }
+
+        let contracts: BTreeMap<_, _> = self
+            .used_contracts
+            .iter()
+            .map(|(hash, code)| (*hash, code))
+            .collect();
+        let other_contracts: BTreeMap<_, _> = other
+            .used_contracts
+            .iter()
+            .map(|(hash, code)| (*hash, code))
+            .collect();
+        for (hash, code) in &contracts {
+            let field = format!("used_contracts[{hash:?}]");
+            let Some(other_code) = other_contracts.get(hash) else {
+                return Err(TeeVerifierInputMismatch::new(
+                    field,
+                    "missing in other input",
+                ));
+            };
+            if code != other_code {
+                let details = format!("{}-byte vs {}-byte bytecode", code.len(), other_code.len());
+                return Err(TeeVerifierInputMismatch::new(field, details));
+            }
+        }
+        if let Some(hash) = other_contracts
+            .keys()
+            .find(|hash| !contracts.contains_key(hash))
+        {
+            return Err(TeeVerifierInputMismatch::new(
+                format!("used_contracts[{hash:?}]"),
+                "missing in this input",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// First difference between [`TeeVerifierInput`]s found by [`TeeVerifierInput::semantically_eq()`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TeeVerifierInputMismatch {
+    /// Path to the differing field, e.g. `l2_blocks_execution_data[3].txs[0]`.
+    pub field: String,
+    /// Human-readable description of the difference.
+    pub details: String,
+}
+
+impl TeeVerifierInputMismatch {
+    fn new(field: impl Into<String>, details: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            details: details.into(),
+        }
+    }
+
+    fn check<T: PartialEq + Debug>(
+        field: impl Into<String>,
+        value: &T,
+        other_value: &T,
+    ) -> Result<(), Self> {
+        if value == other_value {
+            Ok(())
+        } else {
+            Err(Self::new(field, format!("{value:?} vs {other_value:?}")))
+        }
+    }
+}
+
+impl fmt::Display for TeeVerifierInputMismatch {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "`{}` differs: {}", self.field, self.details)
+    }
+}
+
+impl std::error::Error for TeeVerifierInputMismatch {}
+
+/// Structural information about a [`TeeVerifierInput`] returned by [`TeeVerifierInput::summary()`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TeeVerifierInputSummary {
+    pub l1_batch_number: L1BatchNumber,
+    pub protocol_version: ProtocolVersionId,
+    /// Number of L2 blocks in the batch, including the fictive L2 block.
+    pub l2_block_count: usize,
+    /// Total number of transactions in all L2 blocks.
+    pub tx_count: usize,
+    /// Number of storage logs with Merkle paths.
+    pub storage_log_count: usize,
+    /// Number of factory dependencies (i.e., contract bytecodes) used in the batch.
+    pub factory_dep_count: usize,
+    /// Total size of factory dependencies in bytes.
+    pub factory_deps_size: usize,
 }
 
 impl StoredObject for TeeVerifierInput {
@@ -245,6 +475,10 @@ impl StoredObject for TeeVerifierInput {
 
 #[cfg(test)]
 mod tests {
+    use zksync_contracts::{BaseSystemContracts, SystemContractCode};
+    use zksync_multivm::interface::{L2BlockEnv, TxExecutionMode};
+    use zksync_types::L2BlockNumber;
+
     use super::*;
 
     #[test]
@@ -281,4 +515,133 @@ mod tests {
         let logs_from_job: Vec<_> = job.into_merkle_paths().collect();
         assert_eq!(logs_from_job, logs);
     }
+
+    fn create_input() -> V1TeeVerifierInput {
+        V1TeeVerifierInput::new(
+            WitnessInputMerklePaths::new(0),
+            vec![],
+            L1BatchEnv {
+                previous_batch_hash: Some(H256([1; 32])),
+                number: Default::default(),
+                timestamp: 0,
+                fee_input: Default::default(),
+                fee_account: Default::default(),
+                enforced_base_fee: None,
+                first_l2_block: L2BlockEnv {
+                    number: 0,
+                    timestamp: 0,
+                    prev_block_hash: H256([1; 32]),
+                    max_virtual_blocks_to_create: 0,
+                },
+            },
+            SystemEnv {
+                zk_porter_available: false,
+                version: Default::default(),
+                base_system_smart_contracts: BaseSystemContracts {
+                    bootloader: SystemContractCode {
+                        code: vec![U256([1; 4])],
+                        hash: H256([1; 32]),
+                    },
+                    default_aa: SystemContractCode {
+                        code: vec![U256([1; 4])],
+                        hash: H256([1; 32]),
+                    },
+                },
+                bootloader_gas_limit: 0,
+                execution_mode: TxExecutionMode::VerifyExecute,
+                default_validation_computational_gas_limit: 0,
+                chain_id: Default::default(),
+            },
+            vec![(H256([1; 32]), vec![0, 1, 2, 3, 4])],
+        )
+    }
+
+    #[test]
+    fn semantic_equality_of_inputs() {
+        let mut input = create_input();
+        input.used_contracts.push((H256([2; 32]), vec![5, 6]));
+        let mut reordered_input = input.clone();
+        reordered_input.used_contracts.reverse();
+        let (input, reordered_input) = (
+            TeeVerifierInput::new(input),
+            TeeVerifierInput::new(reordered_input),
+        );
+        assert_ne!(input, reordered_input);
+        input.semantically_eq(&reordered_input).unwrap();
+
+        let mut other_input = create_input();
+        other_input.used_contracts[0].1.push(5);
+        let mismatch = input
+            .semantically_eq(&TeeVerifierInput::new(other_input))
+            .unwrap_err();
+        assert_eq!(
+            mismatch.field,
+            format!("used_contracts[{:?}]", H256([1; 32]))
+        );
+
+        let mut other_input = create_input();
+        other_input.l1_batch_env.timestamp = 1;
+        let mismatch = input
+            .semantically_eq(&TeeVerifierInput::new(other_input))
+            .unwrap_err();
+        assert_eq!(mismatch.field, "l1_batch_env");
+
+        let mismatch = input.semantically_eq(&TeeVerifierInput::V0).unwrap_err();
+        assert_eq!(mismatch.field, "version");
+    }
+
+    #[test]
+    fn used_contract_hashes_in_input() {
+        let mut input = create_input();
+        input.used_contracts.insert(0, (H256([2; 32]), vec![5, 6]));
+        let input = TeeVerifierInput::new(input);
+        assert_eq!(input.used_contract_hashes(), [H256([1; 32]), H256([2; 32])]);
+        assert!(TeeVerifierInput::V0.used_contract_hashes().is_empty());
+    }
+
+    #[test]
+    fn input_summary() {
+        assert_eq!(TeeVerifierInput::V0.summary(), None);
+
+        let mut input = create_input();
+        input.l1_batch_env.number = L1BatchNumber(3);
+        input.used_contracts.push((H256([2; 32]), vec![5, 6]));
+        input.l2_blocks_execution_data = (1..=2)
+            .map(|number| L2BlockExecutionData {
+                number: L2BlockNumber(number),
+                timestamp: number.into(),
+                prev_block_hash: H256::zero(),
+                virtual_blocks: 1,
+                txs: vec![],
+            })
+            .collect();
+        for i in 0..3 {
+            input
+                .witness_input_merkle_paths
+                .push_merkle_path(StorageLogMetadata {
+                    root_hash: [i; 32],
+                    is_write: false,
+                    first_write: false,
+                    merkle_paths: vec![[0; 32]; 256],
+                    leaf_hashed_key: U256::from(i),
+                    leaf_enumeration_index: 0,
+                    value_written: [0; 32],
+                    value_read: [0; 32],
+                });
+        }
+
+        let summary = TeeVerifierInput::new(input).summary().unwrap();
+        assert_eq!(
+            summary,
+            TeeVerifierInputSummary {
+                l1_batch_number: L1BatchNumber(3),
+                protocol_version: ProtocolVersionId::latest(),
+                l2_block_count: 2,
+                tx_count: 0,
+                storage_log_count: 3,
+                factory_dep_count: 2,
+                factory_deps_size: 7,
+            }
+        );
+    }
 }
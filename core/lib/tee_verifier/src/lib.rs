@@ -4,7 +4,16 @@
 //! executing the VM and verifying all the accessed memory slots by their
 //! merkle path.
 
-use std::{cell::RefCell, rc::Rc};
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    fmt,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
 
 use anyhow::Context;
 use zksync_crypto_primitives::hasher::blake2::Blake2Hasher;
@@ -13,9 +22,9 @@ use zksync_merkle_tree::{
 };
 use zksync_multivm::{
     interface::{
-        storage::{InMemoryStorage, ReadStorage, StorageView},
-        FinishedL1Batch, L2BlockEnv, VmFactory, VmInterface, VmInterfaceExt,
-        VmInterfaceHistoryEnabled,
+        storage::{InMemoryStorage, ReadStorage, StorageView, StorageViewStats},
+        FinishedL1Batch, L2BlockEnv, SystemEnv, VmExecutionResultAndLogs, VmFactory, VmInterface,
+        VmInterfaceExt, VmInterfaceHistoryEnabled,
     },
     vm_latest::HistoryEnabled,
     LegacyVmInstance,
@@ -23,8 +32,14 @@ use zksync_multivm::{
 use zksync_prover_interface::inputs::{
     StorageLogMetadata, V1TeeVerifierInput, WitnessInputMerklePaths,
 };
-use zksync_types::{block::L2BlockExecutionData, L1BatchNumber, StorageLog, Transaction, H256};
-use zksync_utils::bytecode::hash_bytecode;
+use zksync_types::{
+    block::L2BlockExecutionData, web3::keccak256, L1BatchNumber, L2BlockNumber, StorageLog,
+    Transaction, H256,
+};
+use zksync_utils::{bytecode::hash_bytecode, u256_to_h256};
+
+#[cfg(feature = "testonly")]
+pub mod testonly;
 
 /// A structure to hold the result of verification.
 pub struct VerificationResult {
@@ -32,11 +47,97 @@ pub struct VerificationResult {
     pub value_hash: ValueHash,
     /// The batch number that was verified.
     pub batch_number: L1BatchNumber,
+    /// Hashes of contracts that were loaded by the VM when re-executing the batch.
+    pub used_contract_hashes: Vec<H256>,
+    /// Storage access stats collected when re-executing the batch.
+    pub storage_stats: StorageViewStats,
+    /// Number of storage writes applied to the Merkle tree.
+    pub storage_writes: usize,
+    /// Total number of VM cycles used by all transactions and the block tip of the batch.
+    pub vm_steps: u64,
 }
 
+/// Intermediate state of an L1 batch re-executed up to (and including) a certain L2 block. Comparing intermediate states
+/// (e.g., with the state keeper data) allows to bisect the L2 block introducing a divergence.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartialReplayResult {
+    pub batch_number: L1BatchNumber,
+    /// Number of the last executed L2 block.
+    pub last_l2_block: L2BlockNumber,
+    /// Number of executed transactions.
+    pub tx_count: usize,
+    /// Latest values of storage slots written so far, keyed by the hashed storage key.
+    pub storage_writes: BTreeMap<H256, H256>,
+    /// Hash of `storage_writes` allowing to quickly compare intermediate states.
+    pub state_hash: H256,
+}
+
+/// Handle allowing to cooperatively cancel [verification](Verify::verify_with_cancellation()) from another thread,
+/// e.g. on timeout. Verification checks for cancellation before executing each transaction and between verification stages.
+#[derive(Debug, Clone, Default)]
+pub struct VerificationCancellation(Arc<AtomicBool>);
+
+impl VerificationCancellation {
+    /// Requests cancellation of the verification using this handle.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Checks whether cancellation was requested.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Returns a guard requesting cancellation when dropped. Useful to cancel verification running on another thread
+    /// on any exit from the awaiting code, including the awaiting future being dropped.
+    pub fn cancel_on_drop(&self) -> CancelOnDrop {
+        CancelOnDrop(self.clone())
+    }
+
+    fn check(&self) -> anyhow::Result<()> {
+        if self.is_cancelled() {
+            Err(VerificationCancelled.into())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Guard [cancelling](VerificationCancellation::cancel()) verification when dropped.
+/// Returned by [`VerificationCancellation::cancel_on_drop()`].
+#[derive(Debug)]
+#[must_use = "verification is cancelled when the guard is dropped"]
+pub struct CancelOnDrop(VerificationCancellation);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.cancel();
+    }
+}
+
+/// Error returned if verification was [cancelled](VerificationCancellation::cancel()) before completion.
+#[derive(Debug)]
+pub struct VerificationCancelled;
+
+impl fmt::Display for VerificationCancelled {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("verification was cancelled")
+    }
+}
+
+impl std::error::Error for VerificationCancelled {}
+
 /// A trait for the computations that can be verified in TEE.
-pub trait Verify {
-    fn verify(self) -> anyhow::Result<VerificationResult>;
+pub trait Verify: Sized {
+    fn verify(self) -> anyhow::Result<VerificationResult> {
+        self.verify_with_cancellation(&VerificationCancellation::default())
+    }
+
+    /// Same as [`Self::verify()`], but can be cooperatively cancelled. If cancelled, returns a [`VerificationCancelled`] error.
+    fn verify_with_cancellation(
+        self,
+        cancellation: &VerificationCancellation,
+    ) -> anyhow::Result<VerificationResult>;
 }
 
 impl Verify for V1TeeVerifierInput {
@@ -48,46 +149,145 @@ impl Verify for V1TeeVerifierInput {
     ///
     /// Returns a verbose error of the failure, because any error is
     /// not actionable.
-    fn verify(self) -> anyhow::Result<VerificationResult> {
+    fn verify_with_cancellation(
+        self,
+        cancellation: &VerificationCancellation,
+    ) -> anyhow::Result<VerificationResult> {
         let old_root_hash = self.l1_batch_env.previous_batch_hash.unwrap();
-        let l2_chain_id = self.system_env.chain_id;
         let enumeration_index = self.witness_input_merkle_paths.next_enumeration_index();
-
-        let mut raw_storage = InMemoryStorage::with_custom_system_contracts_and_chain_id(
-            l2_chain_id,
-            hash_bytecode,
-            Vec::with_capacity(0),
+        let (raw_storage, block_output_with_proofs) = prepare_storage(
+            &self.system_env,
+            self.used_contracts,
+            self.witness_input_merkle_paths,
         );
 
-        for (hash, bytes) in self.used_contracts.into_iter() {
-            tracing::trace!("raw_storage.store_factory_dep({hash}, bytes)");
-            raw_storage.store_factory_dep(hash, bytes)
-        }
-
-        let block_output_with_proofs =
-            get_bowp_and_set_initial_values(self.witness_input_merkle_paths, &mut raw_storage);
-
         let storage_view = Rc::new(RefCell::new(StorageView::new(&raw_storage)));
 
         let batch_number = self.l1_batch_env.number;
-        let vm = LegacyVmInstance::new(self.l1_batch_env, self.system_env, storage_view);
+        let vm = LegacyVmInstance::new(self.l1_batch_env, self.system_env, storage_view.clone());
 
-        let vm_out = execute_vm(self.l2_blocks_execution_data, vm)?;
+        let (vm_out, vm_steps) = execute_vm(self.l2_blocks_execution_data, vm, cancellation)?;
+        cancellation.check()?;
+        let storage_stats = storage_view.borrow().stats();
+        let used_contract_hashes = vm_out
+            .final_execution_state
+            .used_contract_hashes
+            .iter()
+            .copied()
+            .map(u256_to_h256)
+            .collect();
 
         let instructions: Vec<TreeInstruction> =
             generate_tree_instructions(enumeration_index, &block_output_with_proofs, vm_out)?;
 
+        cancellation.check()?;
         block_output_with_proofs
             .verify_proofs(&Blake2Hasher, old_root_hash, &instructions)
             .context("Failed to verify_proofs {l1_batch_number} correctly!")?;
+        let storage_writes = instructions
+            .iter()
+            .filter(|instruction| matches!(instruction, TreeInstruction::Write(_)))
+            .count();
 
         Ok(VerificationResult {
             value_hash: block_output_with_proofs.root_hash().unwrap(),
             batch_number,
+            used_contract_hashes,
+            storage_stats,
+            storage_writes,
+            vm_steps,
         })
     }
 }
 
+/// Re-executes the L1 batch up to (and including) the specified L2 block and returns the intermediate state.
+/// Unlike [`Verify::verify()`], this doesn't check Merkle paths since the tree root hash is only known for the entire batch.
+///
+/// # Errors
+///
+/// Returns an error if the L2 block is not in the batch or if any transaction fails to execute.
+pub fn replay_up_to_l2_block(
+    input: V1TeeVerifierInput,
+    last_l2_block: L2BlockNumber,
+) -> anyhow::Result<PartialReplayResult> {
+    let l2_blocks = &input.l2_blocks_execution_data;
+    let last_block_idx = l2_blocks
+        .iter()
+        .position(|block| block.number == last_l2_block)
+        .with_context(|| {
+            format!(
+                "L2 block #{last_l2_block} is not in L1 batch #{}",
+                input.l1_batch_env.number
+            )
+        })?;
+
+    let (raw_storage, _) = prepare_storage(
+        &input.system_env,
+        input.used_contracts,
+        input.witness_input_merkle_paths,
+    );
+    let storage_view = Rc::new(RefCell::new(StorageView::new(&raw_storage)));
+    let batch_number = input.l1_batch_env.number;
+    let mut vm = LegacyVmInstance::new(input.l1_batch_env, input.system_env, storage_view);
+
+    let mut tx_count = 0;
+    let mut storage_writes = BTreeMap::new();
+    for (idx, l2_block_data) in l2_blocks[..=last_block_idx].iter().enumerate() {
+        if idx > 0 {
+            vm.start_new_l2_block(L2BlockEnv::from_l2_block_data(l2_block_data));
+        }
+        for tx in &l2_block_data.txs {
+            let tx_result = execute_tx(tx, &mut vm)
+                .context("failed to execute transaction in partial replay")?;
+            let writes = tx_result
+                .logs
+                .storage_logs
+                .iter()
+                .filter(|log| log.log.is_write());
+            for log in writes {
+                storage_writes.insert(log.log.key.hashed_key(), log.log.value);
+            }
+            tx_count += 1;
+        }
+    }
+
+    let mut hashed_bytes = Vec::with_capacity(storage_writes.len() * 64);
+    for (key, value) in &storage_writes {
+        hashed_bytes.extend_from_slice(key.as_bytes());
+        hashed_bytes.extend_from_slice(value.as_bytes());
+    }
+    Ok(PartialReplayResult {
+        batch_number,
+        last_l2_block,
+        tx_count,
+        storage_writes,
+        state_hash: H256(keccak256(&hashed_bytes)),
+    })
+}
+
+/// Creates storage with the provided factory deps and initial storage values from Merkle paths. Returns the storage
+/// together with `BlockOutputWithProofs` used for verification.
+fn prepare_storage(
+    system_env: &SystemEnv,
+    used_contracts: Vec<(H256, Vec<u8>)>,
+    witness_input_merkle_paths: WitnessInputMerklePaths,
+) -> (InMemoryStorage, BlockOutputWithProofs) {
+    let mut raw_storage = InMemoryStorage::with_custom_system_contracts_and_chain_id(
+        system_env.chain_id,
+        hash_bytecode,
+        Vec::with_capacity(0),
+    );
+
+    for (hash, bytes) in used_contracts {
+        tracing::trace!("raw_storage.store_factory_dep({hash}, bytes)");
+        raw_storage.store_factory_dep(hash, bytes)
+    }
+
+    let block_output_with_proofs =
+        get_bowp_and_set_initial_values(witness_input_merkle_paths, &mut raw_storage);
+    (raw_storage, block_output_with_proofs)
+}
+
 /// Sets the initial storage values and returns `BlockOutputWithProofs`
 fn get_bowp_and_set_initial_values(
     witness_input_merkle_paths: WitnessInputMerklePaths,
@@ -159,7 +359,9 @@ fn get_bowp_and_set_initial_values(
 fn execute_vm<S: ReadStorage>(
     l2_blocks_execution_data: Vec<L2BlockExecutionData>,
     mut vm: LegacyVmInstance<S, HistoryEnabled>,
-) -> anyhow::Result<FinishedL1Batch> {
+    cancellation: &VerificationCancellation,
+) -> anyhow::Result<(FinishedL1Batch, u64)> {
+    let mut vm_steps = 0_u64;
     let next_l2_blocks_data = l2_blocks_execution_data.iter().skip(1);
 
     let l2_blocks_data = l2_blocks_execution_data.iter().zip(next_l2_blocks_data);
@@ -171,9 +373,11 @@ fn execute_vm<S: ReadStorage>(
             l2_block_data.txs.len(),
         );
         for tx in &l2_block_data.txs {
+            cancellation.check()?;
             tracing::trace!("Started execution of tx: {tx:?}");
-            execute_tx(tx, &mut vm)
+            let tx_result = execute_tx(tx, &mut vm)
                 .context("failed to execute transaction in TeeVerifierInputProducer")?;
+            vm_steps += u64::from(tx_result.statistics.cycles_used);
             tracing::trace!("Finished execution of tx: {tx:?}");
         }
         vm.start_new_l2_block(L2BlockEnv::from_l2_block_data(next_l2_block_data));
@@ -181,7 +385,14 @@ fn execute_vm<S: ReadStorage>(
         tracing::trace!("Finished execution of l2_block: {:?}", l2_block_data.number);
     }
 
-    Ok(vm.finish_batch())
+    let finished_batch = vm.finish_batch();
+    vm_steps += u64::from(
+        finished_batch
+            .block_tip_execution_result
+            .statistics
+            .cycles_used,
+    );
+    Ok((finished_batch, vm_steps))
 }
 
 /// Map `LogQuery` and `TreeLogEntry` to a `TreeInstruction`
@@ -229,40 +440,44 @@ fn generate_tree_instructions(
     bowp: &BlockOutputWithProofs,
     vm_out: FinishedL1Batch,
 ) -> anyhow::Result<Vec<TreeInstruction>> {
-    vm_out
-        .final_execution_state
-        .deduplicated_storage_logs
+    let storage_logs = vm_out.final_execution_state.deduplicated_storage_logs;
+    // Otherwise, the excess VM logs would be silently ignored when zipping.
+    anyhow::ensure!(
+        storage_logs.len() == bowp.logs.len(),
+        "VM produced {} deduplicated storage logs, while {} Merkle paths are provided",
+        storage_logs.len(),
+        bowp.logs.len()
+    );
+    storage_logs
         .into_iter()
         .zip(bowp.logs.iter())
         .map(|(log_query, tree_log_entry)| map_log_tree(&log_query, &tree_log_entry.base, &mut idx))
         .collect::<Result<Vec<_>, _>>()
 }
 
+/// Executes a transaction without any tracers (i.e., with the default tracer dispatcher), so call traces
+/// and other tracer outputs not needed for verification are never computed.
 fn execute_tx<S: ReadStorage>(
     tx: &Transaction,
     vm: &mut LegacyVmInstance<S, HistoryEnabled>,
-) -> anyhow::Result<()> {
+) -> anyhow::Result<VmExecutionResultAndLogs> {
     // Attempt to run VM with bytecode compression on.
     vm.make_snapshot();
-    if vm
-        .execute_transaction_with_bytecode_compression(tx.clone(), true)
-        .0
-        .is_ok()
-    {
+    let (compression_result, tx_result) =
+        vm.execute_transaction_with_bytecode_compression(tx.clone(), true);
+    if compression_result.is_ok() {
         vm.pop_snapshot_no_rollback();
-        return Ok(());
+        return Ok(tx_result);
     }
 
     // If failed with bytecode compression, attempt to run without bytecode compression.
     vm.rollback_to_the_latest_snapshot();
-    if vm
-        .execute_transaction_with_bytecode_compression(tx.clone(), false)
-        .0
-        .is_err()
-    {
+    let (compression_result, tx_result) =
+        vm.execute_transaction_with_bytecode_compression(tx.clone(), false);
+    if compression_result.is_err() {
         anyhow::bail!("compression can't fail if we don't apply it");
     }
-    Ok(())
+    Ok(tx_result)
 }
 
 #[cfg(test)]
@@ -271,13 +486,14 @@ mod tests {
     use zksync_multivm::interface::{L1BatchEnv, SystemEnv, TxExecutionMode};
     use zksync_object_store::StoredObject;
     use zksync_prover_interface::inputs::TeeVerifierInput;
-    use zksync_types::U256;
+    use zksync_types::{
+        block::L2BlockHasher, AccountTreeId, Address, ProtocolVersionId, StorageKey, U256,
+    };
 
     use super::*;
 
-    #[test]
-    fn test_v1_serialization() {
-        let tvi = V1TeeVerifierInput::new(
+    fn create_input() -> V1TeeVerifierInput {
+        V1TeeVerifierInput::new(
             WitnessInputMerklePaths::new(0),
             vec![],
             L1BatchEnv {
@@ -313,8 +529,12 @@ mod tests {
                 chain_id: Default::default(),
             },
             vec![(H256([1; 32]), vec![0, 1, 2, 3, 4])],
-        );
-        let tvi = TeeVerifierInput::new(tvi);
+        )
+    }
+
+    #[test]
+    fn test_v1_serialization() {
+        let tvi = TeeVerifierInput::new(create_input());
         let serialized = <TeeVerifierInput as StoredObject>::serialize(&tvi)
             .expect("Failed to serialize TeeVerifierInput.");
         let deserialized: TeeVerifierInput =
@@ -323,4 +543,94 @@ mod tests {
 
         assert_eq!(tvi, deserialized);
     }
+
+    #[test]
+    fn cancellation_guard() {
+        let cancellation = VerificationCancellation::default();
+        let guard = cancellation.cancel_on_drop();
+        assert!(!cancellation.is_cancelled());
+        drop(guard);
+        assert!(cancellation.is_cancelled());
+        let err = cancellation.check().unwrap_err();
+        assert!(err.is::<VerificationCancelled>(), "{err:#}");
+    }
+
+    /// Creates consecutive L2 blocks without transactions, starting from L2 block #1.
+    fn create_l2_blocks(count: u32) -> Vec<L2BlockExecutionData> {
+        let mut prev_block_hash = L2BlockHasher::legacy_hash(L2BlockNumber(0));
+        (1..=count)
+            .map(|number| {
+                let block = L2BlockExecutionData {
+                    number: L2BlockNumber(number),
+                    timestamp: number.into(),
+                    prev_block_hash,
+                    virtual_blocks: 1,
+                    txs: vec![],
+                };
+                prev_block_hash =
+                    L2BlockHasher::new(block.number, block.timestamp, block.prev_block_hash)
+                        .finalize(ProtocolVersionId::latest());
+                block
+            })
+            .collect()
+    }
+
+    #[test]
+    fn partial_replay() {
+        let mut input = create_input();
+        input.l2_blocks_execution_data = create_l2_blocks(3);
+        input.l1_batch_env.timestamp = 1;
+        input.l1_batch_env.first_l2_block =
+            L2BlockEnv::from_l2_block_data(&input.l2_blocks_execution_data[0]);
+
+        for last_l2_block in [L2BlockNumber(1), L2BlockNumber(2)] {
+            let result = replay_up_to_l2_block(input.clone(), last_l2_block).unwrap();
+            assert_eq!(result.batch_number, input.l1_batch_env.number);
+            assert_eq!(result.last_l2_block, last_l2_block);
+            assert_eq!(result.tx_count, 0);
+            assert!(result.storage_writes.is_empty());
+            assert_eq!(result.state_hash, H256(keccak256(&[])));
+        }
+
+        let err = replay_up_to_l2_block(input, L2BlockNumber(4)).unwrap_err();
+        assert!(
+            err.to_string().contains("L2 block #4 is not in L1 batch"),
+            "{err:#}"
+        );
+    }
+
+    #[test]
+    fn tree_instructions_for_mismatched_storage_logs() {
+        let tree_log = TreeLogEntryWithProof {
+            base: TreeLogEntry::ReadMissingKey,
+            merkle_path: vec![],
+            root_hash: H256::zero(),
+        };
+        let bowp = BlockOutputWithProofs {
+            logs: vec![tree_log],
+            leaf_count: 0,
+        };
+        let storage_log = StorageLog::new_read_log(
+            StorageKey::new(AccountTreeId::new(Address::repeat_byte(1)), H256::zero()),
+            H256::zero(),
+        );
+
+        let mut vm_out = FinishedL1Batch::mock();
+        vm_out.final_execution_state.deduplicated_storage_logs = vec![storage_log; 2];
+        let err = generate_tree_instructions(0, &bowp, vm_out).unwrap_err();
+        assert!(
+            err.to_string().contains(
+                "VM produced 2 deduplicated storage logs, while 1 Merkle paths are provided"
+            ),
+            "{err:#}"
+        );
+
+        let mut vm_out = FinishedL1Batch::mock();
+        vm_out.final_execution_state.deduplicated_storage_logs = vec![storage_log];
+        let instructions = generate_tree_instructions(0, &bowp, vm_out).unwrap();
+        assert_eq!(
+            instructions,
+            [TreeInstruction::Read(storage_log.key.hashed_key_u256())]
+        );
+    }
 }
@@ -0,0 +1,45 @@
+//! Building blocks for constructing arbitrary verifier inputs, e.g. to fuzz-test the verifier.
+//!
+//! The verifier relies on the following invariants of [`V1TeeVerifierInput`]. Violating any of them must lead
+//! to a verification error or a panic, but never to a successfully verified root hash:
+//!
+//! - `l1_batch_env.previous_batch_hash` is set and equals the tree root hash before the batch.
+//! - Storage logs correspond one-to-one, in order, to deduplicated storage logs produced by the VM when executing
+//!   the batch. All logs must have Merkle paths of the same length, each proving the read / previous value
+//!   against the root hash after applying the preceding logs; `root_hash` of a log is the root hash after applying it.
+//! - `leaf_enumeration_index` is 0 for reads of missing keys and is the existing leaf index for other reads and updates;
+//!   `next_enumeration_index` is the leaf index assigned to the first inserted key.
+//! - Factory deps are keyed by their bytecode hash. The hash is **not** checked by the verifier; a dep stored
+//!   under a wrong hash only surfaces as a VM execution divergence.
+
+use zksync_multivm::interface::{L1BatchEnv, SystemEnv};
+use zksync_prover_interface::inputs::{
+    StorageLogMetadata, V1TeeVerifierInput, WitnessInputMerklePaths,
+};
+use zksync_types::{block::L2BlockExecutionData, H256};
+
+/// Constructs verifier input from arbitrary storage logs and factory deps.
+///
+/// # Panics
+///
+/// Panics if Merkle paths in `storage_logs` have different lengths.
+pub fn input_from_parts(
+    l1_batch_env: L1BatchEnv,
+    system_env: SystemEnv,
+    l2_blocks_execution_data: Vec<L2BlockExecutionData>,
+    next_enumeration_index: u64,
+    storage_logs: impl IntoIterator<Item = StorageLogMetadata>,
+    factory_deps: impl IntoIterator<Item = (H256, Vec<u8>)>,
+) -> V1TeeVerifierInput {
+    let mut merkle_paths = WitnessInputMerklePaths::new(next_enumeration_index);
+    for log in storage_logs {
+        merkle_paths.push_merkle_path(log);
+    }
+    V1TeeVerifierInput::new(
+        merkle_paths,
+        l2_blocks_execution_data,
+        l1_batch_env,
+        system_env,
+        factory_deps.into_iter().collect(),
+    )
+}
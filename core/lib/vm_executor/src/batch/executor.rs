@@ -9,7 +9,7 @@ use tokio::{
 use zksync_multivm::interface::{
     executor::BatchExecutor,
     storage::{ReadStorage, StorageView},
-    BatchTransactionExecutionResult, FinishedL1Batch, L2BlockEnv,
+    BatchTransactionExecutionResult, FinishedL1Batch, IntermediateBatchOutputs, L2BlockEnv,
 };
 use zksync_types::Transaction;
 
@@ -163,6 +163,29 @@ where
         Ok(())
     }
 
+    #[tracing::instrument(skip_all)]
+    async fn current_outputs(&mut self) -> anyhow::Result<Option<IntermediateBatchOutputs>> {
+        let (response_sender, response_receiver) = oneshot::channel();
+        let send_failed = self
+            .commands
+            .send(Command::CurrentOutputs(response_sender))
+            .await
+            .is_err();
+        if send_failed {
+            return Err(self.handle.wait_for_error().await);
+        }
+
+        let latency = EXECUTOR_METRICS.batch_executor_command_response_time
+            [&ExecutorCommand::CurrentOutputs]
+            .start();
+        let outputs = match response_receiver.await {
+            Ok(outputs) => outputs,
+            Err(_) => return Err(self.handle.wait_for_error().await),
+        };
+        latency.observe();
+        Ok(Some(outputs))
+    }
+
     #[tracing::instrument(skip_all)]
     async fn finish_batch(
         mut self: Box<Self>,
@@ -198,5 +221,6 @@ pub(super) enum Command {
     ),
     StartNextL2Block(L2BlockEnv, oneshot::Sender<()>),
     RollbackLastTx(oneshot::Sender<()>),
+    CurrentOutputs(oneshot::Sender<IntermediateBatchOutputs>),
     FinishBatch(oneshot::Sender<FinishedL1Batch>),
 }
@@ -2,26 +2,28 @@ use std::{borrow::Cow, fmt, marker::PhantomData, rc::Rc, sync::Arc, time::Durati
 
 use anyhow::Context as _;
 use once_cell::sync::OnceCell;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, watch};
 use zksync_multivm::{
     interface::{
         executor::{BatchExecutor, BatchExecutorFactory},
         storage::{ReadStorage, StoragePtr, StorageView, StorageViewStats},
-        utils::DivergenceHandler,
+        utils::{DivergenceAllowlist, DivergenceHandler, ShadowActivation},
         BatchTransactionExecutionResult, BytecodeCompressionError, CompressedBytecodeInfo,
-        ExecutionResult, FinishedL1Batch, Halt, L1BatchEnv, L2BlockEnv, SystemEnv, VmFactory,
-        VmInterface, VmInterfaceHistoryEnabled,
+        ExecutionResult, FinishedL1Batch, Halt, IntermediateBatchOutputs, L1BatchEnv, L2BlockEnv,
+        SystemEnv, VmExecutionResultAndLogs, VmFactory, VmInterface, VmInterfaceHistoryEnabled,
     },
     tracers::CallTracer,
     vm_fast,
     vm_latest::HistoryEnabled,
     FastVmInstance, LegacyVmInstance, MultiVMTracer,
 };
-use zksync_types::{vm::FastVmMode, Transaction};
+use zksync_types::{vm::FastVmMode, StorageKey, StorageValue, Transaction, H256};
 
 use super::{
     executor::{Command, MainBatchExecutor},
-    metrics::{TxExecutionStage, BATCH_TIP_METRICS, EXECUTOR_METRICS, KEEPER_METRICS},
+    metrics::{
+        BytecodeCompression, TxExecutionStage, BATCH_TIP_METRICS, EXECUTOR_METRICS, KEEPER_METRICS,
+    },
 };
 use crate::shared::{InteractionType, Sealed, STORAGE_METRICS};
 
@@ -56,6 +58,132 @@ impl BatchTracer for TraceCalls {
     type Fast = (); // TODO: change once call tracing is implemented in fast VM
 }
 
+/// Cheap custom check applied to transactions before they are executed by [`MainBatchExecutorFactory`] executors.
+/// Allows enforcing policies (e.g., rate limits or address allowlists) without modifying the VM.
+pub trait TxPreCheck: fmt::Debug + Send + Sync + 'static {
+    /// Checks a transaction before its execution. If an error is returned, the transaction is not executed in the VM;
+    /// instead, it's rejected with [`Halt::TracerCustom`] containing the error message.
+    fn check(&self, tx: &Transaction) -> Result<(), String>;
+}
+
+/// Reason of [`Halt::TracerCustom`] returned for transactions not executed because the limit set
+/// by [`MainBatchExecutorFactory::set_max_txs_per_batch()`] is reached. Unlike other halts, this doesn't mean
+/// that the transaction is invalid, so it should be retried in the next batch rather than rejected.
+pub const MAX_TXS_PER_BATCH_HALT_REASON: &str = "max number of transactions per batch reached";
+
+/// Coarse-grained outcome of a transaction reported in [`TxOutcomeEvent`]s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxOutcome {
+    /// Transaction was executed successfully.
+    Success,
+    /// Transaction was executed, but reverted.
+    Reverted,
+    /// Transaction was halted (including transactions rejected without being executed in the VM).
+    Halted,
+}
+
+impl From<&ExecutionResult> for TxOutcome {
+    fn from(result: &ExecutionResult) -> Self {
+        match result {
+            ExecutionResult::Success { .. } => Self::Success,
+            ExecutionResult::Revert { .. } => Self::Reverted,
+            ExecutionResult::Halt { .. } => Self::Halted,
+        }
+    }
+}
+
+/// Lightweight event emitted by [`MainBatchExecutorFactory`] executors after each executed transaction.
+/// See [`MainBatchExecutorFactory::set_tx_outcome_sender()`].
+#[derive(Debug, Clone)]
+pub struct TxOutcomeEvent {
+    /// Hash of the executed transaction.
+    pub tx_hash: H256,
+    /// Outcome of the transaction execution.
+    pub outcome: TxOutcome,
+    /// Gas used by the transaction.
+    pub gas_used: u64,
+    /// Time spent executing the transaction in the VM. Zero for transactions rejected without being executed.
+    pub duration: Duration,
+}
+
+/// Wrapper around the storage used by [`MainBatchExecutorFactory`] executors, e.g. to profile storage access patterns
+/// (count reads, record hot keys etc.). Each method receives the wrapped storage; by default, methods forward calls
+/// to it as is. Implementations must not change the returned values, i.e., must not change execution semantics.
+///
+/// Note that the wrapped storage is only accessed on cache misses of the VM storage view.
+pub trait StorageWrapper: fmt::Debug + Send + Sync + 'static {
+    /// Called when the storage is wrapped for a new L1 batch.
+    fn init_batch(&self, _l1_batch_env: &L1BatchEnv) {}
+
+    fn read_value(&self, storage: &mut dyn ReadStorage, key: &StorageKey) -> StorageValue {
+        storage.read_value(key)
+    }
+
+    fn is_write_initial(&self, storage: &mut dyn ReadStorage, key: &StorageKey) -> bool {
+        storage.is_write_initial(key)
+    }
+
+    fn load_factory_dep(&self, storage: &mut dyn ReadStorage, hash: H256) -> Option<Vec<u8>> {
+        storage.load_factory_dep(hash)
+    }
+
+    fn is_bytecode_known(&self, storage: &mut dyn ReadStorage, bytecode_hash: &H256) -> bool {
+        storage.is_bytecode_known(bytecode_hash)
+    }
+
+    fn get_enumeration_index(
+        &self,
+        storage: &mut dyn ReadStorage,
+        key: &StorageKey,
+    ) -> Option<u64> {
+        storage.get_enumeration_index(key)
+    }
+}
+
+/// Storage optionally wrapped with a [`StorageWrapper`]. If there's no wrapper, all calls are forwarded to the inner storage.
+#[derive(Debug)]
+struct WrappedStorage<S> {
+    inner: S,
+    wrapper: Option<Arc<dyn StorageWrapper>>,
+}
+
+impl<S: ReadStorage> ReadStorage for WrappedStorage<S> {
+    fn read_value(&mut self, key: &StorageKey) -> StorageValue {
+        match &self.wrapper {
+            Some(wrapper) => wrapper.read_value(&mut self.inner, key),
+            None => self.inner.read_value(key),
+        }
+    }
+
+    fn is_write_initial(&mut self, key: &StorageKey) -> bool {
+        match &self.wrapper {
+            Some(wrapper) => wrapper.is_write_initial(&mut self.inner, key),
+            None => self.inner.is_write_initial(key),
+        }
+    }
+
+    fn load_factory_dep(&mut self, hash: H256) -> Option<Vec<u8>> {
+        match &self.wrapper {
+            Some(wrapper) => wrapper.load_factory_dep(&mut self.inner, hash),
+            None => self.inner.load_factory_dep(hash),
+        }
+    }
+
+    fn is_bytecode_known(&mut self, bytecode_hash: &H256) -> bool {
+        match &self.wrapper {
+            Some(wrapper) => wrapper.is_bytecode_known(&mut self.inner, bytecode_hash),
+            None => self.inner.is_bytecode_known(bytecode_hash),
+        }
+    }
+
+    fn get_enumeration_index(&mut self, key: &StorageKey) -> Option<u64> {
+        match &self.wrapper {
+            Some(wrapper) => wrapper.get_enumeration_index(&mut self.inner, key),
+            None => self.inner.get_enumeration_index(key),
+        }
+    }
+}
+
 /// The default implementation of [`BatchExecutorFactory`].
 /// Creates real batch executors which maintain the VM (as opposed to the test factories which don't use the VM).
 #[derive(Debug, Clone)]
@@ -70,6 +198,13 @@ pub struct MainBatchExecutorFactory<Tr> {
     fast_vm_mode: FastVmMode,
     observe_storage_metrics: bool,
     divergence_handler: Option<DivergenceHandler>,
+    divergence_allowlist: Option<DivergenceAllowlist>,
+    shadow_switch: Option<watch::Receiver<bool>>,
+    shadow_activation: Option<ShadowActivation>,
+    tx_pre_check: Option<Arc<dyn TxPreCheck>>,
+    storage_wrapper: Option<Arc<dyn StorageWrapper>>,
+    max_txs_per_batch: Option<usize>,
+    tx_outcome_sender: Option<mpsc::Sender<TxOutcomeEvent>>,
     _tracer: PhantomData<Tr>,
 }
 
@@ -80,10 +215,22 @@ impl<Tr: BatchTracer> MainBatchExecutorFactory<Tr> {
             fast_vm_mode: FastVmMode::Old,
             observe_storage_metrics: false,
             divergence_handler: None,
+            divergence_allowlist: None,
+            shadow_switch: None,
+            shadow_activation: None,
+            tx_pre_check: None,
+            storage_wrapper: None,
+            max_txs_per_batch: None,
+            tx_outcome_sender: None,
             _tracer: PhantomData,
         }
     }
 
+    /// Returns whether executors created by this factory allow transactions with bytecodes that cannot be compressed.
+    pub fn optional_bytecode_compression(&self) -> bool {
+        self.optional_bytecode_compression
+    }
+
     /// Sets the fast VM mode used by this executor.
     pub fn set_fast_vm_mode(&mut self, fast_vm_mode: FastVmMode) {
         if !matches!(fast_vm_mode, FastVmMode::Old) {
@@ -105,6 +252,62 @@ impl<Tr: BatchTracer> MainBatchExecutorFactory<Tr> {
         tracing::info!("Set VM divergence handler");
         self.divergence_handler = Some(handler);
     }
+
+    /// Sets the allowlist of known benign VM divergences, which are not reported in the [`FastVmMode::Shadow`] mode.
+    pub fn set_divergence_allowlist(&mut self, allowlist: DivergenceAllowlist) {
+        tracing::info!("Set VM divergence allowlist");
+        self.divergence_allowlist = Some(allowlist);
+    }
+
+    /// Sets a runtime switch for shadowing in the [`FastVmMode::Shadow`] mode. If the switch is `false` when a batch
+    /// is initialized, the batch is executed by the new VM only. If the switch is turned off mid-batch, the shadow VM
+    /// is dropped, and shadowing resumes from the next batch initialized after the switch is turned back on.
+    pub fn set_shadow_switch(&mut self, switch: watch::Receiver<bool>) {
+        tracing::info!("Set VM shadowing switch");
+        self.shadow_switch = Some(switch);
+    }
+
+    /// Sets a predicate deciding whether to keep shadowing in the [`FastVmMode::Shadow`] mode for each batch,
+    /// e.g. to only shadow batches with certain transactions. See [`ShadowActivation`] for details.
+    pub fn set_shadow_activation(&mut self, activation: ShadowActivation) {
+        tracing::info!("Set VM shadowing activation predicate");
+        self.shadow_activation = Some(activation);
+    }
+
+    /// Sets a check applied to each transaction before it's executed in the VM.
+    pub fn set_tx_pre_check(&mut self, check: Arc<dyn TxPreCheck>) {
+        tracing::info!("Set transaction pre-check: {check:?}");
+        self.tx_pre_check = Some(check);
+    }
+
+    /// Sets a wrapper applied to the storage of each batch executor when it's initialized. By default,
+    /// the storage is used as is.
+    pub fn set_storage_wrapper(&mut self, wrapper: Arc<dyn StorageWrapper>) {
+        tracing::info!("Set storage wrapper: {wrapper:?}");
+        self.storage_wrapper = Some(wrapper);
+    }
+
+    /// Limits the number of transactions executed in each batch. Once the limit is reached, all subsequent transactions
+    /// in the batch are returned with [`Halt::TracerCustom`] with the [`MAX_TXS_PER_BATCH_HALT_REASON`] reason
+    /// without being executed in the VM. Such transactions are not invalid; the state keeper seals the batch
+    /// on this reason and retries the transaction in the next batch. Mostly useful for deterministic batch sealing in tests.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `limit` is zero.
+    pub fn set_max_txs_per_batch(&mut self, limit: usize) {
+        assert!(limit > 0, "max transactions per batch must be positive");
+        tracing::info!("Set max transactions per batch: {limit}");
+        self.max_txs_per_batch = Some(limit);
+    }
+
+    /// Sets a sender for [`TxOutcomeEvent`]s emitted after each executed transaction, e.g. for live monitoring
+    /// of batch execution. Sending is best-effort: if the channel is full or closed, the event is dropped, so that
+    /// the receiver can never slow down execution.
+    pub fn set_tx_outcome_sender(&mut self, sender: mpsc::Sender<TxOutcomeEvent>) {
+        tracing::info!("Set transaction outcome sender");
+        self.tx_outcome_sender = Some(sender);
+    }
 }
 
 impl<S: ReadStorage + Send + 'static, Tr: BatchTracer> BatchExecutorFactory<S>
@@ -124,6 +327,13 @@ impl<S: ReadStorage + Send + 'static, Tr: BatchTracer> BatchExecutorFactory<S>
             fast_vm_mode: self.fast_vm_mode,
             observe_storage_metrics: self.observe_storage_metrics,
             divergence_handler: self.divergence_handler.clone(),
+            divergence_allowlist: self.divergence_allowlist.clone(),
+            shadow_switch: self.shadow_switch.clone(),
+            shadow_activation: self.shadow_activation.clone(),
+            tx_pre_check: self.tx_pre_check.clone(),
+            storage_wrapper: self.storage_wrapper.clone(),
+            max_txs_per_batch: self.max_txs_per_batch,
+            tx_outcome_sender: self.tx_outcome_sender.clone(),
             commands: commands_receiver,
             _storage: PhantomData,
             _tracer: PhantomData::<Tr>,
@@ -244,6 +454,13 @@ struct CommandReceiver<S, Tr> {
     fast_vm_mode: FastVmMode,
     observe_storage_metrics: bool,
     divergence_handler: Option<DivergenceHandler>,
+    divergence_allowlist: Option<DivergenceAllowlist>,
+    shadow_switch: Option<watch::Receiver<bool>>,
+    shadow_activation: Option<ShadowActivation>,
+    tx_pre_check: Option<Arc<dyn TxPreCheck>>,
+    storage_wrapper: Option<Arc<dyn StorageWrapper>>,
+    max_txs_per_batch: Option<usize>,
+    tx_outcome_sender: Option<mpsc::Sender<TxOutcomeEvent>>,
     commands: mpsc::Receiver<Command>,
     _storage: PhantomData<S>,
     _tracer: PhantomData<Tr>,
@@ -256,31 +473,67 @@ impl<S: ReadStorage + 'static, Tr: BatchTracer> CommandReceiver<S, Tr> {
         l1_batch_params: L1BatchEnv,
         system_env: SystemEnv,
     ) -> anyhow::Result<StorageView<S>> {
-        tracing::info!("Starting executing L1 batch #{}", &l1_batch_params.number);
+        tracing::info!(
+            optional_bytecode_compression = self.optional_bytecode_compression,
+            "Starting executing L1 batch #{}",
+            &l1_batch_params.number
+        );
 
+        if let Some(wrapper) = &self.storage_wrapper {
+            wrapper.init_batch(&l1_batch_params);
+        }
+        let storage = WrappedStorage {
+            inner: storage,
+            wrapper: self.storage_wrapper.take(),
+        };
         let storage_view = StorageView::new(storage).to_rc_ptr();
-        let mut vm = BatchVm::<S, Tr>::new(
+        let mut fast_vm_mode = self.fast_vm_mode;
+        let is_shadowing_disabled = self
+            .shadow_switch
+            .as_ref()
+            .is_some_and(|switch| !*switch.borrow());
+        if matches!(fast_vm_mode, FastVmMode::Shadow) && is_shadowing_disabled {
+            tracing::info!(
+                "VM shadowing is disabled by the switch; executing L1 batch #{} on the new VM only",
+                l1_batch_params.number
+            );
+            fast_vm_mode = FastVmMode::New;
+        }
+        let mut vm = BatchVm::<WrappedStorage<S>, Tr>::new(
             l1_batch_params,
             system_env,
             storage_view.clone(),
-            self.fast_vm_mode,
+            fast_vm_mode,
         );
         let mut batch_finished = false;
         let mut prev_storage_stats = StorageViewStats::default();
+        let mut outputs = IntermediateBatchOutputs::default();
+        let mut outputs_before_last_tx = outputs;
 
         if let BatchVm::Fast(FastVmInstance::Shadowed(shadowed)) = &mut vm {
             if let Some(handler) = self.divergence_handler.take() {
                 shadowed.set_divergence_handler(handler);
             }
+            if let Some(allowlist) = self.divergence_allowlist.take() {
+                shadowed.set_divergence_allowlist(allowlist);
+            }
+            if let Some(switch) = self.shadow_switch.take() {
+                shadowed.set_shadow_switch(switch);
+            }
+            if let Some(activation) = self.shadow_activation.take() {
+                shadowed.set_shadow_activation(activation);
+            }
         }
 
         while let Some(cmd) = self.commands.blocking_recv() {
             match cmd {
                 Command::ExecuteTx(tx, resp) => {
                     let tx_hash = tx.hash();
-                    let (result, latency) = self.execute_tx(*tx, &mut vm).with_context(|| {
-                        format!("fatal error executing transaction {tx_hash:?}")
-                    })?;
+                    let (result, latency) = self
+                        .execute_tx(*tx, outputs.executed_tx_count, &mut vm)
+                        .with_context(|| {
+                            format!("fatal error executing transaction {tx_hash:?}")
+                        })?;
 
                     if self.observe_storage_metrics {
                         let storage_stats = storage_view.borrow().stats();
@@ -288,12 +541,16 @@ impl<S: ReadStorage + 'static, Tr: BatchTracer> CommandReceiver<S, Tr> {
                         STORAGE_METRICS.observe(&format!("Tx {tx_hash:?}"), latency, &stats_diff);
                         prev_storage_stats = storage_stats;
                     }
+                    self.report_tx_outcome(tx_hash, &result, latency);
+                    outputs_before_last_tx = outputs;
+                    outputs.push_transaction(&result.tx_result.statistics);
                     if resp.send(result).is_err() {
                         break;
                     }
                 }
                 Command::RollbackLastTx(resp) => {
                     self.rollback_last_tx(&mut vm);
+                    outputs = outputs_before_last_tx;
                     if resp.send(()).is_err() {
                         break;
                     }
@@ -304,6 +561,11 @@ impl<S: ReadStorage + 'static, Tr: BatchTracer> CommandReceiver<S, Tr> {
                         break;
                     }
                 }
+                Command::CurrentOutputs(resp) => {
+                    if resp.send(outputs).is_err() {
+                        break;
+                    }
+                }
                 Command::FinishBatch(resp) => {
                     let vm_block_result = self.finish_batch(&mut vm)?;
                     if resp.send(vm_block_result).is_err() {
@@ -318,7 +580,8 @@ impl<S: ReadStorage + 'static, Tr: BatchTracer> CommandReceiver<S, Tr> {
         drop(vm);
         let storage_view = Rc::into_inner(storage_view)
             .context("storage view leaked")?
-            .into_inner();
+            .into_inner()
+            .map_storage_handle(|storage| storage.inner);
         if batch_finished {
             let stats = storage_view.stats();
             EXECUTOR_METRICS.batch_storage_interaction_duration[&InteractionType::GetValue]
@@ -335,7 +598,8 @@ impl<S: ReadStorage + 'static, Tr: BatchTracer> CommandReceiver<S, Tr> {
     fn execute_tx(
         &self,
         transaction: Transaction,
-        vm: &mut BatchVm<S, Tr>,
+        executed_tx_count: usize,
+        vm: &mut BatchVm<WrappedStorage<S>, Tr>,
     ) -> anyhow::Result<(BatchTransactionExecutionResult, Duration)> {
         // Executing a next transaction means that a previous transaction was either rolled back (in which case its snapshot
         // was already removed), or that we build on top of it (in which case, it can be removed now).
@@ -343,6 +607,28 @@ impl<S: ReadStorage + 'static, Tr: BatchTracer> CommandReceiver<S, Tr> {
         // Save pre-execution VM snapshot.
         vm.make_snapshot();
 
+        if self
+            .max_txs_per_batch
+            .is_some_and(|limit| executed_tx_count >= limit)
+        {
+            tracing::info!(
+                "Transaction {:?} rejected: max number of transactions per batch is reached",
+                transaction.hash()
+            );
+            let reason = MAX_TXS_PER_BATCH_HALT_REASON.to_owned();
+            return Ok((Self::rejected_tx(reason), Duration::ZERO));
+        }
+
+        if let Some(pre_check) = &self.tx_pre_check {
+            if let Err(reason) = pre_check.check(&transaction) {
+                tracing::info!(
+                    "Transaction {:?} rejected by pre-check: {reason}",
+                    transaction.hash()
+                );
+                return Ok((Self::rejected_tx(reason), Duration::ZERO));
+            }
+        }
+
         // Execute the transaction.
         let latency = KEEPER_METRICS.tx_execution_time[&TxExecutionStage::Execution].start();
         let result = if self.optional_bytecode_compression {
@@ -350,17 +636,67 @@ impl<S: ReadStorage + 'static, Tr: BatchTracer> CommandReceiver<S, Tr> {
         } else {
             self.execute_tx_in_vm(&transaction, vm)?
         };
+        let latency = latency.observe();
+
+        if !result.compressed_bytecodes.is_empty() {
+            let compression = BytecodeCompression::from(self.optional_bytecode_compression);
+            let compressed_bytes = result
+                .compressed_bytecodes
+                .iter()
+                .map(|bytecode| bytecode.compressed.len())
+                .sum();
+            EXECUTOR_METRICS.compressed_bytecodes_per_tx[&compression]
+                .observe(result.compressed_bytecodes.len());
+            EXECUTOR_METRICS.compressed_bytecode_bytes_per_tx[&compression]
+                .observe(compressed_bytes);
+        }
+        Ok((result, latency))
+    }
 
-        Ok((result, latency.observe()))
+    fn report_tx_outcome(
+        &self,
+        tx_hash: H256,
+        result: &BatchTransactionExecutionResult,
+        duration: Duration,
+    ) {
+        let Some(sender) = &self.tx_outcome_sender else {
+            return;
+        };
+        let event = TxOutcomeEvent {
+            tx_hash,
+            outcome: TxOutcome::from(&result.tx_result.result),
+            gas_used: result.tx_result.statistics.gas_used,
+            duration,
+        };
+        // Errors are intentionally ignored: events are dropped if the channel is full or closed.
+        sender.try_send(event).ok();
     }
 
-    fn rollback_last_tx(&self, vm: &mut BatchVm<S, Tr>) {
+    fn rejected_tx(reason: String) -> BatchTransactionExecutionResult {
+        BatchTransactionExecutionResult {
+            tx_result: Box::new(VmExecutionResultAndLogs {
+                result: ExecutionResult::Halt {
+                    reason: Halt::TracerCustom(reason),
+                },
+                logs: Default::default(),
+                statistics: Default::default(),
+                refunds: Default::default(),
+            }),
+            compressed_bytecodes: vec![],
+            call_traces: vec![],
+        }
+    }
+
+    fn rollback_last_tx(&self, vm: &mut BatchVm<WrappedStorage<S>, Tr>) {
         let latency = KEEPER_METRICS.tx_execution_time[&TxExecutionStage::TxRollback].start();
         vm.rollback_to_the_latest_snapshot();
         latency.observe();
     }
 
-    fn finish_batch(&self, vm: &mut BatchVm<S, Tr>) -> anyhow::Result<FinishedL1Batch> {
+    fn finish_batch(
+        &self,
+        vm: &mut BatchVm<WrappedStorage<S>, Tr>,
+    ) -> anyhow::Result<FinishedL1Batch> {
         // The vm execution was paused right after the last transaction was executed.
         // There is some post-processing work that the VM needs to do before the block is fully processed.
         let result = vm.finish_batch();
@@ -379,7 +715,7 @@ impl<S: ReadStorage + 'static, Tr: BatchTracer> CommandReceiver<S, Tr> {
     fn execute_tx_in_vm_with_optional_compression(
         &self,
         tx: &Transaction,
-        vm: &mut BatchVm<S, Tr>,
+        vm: &mut BatchVm<WrappedStorage<S>, Tr>,
     ) -> anyhow::Result<BatchTransactionExecutionResult> {
         // Note, that the space where we can put the calldata for compressing transactions
         // is limited and the transactions do not pay for taking it.
@@ -420,7 +756,7 @@ impl<S: ReadStorage + 'static, Tr: BatchTracer> CommandReceiver<S, Tr> {
     fn execute_tx_in_vm(
         &self,
         tx: &Transaction,
-        vm: &mut BatchVm<S, Tr>,
+        vm: &mut BatchVm<WrappedStorage<S>, Tr>,
     ) -> anyhow::Result<BatchTransactionExecutionResult> {
         let res = vm.inspect_transaction(tx.clone(), true);
         if let Ok(compressed_bytecodes) = res.compressed_bytecodes {
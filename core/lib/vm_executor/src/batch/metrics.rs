@@ -2,7 +2,7 @@
 
 use std::time::Duration;
 
-use vise::{Buckets, EncodeLabelSet, EncodeLabelValue, Family, Histogram, Metrics};
+use vise::{Buckets, EncodeLabelSet, EncodeLabelValue, Family, Histogram, Metrics, Unit};
 use zksync_multivm::interface::VmExecutionResultAndLogs;
 
 use crate::shared::InteractionType;
@@ -14,6 +14,7 @@ pub(super) enum ExecutorCommand {
     #[metrics(name = "start_next_miniblock")]
     StartNextL2Block,
     RollbackLastTx,
+    CurrentOutputs,
     FinishBatch,
 }
 
@@ -28,6 +29,24 @@ pub(super) enum TxExecutionStage {
     TxRollback,
 }
 
+/// Whether bytecode compression was optional when executing a transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EncodeLabelValue, EncodeLabelSet)]
+#[metrics(label = "compression", rename_all = "snake_case")]
+pub(super) enum BytecodeCompression {
+    Optional,
+    Mandatory,
+}
+
+impl From<bool> for BytecodeCompression {
+    fn from(optional: bool) -> Self {
+        if optional {
+            Self::Optional
+        } else {
+            Self::Mandatory
+        }
+    }
+}
+
 /// Executor-related metrics.
 #[derive(Debug, Metrics)]
 #[metrics(prefix = "state_keeper")]
@@ -43,6 +62,12 @@ pub(super) struct ExecutorMetrics {
     /// in the batch executor.
     #[metrics(buckets = Buckets::LATENCIES)]
     pub batch_storage_interaction_duration: Family<InteractionType, Histogram<Duration>>,
+    /// Number of compressed bytecodes published by a single transaction. Only transactions publishing bytecodes are observed.
+    #[metrics(buckets = Buckets::exponential(1.0..=64.0, 2.0))]
+    pub compressed_bytecodes_per_tx: Family<BytecodeCompression, Histogram<usize>>,
+    /// Total size of compressed bytecodes published by a single transaction.
+    #[metrics(buckets = Buckets::exponential(32.0..=1_048_576.0, 4.0), unit = Unit::Bytes)]
+    pub compressed_bytecode_bytes_per_tx: Family<BytecodeCompression, Histogram<usize>>,
 }
 
 #[vise::register]
@@ -4,7 +4,10 @@
 
 pub use self::{
     executor::MainBatchExecutor,
-    factory::{BatchTracer, MainBatchExecutorFactory, TraceCalls},
+    factory::{
+        BatchTracer, MainBatchExecutorFactory, StorageWrapper, TraceCalls, TxOutcome,
+        TxOutcomeEvent, TxPreCheck, MAX_TXS_PER_BATCH_HALT_REASON,
+    },
 };
 
 mod executor;
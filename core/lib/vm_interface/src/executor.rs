@@ -8,8 +8,9 @@ use zksync_types::{l2::L2Tx, Transaction};
 use crate::{
     storage::{ReadStorage, StorageView},
     tracer::{ValidationError, ValidationParams},
-    BatchTransactionExecutionResult, FinishedL1Batch, L1BatchEnv, L2BlockEnv, OneshotEnv,
-    OneshotTracingParams, OneshotTransactionExecutionResult, SystemEnv, TxExecutionArgs,
+    BatchTransactionExecutionResult, FinishedL1Batch, IntermediateBatchOutputs, L1BatchEnv,
+    L2BlockEnv, OneshotEnv, OneshotTracingParams, OneshotTransactionExecutionResult, SystemEnv,
+    TxExecutionArgs,
 };
 
 /// Factory of [`BatchExecutor`]s.
@@ -41,6 +42,12 @@ pub trait BatchExecutor<S>: 'static + Send + fmt::Debug {
     /// Starts a next L2 block with the specified params.
     async fn start_next_l2_block(&mut self, env: L2BlockEnv) -> anyhow::Result<()>;
 
+    /// Returns intermediate outputs for the batch executed so far. Returns `None` if the executor doesn't support
+    /// this functionality (which is the default implementation).
+    async fn current_outputs(&mut self) -> anyhow::Result<Option<IntermediateBatchOutputs>> {
+        Ok(None)
+    }
+
     /// Finished the current L1 batch.
     async fn finish_batch(self: Box<Self>) -> anyhow::Result<(FinishedL1Batch, StorageView<S>)>;
 }
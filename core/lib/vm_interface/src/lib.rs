@@ -30,14 +30,17 @@ pub use crate::{
         outputs::{
             BatchTransactionExecutionResult, BootloaderMemory, Call, CallType, CircuitStatistic,
             CompressedBytecodeInfo, CurrentExecutionState, DeduplicatedWritesMetrics,
-            ExecutionResult, FinishedL1Batch, L2Block, OneshotTransactionExecutionResult, Refunds,
-            TransactionExecutionMetrics, TransactionExecutionResult, TxExecutionStatus, VmEvent,
-            VmExecutionLogs, VmExecutionMetrics, VmExecutionResultAndLogs, VmExecutionStatistics,
-            VmMemoryMetrics,
+            ExecutionResult, FinishedL1Batch, IntermediateBatchOutputs, L2Block,
+            OneshotTransactionExecutionResult, Refunds, TransactionExecutionMetrics,
+            TransactionExecutionResult, TxExecutionStatus, VmEvent, VmExecutionLogs,
+            VmExecutionMetrics, VmExecutionResultAndLogs, VmExecutionStatistics, VmMemoryMetrics,
         },
         tracer,
     },
-    vm::{VmFactory, VmInterface, VmInterfaceExt, VmInterfaceHistoryEnabled, VmTrackingContracts},
+    vm::{
+        VmFactory, VmInspectExecutionState, VmInterface, VmInterfaceExt, VmInterfaceHistoryEnabled,
+        VmTrackingContracts,
+    },
 };
 
 pub mod executor;
@@ -1,6 +1,9 @@
-use std::{collections::HashMap, fmt};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+};
 
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize, Serializer};
 use zksync_types::{web3, StorageKey, StorageValue, H256};
 
 use super::ReadStorage;
@@ -12,14 +15,28 @@ use super::ReadStorage;
 /// In contrast, `StorageSnapshot` cannot be modified once created and is intended to represent a complete or almost complete snapshot
 /// for a particular VM execution. It can serve as a preloaded cache for a certain [`ReadStorage`] implementation
 /// that significantly reduces the number of storage accesses.
+///
+/// Serialization is deterministic: map entries are serialized in the ascending key order.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct StorageSnapshot {
     // `Option` encompasses entire map value for more efficient serialization
+    #[serde(serialize_with = "serialize_sorted")]
     storage: HashMap<H256, Option<(H256, u64)>>,
     // `Bytes` are used to have efficient serialization
+    #[serde(serialize_with = "serialize_sorted")]
     factory_deps: HashMap<H256, web3::Bytes>,
 }
 
+/// Serializes a map with entries sorted by key, so that the serialized form doesn't depend on the `HashMap` iteration order.
+fn serialize_sorted<K, V, S>(map: &HashMap<K, V>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    K: Ord + Serialize,
+    V: Serialize,
+    S: Serializer,
+{
+    serializer.collect_map(map.iter().collect::<BTreeMap<_, _>>())
+}
+
 impl StorageSnapshot {
     /// Creates a new storage snapshot.
     ///
@@ -41,6 +58,16 @@ impl StorageSnapshot {
         }
     }
 
+    /// Returns storage slots in this snapshot keyed by the hashed storage key.
+    pub(crate) fn storage_slots(&self) -> &HashMap<H256, Option<(H256, u64)>> {
+        &self.storage
+    }
+
+    /// Returns factory deps in this snapshot keyed by the bytecode hash.
+    pub(crate) fn factory_deps(&self) -> &HashMap<H256, web3::Bytes> {
+        &self.factory_deps
+    }
+
     /// Creates a [`ReadStorage`] implementation based on this snapshot and the provided fallback implementation.
     /// Fallback will be called for storage slots / factory deps not in this snapshot (which, if this snapshot
     /// is reasonably constructed, would be a rare occurrence). If `shadow` flag is set, the fallback will be
@@ -216,4 +243,23 @@ mod tests {
         assert_eq!(restored.storage, snapshot.storage);
         assert_eq!(restored.factory_deps, snapshot.factory_deps);
     }
+
+    #[test]
+    fn snapshot_serialization_is_deterministic() {
+        let storage: Vec<_> = (0_u64..100)
+            .map(|i| (H256::from_low_u64_be(i), Some((H256::repeat_byte(1), i))))
+            .collect();
+        let snapshot = StorageSnapshot::new(storage.iter().copied().collect(), HashMap::new());
+        let reversed_snapshot =
+            StorageSnapshot::new(storage.into_iter().rev().collect(), HashMap::new());
+
+        let serialized = serde_json::to_string(&snapshot).unwrap();
+        assert_eq!(
+            serialized,
+            serde_json::to_string(&reversed_snapshot).unwrap()
+        );
+        let first_key_pos = serialized.find(&format!("{:?}", H256::from_low_u64_be(0)));
+        let last_key_pos = serialized.find(&format!("{:?}", H256::from_low_u64_be(99)));
+        assert!(first_key_pos.unwrap() < last_key_pos.unwrap());
+    }
 }
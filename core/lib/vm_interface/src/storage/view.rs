@@ -102,6 +102,16 @@ impl<S> StorageView<S> {
     pub fn cache(&self) -> StorageViewCache {
         self.cache.clone()
     }
+
+    /// Maps the underlying storage, preserving the cache, modified storage keys and stats of this view.
+    pub fn map_storage_handle<T>(self, map: impl FnOnce(S) -> T) -> StorageView<T> {
+        StorageView {
+            storage_handle: map(self.storage_handle),
+            modified_storage_keys: self.modified_storage_keys,
+            cache: self.cache,
+            stats: self.stats,
+        }
+    }
 }
 
 impl<S> ReadStorage for Box<S>
@@ -177,6 +187,43 @@ impl<S: ReadStorage> StorageView<S> {
     pub fn to_rc_ptr(self) -> Rc<RefCell<Self>> {
         Rc::new(RefCell::new(self))
     }
+
+    /// Iterates over storage slots accessed via this view without cloning the cache. Slots are keyed by the hashed key
+    /// and contain the value before modifications via this view together with the enumeration index (`None` if the slot
+    /// is missing in the underlying storage). Slots are yielded in the ascending order of hashed keys, so that
+    /// the order is deterministic; only keys are sorted, values are read from the cache lazily.
+    pub(crate) fn snapshot_slots(
+        &mut self,
+    ) -> impl ExactSizeIterator<Item = (H256, Option<(H256, u64)>)> + '_ {
+        // Normally, all writes are internally read in order to calculate their gas costs, so this is defensive programming.
+        let unread_writes: Vec<_> = self
+            .cache
+            .initial_writes
+            .keys()
+            .filter(|key| !self.cache.read_storage_keys.contains_key(key))
+            .copied()
+            .collect();
+        for key in unread_writes {
+            let value = self.storage_handle.read_value(&key);
+            self.cache.read_storage_keys.insert(key, value);
+        }
+
+        let mut keys: Vec<_> = self
+            .cache
+            .read_storage_keys
+            .keys()
+            .map(|key| (key.hashed_key(), *key))
+            .collect();
+        keys.sort_unstable_by_key(|(hashed_key, _)| *hashed_key);
+
+        let storage_handle = &mut self.storage_handle;
+        let read_storage_keys = &self.cache.read_storage_keys;
+        keys.into_iter().map(move |(hashed_key, key)| {
+            let value = read_storage_keys[&key];
+            let enum_index = storage_handle.get_enumeration_index(&key);
+            (hashed_key, enum_index.map(|idx| (value, idx)))
+        })
+    }
 }
 
 impl<S: ReadStorage + fmt::Debug> ReadStorage for StorageView<S> {
@@ -1,7 +1,36 @@
 use zksync_types::writes::StateDiffRecord;
 
 use super::{BootloaderMemory, CurrentExecutionState, VmExecutionResultAndLogs};
-use crate::{ExecutionResult, Refunds, VmExecutionLogs, VmExecutionStatistics};
+use crate::{CircuitStatistic, ExecutionResult, Refunds, VmExecutionLogs, VmExecutionStatistics};
+
+/// Intermediate outputs of an L1 batch that is being executed. Can be used to make decisions based on live execution data
+/// (e.g., in seal criteria) without finishing the batch.
+///
+/// All aggregates cover transactions executed in the batch so far, excluding rolled back transactions.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct IntermediateBatchOutputs {
+    /// Number of executed transactions.
+    pub executed_tx_count: usize,
+    /// Total gas used by executed transactions.
+    pub gas_used: u64,
+    /// Total computational gas used by executed transactions.
+    pub computational_gas_used: u64,
+    /// Total pubdata published by executed transactions.
+    pub pubdata_published: u64,
+    /// Total circuit statistic for executed transactions.
+    pub circuit_statistic: CircuitStatistic,
+}
+
+impl IntermediateBatchOutputs {
+    /// Accounts for an executed transaction with the specified statistics.
+    pub fn push_transaction(&mut self, statistics: &VmExecutionStatistics) {
+        self.executed_tx_count += 1;
+        self.gas_used += statistics.gas_used;
+        self.computational_gas_used += u64::from(statistics.computational_gas_used);
+        self.pubdata_published += u64::from(statistics.pubdata_published);
+        self.circuit_statistic = self.circuit_statistic + statistics.circuit_statistic;
+    }
+}
 
 /// State of the VM after the batch execution.
 #[derive(Debug, Clone)]
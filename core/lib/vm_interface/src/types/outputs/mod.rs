@@ -1,3 +1,4 @@
+pub(crate) use self::execution_result::bytecode_len_in_bytes;
 pub use self::{
     bytecode::CompressedBytecodeInfo,
     execution_result::{
@@ -6,7 +7,7 @@ pub use self::{
         VmEvent, VmExecutionLogs, VmExecutionResultAndLogs,
     },
     execution_state::{BootloaderMemory, CurrentExecutionState},
-    finished_l1batch::FinishedL1Batch,
+    finished_l1batch::{FinishedL1Batch, IntermediateBatchOutputs},
     l2_block::L2Block,
     statistic::{
         CircuitStatistic, DeduplicatedWritesMetrics, TransactionExecutionMetrics,
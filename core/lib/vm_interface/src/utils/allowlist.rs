@@ -0,0 +1,266 @@
+//! Allowlist of known benign VM divergences.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+
+use anyhow::Context as _;
+use zksync_types::H256;
+
+/// Pattern for transaction hashes in [`DivergenceAllowlist`] entries.
+#[derive(Debug, Clone, PartialEq)]
+enum TxHashPattern {
+    /// Matches all divergences, including ones not related to a specific transaction (e.g., when finishing a batch).
+    Any,
+    /// Matches transactions with the hash starting with the specified lowercase hex prefix (without `0x`).
+    Prefix(String),
+}
+
+impl TxHashPattern {
+    fn parse(pattern: &str) -> anyhow::Result<Self> {
+        if pattern == "*" {
+            return Ok(Self::Any);
+        }
+        let hex = pattern.strip_prefix("0x").unwrap_or(pattern);
+        let (hex, is_prefix) = match hex.strip_suffix('*') {
+            Some(prefix) => (prefix, true),
+            None => (hex, false),
+        };
+        anyhow::ensure!(
+            hex.len() <= 2 * H256::len_bytes() && hex.bytes().all(|b| b.is_ascii_hexdigit()),
+            "invalid transaction hash pattern `{pattern}`"
+        );
+        anyhow::ensure!(
+            is_prefix || hex.len() == 2 * H256::len_bytes(),
+            "transaction hash `{pattern}` is incomplete; use a `*` suffix to match by prefix"
+        );
+        Ok(Self::Prefix(hex.to_ascii_lowercase()))
+    }
+
+    fn matches(&self, tx_hash: Option<H256>) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Prefix(prefix) => tx_hash
+                .is_some_and(|hash| hex::encode(hash.as_bytes()).starts_with(prefix.as_str())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct AllowlistEntry {
+    context: String,
+    tx_hash: TxHashPattern,
+}
+
+impl AllowlistEntry {
+    /// Parses entries from the allowlist file. Each non-empty line not starting with `#` is an entry
+    /// consisting of the divergence context and the transaction hash pattern separated by whitespace.
+    fn parse_all(contents: &str) -> anyhow::Result<Vec<Self>> {
+        let lines = contents.lines().enumerate().filter(|(_, line)| {
+            let line = line.trim();
+            !line.is_empty() && !line.starts_with('#')
+        });
+        lines
+            .map(|(i, line)| {
+                let mut parts = line.split_whitespace();
+                let (Some(context), Some(tx_hash), None) =
+                    (parts.next(), parts.next(), parts.next())
+                else {
+                    anyhow::bail!(
+                        "line {}: expected `<context> <tx hash pattern>`, got `{line}`",
+                        i + 1
+                    );
+                };
+                let tx_hash =
+                    TxHashPattern::parse(tx_hash).with_context(|| format!("line {}", i + 1))?;
+                Ok(Self {
+                    context: context.to_owned(),
+                    tx_hash,
+                })
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug)]
+struct AllowlistState {
+    entries: Vec<AllowlistEntry>,
+    modified_at: Option<SystemTime>,
+    checked_at: Instant,
+}
+
+/// Allowlist of known benign divergences for [`ShadowVm`](super::ShadowVm). Allowlisted divergences are not reported.
+///
+/// The allowlist is loaded from a file with one `<context> <tx hash pattern>` entry per line, e.g.
+/// `logs.events 0x1234*`. Lines starting with `#` are comments. A transaction hash pattern is either a full hash,
+/// a hash prefix followed by `*`, or `*` matching any divergence with the specified context (including divergences
+/// not related to a specific transaction).
+///
+/// The file is hot-reloaded: its modification time is checked at most once per [reload interval](Self::with_reload_interval()),
+/// and the file is re-read if it has changed. If reloading fails, the previously loaded entries are retained.
+#[derive(Debug, Clone)]
+pub struct DivergenceAllowlist {
+    path: PathBuf,
+    reload_interval: Duration,
+    state: Arc<Mutex<AllowlistState>>,
+}
+
+impl DivergenceAllowlist {
+    const DEFAULT_RELOAD_INTERVAL: Duration = Duration::from_secs(10);
+
+    /// Loads the allowlist from the specified file.
+    pub fn load(path: impl Into<PathBuf>) -> anyhow::Result<Self> {
+        let path = path.into();
+        let (entries, modified_at) = Self::read(&path)?;
+        tracing::info!(
+            "Loaded {} entries from divergence allowlist `{}`",
+            entries.len(),
+            path.display()
+        );
+        Ok(Self {
+            path,
+            reload_interval: Self::DEFAULT_RELOAD_INTERVAL,
+            state: Arc::new(Mutex::new(AllowlistState {
+                entries,
+                modified_at,
+                checked_at: Instant::now(),
+            })),
+        })
+    }
+
+    /// Sets the minimum interval between checks whether the allowlist file was modified. The default interval is 10 seconds.
+    pub fn with_reload_interval(mut self, interval: Duration) -> Self {
+        self.reload_interval = interval;
+        self
+    }
+
+    fn read(path: &Path) -> anyhow::Result<(Vec<AllowlistEntry>, Option<SystemTime>)> {
+        let modified_at = fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("failed reading divergence allowlist `{}`", path.display()))?;
+        let entries = AllowlistEntry::parse_all(&contents)
+            .with_context(|| format!("failed parsing divergence allowlist `{}`", path.display()))?;
+        Ok((entries, modified_at))
+    }
+
+    fn reload_if_modified(&self, state: &mut AllowlistState) {
+        if state.checked_at.elapsed() < self.reload_interval {
+            return;
+        }
+        state.checked_at = Instant::now();
+
+        let modified_at = fs::metadata(&self.path).and_then(|metadata| metadata.modified());
+        let modified_at = match modified_at {
+            Ok(modified_at) => modified_at,
+            Err(err) => {
+                tracing::warn!(
+                    "Failed checking divergence allowlist `{}`: {err}",
+                    self.path.display()
+                );
+                return;
+            }
+        };
+        if state.modified_at == Some(modified_at) {
+            return;
+        }
+
+        match Self::read(&self.path) {
+            Ok((entries, modified_at)) => {
+                tracing::info!(
+                    "Reloaded divergence allowlist `{}` with {} entries",
+                    self.path.display(),
+                    entries.len()
+                );
+                state.entries = entries;
+                state.modified_at = modified_at;
+            }
+            Err(err) => {
+                tracing::warn!(
+                    "{err:#}; retaining {} previously loaded entries",
+                    state.entries.len()
+                );
+                // Don't retry reading until the file is modified again.
+                state.modified_at = Some(modified_at);
+            }
+        }
+    }
+
+    /// Checks whether a divergence with the specified context, which occurred when executing the specified transaction
+    /// (if any), is allowlisted.
+    pub fn is_allowed(&self, context: &str, tx_hash: Option<H256>) -> bool {
+        let mut state = self.state.lock().expect("divergence allowlist is poisoned");
+        self.reload_if_modified(&mut state);
+        state
+            .entries
+            .iter()
+            .any(|entry| entry.context == context && entry.tx_hash.matches(tx_hash))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parsing_allowlist_entries() {
+        let contents = "\
+            # Comment\n\
+            logs.events 0x00ab*\n\
+            \n\
+            gas_remaining *\n";
+        let entries = AllowlistEntry::parse_all(contents).unwrap();
+        assert_eq!(
+            entries,
+            [
+                AllowlistEntry {
+                    context: "logs.events".to_owned(),
+                    tx_hash: TxHashPattern::Prefix("00ab".to_owned()),
+                },
+                AllowlistEntry {
+                    context: "gas_remaining".to_owned(),
+                    tx_hash: TxHashPattern::Any,
+                },
+            ]
+        );
+
+        assert!(AllowlistEntry::parse_all("logs.events").is_err());
+        assert!(AllowlistEntry::parse_all("logs.events 0x00ab").is_err());
+        assert!(AllowlistEntry::parse_all("logs.events 0xzz*").is_err());
+    }
+
+    #[test]
+    fn matching_and_reloading_allowlist() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("allowlist.txt");
+        fs::write(&path, "logs.events 0x00ab*\n").unwrap();
+        let allowlist = DivergenceAllowlist::load(&path)
+            .unwrap()
+            .with_reload_interval(Duration::ZERO);
+
+        let mut tx_hash = H256::repeat_byte(0xab);
+        tx_hash.0[0] = 0;
+        assert!(allowlist.is_allowed("logs.events", Some(tx_hash)));
+        assert!(!allowlist.is_allowed("logs.events", Some(H256::repeat_byte(0xab))));
+        assert!(!allowlist.is_allowed("logs.events", None));
+        assert!(!allowlist.is_allowed("gas_remaining", Some(tx_hash)));
+
+        fs::write(&path, "gas_remaining *\n").unwrap();
+        // Explicitly update the modification time since the file system may have coarse timestamps.
+        let file = fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(10))
+            .unwrap();
+        assert!(allowlist.is_allowed("gas_remaining", None));
+        assert!(!allowlist.is_allowed("logs.events", Some(tx_hash)));
+
+        // Invalid allowlist must not be loaded.
+        fs::write(&path, "gas_remaining").unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(20))
+            .unwrap();
+        assert!(allowlist.is_allowed("gas_remaining", None));
+    }
+}
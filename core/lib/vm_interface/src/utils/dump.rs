@@ -1,13 +1,31 @@
-use std::collections::HashMap;
+use std::{
+    borrow::Cow,
+    cell::Cell,
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fs,
+    hash::Hash,
+    io, mem,
+    path::Path,
+};
 
-use serde::{Deserialize, Serialize};
-use zksync_types::{block::L2BlockExecutionData, L1BatchNumber, L2BlockNumber, Transaction, H256};
+use anyhow::Context as _;
+use serde::{
+    ser::{self, SerializeMap, SerializeStruct},
+    Deserialize, Serialize, Serializer,
+};
+use zksync_types::{
+    block::L2BlockExecutionData,
+    web3::{self, keccak256},
+    L1BatchNumber, L2BlockNumber, Transaction, H256,
+};
 
+use super::shadow::{record_finished_batch, record_results, DivergenceErrors, ShadowVm};
 use crate::{
     storage::{ReadStorage, StoragePtr, StorageSnapshot, StorageView},
-    BytecodeCompressionResult, FinishedL1Batch, L1BatchEnv, L2BlockEnv, SystemEnv, VmExecutionMode,
-    VmExecutionResultAndLogs, VmFactory, VmInterface, VmInterfaceExt, VmInterfaceHistoryEnabled,
-    VmMemoryMetrics, VmTrackingContracts,
+    types::outputs::bytecode_len_in_bytes,
+    BytecodeCompressionResult, CurrentExecutionState, FinishedL1Batch, L1BatchEnv, L2BlockEnv,
+    SystemEnv, VmExecutionMode, VmExecutionResultAndLogs, VmFactory, VmInspectExecutionState,
+    VmInterface, VmInterfaceExt, VmInterfaceHistoryEnabled, VmMemoryMetrics, VmTrackingContracts,
 };
 
 fn create_storage_snapshot<S: ReadStorage>(
@@ -15,36 +33,266 @@ fn create_storage_snapshot<S: ReadStorage>(
     used_contract_hashes: Vec<H256>,
 ) -> StorageSnapshot {
     let mut storage = storage.borrow_mut();
-    let storage_cache = storage.cache();
-    let mut storage_slots: HashMap<_, _> = storage_cache
-        .read_storage_keys()
+    let storage_slots = storage.snapshot_slots().collect();
+    let factory_deps = used_contract_hashes
         .into_iter()
-        .map(|(key, value)| {
-            let enum_index = storage.get_enumeration_index(&key);
-            let value_and_index = enum_index.map(|idx| (value, idx));
-            (key.hashed_key(), value_and_index)
-        })
+        .filter_map(|hash| Some((hash, storage.load_factory_dep(hash)?)))
         .collect();
 
-    // Normally, all writes are internally read in order to calculate their gas costs, so the code below
-    // is defensive programming.
-    for (key, _) in storage_cache.initial_writes() {
-        let hashed_key = key.hashed_key();
-        if storage_slots.contains_key(&hashed_key) {
-            continue;
+    StorageSnapshot::new(storage_slots, factory_deps)
+}
+
+/// View of a [`VmDump`] serialized with the same layout, which doesn't require building the dump in memory.
+/// Storage slots and factory deps are read from storage, and transaction calldata is compacted on the fly
+/// during serialization.
+#[derive(Serialize)]
+struct StreamingVmDump<'a, S> {
+    l1_batch_env: &'a L1BatchEnv,
+    system_env: &'a SystemEnv,
+    l2_blocks: CompactedL2Blocks<'a>,
+    storage: StreamingStorageSnapshot<'a, S>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    outputs: &'a [RecordedOutputs],
+}
+
+struct CompactedL2Blocks<'a> {
+    blocks: &'a [L2BlockExecutionData],
+    mode: CalldataDumpMode,
+}
+
+impl Serialize for CompactedL2Blocks<'_> {
+    fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        // Blocks are cloned one by one, so that at most one block is duplicated in memory.
+        serializer.collect_seq(self.blocks.iter().map(|block| {
+            let mut block = block.clone();
+            for tx in &mut block.txs {
+                self.mode.compact(tx);
+            }
+            block
+        }))
+    }
+}
+
+/// Serialized in the same way as [`StorageSnapshot`] (incl. sorting map entries by key), but reads entries from storage
+/// on the fly. Like in [`StorageSnapshot`]s created in memory, factory deps missing from storage (e.g., ones deployed
+/// in the dumped batch) are skipped.
+struct StreamingStorageSnapshot<'a, S> {
+    storage: &'a StoragePtr<StorageView<S>>,
+    factory_dep_hashes: Vec<H256>,
+}
+
+impl<S: ReadStorage> Serialize for StreamingStorageSnapshot<'_, S> {
+    fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        let mut snapshot = serializer.serialize_struct("StorageSnapshot", 2)?;
+        {
+            let mut storage = self.storage.borrow_mut();
+            let slots = storage.snapshot_slots();
+            let slots = StreamingMap::new(slots.len(), slots.map(Ok::<_, String>));
+            snapshot.serialize_field("storage", &slots)?;
         }
 
-        let enum_index = storage.get_enumeration_index(&key);
-        let value_and_index = enum_index.map(|idx| (storage.read_value(&key), idx));
-        storage_slots.insert(hashed_key, value_and_index);
+        // Non-self-describing formats require the map length upfront, so present deps are determined before
+        // streaming them. This loads each dep twice, but only keeps one dep in memory at a time.
+        let mut present_hashes: Vec<_> = self
+            .factory_dep_hashes
+            .iter()
+            .copied()
+            .filter(|&hash| self.storage.borrow_mut().load_factory_dep(hash).is_some())
+            .collect();
+        present_hashes.sort_unstable();
+        present_hashes.dedup();
+        let factory_deps = present_hashes.iter().map(|&hash| {
+            let bytecode = self.storage.borrow_mut().load_factory_dep(hash);
+            let bytecode =
+                bytecode.ok_or_else(|| format!("factory dep {hash:?} disappeared from storage"))?;
+            Ok((hash, web3::Bytes(bytecode)))
+        });
+        let factory_deps = StreamingMap::new(present_hashes.len(), factory_deps);
+        snapshot.serialize_field("factory_deps", &factory_deps)?;
+        snapshot.end()
     }
+}
 
-    let factory_deps = used_contract_hashes
-        .into_iter()
-        .filter_map(|hash| Some((hash, storage.load_factory_dep(hash)?)))
-        .collect();
+/// Map with a known number of entries serialized directly from an iterator. The length is required
+/// by non-self-describing formats like `bincode`.
+struct StreamingMap<I> {
+    len: usize,
+    entries: Cell<Option<I>>,
+}
 
-    StorageSnapshot::new(storage_slots, factory_deps)
+impl<I> StreamingMap<I> {
+    fn new(len: usize, entries: I) -> Self {
+        Self {
+            len,
+            entries: Cell::new(Some(entries)),
+        }
+    }
+}
+
+impl<K, V, I> Serialize for StreamingMap<I>
+where
+    K: Serialize,
+    V: Serialize,
+    I: Iterator<Item = Result<(K, V), String>>,
+{
+    fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        let entries = self.entries.take().expect("streaming map serialized twice");
+        let mut map = serializer.serialize_map(Some(self.len))?;
+        for entry in entries {
+            let (key, value) = entry.map_err(<Ser::Error as ser::Error>::custom)?;
+            map.serialize_entry(&key, &value)?;
+        }
+        map.end()
+    }
+}
+
+/// Outputs of a single VM operation recorded in a [`VmDump`].
+///
+/// Outputs are recorded as pretty-printed `Debug` representations of the values compared by [`ShadowVm`], keyed by the comparison
+/// context (e.g., `logs.events`). This allows using a dump with recorded outputs as an expected ("golden") trace for the VM.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedOutputs {
+    /// Name of the VM operation that has produced outputs, e.g. `inspect_transaction` or `finish_batch`.
+    pub operation: String,
+    /// Recorded output values keyed by the comparison context.
+    pub values: BTreeMap<String, String>,
+}
+
+/// Handling of transaction calldata in [`VmDump`]s.
+///
+/// Large calldata can significantly bloat dumps. Non-[`Full`](Self::Full) modes allow reducing dump size while keeping
+/// the structure of transactions (e.g., their hashes, senders, contract addresses and factory deps) intact. Beware that
+/// dumps with compacted calldata cannot be faithfully played back.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CalldataDumpMode {
+    /// Calldata is dumped as is.
+    #[default]
+    Full,
+    /// Calldata exceeding `max_len` bytes is truncated to its first `max_len` bytes.
+    Truncated { max_len: usize },
+    /// Calldata exceeding `max_len` bytes is replaced with its keccak256 hash.
+    Hashed { max_len: usize },
+}
+
+impl CalldataDumpMode {
+    /// Compacts calldata of the transaction. Returns `true` if the calldata was modified.
+    fn compact(self, tx: &mut Transaction) -> bool {
+        let calldata = &mut tx.execute.calldata;
+        match self {
+            Self::Full => return false,
+            Self::Truncated { max_len } if calldata.len() > max_len => {
+                calldata.truncate(max_len);
+            }
+            Self::Hashed { max_len } if calldata.len() > max_len => {
+                *calldata = keccak256(calldata).to_vec();
+            }
+            Self::Truncated { .. } | Self::Hashed { .. } => return false,
+        }
+        // Raw transaction bytes contain the original calldata, so they are removed as well.
+        tx.raw_bytes = None;
+        true
+    }
+}
+
+/// Serialization format of [`VmDump`]s.
+///
+/// JSON dumps are human-readable and can be validated using the [JSON schema](VmDump::json_schema()), but are large
+/// and slow to produce for big batches. Binary dumps are more compact and are thus preferable for production use.
+/// The format of a serialized dump is detected automatically when [reading](VmDump::from_bytes()) the dump.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum VmDumpFormat {
+    /// JSON serialization via `serde_json`.
+    #[default]
+    Json,
+    /// Binary serialization via `bincode` prefixed with a magic byte sequence.
+    Bincode,
+}
+
+impl VmDumpFormat {
+    /// Prefix of binary dumps. Starts with a zero byte, so it cannot be confused with the start of a JSON dump.
+    const BINCODE_MAGIC: &'static [u8] = b"\0zksync_vm_dump_v1";
+
+    /// Detects the format of a serialized dump.
+    pub fn detect(bytes: &[u8]) -> Self {
+        if bytes.starts_with(Self::BINCODE_MAGIC) {
+            Self::Bincode
+        } else {
+            Self::Json
+        }
+    }
+
+    /// Returns the conventional file extension for dumps in this format (without the leading dot).
+    pub fn file_extension(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Bincode => "bin",
+        }
+    }
+}
+
+/// Compression applied to serialized [`VmDump`]s. Compressed dumps are detected and decompressed automatically
+/// when [reading](VmDump::from_bytes()) them.
+///
+/// By default, dumps are compressed with zstd using a balanced level. Lower (incl. negative) levels trade compression
+/// ratio for speed and may be preferable on CPU-constrained nodes; higher levels (up to 22) are suitable for archival.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VmDumpCompression {
+    /// No compression.
+    None,
+    /// zstd compression with the specified level.
+    Zstd { level: i32 },
+}
+
+impl Default for VmDumpCompression {
+    fn default() -> Self {
+        Self::Zstd {
+            level: Self::DEFAULT_ZSTD_LEVEL,
+        }
+    }
+}
+
+impl VmDumpCompression {
+    /// Default zstd compression level.
+    pub const DEFAULT_ZSTD_LEVEL: i32 = 3;
+    /// Magic number starting zstd frames.
+    const ZSTD_MAGIC: &'static [u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+    /// Returns the file extension appended to the [format extension](VmDumpFormat::file_extension())
+    /// for dumps with this compression (without the leading dot), if any.
+    pub fn file_extension(self) -> Option<&'static str> {
+        match self {
+            Self::None => None,
+            Self::Zstd { .. } => Some("zst"),
+        }
+    }
+
+    fn compress(self, bytes: Vec<u8>) -> anyhow::Result<Vec<u8>> {
+        match self {
+            Self::None => Ok(bytes),
+            Self::Zstd { level } => {
+                Self::check_zstd_level(level)?;
+                zstd::encode_all(bytes.as_slice(), level).context("failed compressing VM dump")
+            }
+        }
+    }
+
+    fn check_zstd_level(level: i32) -> anyhow::Result<()> {
+        let levels = zstd::compression_level_range();
+        anyhow::ensure!(
+            levels.contains(&level),
+            "zstd compression level {level} is outside the supported range {levels:?}"
+        );
+        Ok(())
+    }
+
+    fn decompress(bytes: &[u8]) -> anyhow::Result<Cow<'_, [u8]>> {
+        if bytes.starts_with(Self::ZSTD_MAGIC) {
+            let decompressed = zstd::decode_all(bytes).context("failed decompressing VM dump")?;
+            Ok(Cow::Owned(decompressed))
+        } else {
+            Ok(Cow::Borrowed(bytes))
+        }
+    }
 }
 
 /// VM dump allowing to re-run the VM on the same inputs. Can be (de)serialized.
@@ -54,6 +302,10 @@ pub struct VmDump {
     pub system_env: SystemEnv,
     pub l2_blocks: Vec<L2BlockExecutionData>,
     pub storage: StorageSnapshot,
+    /// Outputs of the VM operations in the order of their execution. Only populated if output recording
+    /// is [enabled](ShadowVm::record_outputs()) for the dumped VM.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub outputs: Vec<RecordedOutputs>,
 }
 
 impl VmDump {
@@ -61,6 +313,252 @@ impl VmDump {
         self.l1_batch_env.number
     }
 
+    /// Returns the conventional file name for a dump of the specified L1 batch produced on a divergence with
+    /// the specified [stable hash](DivergenceErrors::stable_hash()). Since the name depends on the hash,
+    /// dumps for a recurring divergence share the name.
+    pub fn file_name(
+        l1_batch_number: L1BatchNumber,
+        err_hash: H256,
+        format: VmDumpFormat,
+        compression: VmDumpCompression,
+    ) -> String {
+        let batch_number = l1_batch_number.0;
+        let mut extension = format.file_extension().to_owned();
+        if let Some(compression_extension) = compression.file_extension() {
+            extension = format!("{extension}.{compression_extension}");
+        }
+        format!("shadow_vm_dump_batch{batch_number:08}_{err_hash:x}.{extension}")
+    }
+
+    /// Serializes this dump in the specified format.
+    pub fn to_bytes(&self, format: VmDumpFormat) -> anyhow::Result<Vec<u8>> {
+        match format {
+            VmDumpFormat::Json => {
+                serde_json::to_vec(self).context("failed serializing VM dump to JSON")
+            }
+            VmDumpFormat::Bincode => {
+                // `bincode` isn't self-describing, so fields skipped during serialization (like `outputs`) would break
+                // deserialization. Hence, all fields are serialized as a tuple.
+                let fields = (
+                    &self.l1_batch_env,
+                    &self.system_env,
+                    &self.l2_blocks,
+                    &self.storage,
+                    &self.outputs,
+                );
+                let mut bytes = VmDumpFormat::BINCODE_MAGIC.to_vec();
+                bincode::serialize_into(&mut bytes, &fields)
+                    .context("failed serializing VM dump with bincode")?;
+                Ok(bytes)
+            }
+        }
+    }
+
+    /// Serializes this dump in the specified format and compresses it.
+    pub fn to_compressed_bytes(
+        &self,
+        format: VmDumpFormat,
+        compression: VmDumpCompression,
+    ) -> anyhow::Result<Vec<u8>> {
+        compression.compress(self.to_bytes(format)?)
+    }
+
+    /// Deserializes a dump, automatically detecting its [format](VmDumpFormat) and [compression](VmDumpCompression).
+    pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        let bytes = &*VmDumpCompression::decompress(bytes)?;
+        match VmDumpFormat::detect(bytes) {
+            VmDumpFormat::Json => {
+                serde_json::from_slice(bytes).context("failed deserializing JSON VM dump")
+            }
+            VmDumpFormat::Bincode => {
+                let bytes = &bytes[VmDumpFormat::BINCODE_MAGIC.len()..];
+                let (l1_batch_env, system_env, l2_blocks, storage, outputs) =
+                    bincode::deserialize(bytes).context("failed deserializing binary VM dump")?;
+                Ok(Self {
+                    l1_batch_env,
+                    system_env,
+                    l2_blocks,
+                    storage,
+                    outputs,
+                })
+            }
+        }
+    }
+
+    /// Writes this dump to the specified file in the specified format and compression.
+    /// The file is overwritten if it exists.
+    pub fn dump_to_file(
+        &self,
+        path: &Path,
+        format: VmDumpFormat,
+        compression: VmDumpCompression,
+    ) -> anyhow::Result<()> {
+        let bytes = self.to_compressed_bytes(format, compression)?;
+        fs::write(path, bytes)
+            .with_context(|| format!("failed writing VM dump to `{}`", path.display()))
+    }
+
+    /// Reads a dump from the specified file, automatically detecting its [format](VmDumpFormat)
+    /// and [compression](VmDumpCompression).
+    pub fn read_from_file(path: &Path) -> anyhow::Result<Self> {
+        let bytes = fs::read(path)
+            .with_context(|| format!("failed reading VM dump from `{}`", path.display()))?;
+        Self::from_bytes(&bytes).with_context(|| format!("invalid VM dump `{}`", path.display()))
+    }
+
+    /// Returns the JSON schema (draft 2020-12) for dumps serialized with `serde_json`. Can be used by external tooling
+    /// to validate dump files.
+    ///
+    /// The schema fully describes the dump structure and storage, while leaving VM environment components reused
+    /// from other crates (e.g., fee inputs, base system contracts and transactions) loosely typed.
+    pub fn json_schema() -> serde_json::Value {
+        serde_json::json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "title": "VmDump",
+            "type": "object",
+            "required": ["l1_batch_env", "system_env", "l2_blocks", "storage"],
+            "additionalProperties": false,
+            "properties": {
+                "l1_batch_env": {
+                    "type": "object",
+                    "required": [
+                        "previous_batch_hash",
+                        "number",
+                        "timestamp",
+                        "fee_input",
+                        "fee_account",
+                        "enforced_base_fee",
+                        "first_l2_block",
+                    ],
+                    "additionalProperties": false,
+                    "properties": {
+                        "previous_batch_hash": { "oneOf": [{ "type": "null" }, { "$ref": "#/$defs/h256" }] },
+                        "number": { "type": "integer", "minimum": 0 },
+                        "timestamp": { "type": "integer", "minimum": 0 },
+                        "fee_input": { "type": "object" },
+                        "fee_account": { "$ref": "#/$defs/address" },
+                        "enforced_base_fee": { "type": ["integer", "null"], "minimum": 0 },
+                        "first_l2_block": { "$ref": "#/$defs/l2_block_env" },
+                    },
+                },
+                "system_env": {
+                    "type": "object",
+                    "required": [
+                        "zk_porter_available",
+                        "version",
+                        "base_system_smart_contracts",
+                        "bootloader_gas_limit",
+                        "execution_mode",
+                        "default_validation_computational_gas_limit",
+                        "chain_id",
+                    ],
+                    "additionalProperties": false,
+                    "properties": {
+                        "zk_porter_available": { "type": "boolean" },
+                        "version": {},
+                        "base_system_smart_contracts": { "type": "object" },
+                        "bootloader_gas_limit": { "type": "integer", "minimum": 0 },
+                        "execution_mode": { "type": "string" },
+                        "default_validation_computational_gas_limit": { "type": "integer", "minimum": 0 },
+                        "chain_id": {},
+                    },
+                },
+                "l2_blocks": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "required": ["number", "timestamp", "prev_block_hash", "virtual_blocks", "txs"],
+                        "additionalProperties": false,
+                        "properties": {
+                            "number": { "type": "integer", "minimum": 0 },
+                            "timestamp": { "type": "integer", "minimum": 0 },
+                            "prev_block_hash": { "$ref": "#/$defs/h256" },
+                            "virtual_blocks": { "type": "integer", "minimum": 0 },
+                            "txs": { "type": "array", "items": { "type": "object" } },
+                        },
+                    },
+                },
+                "storage": {
+                    "type": "object",
+                    "required": ["storage", "factory_deps"],
+                    "additionalProperties": false,
+                    "properties": {
+                        "storage": {
+                            "description": "Hashed storage keys mapped to (value, enumeration index) tuples; `null` for non-existing slots",
+                            "type": "object",
+                            "propertyNames": { "$ref": "#/$defs/h256" },
+                            "additionalProperties": {
+                                "oneOf": [
+                                    { "type": "null" },
+                                    {
+                                        "type": "array",
+                                        "prefixItems": [
+                                            { "$ref": "#/$defs/h256" },
+                                            { "type": "integer", "minimum": 0 },
+                                        ],
+                                        "items": false,
+                                    },
+                                ],
+                            },
+                        },
+                        "factory_deps": {
+                            "description": "Bytecode hashes mapped to hex-encoded bytecodes",
+                            "type": "object",
+                            "propertyNames": { "$ref": "#/$defs/h256" },
+                            "additionalProperties": { "$ref": "#/$defs/bytes" },
+                        },
+                    },
+                },
+                "outputs": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "required": ["operation", "values"],
+                        "additionalProperties": false,
+                        "properties": {
+                            "operation": { "type": "string" },
+                            "values": {
+                                "type": "object",
+                                "additionalProperties": { "type": "string" },
+                            },
+                        },
+                    },
+                },
+            },
+            "$defs": {
+                "h256": { "type": "string", "pattern": "^0x[0-9a-fA-F]{64}$" },
+                "address": { "type": "string", "pattern": "^0x[0-9a-fA-F]{40}$" },
+                "bytes": { "type": "string", "pattern": "^0x([0-9a-fA-F]{2})*$" },
+                "l2_block_env": {
+                    "type": "object",
+                    "required": ["number", "timestamp", "prev_block_hash", "max_virtual_blocks_to_create"],
+                    "additionalProperties": false,
+                    "properties": {
+                        "number": { "type": "integer", "minimum": 0 },
+                        "timestamp": { "type": "integer", "minimum": 0 },
+                        "prev_block_hash": { "$ref": "#/$defs/h256" },
+                        "max_virtual_blocks_to_create": { "type": "integer", "minimum": 0 },
+                    },
+                },
+            },
+        })
+    }
+
+    /// Compacts calldata of all transactions in this dump according to the specified mode.
+    pub fn compact_calldata(&mut self, mode: CalldataDumpMode) {
+        let txs = self.l2_blocks.iter_mut().flat_map(|block| &mut block.txs);
+        let compacted_count = txs
+            .map(|tx| mode.compact(tx))
+            .filter(|&compacted| compacted)
+            .count();
+        if compacted_count > 0 {
+            tracing::debug!(
+                "Compacted calldata for {compacted_count} transactions in dump for L1 batch #{} using {mode:?}",
+                self.l1_batch_number()
+            );
+        }
+    }
+
     /// Plays back this dump on the specified VM.
     pub fn play_back<Vm>(self) -> Vm
     where
@@ -69,6 +567,18 @@ impl VmDump {
         self.play_back_custom(Vm::new)
     }
 
+    /// Plays back this dump on the specified VM, comparing VM outputs with the [outputs](Self::outputs) recorded in the dump
+    /// instead of a live shadow VM. Divergences are handled in the same way as for [`ShadowVm`].
+    pub fn play_back_with_recorded_outputs<Vm>(mut self) -> ShadowVm<StorageSnapshot, Vm, Vm>
+    where
+        Vm: VmFactory<StorageView<StorageSnapshot>> + VmTrackingContracts,
+    {
+        let outputs = mem::take(&mut self.outputs);
+        self.play_back_custom(|l1_batch_env, system_env, storage| {
+            ShadowVm::with_recorded_outputs(l1_batch_env, system_env, storage, outputs)
+        })
+    }
+
     /// Plays back this dump on a VM created using the provided closure.
     #[doc(hidden)] // too low-level
     pub fn play_back_custom<Vm: VmInterface>(
@@ -103,10 +613,71 @@ impl VmDump {
     }
 }
 
+/// Compares inputs and storage recorded in 2 VM dumps (e.g., produced by different VM builds for the same batch).
+/// Can be used to check whether divergences in different dumps share the same root cause.
+///
+/// Storage slots and factory deps are compared by entries present in either dump; only differing entries are reported.
+pub fn diff_dumps(a: &VmDump, b: &VmDump) -> DivergenceErrors {
+    let mut errors = DivergenceErrors::new();
+    errors.check_match("l1_batch_env", &a.l1_batch_env, &b.l1_batch_env);
+    errors.check_match("system_env", &a.system_env, &b.system_env);
+
+    let block_summaries = |dump: &VmDump| -> Vec<_> {
+        dump.l2_blocks
+            .iter()
+            .map(|block| {
+                let tx_hashes: Vec<_> = block.txs.iter().map(Transaction::hash).collect();
+                (
+                    block.number,
+                    block.timestamp,
+                    block.prev_block_hash,
+                    block.virtual_blocks,
+                    tx_hashes,
+                )
+            })
+            .collect()
+    };
+    errors.check_match("l2_blocks", &block_summaries(a), &block_summaries(b));
+
+    let (a_slots, b_slots) =
+        differing_entries(a.storage.storage_slots(), b.storage.storage_slots());
+    errors.check_match("storage.slots", &a_slots, &b_slots);
+    let a_deps: BTreeSet<_> = a.storage.factory_deps().keys().collect();
+    let b_deps: BTreeSet<_> = b.storage.factory_deps().keys().collect();
+    let a_only_deps: BTreeSet<_> = a_deps.difference(&b_deps).collect();
+    let b_only_deps: BTreeSet<_> = b_deps.difference(&a_deps).collect();
+    errors.check_match("storage.factory_deps", &a_only_deps, &b_only_deps);
+
+    errors.check_match("outputs", &a.outputs, &b.outputs);
+    errors.context(format!(
+        "comparing dumps for L1 batches #{} and #{}",
+        a.l1_batch_number(),
+        b.l1_batch_number()
+    ))
+}
+
+/// Returns entries that differ between maps (including entries present only in one of the maps), sorted by key.
+fn differing_entries<'a, K: Ord + Hash, V: PartialEq>(
+    a: &'a HashMap<K, V>,
+    b: &'a HashMap<K, V>,
+) -> (
+    BTreeMap<&'a K, Option<&'a V>>,
+    BTreeMap<&'a K, Option<&'a V>>,
+) {
+    let keys: BTreeSet<_> = a.keys().chain(b.keys()).collect();
+    keys.into_iter()
+        .filter_map(|key| {
+            let (a_value, b_value) = (a.get(key), b.get(key));
+            (a_value != b_value).then_some(((key, a_value), (key, b_value)))
+        })
+        .unzip()
+}
+
 #[derive(Debug, Clone, Copy)]
 struct L2BlocksSnapshot {
     block_count: usize,
     tx_count_in_last_block: usize,
+    output_count: usize,
 }
 
 /// VM wrapper that can create [`VmDump`]s during execution.
@@ -118,6 +689,9 @@ pub(super) struct DumpingVm<S, Vm> {
     system_env: SystemEnv,
     l2_blocks: Vec<L2BlockExecutionData>,
     l2_blocks_snapshot: Option<L2BlocksSnapshot>,
+    /// `None` if output recording is disabled.
+    outputs: Option<Vec<RecordedOutputs>>,
+    calldata_dump_mode: CalldataDumpMode,
 }
 
 impl<S: ReadStorage, Vm: VmTrackingContracts> DumpingVm<S, Vm> {
@@ -129,13 +703,137 @@ impl<S: ReadStorage, Vm: VmTrackingContracts> DumpingVm<S, Vm> {
         self.last_block_mut().txs.push(tx);
     }
 
+    fn record_outputs(&mut self, create_outputs: impl FnOnce() -> RecordedOutputs) {
+        if let Some(outputs) = &mut self.outputs {
+            outputs.push(create_outputs());
+        }
+    }
+
+    /// Enables recording VM outputs. Outputs are only recorded for operations executed after this call.
+    pub fn enable_output_recording(&mut self) {
+        self.outputs.get_or_insert_with(Vec::new);
+    }
+
+    pub fn set_calldata_dump_mode(&mut self, mode: CalldataDumpMode) {
+        self.calldata_dump_mode = mode;
+    }
+
+    pub fn l1_batch_number(&self) -> L1BatchNumber {
+        self.l1_batch_env.number
+    }
+
+    pub fn l1_batch_env(&self) -> &L1BatchEnv {
+        &self.l1_batch_env
+    }
+
+    /// Returns the number and timestamp of the current L2 block.
+    pub fn current_l2_block(&self) -> (L2BlockNumber, u64) {
+        let block = self.l2_blocks.last().unwrap();
+        (block.number, block.timestamp)
+    }
+
+    /// Returns the number of transactions in the batch, including the transaction being executed (if any).
+    pub fn tx_count(&self) -> usize {
+        self.l2_blocks.iter().map(|block| block.txs.len()).sum()
+    }
+
+    /// Returns a rough estimate of the size of the [dump](Self::dump_state()) in memory. The estimate is dominated
+    /// by transaction calldata, storage slots and factory deps. It's computed from sizes already known to the VM,
+    /// without reading storage; in particular, factory dep sizes are derived from their hashes.
+    pub fn estimated_dump_size(&self) -> usize {
+        let txs_size: usize = self
+            .l2_blocks
+            .iter()
+            .flat_map(|block| &block.txs)
+            .map(|tx| {
+                let factory_deps_size: usize = tx.execute.factory_deps.iter().map(Vec::len).sum();
+                tx.execute.calldata.len() + factory_deps_size
+            })
+            .sum();
+        // Storage slots in the dump are taken from the storage cache, so the cache size is a reasonable estimate.
+        let storage_size = self.storage.borrow().stats().cache_size;
+        let factory_deps_size: usize = self
+            .inner
+            .used_contract_hashes()
+            .into_iter()
+            .map(bytecode_len_in_bytes)
+            .sum();
+        txs_size + storage_size + factory_deps_size
+    }
+
+    /// Writes the [dump](Self::dump_state()) in the specified format and compression to the provided writer without
+    /// building it in memory. The output can be read using [`VmDump::from_bytes()`] or [`VmDump::read_from_file()`].
+    ///
+    /// Without compression, the output is equal to [`VmDump::to_bytes()`] for the [dump](Self::dump_state()).
+    pub fn write_dump(
+        &self,
+        writer: impl io::Write,
+        format: VmDumpFormat,
+        compression: VmDumpCompression,
+    ) -> anyhow::Result<()> {
+        match compression {
+            VmDumpCompression::None => self.write_uncompressed_dump(writer, format),
+            VmDumpCompression::Zstd { level } => {
+                VmDumpCompression::check_zstd_level(level)?;
+                let mut encoder = zstd::Encoder::new(writer, level)
+                    .context("failed initializing zstd encoder for VM dump")?;
+                self.write_uncompressed_dump(&mut encoder, format)?;
+                encoder.finish().context("failed compressing VM dump")?;
+                Ok(())
+            }
+        }
+    }
+
+    fn write_uncompressed_dump(
+        &self,
+        mut writer: impl io::Write,
+        format: VmDumpFormat,
+    ) -> anyhow::Result<()> {
+        let dump = StreamingVmDump {
+            l1_batch_env: &self.l1_batch_env,
+            system_env: &self.system_env,
+            l2_blocks: CompactedL2Blocks {
+                blocks: &self.l2_blocks,
+                mode: self.calldata_dump_mode,
+            },
+            storage: StreamingStorageSnapshot {
+                storage: &self.storage,
+                factory_dep_hashes: self.inner.used_contract_hashes(),
+            },
+            outputs: self.outputs.as_deref().unwrap_or_default(),
+        };
+        match format {
+            VmDumpFormat::Json => {
+                serde_json::to_writer(writer, &dump).context("failed serializing VM dump to JSON")
+            }
+            VmDumpFormat::Bincode => {
+                // Same layout as in `VmDump::to_bytes()`.
+                let fields = (
+                    dump.l1_batch_env,
+                    dump.system_env,
+                    &dump.l2_blocks,
+                    &dump.storage,
+                    dump.outputs,
+                );
+                writer
+                    .write_all(VmDumpFormat::BINCODE_MAGIC)
+                    .context("failed writing VM dump")?;
+                bincode::serialize_into(writer, &fields)
+                    .context("failed serializing VM dump with bincode")
+            }
+        }
+    }
+
     pub fn dump_state(&self) -> VmDump {
-        VmDump {
+        let mut dump = VmDump {
             l1_batch_env: self.l1_batch_env.clone(),
             system_env: self.system_env.clone(),
             l2_blocks: self.l2_blocks.clone(),
             storage: create_storage_snapshot(&self.storage, self.inner.used_contract_hashes()),
-        }
+            outputs: self.outputs.clone().unwrap_or_default(),
+        };
+        dump.compact_calldata(self.calldata_dump_mode);
+        dump
     }
 }
 
@@ -152,7 +850,9 @@ impl<S: ReadStorage, Vm: VmTrackingContracts> VmInterface for DumpingVm<S, Vm> {
         dispatcher: &mut Self::TracerDispatcher,
         execution_mode: VmExecutionMode,
     ) -> VmExecutionResultAndLogs {
-        self.inner.inspect(dispatcher, execution_mode)
+        let result = self.inner.inspect(dispatcher, execution_mode);
+        self.record_outputs(|| record_results("inspect", &result));
+        result
     }
 
     fn start_new_l2_block(&mut self, l2_block_env: L2BlockEnv) {
@@ -173,8 +873,11 @@ impl<S: ReadStorage, Vm: VmTrackingContracts> VmInterface for DumpingVm<S, Vm> {
         with_compression: bool,
     ) -> (BytecodeCompressionResult, VmExecutionResultAndLogs) {
         self.record_transaction(tx.clone());
-        self.inner
-            .inspect_transaction_with_bytecode_compression(tracer, tx, with_compression)
+        let (compression_result, tx_result) = self
+            .inner
+            .inspect_transaction_with_bytecode_compression(tracer, tx, with_compression);
+        self.record_outputs(|| record_results("inspect_transaction", &tx_result));
+        (compression_result, tx_result)
     }
 
     fn record_vm_memory_metrics(&self) -> VmMemoryMetrics {
@@ -182,7 +885,19 @@ impl<S: ReadStorage, Vm: VmTrackingContracts> VmInterface for DumpingVm<S, Vm> {
     }
 
     fn finish_batch(&mut self) -> FinishedL1Batch {
-        self.inner.finish_batch()
+        let batch = self.inner.finish_batch();
+        self.record_outputs(|| record_finished_batch(&batch));
+        batch
+    }
+}
+
+impl<S, Vm> VmInspectExecutionState for DumpingVm<S, Vm>
+where
+    S: ReadStorage,
+    Vm: VmInspectExecutionState + VmTrackingContracts,
+{
+    fn current_execution_state(&self) -> CurrentExecutionState {
+        self.inner.current_execution_state()
     }
 }
 
@@ -195,6 +910,7 @@ where
         self.l2_blocks_snapshot = Some(L2BlocksSnapshot {
             block_count: self.l2_blocks.len(),
             tx_count_in_last_block: self.last_block_mut().txs.len(),
+            output_count: self.outputs.as_ref().map_or(0, Vec::len),
         });
         self.inner.make_snapshot();
     }
@@ -214,6 +930,9 @@ where
         self.last_block_mut()
             .txs
             .truncate(snapshot.tx_count_in_last_block);
+        if let Some(outputs) = &mut self.outputs {
+            outputs.truncate(snapshot.output_count);
+        }
     }
 
     fn pop_snapshot_no_rollback(&mut self) {
@@ -245,6 +964,8 @@ where
             system_env,
             l2_blocks: vec![first_block],
             l2_blocks_snapshot: None,
+            outputs: None,
+            calldata_dump_mode: CalldataDumpMode::default(),
             storage,
             inner,
         }
@@ -0,0 +1,19 @@
+//! Shadow VM metrics.
+
+use vise::{Buckets, Counter, Histogram, Metrics};
+
+#[derive(Debug, Metrics)]
+#[metrics(prefix = "vm_shadow")]
+pub(super) struct ShadowVmMetrics {
+    /// Number of transactions processed in an L1 batch (including the diverging one, if any)
+    /// when a reported divergence was detected.
+    #[metrics(buckets = Buckets::exponential(1.0..=8_192.0, 2.0))]
+    pub divergence_tx_count: Histogram<usize>,
+    /// Number of diverging transactions isolated by halting them, so that they are rolled back on both VMs.
+    pub isolated_divergent_txs: Counter,
+    /// Number of VM dumps spilled to disk because their estimated size exceeded the in-memory limit.
+    pub spilled_dumps: Counter,
+}
+
+#[vise::register]
+pub(super) static METRICS: vise::Global<ShadowVmMetrics> = vise::Global::new();
@@ -1,9 +1,22 @@
 //! Miscellaneous VM utils.
 
 pub use self::{
-    dump::VmDump,
-    shadow::{DivergenceErrors, DivergenceHandler, ShadowVm},
+    allowlist::DivergenceAllowlist,
+    dump::{
+        diff_dumps, CalldataDumpMode, RecordedOutputs, VmDump, VmDumpCompression, VmDumpFormat,
+    },
+    shadow::{
+        DivergenceCallback, DivergenceErrors, DivergenceHandler, DivergenceRateLimit,
+        DivergenceSeverities, DivergenceSeverity, ExecutionSteps, ShadowActivation,
+        ShadowActivationInput, ShadowVm, StorageLogsComparison, SystemLogsComparison,
+        TracerComparator, DIVERGENCE_CONTEXTS, ISOLATED_DIVERGENCE_HALT_REASON,
+    },
 };
 
+mod allowlist;
 mod dump;
+mod metrics;
+mod pubdata;
 mod shadow;
+#[cfg(feature = "testonly")]
+pub mod testonly;
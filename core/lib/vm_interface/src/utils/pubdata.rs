@@ -0,0 +1,115 @@
+//! Structural parsing of the pubdata input produced by the VM.
+
+use std::fmt;
+
+/// Length of a packed user L2-to-L1 log.
+const PACKED_L2_TO_L1_LOG_LEN: usize = 88;
+
+/// Hex-encoded bytes, used to make divergence reports readable.
+#[derive(PartialEq)]
+pub(super) struct HexBytes<'a>(&'a [u8]);
+
+impl fmt::Debug for HexBytes<'_> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "0x{}", hex::encode(self.0))
+    }
+}
+
+/// Sections of the pubdata input. Sections are parsed according to the L1 messenger pubdata format:
+///
+/// ```text
+/// [# user logs as u32 || user logs (88 bytes each)
+///  || # messages as u32 || (message length as u32 || message)*
+///  || # bytecodes as u32 || (bytecode length as u32 || bytecode)*
+///  || state diffs]
+/// ```
+///
+/// State diffs are not parsed further since their encoding depends on the protocol version.
+#[derive(Debug, PartialEq)]
+pub(super) struct PubdataSections<'a> {
+    pub user_logs: Vec<HexBytes<'a>>,
+    pub l2_to_l1_messages: Vec<HexBytes<'a>>,
+    pub published_bytecodes: Vec<HexBytes<'a>>,
+    pub state_diffs: HexBytes<'a>,
+}
+
+impl<'a> PubdataSections<'a> {
+    /// Parses pubdata sections. Returns `None` if the pubdata doesn't conform to the expected format.
+    pub fn parse(pubdata: &'a [u8]) -> Option<Self> {
+        let mut reader = Reader(pubdata);
+        let user_logs_count = reader.read_u32()?;
+        let user_logs = (0..user_logs_count)
+            .map(|_| reader.read_bytes(PACKED_L2_TO_L1_LOG_LEN))
+            .collect::<Option<_>>()?;
+        let l2_to_l1_messages = reader.read_length_prefixed_list()?;
+        let published_bytecodes = reader.read_length_prefixed_list()?;
+        Some(Self {
+            user_logs,
+            l2_to_l1_messages,
+            published_bytecodes,
+            state_diffs: HexBytes(reader.0),
+        })
+    }
+}
+
+#[derive(Debug)]
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn read_bytes(&mut self, len: usize) -> Option<HexBytes<'a>> {
+        if self.0.len() < len {
+            return None;
+        }
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Some(HexBytes(bytes))
+    }
+
+    fn read_u32(&mut self) -> Option<u32> {
+        let bytes = self.read_bytes(4)?.0;
+        Some(u32::from_be_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn read_length_prefixed_list(&mut self) -> Option<Vec<HexBytes<'a>>> {
+        let count = self.read_u32()?;
+        (0..count)
+            .map(|_| {
+                let len = self.read_u32()?;
+                self.read_bytes(len as usize)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parsing_pubdata_sections() {
+        let mut pubdata = vec![];
+        pubdata.extend(1_u32.to_be_bytes());
+        pubdata.extend([1; PACKED_L2_TO_L1_LOG_LEN]);
+        pubdata.extend(2_u32.to_be_bytes());
+        pubdata.extend(3_u32.to_be_bytes());
+        pubdata.extend([2; 3]);
+        pubdata.extend(0_u32.to_be_bytes());
+        pubdata.extend(0_u32.to_be_bytes());
+        pubdata.extend(0_u32.to_be_bytes());
+        pubdata.extend([3; 10]);
+
+        let sections = PubdataSections::parse(&pubdata).unwrap();
+        assert_eq!(
+            sections.user_logs,
+            [HexBytes(&[1; PACKED_L2_TO_L1_LOG_LEN])]
+        );
+        assert_eq!(
+            sections.l2_to_l1_messages,
+            [HexBytes(&[2; 3]), HexBytes(&[])]
+        );
+        assert!(sections.published_bytecodes.is_empty());
+        assert_eq!(sections.state_diffs, HexBytes(&[3; 10]));
+
+        assert!(PubdataSections::parse(&pubdata[..50]).is_none());
+    }
+}
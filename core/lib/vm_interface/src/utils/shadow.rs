@@ -1,81 +1,632 @@
 use std::{
     cell::RefCell,
-    collections::{BTreeMap, BTreeSet},
-    fmt,
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt, fs,
+    io::{self, Write as _},
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
     sync::Arc,
+    thread,
+    time::{Duration, Instant},
 };
 
-use zksync_types::{StorageKey, StorageLog, StorageLogWithPreviousValue, Transaction};
+use anyhow::Context as _;
+use tokio::sync::watch;
+use zksync_types::{
+    l2_to_l1_log::SystemL2ToL1Log, web3::keccak256, L1BatchNumber, L2BlockNumber, StorageKey,
+    StorageLog, StorageLogWithPreviousValue, Transaction, H256,
+};
 
-use super::dump::{DumpingVm, VmDump};
+use super::{
+    allowlist::DivergenceAllowlist,
+    dump::{CalldataDumpMode, DumpingVm, RecordedOutputs, VmDump, VmDumpCompression, VmDumpFormat},
+    metrics::METRICS,
+    pubdata::PubdataSections,
+};
 use crate::{
     storage::{ReadStorage, StoragePtr, StorageView},
-    BytecodeCompressionResult, CurrentExecutionState, FinishedL1Batch, L1BatchEnv, L2BlockEnv,
-    SystemEnv, VmExecutionMode, VmExecutionResultAndLogs, VmFactory, VmInterface,
-    VmInterfaceHistoryEnabled, VmMemoryMetrics, VmTrackingContracts,
+    BytecodeCompressionResult, CurrentExecutionState, ExecutionResult, FinishedL1Batch, Halt,
+    L1BatchEnv, L2BlockEnv, SystemEnv, VmExecutionMode, VmExecutionResultAndLogs, VmFactory,
+    VmInspectExecutionState, VmInterface, VmInterfaceHistoryEnabled, VmMemoryMetrics,
+    VmTrackingContracts,
 };
 
+/// Reason of [`Halt::TracerCustom`] returned by [`ShadowVm`] for transactions
+/// [isolated because of a divergence](ShadowVm::set_divergent_tx_isolation()).
+pub const ISOLATED_DIVERGENCE_HALT_REASON: &str =
+    "transaction execution diverged between main and shadow VMs";
+
+/// All divergence contexts that can be reported by [`ShadowVm`] itself, sorted alphabetically. Can be used to validate
+/// context names in [`DivergenceSeverities`] or [`DivergenceAllowlist`](super::DivergenceAllowlist) configs.
+///
+/// Contexts reported by a [`TracerComparator`] are defined by the comparator and are not included.
+pub const DIVERGENCE_CONTEXTS: &[&str] = &[
+    "deduplicated_storage_logs",
+    "final_bootloader_memory",
+    "final_state.events",
+    "final_state.pubdata_costs",
+    "final_state.storage_refunds",
+    "final_state.system_logs",
+    "final_state.used_contract_hashes",
+    "final_state.user_l2_to_l1_logs",
+    "gas_remaining",
+    "logs.events",
+    "logs.storage_logs",
+    "logs.system_l2_to_l1_logs",
+    "logs.user_l2_to_l1_logs",
+    "operation",
+    "pubdata_input",
+    "refunds",
+    "result",
+    "state_diffs",
+    "statistics.circuit_statistic",
+];
+
 /// Handler for VM divergences.
 #[derive(Clone)]
-pub struct DivergenceHandler(Arc<dyn Fn(DivergenceErrors, VmDump) + Send + Sync>);
+pub struct DivergenceHandler {
+    handle_dump: Arc<dyn Fn(DivergenceErrors, VmDump) + Send + Sync>,
+    dump_spill: Option<DumpSpill>,
+}
 
 impl fmt::Debug for DivergenceHandler {
     fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
         formatter
-            .debug_tuple("DivergenceHandler")
-            .field(&"_")
-            .finish()
+            .debug_struct("DivergenceHandler")
+            .field("dump_spill", &self.dump_spill)
+            .finish_non_exhaustive()
     }
 }
 
 /// Default handler that panics.
 impl Default for DivergenceHandler {
     fn default() -> Self {
-        Self(Arc::new(|err, _| {
+        Self::new(|err, _| {
             // There's no easy way to output the VM dump; it's too large to be logged.
             panic!("{err}");
-        }))
+        })
     }
 }
 
 impl DivergenceHandler {
     /// Creates a new handler from the provided closure.
     pub fn new(f: impl Fn(DivergenceErrors, VmDump) + Send + Sync + 'static) -> Self {
+        Self {
+            handle_dump: Arc::new(f),
+            dump_spill: None,
+        }
+    }
+
+    /// Makes this handler spill dumps with the estimated in-memory size exceeding `max_in_memory_size` bytes
+    /// to files in `dir`, so that reporting a divergence in a huge batch doesn't exhaust memory. Spilled dumps
+    /// are streamed to disk without building them in memory, and are passed to `on_spilled` by their path instead
+    /// of the closure provided in [`Self::new()`]. If spilling fails, divergence errors are still passed to `on_spilled`
+    /// together with the spilling error, and the dump is discarded.
+    ///
+    /// Spilled dumps are written in the specified `format` and `compression`, and can be read using
+    /// [`VmDump::read_from_file()`]. Dumps are named using [`VmDump::file_name()`], so a dump for a recurring divergence
+    /// is overwritten. A dump is written to a temporary file first, so that a failed write doesn't leave a truncated dump
+    /// under the final name.
+    pub fn with_dump_spill(
+        mut self,
+        max_in_memory_size: usize,
+        dir: impl Into<PathBuf>,
+        format: VmDumpFormat,
+        compression: VmDumpCompression,
+        on_spilled: impl Fn(DivergenceErrors, anyhow::Result<&Path>) + Send + Sync + 'static,
+    ) -> Self {
+        self.dump_spill = Some(DumpSpill {
+            max_in_memory_size,
+            dir: dir.into(),
+            format,
+            compression,
+            on_spilled: Arc::new(on_spilled),
+        });
+        self
+    }
+
+    fn handle<S: ReadStorage, Vm: VmTrackingContracts>(
+        &self,
+        err: DivergenceErrors,
+        vm: &DumpingVm<S, Vm>,
+    ) {
+        if let Some(spill) = &self.dump_spill {
+            let estimated_size = vm.estimated_dump_size();
+            if estimated_size > spill.max_in_memory_size {
+                tracing::info!(
+                    "Estimated VM dump size ({estimated_size} bytes) exceeds {} bytes; spilling dump to disk",
+                    spill.max_in_memory_size
+                );
+                match spill.write(&err, vm) {
+                    Ok(path) => {
+                        METRICS.spilled_dumps.inc();
+                        (spill.on_spilled)(err, Ok(&path));
+                    }
+                    Err(spill_err) => {
+                        tracing::error!(
+                            "Failed spilling VM dump for L1 batch #{}: {spill_err:#}",
+                            vm.l1_batch_number()
+                        );
+                        (spill.on_spilled)(err, Err(spill_err));
+                    }
+                }
+                return;
+            }
+        }
+        (self.handle_dump)(err, vm.dump_state());
+    }
+}
+
+/// Spilling of large VM dumps to disk for a [`DivergenceHandler`].
+#[derive(Clone)]
+struct DumpSpill {
+    max_in_memory_size: usize,
+    dir: PathBuf,
+    format: VmDumpFormat,
+    compression: VmDumpCompression,
+    on_spilled: Arc<dyn Fn(DivergenceErrors, anyhow::Result<&Path>) + Send + Sync>,
+}
+
+impl fmt::Debug for DumpSpill {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("DumpSpill")
+            .field("max_in_memory_size", &self.max_in_memory_size)
+            .field("dir", &self.dir)
+            .field("format", &self.format)
+            .field("compression", &self.compression)
+            .finish_non_exhaustive()
+    }
+}
+
+impl DumpSpill {
+    fn write<S: ReadStorage, Vm: VmTrackingContracts>(
+        &self,
+        err: &DivergenceErrors,
+        vm: &DumpingVm<S, Vm>,
+    ) -> anyhow::Result<PathBuf> {
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("failed creating directory `{}`", self.dir.display()))?;
+        let file_name = VmDump::file_name(
+            vm.l1_batch_number(),
+            err.stable_hash(),
+            self.format,
+            self.compression,
+        );
+        let path = self.dir.join(&file_name);
+        let tmp_path = self.dir.join(format!("{file_name}.tmp"));
+        if let Err(err) = self.write_to_file(&tmp_path, vm) {
+            fs::remove_file(&tmp_path).ok();
+            return Err(err);
+        }
+        fs::rename(&tmp_path, &path)
+            .with_context(|| format!("failed renaming dump to `{}`", path.display()))?;
+        Ok(path)
+    }
+
+    fn write_to_file<S: ReadStorage, Vm: VmTrackingContracts>(
+        &self,
+        path: &Path,
+        vm: &DumpingVm<S, Vm>,
+    ) -> anyhow::Result<()> {
+        let file = fs::File::create(path)
+            .with_context(|| format!("failed creating `{}`", path.display()))?;
+        let mut writer = io::BufWriter::new(file);
+        vm.write_dump(&mut writer, self.format, self.compression)?;
+        writer
+            .flush()
+            .with_context(|| format!("failed writing `{}`", path.display()))
+    }
+}
+
+/// Custom callback invoked on VM divergences in addition to the [`DivergenceHandler`], e.g. to page an on-call engineer
+/// or to send the divergence to a specialized system. Unlike the handler, the callback is invoked for each reported
+/// divergence (i.e., it isn't throttled) and doesn't receive a VM dump.
+///
+/// Panics in the callback are caught and logged, so that the callback cannot bring down the VM.
+#[derive(Clone)]
+pub struct DivergenceCallback(Arc<dyn Fn(&DivergenceErrors) + Send + Sync>);
+
+impl fmt::Debug for DivergenceCallback {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_tuple("DivergenceCallback")
+            .field(&"_")
+            .finish()
+    }
+}
+
+impl DivergenceCallback {
+    /// Creates a new callback from the provided closure.
+    pub fn new(f: impl Fn(&DivergenceErrors) + Send + Sync + 'static) -> Self {
         Self(Arc::new(f))
     }
 
-    fn handle(&self, err: DivergenceErrors, dump: VmDump) {
-        self.0(err, dump);
+    fn invoke(&self, err: &DivergenceErrors) {
+        let Err(panic) = panic::catch_unwind(AssertUnwindSafe(|| self.0(err))) else {
+            return;
+        };
+        let message = if let Some(message) = panic.downcast_ref::<&str>() {
+            *message
+        } else if let Some(message) = panic.downcast_ref::<String>() {
+            message.as_str()
+        } else {
+            "(non-string panic payload)"
+        };
+        tracing::error!("Divergence callback panicked: {message}");
+    }
+}
+
+/// Input of a [`ShadowActivation`] predicate.
+#[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
+pub enum ShadowActivationInput<'a> {
+    /// Environment of the executed L1 batch. The predicate is evaluated on it once, when it's set for the VM.
+    BatchInit(&'a L1BatchEnv),
+    /// Transaction about to be executed in the VM.
+    Transaction(&'a Transaction),
+}
+
+/// Predicate deciding whether to keep the shadow VM active, e.g. to only shadow batches with transactions for which
+/// the new VM is riskiest. Once the predicate returns `false`, the shadow VM is dropped for the rest of the batch.
+#[derive(Clone)]
+pub struct ShadowActivation(Arc<dyn Fn(ShadowActivationInput<'_>) -> bool + Send + Sync>);
+
+impl fmt::Debug for ShadowActivation {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_tuple("ShadowActivation")
+            .field(&"_")
+            .finish()
+    }
+}
+
+impl ShadowActivation {
+    /// Creates a predicate from the provided closure.
+    pub fn new(f: impl Fn(ShadowActivationInput<'_>) -> bool + Send + Sync + 'static) -> Self {
+        Self(Arc::new(f))
+    }
+
+    fn keep_shadow(&self, input: ShadowActivationInput<'_>) -> bool {
+        self.0(input)
+    }
+}
+
+/// Severity of a VM divergence, which determines how the divergence is handled by [`ShadowVm`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DivergenceSeverity {
+    /// Divergence is logged as a warning.
+    Warn,
+    /// Divergence is logged as an error.
+    Error,
+    /// Divergence is passed to the [`DivergenceHandler`] (which panics by default), after which the shadow VM is dropped.
+    Panic,
+}
+
+/// Severities of VM divergences keyed by the divergence context (e.g., `final_state.events` or `deduplicated_storage_logs`).
+/// By default, all divergences have [`DivergenceSeverity::Panic`].
+#[derive(Debug, Clone)]
+pub struct DivergenceSeverities {
+    default: DivergenceSeverity,
+    by_context: HashMap<String, DivergenceSeverity>,
+}
+
+impl Default for DivergenceSeverities {
+    fn default() -> Self {
+        Self::new(DivergenceSeverity::Panic)
+    }
+}
+
+impl DivergenceSeverities {
+    /// Creates severities with the specified default severity for all contexts.
+    pub fn new(default: DivergenceSeverity) -> Self {
+        Self {
+            default,
+            by_context: HashMap::new(),
+        }
+    }
+
+    /// Sets the severity for the specified divergence context.
+    #[must_use]
+    pub fn with_context(
+        mut self,
+        context: impl Into<String>,
+        severity: DivergenceSeverity,
+    ) -> Self {
+        self.by_context.insert(context.into(), severity);
+        self
+    }
+
+    /// Returns severity for the specified divergence context.
+    pub fn get(&self, context: &str) -> DivergenceSeverity {
+        self.by_context
+            .get(context)
+            .copied()
+            .unwrap_or(self.default)
+    }
+}
+
+/// Determines how storage logs produced by the main and shadow VMs are compared by [`ShadowVm`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StorageLogsComparison {
+    /// Storage logs are normalized before comparison:
+    ///
+    /// - Read logs are dropped.
+    /// - Write logs are deduplicated by the storage key; the deduplicated log has the previous value
+    ///   of the first write and the value of the last write to the key.
+    /// - Deduplicated no-op writes (i.e., ones with the value equal to the previous value) are dropped.
+    /// - The remaining logs are ordered by the storage key, so the access order is not compared.
+    ///
+    /// This accounts for the legacy VM quirks and is the default.
+    #[default]
+    Lenient,
+    /// Storage logs are compared as is, including read logs, log kinds, previous values and the access order.
+    Strict,
+}
+
+/// Determines how system logs in the final VM state are compared by [`ShadowVm`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SystemLogsComparison {
+    /// System logs are compared as a sequence. This is the default since the order of system logs is significant
+    /// for the protocol: logs are hashed in the emitted order when computing the L1 batch commitment.
+    #[default]
+    Sequence,
+    /// System logs are compared as a multiset, i.e., ignoring their order (but not their multiplicity).
+    /// Can be used to suppress divergences caused by the new VM emitting system logs in a different order.
+    Set,
+}
+
+/// Options for comparing outputs of the main and shadow VMs.
+#[derive(Debug, Clone, Copy, Default)]
+struct ComparisonOptions {
+    storage_logs: StorageLogsComparison,
+    system_logs: SystemLogsComparison,
+}
+
+/// Limit on the number of divergence reports (i.e., logged divergences and calls to the [`DivergenceHandler`])
+/// produced by a [`ShadowVm`] for a single L1 batch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DivergenceRateLimit {
+    /// Maximum number of reports per `window`.
+    pub max_reports: usize,
+    /// Duration of the time window.
+    pub window: Duration,
+}
+
+/// Throttles divergence reports according to an optional [`DivergenceRateLimit`].
+#[derive(Debug, Default)]
+struct ReportLimiter {
+    limit: Option<DivergenceRateLimit>,
+    window_start: Option<Instant>,
+    reports_in_window: usize,
+    suppressed_count: usize,
+}
+
+impl ReportLimiter {
+    /// Returns whether a report is allowed. If it's not, the report is counted as suppressed.
+    fn allow(&mut self) -> bool {
+        self.allow_at(Instant::now())
+    }
+
+    fn allow_at(&mut self, now: Instant) -> bool {
+        let Some(limit) = self.limit else {
+            return true;
+        };
+        let is_new_window = self
+            .window_start
+            .map_or(true, |start| now.duration_since(start) >= limit.window);
+        if is_new_window {
+            self.window_start = Some(now);
+            self.reports_in_window = 0;
+        }
+
+        if self.reports_in_window < limit.max_reports {
+            self.reports_in_window += 1;
+            true
+        } else {
+            self.suppressed_count += 1;
+            false
+        }
+    }
+
+    fn log_summary(&self, l1_batch_number: L1BatchNumber) {
+        if self.suppressed_count > 0 {
+            tracing::warn!(
+                "{} VM divergence reports were suppressed by the rate limit for L1 batch #{l1_batch_number}",
+                self.suppressed_count
+            );
+        }
+    }
+}
+
+/// Comparator of outputs produced by the tracers of the main and shadow VMs. Tracer outputs are not compared by default
+/// since not all tracers have equivalents for all VMs; comparison can be enabled using [`ShadowVm::with_tracer_comparator()`].
+///
+/// The comparator is called after each VM operation accepting tracers (i.e., [`VmInterface::inspect()`] and
+/// [`VmInterface::inspect_transaction_with_bytecode_compression()`]) if the shadow VM is live.
+pub trait TracerComparator<MainTracer, ShadowTracer> {
+    /// Compares outputs of the tracers, recording divergences (if any) in `errors`.
+    fn compare(&self, main: &MainTracer, shadow: &ShadowTracer, errors: &mut DivergenceErrors);
+}
+
+/// No-op comparator.
+impl<MainTracer, ShadowTracer> TracerComparator<MainTracer, ShadowTracer> for () {
+    fn compare(&self, _main: &MainTracer, _shadow: &ShadowTracer, _errors: &mut DivergenceErrors) {
+        // Do nothing
+    }
+}
+
+/// Outputs of the main VM recorded in a [`VmDump`] that are used instead of a live shadow VM.
+#[derive(Debug)]
+struct RecordedTrace {
+    outputs: Vec<RecordedOutputs>,
+    position: usize,
+    snapshot: Option<usize>,
+}
+
+impl RecordedTrace {
+    fn new(outputs: Vec<RecordedOutputs>) -> Self {
+        Self {
+            outputs,
+            position: 0,
+            snapshot: None,
+        }
+    }
+
+    /// Checks outputs of the next VM operation against the recorded outputs.
+    fn check(
+        &mut self,
+        operation: &str,
+        visit: impl FnOnce(&mut RecordedOutputsChecker<'_>),
+    ) -> DivergenceErrors {
+        let mut errors = DivergenceErrors::new();
+        let Some(recorded) = self.outputs.get(self.position) else {
+            let err = format!(
+                "`{operation}` is not recorded; the trace contains only {} operations",
+                self.outputs.len()
+            );
+            errors.push("operation", err);
+            return errors;
+        };
+        self.position += 1;
+
+        errors.check_match("operation", &operation, &recorded.operation.as_str());
+        if recorded.operation != operation {
+            return errors;
+        }
+        let mut checker = RecordedOutputsChecker {
+            recorded: &recorded.values,
+            errors,
+        };
+        visit(&mut checker);
+        checker.errors
+    }
+
+    fn check_fully_consumed(&self, errors: &mut DivergenceErrors) {
+        let unconsumed_count = self.outputs.len().saturating_sub(self.position);
+        if unconsumed_count > 0 {
+            let err = format!("{unconsumed_count} recorded operations were not executed");
+            errors.push("operation", err);
+        }
+    }
+
+    fn make_snapshot(&mut self) {
+        self.snapshot = Some(self.position);
+    }
+
+    fn rollback_to_the_latest_snapshot(&mut self) {
+        self.position = self.snapshot.take().expect("rollback w/o snapshot");
+    }
+
+    fn pop_snapshot_no_rollback(&mut self) {
+        self.snapshot = None;
     }
 }
 
+/// Shadow side of a [`ShadowVm`].
+#[derive(Debug)]
+enum ShadowTarget<Shadow> {
+    /// Live shadow VM.
+    Vm(Shadow),
+    /// Outputs recorded in a dump.
+    Recorded(RecordedTrace),
+}
+
 #[derive(Debug)]
 struct VmWithReporting<Shadow> {
-    vm: Shadow,
+    vm: ShadowTarget<Shadow>,
     divergence_handler: DivergenceHandler,
+    divergence_callback: Option<DivergenceCallback>,
+    divergence_severities: DivergenceSeverities,
+    report_limiter: ReportLimiter,
+    /// Maximum number of threads used to compare finished batches.
+    finish_batch_concurrency: usize,
+    /// Runtime switch for the shadow VM; if it's set to `false`, the shadow VM is dropped.
+    switch: Option<watch::Receiver<bool>>,
+    /// Predicate deciding whether to keep the shadow VM, evaluated for each pushed transaction.
+    activation: Option<ShadowActivation>,
+    allowlist: Option<DivergenceAllowlist>,
+    comparison_options: ComparisonOptions,
+    /// Whether to isolate diverging transactions instead of dropping the shadow VM.
+    isolate_divergent_txs: bool,
 }
 
 impl<Shadow: VmInterface> VmWithReporting<Shadow> {
-    fn report(self, err: DivergenceErrors, dump: VmDump) {
-        tracing::error!("{err}");
-        self.divergence_handler.handle(err, dump);
+    fn new(vm: ShadowTarget<Shadow>) -> Self {
+        Self {
+            vm,
+            divergence_handler: DivergenceHandler::default(),
+            divergence_callback: None,
+            divergence_severities: DivergenceSeverities::default(),
+            report_limiter: ReportLimiter::default(),
+            finish_batch_concurrency: 1,
+            switch: None,
+            activation: None,
+            allowlist: None,
+            comparison_options: ComparisonOptions::default(),
+            isolate_divergent_txs: false,
+        }
+    }
+
+    fn is_disabled(&self) -> bool {
+        self.switch.as_ref().is_some_and(|switch| !*switch.borrow())
+    }
+
+    /// Reports a divergence; `main` is the main VM used to produce a dump.
+    fn report<S: ReadStorage, Main: VmTrackingContracts>(
+        mut self,
+        err: DivergenceErrors,
+        main: &DumpingVm<S, Main>,
+    ) {
+        if let Some(callback) = &self.divergence_callback {
+            callback.invoke(&err);
+        }
+        if self.report_limiter.allow() {
+            tracing::error!("{err}");
+            self.divergence_handler.handle(err, main);
+        }
         tracing::warn!(
             "New VM is dropped; following VM actions will be executed only on the main VM"
         );
+        self.report_limiter.log_summary(main.l1_batch_number());
+    }
+
+    /// Reports a divergence in an isolated transaction. Unlike [`Self::report()`], keeps the shadow VM.
+    fn report_isolated<S: ReadStorage, Main: VmTrackingContracts>(
+        &mut self,
+        err: DivergenceErrors,
+        main: &DumpingVm<S, Main>,
+    ) {
+        if let Some(callback) = &self.divergence_callback {
+            callback.invoke(&err);
+        }
+        if self.report_limiter.allow() {
+            tracing::error!("{err}");
+            self.divergence_handler.handle(err, main);
+        }
+        tracing::warn!("Diverging transaction is halted so that it's rolled back on both VMs");
+        METRICS.isolated_divergent_txs.inc();
     }
 }
 
 /// Shadowed VM that executes 2 VMs for each operation and compares their outputs.
 ///
-/// If a divergence is detected, the VM state is dumped using [a pluggable handler](Self::set_dump_handler()),
+/// Alternatively, the main VM outputs can be compared with the outputs [recorded in a dump](Self::with_recorded_outputs())
+/// rather than with a live shadow VM.
+///
+/// If a divergence is detected, the VM state is dumped using [a pluggable handler](Self::set_divergence_handler()),
 /// after which the VM drops the shadowed VM (since it's assumed that its state can contain arbitrary garbage at this point).
+///
+/// By default, outputs of the tracers passed to the VMs are not compared. This can be changed by specifying
+/// a [`TracerComparator`] via [`Self::with_tracer_comparator()`].
 #[derive(Debug)]
-pub struct ShadowVm<S, Main, Shadow> {
+pub struct ShadowVm<S, Main, Shadow, Cmp = ()> {
     main: DumpingVm<S, Main>,
     shadow: RefCell<Option<VmWithReporting<Shadow>>>,
+    tracer_comparator: Cmp,
 }
 
-impl<S, Main, Shadow> ShadowVm<S, Main, Shadow>
+impl<S, Main, Shadow, Cmp> ShadowVm<S, Main, Shadow, Cmp>
 where
     S: ReadStorage,
     Main: VmTrackingContracts,
@@ -88,6 +639,149 @@ where
         }
     }
 
+    /// Sets a custom callback invoked on each reported divergence before it's passed to the
+    /// [divergence handler](Self::set_divergence_handler()).
+    pub fn set_divergence_callback(&mut self, callback: DivergenceCallback) {
+        if let Some(shadow) = self.shadow.get_mut() {
+            shadow.divergence_callback = Some(callback);
+        }
+    }
+
+    /// Sets severities of divergences for this VM. Only divergences with [`DivergenceSeverity::Panic`]
+    /// are passed to the [divergence handler](Self::set_divergence_handler()); other divergences are logged.
+    pub fn set_divergence_severities(&mut self, severities: DivergenceSeverities) {
+        if let Some(shadow) = self.shadow.get_mut() {
+            shadow.divergence_severities = severities;
+        }
+    }
+
+    /// Sets the allowlist of known benign divergences for this VM. Allowlisted divergences are neither logged
+    /// nor passed to the [divergence handler](Self::set_divergence_handler()).
+    pub fn set_divergence_allowlist(&mut self, allowlist: DivergenceAllowlist) {
+        if let Some(shadow) = self.shadow.get_mut() {
+            shadow.allowlist = Some(allowlist);
+        }
+    }
+
+    /// Sets the limit on the number of divergence reports for this VM. Reports exceeding the limit are suppressed;
+    /// the number of suppressed reports is logged when the batch is finished or the shadow VM is dropped.
+    /// By default, reports are not limited.
+    pub fn set_divergence_rate_limit(&mut self, limit: DivergenceRateLimit) {
+        if let Some(shadow) = self.shadow.get_mut() {
+            shadow.report_limiter.limit = Some(limit);
+        }
+    }
+
+    /// Sets the maximum number of threads used to compare outputs of the main and shadow VMs when finishing a batch.
+    /// Independent parts of the outputs (e.g., logs and state diffs) are compared concurrently; divergences
+    /// are reported in the same order as with sequential comparison. By default, outputs are compared on the current thread.
+    pub fn set_finish_batch_concurrency(&mut self, concurrency: usize) {
+        if let Some(shadow) = self.shadow.get_mut() {
+            shadow.finish_batch_concurrency = concurrency.max(1);
+        }
+    }
+
+    /// Sets how storage logs produced by the main and shadow VMs are compared. By default, logs are compared
+    /// [leniently](StorageLogsComparison::Lenient). Outputs [recorded in a dump](Self::with_recorded_outputs())
+    /// are always compared leniently since the recorded logs are normalized.
+    pub fn set_storage_logs_comparison(&mut self, comparison: StorageLogsComparison) {
+        if let Some(shadow) = self.shadow.get_mut() {
+            shadow.comparison_options.storage_logs = comparison;
+        }
+    }
+
+    /// Sets how system logs in the final VM state produced by the main and shadow VMs are compared. By default,
+    /// logs are compared [as a sequence](SystemLogsComparison::Sequence). Outputs [recorded in a dump](Self::with_recorded_outputs())
+    /// are always compared as a sequence.
+    pub fn set_system_logs_comparison(&mut self, comparison: SystemLogsComparison) {
+        if let Some(shadow) = self.shadow.get_mut() {
+            shadow.comparison_options.system_logs = comparison;
+        }
+    }
+
+    /// Enables or disables isolating diverging transactions. If enabled, a transaction diverging between the main
+    /// and live shadow VMs doesn't lead to the shadow VM being dropped. Instead, the divergence is reported,
+    /// and the transaction result is replaced with [`Halt::TracerCustom`] with the [`ISOLATED_DIVERGENCE_HALT_REASON`]
+    /// reason. Callers following the standard protocol (making a VM snapshot before each transaction and rolling back
+    /// halted transactions) thus roll back the transaction on both VMs, re-synchronizing their state, and can continue
+    /// comparing subsequent transactions.
+    ///
+    /// **Important.** This changes the outputs of the main VM for diverging transactions. Divergences in other
+    /// operations (e.g., finishing the batch) are handled as usual. Isolation is disabled by default.
+    pub fn set_divergent_tx_isolation(&mut self, isolate: bool) {
+        if let Some(shadow) = self.shadow.get_mut() {
+            shadow.isolate_divergent_txs = isolate;
+        }
+    }
+
+    /// Gates the shadow VM by the provided switch. Once the switch is set to `false`, the shadow VM is dropped before
+    /// the next VM operation, and all following operations are executed only on the main VM. Since the shadow VM state
+    /// cannot be restored after that, re-enabling the switch only has effect for newly created VMs.
+    pub fn set_shadow_switch(&mut self, switch: watch::Receiver<bool>) {
+        if let Some(shadow) = self.shadow.get_mut() {
+            shadow.switch = Some(switch);
+        }
+    }
+
+    /// Drops the shadow VM if it was disabled using the [switch](Self::set_shadow_switch()).
+    fn check_shadow_switch(&mut self) {
+        let shadow = self.shadow.get_mut();
+        if shadow.as_ref().is_some_and(VmWithReporting::is_disabled) {
+            *shadow = None;
+            tracing::info!(
+                "Shadow VM is disabled by the switch; following VM actions for L1 batch #{} will be executed only on the main VM",
+                self.main.l1_batch_number()
+            );
+        }
+    }
+
+    /// Sets a predicate deciding whether to keep the shadow VM active. The predicate is evaluated on the batch environment
+    /// immediately, and then before each transaction is executed. Once the predicate returns `false`, the shadow VM
+    /// is dropped, and all following operations in the batch are executed only on the main VM. Since the shadow VM state
+    /// cannot be restored, the predicate can only narrow shadowing based on the batch data seen so far.
+    pub fn set_shadow_activation(&mut self, activation: ShadowActivation) {
+        let input = ShadowActivationInput::BatchInit(self.main.l1_batch_env());
+        let keep_shadow = activation.keep_shadow(input);
+        let shadow = self.shadow.get_mut();
+        if !keep_shadow {
+            if shadow.take().is_some() {
+                tracing::info!(
+                    "Shadow VM is deactivated for L1 batch #{}; it will be executed only on the main VM",
+                    self.main.l1_batch_number()
+                );
+            }
+        } else if let Some(shadow) = shadow {
+            shadow.activation = Some(activation);
+        }
+    }
+
+    /// Drops the shadow VM if it was deactivated by the [predicate](Self::set_shadow_activation()).
+    fn check_shadow_activation(&mut self, tx: &Transaction) {
+        let shadow = self.shadow.get_mut();
+        let Some(activation) = shadow
+            .as_ref()
+            .and_then(|shadow| shadow.activation.as_ref())
+        else {
+            return;
+        };
+        if !activation.keep_shadow(ShadowActivationInput::Transaction(tx)) {
+            *shadow = None;
+            tracing::info!(
+                "Shadow VM is deactivated on transaction {:?}; following VM actions for L1 batch #{} will be executed only on the main VM",
+                tx.hash(),
+                self.main.l1_batch_number()
+            );
+        }
+    }
+
+    /// Returns the shadow VM if it's live (i.e., not replaced with recorded outputs) and wasn't dropped.
+    fn live_shadow_vm(&mut self) -> Option<&mut Shadow> {
+        match &mut self.shadow.get_mut().as_mut()?.vm {
+            ShadowTarget::Vm(vm) => Some(vm),
+            ShadowTarget::Recorded(_) => None,
+        }
+    }
+
     /// Mutable ref is not necessary, but it automatically drops potential borrows.
     fn report(&mut self, err: DivergenceErrors) {
         self.report_shared(err);
@@ -95,16 +789,67 @@ where
 
     /// The caller is responsible for dropping any `shadow` borrows beforehand.
     fn report_shared(&self, err: DivergenceErrors) {
+        let tx_count = self.main.tx_count();
+        METRICS.divergence_tx_count.observe(tx_count);
         self.shadow
             .take()
             .unwrap()
-            .report(err, self.main.dump_state());
+            .report(err.with_tx_count(tx_count), &self.main);
     }
 
     /// Dumps the current VM state.
     pub fn dump_state(&self) -> VmDump {
         self.main.dump_state()
     }
+
+    /// Streams the current VM state to `writer` without building the [dump](Self::dump_state()) in memory.
+    /// Without compression, the output is equal to [`VmDump::to_bytes()`] for the dump.
+    pub fn write_dump(
+        &self,
+        writer: impl io::Write,
+        format: VmDumpFormat,
+        compression: VmDumpCompression,
+    ) -> anyhow::Result<()> {
+        self.main.write_dump(writer, format, compression)
+    }
+
+    /// Returns a snapshot of the current execution state of the shadow VM for debugging. The state is not compared
+    /// with the main VM. Returns `None` if the shadow VM was dropped (e.g., after a divergence) or if the main VM
+    /// is checked against [recorded outputs](ShadowVm::with_recorded_outputs()).
+    pub fn shadow_execution_state(&self) -> Option<CurrentExecutionState>
+    where
+        Shadow: VmInspectExecutionState,
+    {
+        match &self.shadow.borrow().as_ref()?.vm {
+            ShadowTarget::Vm(vm) => Some(vm.current_execution_state()),
+            ShadowTarget::Recorded(_) => None,
+        }
+    }
+
+    /// Sets how transaction calldata is handled in [dumps](Self::dump_state()) produced by this VM. By default,
+    /// calldata is dumped in full.
+    pub fn set_calldata_dump_mode(&mut self, mode: CalldataDumpMode) {
+        self.main.set_calldata_dump_mode(mode);
+    }
+
+    /// Enables recording outputs of the main VM. Recorded outputs are included into [dumps](Self::dump_state()),
+    /// which can then be [played back](VmDump::play_back_with_recorded_outputs()) to check the VM against them.
+    pub fn record_outputs(&mut self) {
+        self.main.enable_output_recording();
+    }
+
+    /// Enables comparing tracer outputs of the main and shadow VMs using the provided comparator.
+    /// Tracer outputs are not compared for [recorded outputs](Self::with_recorded_outputs()).
+    pub fn with_tracer_comparator<C>(self, tracer_comparator: C) -> ShadowVm<S, Main, Shadow, C>
+    where
+        C: TracerComparator<Main::TracerDispatcher, Shadow::TracerDispatcher>,
+    {
+        ShadowVm {
+            main: self.main,
+            shadow: self.shadow,
+            tracer_comparator,
+        }
+    }
 }
 
 impl<S, Main, Shadow> ShadowVm<S, Main, Shadow>
@@ -125,13 +870,46 @@ where
     {
         let main = DumpingVm::new(batch_env.clone(), system_env.clone(), storage.clone());
         let shadow = Shadow::new(batch_env.clone(), system_env.clone(), shadow_storage);
-        let shadow = VmWithReporting {
-            vm: shadow,
-            divergence_handler: DivergenceHandler::default(),
-        };
+        let shadow = VmWithReporting::new(ShadowTarget::Vm(shadow));
         Self {
             main,
             shadow: RefCell::new(Some(shadow)),
+            tracer_comparator: (),
+        }
+    }
+
+    /// Creates a VM with the provided shadow VM. Can be used to inject VM stubs, such as [`DivergingVm`](super::testonly::DivergingVm),
+    /// to test divergence handling.
+    #[cfg(feature = "testonly")]
+    pub fn with_shadow_vm(
+        batch_env: L1BatchEnv,
+        system_env: SystemEnv,
+        storage: StoragePtr<StorageView<S>>,
+        shadow: Shadow,
+    ) -> Self {
+        let main = DumpingVm::new(batch_env, system_env, storage);
+        let shadow = VmWithReporting::new(ShadowTarget::Vm(shadow));
+        Self {
+            main,
+            shadow: RefCell::new(Some(shadow)),
+            tracer_comparator: (),
+        }
+    }
+
+    /// Creates a VM that compares outputs of the main VM with the outputs [recorded](Self::record_outputs()) in a [`VmDump`]
+    /// instead of running a live shadow VM. The recorded outputs must be produced for the same inputs.
+    pub fn with_recorded_outputs(
+        batch_env: L1BatchEnv,
+        system_env: SystemEnv,
+        storage: StoragePtr<StorageView<S>>,
+        outputs: Vec<RecordedOutputs>,
+    ) -> Self {
+        let main = DumpingVm::new(batch_env, system_env, storage);
+        let shadow = VmWithReporting::new(ShadowTarget::Recorded(RecordedTrace::new(outputs)));
+        Self {
+            main,
+            shadow: RefCell::new(Some(shadow)),
+            tracer_comparator: (),
         }
     }
 }
@@ -151,12 +929,13 @@ where
     }
 }
 
-/// **Important.** This doesn't properly handle tracers; they are not passed to the shadow VM!
-impl<S, Main, Shadow> VmInterface for ShadowVm<S, Main, Shadow>
+/// Tracers are passed to the corresponding VMs. Tracer outputs are only compared if a [`TracerComparator`] is specified.
+impl<S, Main, Shadow, Cmp> VmInterface for ShadowVm<S, Main, Shadow, Cmp>
 where
     S: ReadStorage,
     Main: VmTrackingContracts,
     Shadow: VmInterface,
+    Cmp: TracerComparator<Main::TracerDispatcher, Shadow::TracerDispatcher>,
 {
     type TracerDispatcher = (
         <Main as VmInterface>::TracerDispatcher,
@@ -164,8 +943,10 @@ where
     );
 
     fn push_transaction(&mut self, tx: Transaction) {
-        if let Some(shadow) = self.shadow.get_mut() {
-            shadow.vm.push_transaction(tx.clone());
+        self.check_shadow_switch();
+        self.check_shadow_activation(&tx);
+        if let Some(shadow) = self.live_shadow_vm() {
+            shadow.push_transaction(tx.clone());
         }
         self.main.push_transaction(tx);
     }
@@ -175,24 +956,45 @@ where
         (main_tracer, shadow_tracer): &mut Self::TracerDispatcher,
         execution_mode: VmExecutionMode,
     ) -> VmExecutionResultAndLogs {
+        self.check_shadow_switch();
         let main_result = self.main.inspect(main_tracer, execution_mode);
         if let Some(shadow) = self.shadow.get_mut() {
-            let shadow_result = shadow.vm.inspect(shadow_tracer, execution_mode);
-            let mut errors = DivergenceErrors::new();
-            errors.check_results_match(&main_result, &shadow_result);
+            let errors = match &mut shadow.vm {
+                ShadowTarget::Vm(vm) => {
+                    let shadow_result = vm.inspect(shadow_tracer, execution_mode);
+                    let mut errors =
+                        DivergenceErrors::new().with_comparison_options(shadow.comparison_options);
+                    errors.check_results_match(&main_result, &shadow_result);
+                    self.tracer_comparator
+                        .compare(main_tracer, shadow_tracer, &mut errors);
+                    errors
+                }
+                ShadowTarget::Recorded(trace) => trace.check("inspect", |checker| {
+                    visit_results(checker, &main_result, &main_result);
+                }),
+            };
+            let errors = errors.with_l2_block_for_logs(self.main.current_l2_block());
 
             if let Err(err) = errors.into_result() {
                 let ctx = format!("executing VM with mode {execution_mode:?}");
-                self.report(err.context(ctx));
+                if let Err(err) = err.context(ctx).triage(
+                    &shadow.divergence_severities,
+                    shadow.allowlist.as_ref(),
+                    None,
+                    &mut shadow.report_limiter,
+                ) {
+                    self.report(err);
+                }
             }
         }
         main_result
     }
 
     fn start_new_l2_block(&mut self, l2_block_env: L2BlockEnv) {
+        self.check_shadow_switch();
         self.main.start_new_l2_block(l2_block_env);
-        if let Some(shadow) = self.shadow.get_mut() {
-            shadow.vm.start_new_l2_block(l2_block_env);
+        if let Some(shadow) = self.live_shadow_vm() {
+            shadow.start_new_l2_block(l2_block_env);
         }
     }
 
@@ -202,8 +1004,10 @@ where
         tx: Transaction,
         with_compression: bool,
     ) -> (BytecodeCompressionResult<'_>, VmExecutionResultAndLogs) {
+        self.check_shadow_switch();
+        self.check_shadow_activation(&tx);
         let tx_hash = tx.hash();
-        let (main_bytecodes_result, main_tx_result) =
+        let (main_bytecodes_result, mut main_tx_result) =
             self.main.inspect_transaction_with_bytecode_compression(
                 main_tracer,
                 tx.clone(),
@@ -215,18 +1019,47 @@ where
             main_bytecodes_result.map(|bytecodes| bytecodes.into_owned().into());
 
         if let Some(shadow) = self.shadow.get_mut() {
-            let shadow_result = shadow.vm.inspect_transaction_with_bytecode_compression(
-                shadow_tracer,
-                tx,
-                with_compression,
-            );
-            let mut errors = DivergenceErrors::new();
-            errors.check_results_match(&main_tx_result, &shadow_result.1);
+            let errors = match &mut shadow.vm {
+                ShadowTarget::Vm(vm) => {
+                    let shadow_result = vm.inspect_transaction_with_bytecode_compression(
+                        shadow_tracer,
+                        tx,
+                        with_compression,
+                    );
+                    let mut errors =
+                        DivergenceErrors::new().with_comparison_options(shadow.comparison_options);
+                    errors.check_results_match(&main_tx_result, &shadow_result.1);
+                    self.tracer_comparator
+                        .compare(main_tracer, shadow_tracer, &mut errors);
+                    errors
+                }
+                ShadowTarget::Recorded(trace) => trace.check("inspect_transaction", |checker| {
+                    visit_results(checker, &main_tx_result, &main_tx_result);
+                }),
+            };
+            let errors = errors.with_l2_block_for_logs(self.main.current_l2_block());
             if let Err(err) = errors.into_result() {
                 let ctx = format!(
                     "inspecting transaction {tx_hash:?}, with_compression={with_compression:?}"
                 );
-                self.report(err.context(ctx));
+                if let Err(err) = err.context(ctx).triage(
+                    &shadow.divergence_severities,
+                    shadow.allowlist.as_ref(),
+                    Some(tx_hash),
+                    &mut shadow.report_limiter,
+                ) {
+                    if shadow.isolate_divergent_txs && matches!(shadow.vm, ShadowTarget::Vm(_)) {
+                        let tx_count = self.main.tx_count();
+                        METRICS.divergence_tx_count.observe(tx_count);
+                        let err = err.with_tx_count(tx_count);
+                        shadow.report_isolated(err, &self.main);
+                        main_tx_result.result = ExecutionResult::Halt {
+                            reason: Halt::TracerCustom(ISOLATED_DIVERGENCE_HALT_REASON.to_owned()),
+                        };
+                    } else {
+                        self.report(err);
+                    }
+                }
             }
         }
         (main_bytecodes_result, main_tx_result)
@@ -237,163 +1070,314 @@ where
     }
 
     fn finish_batch(&mut self) -> FinishedL1Batch {
+        self.check_shadow_switch();
         let main_batch = self.main.finish_batch();
         if let Some(shadow) = self.shadow.get_mut() {
-            let shadow_batch = shadow.vm.finish_batch();
-            let mut errors = DivergenceErrors::new();
-            errors.check_results_match(
-                &main_batch.block_tip_execution_result,
-                &shadow_batch.block_tip_execution_result,
-            );
-            errors.check_final_states_match(
-                &main_batch.final_execution_state,
-                &shadow_batch.final_execution_state,
-            );
-            errors.check_match(
-                "final_bootloader_memory",
-                &main_batch.final_bootloader_memory,
-                &shadow_batch.final_bootloader_memory,
-            );
-            errors.check_match(
-                "pubdata_input",
-                &main_batch.pubdata_input,
-                &shadow_batch.pubdata_input,
-            );
-            errors.check_match(
-                "state_diffs",
-                &main_batch.state_diffs,
-                &shadow_batch.state_diffs,
-            );
+            let errors = match &mut shadow.vm {
+                ShadowTarget::Vm(vm) => {
+                    let shadow_batch = vm.finish_batch();
+                    check_finished_batches_concurrently(
+                        &main_batch,
+                        &shadow_batch,
+                        shadow.finish_batch_concurrency,
+                        shadow.comparison_options,
+                    )
+                }
+                ShadowTarget::Recorded(trace) => {
+                    let mut errors = trace.check("finish_batch", |checker| {
+                        visit_finished_batches(checker, &main_batch, &main_batch);
+                    });
+                    trace.check_fully_consumed(&mut errors);
+                    errors
+                }
+            };
 
-            if let Err(err) = errors.into_result() {
-                self.report(err);
+            match errors.triage(
+                &shadow.divergence_severities,
+                shadow.allowlist.as_ref(),
+                None,
+                &mut shadow.report_limiter,
+            ) {
+                Ok(()) => shadow
+                    .report_limiter
+                    .log_summary(self.main.l1_batch_number()),
+                Err(err) => self.report(err),
             }
         }
         main_batch
     }
 }
 
+#[derive(Debug)]
+struct Divergence {
+    /// Context of the divergence, e.g. `logs.events`.
+    context: String,
+    message: String,
+    /// Number and timestamp of the L2 block the diverging data belongs to, if known. Only displayed; not covered
+    /// by [`DivergenceErrors::stable_hash()`] since otherwise the same divergence would be hashed differently
+    /// in each block.
+    l2_block: Option<(L2BlockNumber, u64)>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some((number, timestamp)) = self.l2_block {
+            write!(formatter, "[L2 block #{number}, timestamp {timestamp}] ")?;
+        }
+        formatter.write_str(&self.message)
+    }
+}
+
+/// Number of execution steps (VM cycles) performed by the main and shadow VMs during a diverging operation.
+/// VMs don't expose the last executed opcode, so this is the finest available attribution of a divergence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExecutionSteps {
+    pub main: u32,
+    pub shadow: u32,
+}
+
 #[derive(Debug)]
 pub struct DivergenceErrors {
-    divergences: Vec<String>,
+    divergences: Vec<Divergence>,
     context: Option<String>,
+    execution_steps: Option<ExecutionSteps>,
+    tx_count: Option<usize>,
+    comparison_options: ComparisonOptions,
 }
 
 impl fmt::Display for DivergenceErrors {
     fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
-        if let Some(context) = &self.context {
-            write!(
-                formatter,
-                "VM execution diverged: {context}: [{}]",
-                self.divergences.join(", ")
-            )
-        } else {
-            write!(
-                formatter,
-                "VM execution diverged: [{}]",
-                self.divergences.join(", ")
-            )
-        }
+        let messages: Vec<_> = self.divergences.iter().map(Divergence::to_string).collect();
+        write!(
+            formatter,
+            "{}: {}: [{}]",
+            self.prefix(),
+            self.summary(),
+            messages.join(", ")
+        )
     }
 }
 
 impl DivergenceErrors {
-    fn new() -> Self {
+    pub(super) fn new() -> Self {
         Self {
             divergences: vec![],
             context: None,
+            execution_steps: None,
+            tx_count: None,
+            comparison_options: ComparisonOptions::default(),
+        }
+    }
+
+    fn with_comparison_options(mut self, options: ComparisonOptions) -> Self {
+        self.comparison_options = options;
+        self
+    }
+
+    /// Returns the number of execution steps performed by the VMs during the diverging operation, if known.
+    /// Steps are only known for divergences in transaction / bootloader execution between 2 live VMs.
+    pub fn execution_steps(&self) -> Option<ExecutionSteps> {
+        self.execution_steps
+    }
+
+    /// Returns the number of transactions processed in the L1 batch (including the diverging transaction, if any)
+    /// when the divergence was detected. Only known for divergences reported by [`ShadowVm`].
+    pub fn tx_count(&self) -> Option<usize> {
+        self.tx_count
+    }
+
+    /// Annotates divergences in L2-to-L1 logs produced by a transaction / bootloader execution with the L2 block
+    /// the logs belong to.
+    fn with_l2_block_for_logs(mut self, l2_block: (L2BlockNumber, u64)) -> Self {
+        const L2_TO_L1_LOG_CONTEXTS: [&str; 2] =
+            ["logs.system_l2_to_l1_logs", "logs.user_l2_to_l1_logs"];
+
+        for divergence in &mut self.divergences {
+            if L2_TO_L1_LOG_CONTEXTS.contains(&divergence.context.as_str()) {
+                divergence.l2_block = Some(l2_block);
+            }
+        }
+        self
+    }
+
+    fn with_tx_count(mut self, tx_count: usize) -> Self {
+        self.tx_count = Some(tx_count);
+        self
+    }
+
+    fn prefix(&self) -> String {
+        let mut prefix = "VM execution diverged".to_owned();
+        if let Some(context) = &self.context {
+            prefix += &format!(": {context}");
+        }
+        if let Some(ExecutionSteps { main, shadow }) = self.execution_steps {
+            prefix += &format!(" (after {main} steps on main VM, {shadow} steps on shadow VM)");
+        }
+        if let Some(tx_count) = self.tx_count {
+            prefix += &format!(" [{tx_count} transaction(s) processed in L1 batch]");
+        }
+        prefix
+    }
+
+    /// Summarizes the number of divergences per context, e.g. `3 divergence(s) across 2 context(s) (refunds: 2, gas_remaining: 1)`.
+    /// Contexts are ordered by the descending number of divergences.
+    fn summary(&self) -> String {
+        let mut counts = BTreeMap::<_, usize>::new();
+        for divergence in &self.divergences {
+            *counts.entry(divergence.context.as_str()).or_default() += 1;
         }
+        let mut counts: Vec<_> = counts.into_iter().collect();
+        // The sort is stable, so contexts with the same count remain ordered alphabetically.
+        counts.sort_by(|(_, lhs), (_, rhs)| rhs.cmp(lhs));
+
+        let counts: Vec<_> = counts
+            .into_iter()
+            .map(|(context, count)| format!("{context}: {count}"))
+            .collect();
+        format!(
+            "{} divergence(s) across {} context(s) ({})",
+            self.divergences.len(),
+            counts.len(),
+            counts.join(", ")
+        )
     }
 
-    fn context(mut self, context: String) -> Self {
+    pub(super) fn context(mut self, context: String) -> Self {
         self.context = Some(context);
         self
     }
 
+    /// Computes a hash of divergences that is stable across runs. Can be used to deduplicate recurring divergences.
+    ///
+    /// The hash covers contexts and messages of all divergences sorted by context. It doesn't depend on the context
+    /// of the errors as a whole (which can contain e.g. a transaction hash).
+    pub fn stable_hash(&self) -> H256 {
+        let mut entries: Vec<_> = self
+            .divergences
+            .iter()
+            .map(|divergence| (divergence.context.as_str(), divergence.message.as_str()))
+            .collect();
+        entries.sort_unstable();
+
+        let mut hashed_bytes = vec![];
+        for (context, message) in entries {
+            for part in [context, message] {
+                // Length-prefix parts so that hashed bytes are unambiguous.
+                hashed_bytes.extend_from_slice(&(part.len() as u64).to_be_bytes());
+                hashed_bytes.extend_from_slice(part.as_bytes());
+            }
+        }
+        H256(keccak256(&hashed_bytes))
+    }
+
+    /// Returns contexts of all divergences (e.g., `logs.events`). Divergences reported by [`ShadowVm`] are sorted
+    /// by context; divergences with the same context are listed in the order they were detected.
+    pub fn contexts(&self) -> impl Iterator<Item = &str> + '_ {
+        self.divergences
+            .iter()
+            .map(|divergence| divergence.context.as_str())
+    }
+
+    fn push(&mut self, context: &str, message: String) {
+        self.divergences.push(Divergence {
+            context: context.to_owned(),
+            message,
+            l2_block: None,
+        });
+    }
+
     fn check_results_match(
         &mut self,
         main_result: &VmExecutionResultAndLogs,
         shadow_result: &VmExecutionResultAndLogs,
     ) {
-        self.check_match("result", &main_result.result, &shadow_result.result);
-        self.check_match(
-            "logs.events",
-            &main_result.logs.events,
-            &shadow_result.logs.events,
-        );
-        self.check_match(
-            "logs.system_l2_to_l1_logs",
-            &main_result.logs.system_l2_to_l1_logs,
-            &shadow_result.logs.system_l2_to_l1_logs,
-        );
-        self.check_match(
-            "logs.user_l2_to_l1_logs",
-            &main_result.logs.user_l2_to_l1_logs,
-            &shadow_result.logs.user_l2_to_l1_logs,
-        );
-        let main_logs = UniqueStorageLogs::new(&main_result.logs.storage_logs);
-        let shadow_logs = UniqueStorageLogs::new(&shadow_result.logs.storage_logs);
-        self.check_match("logs.storage_logs", &main_logs, &shadow_logs);
-        self.check_match("refunds", &main_result.refunds, &shadow_result.refunds);
-        self.check_match(
-            "statistics.circuit_statistic",
-            &main_result.statistics.circuit_statistic,
-            &shadow_result.statistics.circuit_statistic,
-        );
-        self.check_match(
-            "gas_remaining",
-            &main_result.statistics.gas_remaining,
-            &shadow_result.statistics.gas_remaining,
-        );
+        visit_results(self, main_result, shadow_result);
+        self.execution_steps = Some(ExecutionSteps {
+            main: main_result.statistics.cycles_used,
+            shadow: shadow_result.statistics.cycles_used,
+        });
+    }
+
+    /// Compares execution states field by field, so that the divergence report points to the diverging fields
+    /// rather than containing the entire state.
+    fn check_final_states_match(
+        &mut self,
+        main_state: &CurrentExecutionState,
+        shadow_state: &CurrentExecutionState,
+    ) {
+        visit_final_states(self, main_state, shadow_state);
     }
 
-    fn check_match<T: fmt::Debug + PartialEq>(&mut self, context: &str, main: &T, shadow: &T) {
+    /// Checks that the main and shadow values match, recording a divergence with the specified context otherwise.
+    pub fn check_match<T: fmt::Debug + PartialEq>(&mut self, context: &str, main: &T, shadow: &T) {
         if main != shadow {
             let comparison = pretty_assertions::Comparison::new(main, shadow);
             let err = format!("`{context}` mismatch: {comparison}");
-            self.divergences.push(err);
+            self.push(context, err);
         }
     }
 
-    fn check_final_states_match(
+    /// Compares pubdata inputs section by section, so that the divergence report points to the diverging section.
+    /// Divergences are recorded with the provided `context` regardless of the section. If either pubdata input
+    /// cannot be parsed, pubdata inputs are compared as opaque blobs.
+    fn check_pubdata_match(
         &mut self,
-        main: &CurrentExecutionState,
-        shadow: &CurrentExecutionState,
+        context: &str,
+        main: &Option<Vec<u8>>,
+        shadow: &Option<Vec<u8>>,
     ) {
-        self.check_match("final_state.events", &main.events, &shadow.events);
-        self.check_match(
-            "final_state.user_l2_to_l1_logs",
-            &main.user_l2_to_l1_logs,
-            &shadow.user_l2_to_l1_logs,
-        );
-        self.check_match(
-            "final_state.system_logs",
-            &main.system_logs,
-            &shadow.system_logs,
+        if main == shadow {
+            return;
+        }
+        let (Some(main), Some(shadow)) = (main, shadow) else {
+            self.check_match(context, main, shadow);
+            return;
+        };
+        let (Some(main_sections), Some(shadow_sections)) =
+            (PubdataSections::parse(main), PubdataSections::parse(shadow))
+        else {
+            self.check_match(context, main, shadow);
+            return;
+        };
+
+        self.check_pubdata_section(
+            context,
+            "user_logs",
+            &main_sections.user_logs,
+            &shadow_sections.user_logs,
         );
-        self.check_match(
-            "final_state.storage_refunds",
-            &main.storage_refunds,
-            &shadow.storage_refunds,
+        self.check_pubdata_section(
+            context,
+            "l2_to_l1_messages",
+            &main_sections.l2_to_l1_messages,
+            &shadow_sections.l2_to_l1_messages,
         );
-        self.check_match(
-            "final_state.pubdata_costs",
-            &main.pubdata_costs,
-            &shadow.pubdata_costs,
+        self.check_pubdata_section(
+            context,
+            "published_bytecodes",
+            &main_sections.published_bytecodes,
+            &shadow_sections.published_bytecodes,
         );
-        self.check_match(
-            "final_state.used_contract_hashes",
-            &main.used_contract_hashes.iter().collect::<BTreeSet<_>>(),
-            &shadow.used_contract_hashes.iter().collect::<BTreeSet<_>>(),
+        self.check_pubdata_section(
+            context,
+            "state_diffs",
+            &main_sections.state_diffs,
+            &shadow_sections.state_diffs,
         );
+    }
 
-        let main_deduplicated_logs = Self::gather_logs(&main.deduplicated_storage_logs);
-        let shadow_deduplicated_logs = Self::gather_logs(&shadow.deduplicated_storage_logs);
-        self.check_match(
-            "deduplicated_storage_logs",
-            &main_deduplicated_logs,
-            &shadow_deduplicated_logs,
-        );
+    fn check_pubdata_section<T: fmt::Debug + PartialEq>(
+        &mut self,
+        context: &str,
+        section: &str,
+        main: &T,
+        shadow: &T,
+    ) {
+        if main != shadow {
+            let comparison = pretty_assertions::Comparison::new(main, shadow);
+            let err = format!("`{context}` mismatch in section `{section}`: {comparison}");
+            self.push(context, err);
+        }
     }
 
     fn gather_logs(logs: &[StorageLog]) -> BTreeMap<StorageKey, &StorageLog> {
@@ -403,17 +1387,368 @@ impl DivergenceErrors {
             .collect()
     }
 
-    fn into_result(self) -> Result<(), Self> {
+    /// Converts these errors into a result. Divergences are sorted by context (the sort is stable), so that the output
+    /// doesn't depend on the order in which checks were performed.
+    fn into_result(mut self) -> Result<(), Self> {
         if self.divergences.is_empty() {
             Ok(())
         } else {
+            self.divergences
+                .sort_by(|lhs, rhs| lhs.context.cmp(&rhs.context));
             Err(self)
         }
     }
+
+    /// Drops divergences in the `allowlist`, logs divergences that have severity lower than [`DivergenceSeverity::Panic`]
+    /// and returns the remaining divergences (if any) as an error. Logged divergences are throttled by the `limiter`.
+    fn triage(
+        mut self,
+        severities: &DivergenceSeverities,
+        allowlist: Option<&DivergenceAllowlist>,
+        tx_hash: Option<H256>,
+        limiter: &mut ReportLimiter,
+    ) -> Result<(), Self> {
+        let prefix = self.prefix();
+        if let Some(allowlist) = allowlist {
+            self.divergences.retain(|divergence| {
+                let is_allowed = allowlist.is_allowed(&divergence.context, tx_hash);
+                if is_allowed {
+                    tracing::debug!("{prefix}: allowlisted divergence: {divergence}");
+                }
+                !is_allowed
+            });
+        }
+        self.divergences
+            .retain(|divergence| match severities.get(&divergence.context) {
+                DivergenceSeverity::Panic => true,
+                DivergenceSeverity::Error => {
+                    if limiter.allow() {
+                        tracing::error!("{prefix}: {divergence}");
+                    }
+                    false
+                }
+                DivergenceSeverity::Warn => {
+                    if limiter.allow() {
+                        tracing::warn!("{prefix}: {divergence}");
+                    }
+                    false
+                }
+            });
+        self.into_result()
+    }
+}
+
+/// Visitor of the VM outputs compared by [`ShadowVm`]. Allows reusing the same traversal of outputs for comparing 2 VMs
+/// and for recording / checking [`RecordedOutputs`].
+trait OutputsVisitor {
+    fn visit<T: fmt::Debug + PartialEq>(&mut self, context: &str, main: &T, shadow: &T);
+
+    /// Visits storage logs. By default, logs are normalized as per [`StorageLogsComparison::Lenient`].
+    fn visit_storage_logs(
+        &mut self,
+        context: &str,
+        main: &[StorageLogWithPreviousValue],
+        shadow: &[StorageLogWithPreviousValue],
+    ) {
+        let main = UniqueStorageLogs::new(main);
+        let shadow = UniqueStorageLogs::new(shadow);
+        self.visit(context, &main, &shadow);
+    }
+
+    /// Visits system logs. By default, logs are visited as a [sequence](SystemLogsComparison::Sequence).
+    fn visit_system_logs(
+        &mut self,
+        context: &str,
+        main: &[SystemL2ToL1Log],
+        shadow: &[SystemL2ToL1Log],
+    ) {
+        self.visit(context, &main, &shadow);
+    }
+
+    /// Visits pubdata inputs. By default, pubdata inputs are visited as opaque blobs.
+    fn visit_pubdata(&mut self, context: &str, main: &Option<Vec<u8>>, shadow: &Option<Vec<u8>>) {
+        self.visit(context, main, shadow);
+    }
+}
+
+impl OutputsVisitor for DivergenceErrors {
+    fn visit<T: fmt::Debug + PartialEq>(&mut self, context: &str, main: &T, shadow: &T) {
+        self.check_match(context, main, shadow);
+    }
+
+    fn visit_storage_logs(
+        &mut self,
+        context: &str,
+        main: &[StorageLogWithPreviousValue],
+        shadow: &[StorageLogWithPreviousValue],
+    ) {
+        match self.comparison_options.storage_logs {
+            StorageLogsComparison::Lenient => {
+                let main = UniqueStorageLogs::new(main);
+                let shadow = UniqueStorageLogs::new(shadow);
+                self.check_match(context, &main, &shadow);
+            }
+            StorageLogsComparison::Strict => self.check_match(context, &main, &shadow),
+        }
+    }
+
+    fn visit_system_logs(
+        &mut self,
+        context: &str,
+        main: &[SystemL2ToL1Log],
+        shadow: &[SystemL2ToL1Log],
+    ) {
+        match self.comparison_options.system_logs {
+            SystemLogsComparison::Sequence => self.check_match(context, &main, &shadow),
+            SystemLogsComparison::Set => {
+                let sort_logs = |logs: &[SystemL2ToL1Log]| {
+                    let mut logs = logs.to_vec();
+                    logs.sort_by_cached_key(|log| log.0.packed_encoding());
+                    logs
+                };
+                self.check_match(context, &sort_logs(main), &sort_logs(shadow));
+            }
+        }
+    }
+
+    fn visit_pubdata(&mut self, context: &str, main: &Option<Vec<u8>>, shadow: &Option<Vec<u8>>) {
+        self.check_pubdata_match(context, main, shadow);
+    }
+}
+
+/// Records main VM outputs; shadow outputs are ignored.
+#[derive(Debug, Default)]
+struct OutputsRecorder(BTreeMap<String, String>);
+
+impl OutputsVisitor for OutputsRecorder {
+    fn visit<T: fmt::Debug + PartialEq>(&mut self, context: &str, main: &T, _shadow: &T) {
+        self.0.insert(context.to_owned(), format!("{main:#?}"));
+    }
+}
+
+impl OutputsRecorder {
+    fn into_outputs(self, operation: &str) -> RecordedOutputs {
+        RecordedOutputs {
+            operation: operation.to_owned(),
+            values: self.0,
+        }
+    }
+}
+
+/// Checks main VM outputs against the recorded ones; shadow outputs are ignored.
+#[derive(Debug)]
+struct RecordedOutputsChecker<'a> {
+    recorded: &'a BTreeMap<String, String>,
+    errors: DivergenceErrors,
+}
+
+impl OutputsVisitor for RecordedOutputsChecker<'_> {
+    fn visit<T: fmt::Debug + PartialEq>(&mut self, context: &str, main: &T, _shadow: &T) {
+        let actual = format!("{main:#?}");
+        match self.recorded.get(context) {
+            Some(recorded) if *recorded == actual => { /* outputs match */ }
+            Some(recorded) => {
+                let comparison = pretty_assertions::StrComparison::new(&actual, recorded);
+                let err = format!("`{context}` mismatch: {comparison}");
+                self.errors.push(context, err);
+            }
+            None => {
+                let err = format!("`{context}` is not recorded");
+                self.errors.push(context, err);
+            }
+        }
+    }
+}
+
+pub(super) fn record_results(
+    operation: &str,
+    result: &VmExecutionResultAndLogs,
+) -> RecordedOutputs {
+    let mut recorder = OutputsRecorder::default();
+    visit_results(&mut recorder, result, result);
+    recorder.into_outputs(operation)
+}
+
+pub(super) fn record_finished_batch(batch: &FinishedL1Batch) -> RecordedOutputs {
+    let mut recorder = OutputsRecorder::default();
+    visit_finished_batches(&mut recorder, batch, batch);
+    recorder.into_outputs("finish_batch")
+}
+
+fn visit_results(
+    visitor: &mut impl OutputsVisitor,
+    main_result: &VmExecutionResultAndLogs,
+    shadow_result: &VmExecutionResultAndLogs,
+) {
+    visitor.visit("result", &main_result.result, &shadow_result.result);
+    visitor.visit(
+        "logs.events",
+        &main_result.logs.events,
+        &shadow_result.logs.events,
+    );
+    visitor.visit(
+        "logs.system_l2_to_l1_logs",
+        &main_result.logs.system_l2_to_l1_logs,
+        &shadow_result.logs.system_l2_to_l1_logs,
+    );
+    visitor.visit(
+        "logs.user_l2_to_l1_logs",
+        &main_result.logs.user_l2_to_l1_logs,
+        &shadow_result.logs.user_l2_to_l1_logs,
+    );
+    visitor.visit_storage_logs(
+        "logs.storage_logs",
+        &main_result.logs.storage_logs,
+        &shadow_result.logs.storage_logs,
+    );
+    visitor.visit("refunds", &main_result.refunds, &shadow_result.refunds);
+    visitor.visit(
+        "statistics.circuit_statistic",
+        &main_result.statistics.circuit_statistic,
+        &shadow_result.statistics.circuit_statistic,
+    );
+    visitor.visit(
+        "gas_remaining",
+        &main_result.statistics.gas_remaining,
+        &shadow_result.statistics.gas_remaining,
+    );
+}
+
+fn visit_final_states(
+    visitor: &mut impl OutputsVisitor,
+    main: &CurrentExecutionState,
+    shadow: &CurrentExecutionState,
+) {
+    visitor.visit("final_state.events", &main.events, &shadow.events);
+    visitor.visit(
+        "final_state.user_l2_to_l1_logs",
+        &main.user_l2_to_l1_logs,
+        &shadow.user_l2_to_l1_logs,
+    );
+    visitor.visit_system_logs(
+        "final_state.system_logs",
+        &main.system_logs,
+        &shadow.system_logs,
+    );
+    visitor.visit(
+        "final_state.storage_refunds",
+        &main.storage_refunds,
+        &shadow.storage_refunds,
+    );
+    visitor.visit(
+        "final_state.pubdata_costs",
+        &main.pubdata_costs,
+        &shadow.pubdata_costs,
+    );
+    visitor.visit(
+        "final_state.used_contract_hashes",
+        &main.used_contract_hashes.iter().collect::<BTreeSet<_>>(),
+        &shadow.used_contract_hashes.iter().collect::<BTreeSet<_>>(),
+    );
+
+    let main_deduplicated_logs = DivergenceErrors::gather_logs(&main.deduplicated_storage_logs);
+    let shadow_deduplicated_logs = DivergenceErrors::gather_logs(&shadow.deduplicated_storage_logs);
+    visitor.visit(
+        "deduplicated_storage_logs",
+        &main_deduplicated_logs,
+        &shadow_deduplicated_logs,
+    );
+}
+
+/// Number of independent parts of finished batches visited by [`visit_finished_batch_part()`].
+const FINISHED_BATCH_PARTS: usize = 5;
+
+fn visit_finished_batch_part(
+    visitor: &mut impl OutputsVisitor,
+    part: usize,
+    main_batch: &FinishedL1Batch,
+    shadow_batch: &FinishedL1Batch,
+) {
+    match part {
+        0 => visit_results(
+            visitor,
+            &main_batch.block_tip_execution_result,
+            &shadow_batch.block_tip_execution_result,
+        ),
+        1 => visit_final_states(
+            visitor,
+            &main_batch.final_execution_state,
+            &shadow_batch.final_execution_state,
+        ),
+        2 => visitor.visit(
+            "final_bootloader_memory",
+            &main_batch.final_bootloader_memory,
+            &shadow_batch.final_bootloader_memory,
+        ),
+        3 => visitor.visit_pubdata(
+            "pubdata_input",
+            &main_batch.pubdata_input,
+            &shadow_batch.pubdata_input,
+        ),
+        4 => visitor.visit(
+            "state_diffs",
+            &main_batch.state_diffs,
+            &shadow_batch.state_diffs,
+        ),
+        _ => unreachable!("invalid finished batch part: {part}"),
+    }
+}
+
+fn visit_finished_batches(
+    visitor: &mut impl OutputsVisitor,
+    main_batch: &FinishedL1Batch,
+    shadow_batch: &FinishedL1Batch,
+) {
+    for part in 0..FINISHED_BATCH_PARTS {
+        visit_finished_batch_part(visitor, part, main_batch, shadow_batch);
+    }
+}
+
+/// Compares finished batches distributing independent parts among up to `concurrency` threads. Divergences
+/// are aggregated in the part order, so that the result is identical to [`visit_finished_batches()`].
+fn check_finished_batches_concurrently(
+    main_batch: &FinishedL1Batch,
+    shadow_batch: &FinishedL1Batch,
+    concurrency: usize,
+    comparison_options: ComparisonOptions,
+) -> DivergenceErrors {
+    let mut errors = DivergenceErrors::new().with_comparison_options(comparison_options);
+    let concurrency = concurrency.clamp(1, FINISHED_BATCH_PARTS);
+    if concurrency == 1 {
+        visit_finished_batches(&mut errors, main_batch, shadow_batch);
+        return errors;
+    }
+
+    let mut part_errors: Vec<_> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..concurrency)
+            .map(|thread_idx| {
+                scope.spawn(move || {
+                    let parts = (thread_idx..FINISHED_BATCH_PARTS).step_by(concurrency);
+                    parts
+                        .map(|part| {
+                            let mut errors =
+                                DivergenceErrors::new().with_comparison_options(comparison_options);
+                            visit_finished_batch_part(&mut errors, part, main_batch, shadow_batch);
+                            (part, errors)
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("finished batch comparison panicked"))
+            .collect()
+    });
+    part_errors.sort_unstable_by_key(|(part, _)| *part);
+    for (_, part_errors) in part_errors {
+        errors.divergences.extend(part_errors.divergences);
+    }
+    errors
 }
 
 // The new VM doesn't support read logs yet, doesn't order logs by access and deduplicates them
-// inside the VM, hence this auxiliary struct.
+// inside the VM, hence this auxiliary struct. Used for [`StorageLogsComparison::Lenient`] comparison.
 #[derive(PartialEq)]
 struct UniqueStorageLogs(BTreeMap<StorageKey, StorageLogWithPreviousValue>);
 
@@ -450,30 +1785,388 @@ impl UniqueStorageLogs {
     }
 }
 
-impl<S, Main, Shadow> VmInterfaceHistoryEnabled for ShadowVm<S, Main, Shadow>
+impl<S, Main, Shadow, Cmp> VmInterfaceHistoryEnabled for ShadowVm<S, Main, Shadow, Cmp>
 where
     S: ReadStorage,
     Main: VmInterfaceHistoryEnabled + VmTrackingContracts,
     Shadow: VmInterfaceHistoryEnabled,
+    Cmp: TracerComparator<Main::TracerDispatcher, Shadow::TracerDispatcher>,
 {
     fn make_snapshot(&mut self) {
         if let Some(shadow) = self.shadow.get_mut() {
-            shadow.vm.make_snapshot();
+            match &mut shadow.vm {
+                ShadowTarget::Vm(vm) => vm.make_snapshot(),
+                ShadowTarget::Recorded(trace) => trace.make_snapshot(),
+            }
         }
         self.main.make_snapshot();
     }
 
     fn rollback_to_the_latest_snapshot(&mut self) {
         if let Some(shadow) = self.shadow.get_mut() {
-            shadow.vm.rollback_to_the_latest_snapshot();
+            match &mut shadow.vm {
+                ShadowTarget::Vm(vm) => vm.rollback_to_the_latest_snapshot(),
+                ShadowTarget::Recorded(trace) => trace.rollback_to_the_latest_snapshot(),
+            }
         }
         self.main.rollback_to_the_latest_snapshot();
     }
 
     fn pop_snapshot_no_rollback(&mut self) {
         if let Some(shadow) = self.shadow.get_mut() {
-            shadow.vm.pop_snapshot_no_rollback();
+            match &mut shadow.vm {
+                ShadowTarget::Vm(vm) => vm.pop_snapshot_no_rollback(),
+                ShadowTarget::Recorded(trace) => trace.pop_snapshot_no_rollback(),
+            }
         }
         self.main.pop_snapshot_no_rollback();
     }
 }
+
+/// Execution states are compared field by field, using the same contexts as final states of finished batches.
+/// States are not compared if the main VM is checked against [recorded outputs](ShadowVm::with_recorded_outputs()).
+impl<S, Main, Shadow, Cmp> VmInspectExecutionState for ShadowVm<S, Main, Shadow, Cmp>
+where
+    S: ReadStorage,
+    Main: VmInspectExecutionState + VmTrackingContracts,
+    Shadow: VmInspectExecutionState,
+    Cmp: TracerComparator<Main::TracerDispatcher, Shadow::TracerDispatcher>,
+{
+    fn current_execution_state(&self) -> CurrentExecutionState {
+        let main_state = self.main.current_execution_state();
+        let triage_result = {
+            let mut shadow = self.shadow.borrow_mut();
+            let Some(shadow) = shadow.as_mut() else {
+                return main_state;
+            };
+            let ShadowTarget::Vm(vm) = &shadow.vm else {
+                return main_state;
+            };
+            let shadow_state = vm.current_execution_state();
+            let mut errors =
+                DivergenceErrors::new().with_comparison_options(shadow.comparison_options);
+            errors.check_final_states_match(&main_state, &shadow_state);
+            errors.into_result().or_else(|err| {
+                err.context("getting current execution state".to_owned())
+                    .triage(
+                        &shadow.divergence_severities,
+                        shadow.allowlist.as_ref(),
+                        None,
+                        &mut shadow.report_limiter,
+                    )
+            })
+        };
+
+        if let Err(err) = triage_result {
+            self.report_shared(err);
+        }
+        main_state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use zksync_types::{l2_to_l1_log::L2ToL1Log, AccountTreeId, Address};
+
+    use super::*;
+
+    #[test]
+    fn divergence_contexts_are_sorted_and_unique() {
+        assert!(DIVERGENCE_CONTEXTS
+            .windows(2)
+            .all(|window| window[0] < window[1]));
+    }
+
+    #[test]
+    fn execution_states_are_compared_field_by_field() {
+        let main_state = FinishedL1Batch::mock().final_execution_state;
+        let mut shadow_state = main_state.clone();
+        shadow_state.storage_refunds.push(1);
+        shadow_state.pubdata_costs.push(-1);
+
+        let mut errors = DivergenceErrors::new();
+        errors.check_final_states_match(&main_state, &shadow_state);
+        let errors = errors.into_result().unwrap_err();
+        let contexts: Vec<_> = errors.contexts().collect();
+        assert_eq!(
+            contexts,
+            ["final_state.pubdata_costs", "final_state.storage_refunds"]
+        );
+    }
+
+    #[test]
+    fn recorded_outputs_use_known_divergence_contexts() {
+        let batch = FinishedL1Batch::mock();
+        let outputs = record_finished_batch(&batch);
+        for context in outputs.values.keys() {
+            assert!(
+                DIVERGENCE_CONTEXTS.contains(&context.as_str()),
+                "unknown context: {context}"
+            );
+        }
+        let outputs = record_results("inspect", &batch.block_tip_execution_result);
+        for context in outputs.values.keys() {
+            assert!(
+                DIVERGENCE_CONTEXTS.contains(&context.as_str()),
+                "unknown context: {context}"
+            );
+        }
+    }
+
+    #[test]
+    fn all_check_sites_use_known_divergence_contexts() {
+        const CHECK_METHODS: &[&str] = &[
+            "check_match(",
+            "push(",
+            "visit(",
+            "visit_storage_logs(",
+            "visit_system_logs(",
+            "visit_pubdata(",
+        ];
+
+        let source = include_str!("shadow.rs");
+        // Only check the non-test code, since tests use arbitrary contexts.
+        let (source, _) = source.split_once("#[cfg(test)]\nmod tests").unwrap();
+
+        let mut checked_contexts = BTreeSet::new();
+        for method in CHECK_METHODS {
+            for (pos, _) in source.match_indices(method) {
+                let args = source[pos + method.len()..].trim_start();
+                let Some(args) = args.strip_prefix('"') else {
+                    continue; // context is not a literal
+                };
+                let (context, _) = args.split_once('"').unwrap();
+                assert!(
+                    DIVERGENCE_CONTEXTS.contains(&context),
+                    "`{method}..)` uses unknown context: {context}"
+                );
+                checked_contexts.insert(context);
+            }
+        }
+        let known_contexts: BTreeSet<_> = DIVERGENCE_CONTEXTS.iter().copied().collect();
+        assert_eq!(checked_contexts, known_contexts);
+    }
+
+    #[test]
+    fn divergences_are_sorted_by_context() {
+        let mut errors = DivergenceErrors::new();
+        errors.check_match("refunds", &1, &2);
+        errors.check_match("logs.events", &3, &4);
+        errors.check_match("gas_remaining", &5, &6);
+        errors.check_match("logs.events", &7, &8);
+        let errors = errors.into_result().unwrap_err();
+        let contexts: Vec<_> = errors.contexts().collect();
+        assert_eq!(
+            contexts,
+            ["gas_remaining", "logs.events", "logs.events", "refunds"]
+        );
+
+        let mut reordered_errors = DivergenceErrors::new();
+        reordered_errors.check_match("logs.events", &3, &4);
+        reordered_errors.check_match("gas_remaining", &5, &6);
+        reordered_errors.check_match("logs.events", &7, &8);
+        reordered_errors.check_match("refunds", &1, &2);
+        let reordered_errors = reordered_errors.into_result().unwrap_err();
+        assert_eq!(reordered_errors.to_string(), errors.to_string());
+    }
+
+    #[test]
+    fn divergence_summary() {
+        let mut errors = DivergenceErrors::new();
+        errors.check_match("refunds", &1, &2);
+        errors.check_match("logs.events", &3, &4);
+        errors.check_match("gas_remaining", &5, &6);
+        errors.check_match("logs.events", &7, &8);
+        let errors = errors.into_result().unwrap_err();
+
+        let summary =
+            "4 divergence(s) across 3 context(s) (logs.events: 2, gas_remaining: 1, refunds: 1)";
+        assert_eq!(errors.summary(), summary);
+        let message = errors.to_string();
+        assert!(
+            message.starts_with(&format!("VM execution diverged: {summary}: [")),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn l2_block_for_logs_is_displayed_but_not_hashed() {
+        let errors_in_block = |number: u32| {
+            let mut errors = DivergenceErrors::new();
+            errors.check_match("logs.user_l2_to_l1_logs", &1, &2);
+            errors.check_match("refunds", &3, &4);
+            errors
+                .into_result()
+                .unwrap_err()
+                .with_l2_block_for_logs((L2BlockNumber(number), u64::from(number) * 10))
+        };
+
+        let errors = errors_in_block(1);
+        let message = errors.to_string();
+        assert!(
+            message.contains("[L2 block #1, timestamp 10] "),
+            "{message}"
+        );
+        assert_eq!(message.matches("[L2 block #").count(), 1, "{message}");
+
+        let other_errors = errors_in_block(2);
+        assert_ne!(other_errors.to_string(), message);
+        assert_eq!(other_errors.stable_hash(), errors.stable_hash());
+    }
+
+    #[test]
+    fn report_limiter_window() {
+        let mut limiter = ReportLimiter {
+            limit: Some(DivergenceRateLimit {
+                max_reports: 2,
+                window: Duration::from_secs(10),
+            }),
+            ..ReportLimiter::default()
+        };
+        let start = Instant::now();
+        assert!(limiter.allow_at(start));
+        assert!(limiter.allow_at(start + Duration::from_secs(1)));
+        assert!(!limiter.allow_at(start + Duration::from_secs(2)));
+        assert!(!limiter.allow_at(start + Duration::from_secs(9)));
+        assert_eq!(limiter.suppressed_count, 2);
+
+        // The window is restarted by the first report after it has elapsed.
+        let new_start = start + Duration::from_secs(10);
+        assert!(limiter.allow_at(new_start));
+        assert!(limiter.allow_at(new_start + Duration::from_secs(5)));
+        assert!(!limiter.allow_at(new_start + Duration::from_secs(9)));
+        assert!(limiter.allow_at(new_start + Duration::from_secs(10)));
+        assert_eq!(limiter.suppressed_count, 3);
+
+        let mut unlimited = ReportLimiter::default();
+        assert!((0..100).all(|_| unlimited.allow_at(start)));
+        assert_eq!(unlimited.suppressed_count, 0);
+    }
+
+    #[test]
+    fn concurrent_batch_comparison_is_deterministic() {
+        let main_batch = FinishedL1Batch::mock();
+        let mut shadow_batch = FinishedL1Batch::mock();
+        shadow_batch.block_tip_execution_result.refunds.gas_refunded = 1;
+        shadow_batch.final_execution_state.storage_refunds.push(1);
+        shadow_batch.final_execution_state.pubdata_costs.push(-1);
+        shadow_batch.final_bootloader_memory = None;
+        shadow_batch.state_diffs = None;
+
+        let mut expected_errors = DivergenceErrors::new();
+        visit_finished_batches(&mut expected_errors, &main_batch, &shadow_batch);
+        let expected_contexts: Vec<_> = expected_errors.contexts().collect();
+        assert_eq!(
+            expected_contexts,
+            [
+                "refunds",
+                "final_state.storage_refunds",
+                "final_state.pubdata_costs",
+                "final_bootloader_memory",
+                "state_diffs"
+            ]
+        );
+
+        for concurrency in 0..=FINISHED_BATCH_PARTS + 1 {
+            let errors = check_finished_batches_concurrently(
+                &main_batch,
+                &shadow_batch,
+                concurrency,
+                ComparisonOptions::default(),
+            );
+            assert_eq!(
+                errors.to_string(),
+                expected_errors.to_string(),
+                "concurrency = {concurrency}"
+            );
+        }
+    }
+
+    fn storage_key(byte: u8) -> StorageKey {
+        StorageKey::new(
+            AccountTreeId::new(Address::repeat_byte(1)),
+            H256::repeat_byte(byte),
+        )
+    }
+
+    fn write_log(key: StorageKey, previous_value: u8, value: u8) -> StorageLogWithPreviousValue {
+        StorageLogWithPreviousValue {
+            log: StorageLog::new_write_log(key, H256::repeat_byte(value)),
+            previous_value: H256::repeat_byte(previous_value),
+        }
+    }
+
+    #[test]
+    fn lenient_and_strict_storage_logs_comparison() {
+        let main_logs = [
+            StorageLogWithPreviousValue {
+                log: StorageLog::new_read_log(storage_key(1), H256::zero()),
+                previous_value: H256::zero(),
+            },
+            write_log(storage_key(2), 0, 1),
+            write_log(storage_key(2), 1, 2),
+            // No-op write
+            write_log(storage_key(3), 5, 5),
+        ];
+        let shadow_logs = [write_log(storage_key(2), 0, 2)];
+
+        let mut errors = DivergenceErrors::new();
+        errors.visit_storage_logs("logs.storage_logs", &main_logs, &shadow_logs);
+        errors.into_result().unwrap();
+
+        let options = ComparisonOptions {
+            storage_logs: StorageLogsComparison::Strict,
+            ..ComparisonOptions::default()
+        };
+        let mut errors = DivergenceErrors::new().with_comparison_options(options);
+        errors.visit_storage_logs("logs.storage_logs", &main_logs, &shadow_logs);
+        let errors = errors.into_result().unwrap_err();
+        let contexts: Vec<_> = errors.contexts().collect();
+        assert_eq!(contexts, ["logs.storage_logs"]);
+
+        // Different access order is a divergence only in the strict mode.
+        let main_logs = [
+            write_log(storage_key(1), 0, 1),
+            write_log(storage_key(2), 0, 2),
+        ];
+        let shadow_logs = [main_logs[1], main_logs[0]];
+        let mut errors = DivergenceErrors::new();
+        errors.visit_storage_logs("logs.storage_logs", &main_logs, &shadow_logs);
+        errors.into_result().unwrap();
+        let mut errors = DivergenceErrors::new().with_comparison_options(options);
+        errors.visit_storage_logs("logs.storage_logs", &main_logs, &shadow_logs);
+        errors.into_result().unwrap_err();
+    }
+
+    #[test]
+    fn sequence_and_set_system_logs_comparison() {
+        let system_log = |key: u8| {
+            SystemL2ToL1Log(L2ToL1Log {
+                key: H256::repeat_byte(key),
+                ..L2ToL1Log::default()
+            })
+        };
+        let main_logs = [system_log(1), system_log(2), system_log(2)];
+        let reordered_logs = [system_log(2), system_log(1), system_log(2)];
+        let set_options = ComparisonOptions {
+            system_logs: SystemLogsComparison::Set,
+            ..ComparisonOptions::default()
+        };
+
+        let mut errors = DivergenceErrors::new();
+        errors.visit_system_logs("final_state.system_logs", &main_logs, &reordered_logs);
+        let errors = errors.into_result().unwrap_err();
+        let contexts: Vec<_> = errors.contexts().collect();
+        assert_eq!(contexts, ["final_state.system_logs"]);
+
+        let mut errors = DivergenceErrors::new().with_comparison_options(set_options);
+        errors.visit_system_logs("final_state.system_logs", &main_logs, &reordered_logs);
+        errors.into_result().unwrap();
+
+        // Multiplicity is still compared.
+        let deduplicated_logs = [system_log(2), system_log(1)];
+        let mut errors = DivergenceErrors::new().with_comparison_options(set_options);
+        errors.visit_system_logs("final_state.system_logs", &main_logs, &deduplicated_logs);
+        errors.into_result().unwrap_err();
+    }
+}
@@ -0,0 +1,107 @@
+//! Test utilities for VM wrappers, such as [`ShadowVm`](super::ShadowVm).
+
+use std::fmt;
+
+use zksync_types::{Transaction, H256};
+
+use crate::{
+    BytecodeCompressionResult, FinishedL1Batch, L2BlockEnv, VmExecutionMode,
+    VmExecutionResultAndLogs, VmInterface, VmInterfaceHistoryEnabled, VmMemoryMetrics,
+    VmTrackingContracts,
+};
+
+/// Deliberately wrong VM stub that wraps a VM and mutates execution results returned by it. Can be used as a shadow VM
+/// (e.g., via [`ShadowVm::with_shadow_vm()`](super::ShadowVm::with_shadow_vm())) to test divergence handling end to end.
+///
+/// Results of [`VmInterface::inspect()`] and [`VmInterface::inspect_transaction_with_bytecode_compression()`] are mutated;
+/// other VM outputs are returned as is.
+pub struct DivergingVm<Vm> {
+    inner: Vm,
+    mutate_result: Box<dyn FnMut(&mut VmExecutionResultAndLogs)>,
+}
+
+impl<Vm: fmt::Debug> fmt::Debug for DivergingVm<Vm> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("DivergingVm")
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<Vm: VmInterface> DivergingVm<Vm> {
+    /// Wraps the provided VM. `mutate_result` is called on each mutated result, so it can be stateful (e.g., only
+    /// mutate the first result).
+    pub fn new(
+        inner: Vm,
+        mutate_result: impl FnMut(&mut VmExecutionResultAndLogs) + 'static,
+    ) -> Self {
+        Self {
+            inner,
+            mutate_result: Box::new(mutate_result),
+        }
+    }
+}
+
+impl<Vm: VmInterface> VmInterface for DivergingVm<Vm> {
+    type TracerDispatcher = Vm::TracerDispatcher;
+
+    fn push_transaction(&mut self, tx: Transaction) {
+        self.inner.push_transaction(tx);
+    }
+
+    fn inspect(
+        &mut self,
+        dispatcher: &mut Self::TracerDispatcher,
+        execution_mode: VmExecutionMode,
+    ) -> VmExecutionResultAndLogs {
+        let mut result = self.inner.inspect(dispatcher, execution_mode);
+        (self.mutate_result)(&mut result);
+        result
+    }
+
+    fn start_new_l2_block(&mut self, l2_block_env: L2BlockEnv) {
+        self.inner.start_new_l2_block(l2_block_env);
+    }
+
+    fn inspect_transaction_with_bytecode_compression(
+        &mut self,
+        tracer: &mut Self::TracerDispatcher,
+        tx: Transaction,
+        with_compression: bool,
+    ) -> (BytecodeCompressionResult<'_>, VmExecutionResultAndLogs) {
+        let (compression_result, mut tx_result) = self
+            .inner
+            .inspect_transaction_with_bytecode_compression(tracer, tx, with_compression);
+        (self.mutate_result)(&mut tx_result);
+        (compression_result, tx_result)
+    }
+
+    fn record_vm_memory_metrics(&self) -> VmMemoryMetrics {
+        self.inner.record_vm_memory_metrics()
+    }
+
+    fn finish_batch(&mut self) -> FinishedL1Batch {
+        self.inner.finish_batch()
+    }
+}
+
+impl<Vm: VmInterfaceHistoryEnabled> VmInterfaceHistoryEnabled for DivergingVm<Vm> {
+    fn make_snapshot(&mut self) {
+        self.inner.make_snapshot();
+    }
+
+    fn rollback_to_the_latest_snapshot(&mut self) {
+        self.inner.rollback_to_the_latest_snapshot();
+    }
+
+    fn pop_snapshot_no_rollback(&mut self) {
+        self.inner.pop_snapshot_no_rollback();
+    }
+}
+
+impl<Vm: VmTrackingContracts> VmTrackingContracts for DivergingVm<Vm> {
+    fn used_contract_hashes(&self) -> Vec<H256> {
+        self.inner.used_contract_hashes()
+    }
+}
@@ -14,8 +14,8 @@
 use zksync_types::{Transaction, H256};
 
 use crate::{
-    storage::StoragePtr, BytecodeCompressionResult, FinishedL1Batch, L1BatchEnv, L2BlockEnv,
-    SystemEnv, VmExecutionMode, VmExecutionResultAndLogs, VmMemoryMetrics,
+    storage::StoragePtr, BytecodeCompressionResult, CurrentExecutionState, FinishedL1Batch,
+    L1BatchEnv, L2BlockEnv, SystemEnv, VmExecutionMode, VmExecutionResultAndLogs, VmMemoryMetrics,
 };
 
 pub trait VmInterface {
@@ -110,3 +110,9 @@ pub trait VmTrackingContracts: VmInterface {
     /// Returns hashes of all decommitted bytecodes.
     fn used_contract_hashes(&self) -> Vec<H256>;
 }
+
+/// VM that allows inspecting its current execution state without finishing the batch. Intended for debugging and tests.
+pub trait VmInspectExecutionState: VmInterface {
+    /// Returns the current execution state of the VM (events, L2-to-L1 logs, deduplicated storage logs etc.).
+    fn current_execution_state(&self) -> CurrentExecutionState;
+}
@@ -64,6 +64,8 @@ impl Task for TeeVerifierInputProducer {
     }
 
     async fn run(self: Box<Self>, stop_receiver: StopReceiver) -> anyhow::Result<()> {
-        (*self).run(stop_receiver.0, None).await
+        let mut producer = *self;
+        producer.set_stop_receiver(stop_receiver.0.clone());
+        producer.run(stop_receiver.0, None).await
     }
 }
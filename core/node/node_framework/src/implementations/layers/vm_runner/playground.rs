@@ -1,7 +1,8 @@
 use async_trait::async_trait;
-use zksync_config::configs::ExperimentalVmPlaygroundConfig;
+use zksync_config::configs::{ExperimentalVmPlaygroundConfig, VmPlaygroundDumpFormat};
 use zksync_node_framework_derive::{FromContext, IntoContext};
 use zksync_types::L2ChainId;
+use zksync_vm_interface::utils::{VmDumpCompression, VmDumpFormat};
 use zksync_vm_runner::{
     impls::{
         VmPlayground, VmPlaygroundCursorOptions, VmPlaygroundIo, VmPlaygroundLoaderTask,
@@ -96,9 +97,19 @@ impl WiringLayer for VmPlaygroundLayer {
         } else {
             VmPlaygroundStorageOptions::Snapshots { shadow: false }
         };
+        let dumps_format = match self.config.dumps_format {
+            VmPlaygroundDumpFormat::Json => VmDumpFormat::Json,
+            VmPlaygroundDumpFormat::Bincode => VmDumpFormat::Bincode,
+        };
+        let dumps_compression = match self.config.dumps_compression_level {
+            Some(level) => VmDumpCompression::Zstd { level },
+            None => VmDumpCompression::default(),
+        };
         let (playground, tasks) = VmPlayground::new(
             connection_pool,
             dumps_object_store.map(|resource| resource.0),
+            dumps_format,
+            dumps_compression,
             self.config.fast_vm_mode,
             storage,
             self.zksync_network_id,
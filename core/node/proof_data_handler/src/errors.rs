@@ -8,6 +8,7 @@ use zksync_object_store::ObjectStoreError;
 pub(crate) enum RequestProcessorError {
     ObjectStore(ObjectStoreError),
     Dal(DalError),
+    InvalidIdempotencyKey,
 }
 
 impl From<DalError> for RequestProcessorError {
@@ -38,6 +39,10 @@ impl IntoResponse for RequestProcessorError {
                     ),
                 }
             }
+            RequestProcessorError::InvalidIdempotencyKey => (
+                StatusCode::BAD_REQUEST,
+                "Idempotency key doesn't match the submitted proof".to_owned(),
+            ),
         };
         (status_code, message).into_response()
     }
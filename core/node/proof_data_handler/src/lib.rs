@@ -1,7 +1,7 @@
 use std::{net::SocketAddr, sync::Arc};
 
 use anyhow::Context as _;
-use axum::{extract::Path, routing::post, Json, Router};
+use axum::{extract::Path, http::HeaderMap, routing::post, Json, Router};
 use request_processor::RequestProcessor;
 use tee_request_processor::TeeRequestProcessor;
 use tokio::sync::watch;
@@ -92,36 +92,39 @@ fn create_proof_processing_router(
         let submit_tee_proof_processor = get_tee_proof_gen_processor.clone();
         let register_tee_attestation_processor = get_tee_proof_gen_processor.clone();
 
-        router = router.route(
-            "/tee/proof_inputs",
-            post(
-                move |payload: Json<TeeProofGenerationDataRequest>| async move {
-                    get_tee_proof_gen_processor
-                        .get_proof_generation_data(payload)
-                        .await
-                },
-            ),
-        )
-        .route(
-            "/tee/submit_proofs/:l1_batch_number",
-            post(
-                move |l1_batch_number: Path<u32>, payload: Json<SubmitTeeProofRequest>| async move {
-                    submit_tee_proof_processor
-                        .submit_proof(l1_batch_number, payload)
-                        .await
-                },
-            ),
-        )
-        .route(
-            "/tee/register_attestation",
-            post(
-                move |payload: Json<RegisterTeeAttestationRequest>| async move {
-                    register_tee_attestation_processor
-                        .register_tee_attestation(payload)
-                        .await
-                },
-            ),
-        );
+        router = router
+            .route(
+                "/tee/proof_inputs",
+                post(
+                    move |payload: Json<TeeProofGenerationDataRequest>| async move {
+                        get_tee_proof_gen_processor
+                            .get_proof_generation_data(payload)
+                            .await
+                    },
+                ),
+            )
+            .route(
+                "/tee/submit_proofs/:l1_batch_number",
+                post(
+                    move |l1_batch_number: Path<u32>,
+                          headers: HeaderMap,
+                          payload: Json<SubmitTeeProofRequest>| async move {
+                        submit_tee_proof_processor
+                            .submit_proof(l1_batch_number, headers, payload)
+                            .await
+                    },
+                ),
+            )
+            .route(
+                "/tee/register_attestation",
+                post(
+                    move |payload: Json<RegisterTeeAttestationRequest>| async move {
+                        register_tee_attestation_processor
+                            .register_tee_attestation(payload)
+                            .await
+                    },
+                ),
+            );
     }
 
     router
@@ -1,12 +1,17 @@
 use std::sync::Arc;
 
-use axum::{extract::Path, Json};
+use axum::{
+    extract::Path,
+    http::{HeaderMap, HeaderValue},
+    Json,
+};
 use zksync_config::configs::ProofDataHandlerConfig;
 use zksync_dal::{ConnectionPool, Core, CoreDal};
 use zksync_object_store::{ObjectStore, ObjectStoreError};
 use zksync_prover_interface::api::{
     RegisterTeeAttestationRequest, RegisterTeeAttestationResponse, SubmitProofResponse,
     SubmitTeeProofRequest, TeeProofGenerationDataRequest, TeeProofGenerationDataResponse,
+    IDEMPOTENCY_KEY_HEADER, TEE_PENDING_BATCHES_HEADER,
 };
 use zksync_types::{tee_types::TeeType, L1BatchNumber};
 
@@ -35,7 +40,7 @@ impl TeeRequestProcessor {
     pub(crate) async fn get_proof_generation_data(
         &self,
         request: Json<TeeProofGenerationDataRequest>,
-    ) -> Result<Json<TeeProofGenerationDataResponse>, RequestProcessorError> {
+    ) -> Result<(HeaderMap, Json<TeeProofGenerationDataResponse>), RequestProcessorError> {
         tracing::info!("Received request for proof generation data: {:?}", request);
 
         let mut min_batch_number: Option<L1BatchNumber> = None;
@@ -75,7 +80,20 @@ impl TeeRequestProcessor {
             );
         }
 
-        result
+        let response = result?;
+        let pending_batches = self
+            .pool
+            .connection()
+            .await?
+            .tee_proof_generation_dal()
+            .count_unpicked_batches(request.tee_type)
+            .await?;
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            TEE_PENDING_BATCHES_HEADER,
+            HeaderValue::from(pending_batches),
+        );
+        Ok((headers, response))
     }
 
     async fn lock_batch_for_proving(
@@ -114,6 +132,7 @@ impl TeeRequestProcessor {
     pub(crate) async fn submit_proof(
         &self,
         Path(l1_batch_number): Path<u32>,
+        headers: HeaderMap,
         Json(proof): Json<SubmitTeeProofRequest>,
     ) -> Result<Json<SubmitProofResponse>, RequestProcessorError> {
         let l1_batch_number = L1BatchNumber(l1_batch_number);
@@ -125,6 +144,30 @@ impl TeeRequestProcessor {
             proof,
             l1_batch_number
         );
+
+        if let Some(idempotency_key) = headers.get(IDEMPOTENCY_KEY_HEADER) {
+            let expected_key = proof.idempotency_key(l1_batch_number);
+            if idempotency_key.as_bytes() != expected_key.as_bytes() {
+                tracing::warn!(
+                    "Idempotency key {idempotency_key:?} for batch {l1_batch_number} doesn't match the expected `{expected_key}`"
+                );
+                return Err(RequestProcessorError::InvalidIdempotencyKey);
+            }
+
+            let stored_proofs = dal
+                .get_tee_proofs(l1_batch_number, Some(proof.0.tee_type))
+                .await?;
+            let is_duplicate = stored_proofs.iter().any(|stored| {
+                stored.pubkey.as_ref() == Some(&proof.0.pubkey)
+                    && stored.proof.as_ref() == Some(&proof.0.proof)
+            });
+            if is_duplicate {
+                tracing::info!(
+                    "Proof for batch {l1_batch_number} with idempotency key `{expected_key}` is already stored; skipping"
+                );
+                return Ok(Json(SubmitProofResponse::Success));
+            }
+        }
         dal.save_proof_artifacts_metadata(
             l1_batch_number,
             proof.0.tee_type,
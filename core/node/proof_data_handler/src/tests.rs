@@ -15,7 +15,7 @@ use zksync_dal::{ConnectionPool, CoreDal};
 use zksync_multivm::interface::{L1BatchEnv, L2BlockEnv, SystemEnv, TxExecutionMode};
 use zksync_object_store::MockObjectStore;
 use zksync_prover_interface::{
-    api::SubmitTeeProofRequest,
+    api::{SubmitTeeProofRequest, IDEMPOTENCY_KEY_HEADER, TEE_PENDING_BATCHES_HEADER},
     inputs::{TeeVerifierInput, V1TeeVerifierInput, WitnessInputMerklePaths},
 };
 use zksync_types::{commitment::L1BatchCommitmentMode, tee_types::TeeType, L1BatchNumber, H256};
@@ -108,6 +108,8 @@ async fn request_tee_proof_inputs() {
         .unwrap();
 
     assert_eq!(response.status(), StatusCode::OK);
+    // The only batch is returned in the response, so there are no pending batches.
+    assert_eq!(response.headers()[TEE_PENDING_BATCHES_HEADER], "0");
 
     let body = axum::body::to_bytes(response.into_body(), usize::MAX)
         .await
@@ -152,7 +154,7 @@ async fn submit_tee_proof() {
 
     // this should fail because we haven't saved the attestation for the pubkey yet
 
-    let response = send_submit_tee_proof_request(&app, &uri, &tee_proof_request).await;
+    let response = send_submit_tee_proof_request(&app, &uri, &tee_proof_request, None).await;
     assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
 
     // save the attestation for the pubkey
@@ -167,7 +169,7 @@ async fn submit_tee_proof() {
 
     // resend the same request; this time, it should be successful.
 
-    let response = send_submit_tee_proof_request(&app, &uri, &tee_proof_request).await;
+    let response = send_submit_tee_proof_request(&app, &uri, &tee_proof_request, None).await;
     assert_eq!(response.status(), StatusCode::OK);
 
     // there should not be any batches awaiting proof in the db anymore
@@ -202,6 +204,69 @@ async fn submit_tee_proof() {
     assert_eq!(proof.pubkey.as_ref().unwrap(), &tee_proof_request.0.pubkey);
 }
 
+#[tokio::test]
+async fn submit_tee_proof_with_idempotency_key() {
+    let blob_store = MockObjectStore::arc();
+    let db_conn_pool = ConnectionPool::test_pool().await;
+    let batch_number = L1BatchNumber::from(1);
+    mock_tee_batch_status(db_conn_pool.clone(), batch_number, "mocked_object_path").await;
+
+    let tee_proof_request_str = r#"{
+        "signature": "0001020304",
+        "pubkey": "0506070809",
+        "proof": "0A0B0C0D0E",
+        "tee_type": "sgx"
+    }"#;
+    let tee_proof_request =
+        serde_json::from_str::<SubmitTeeProofRequest>(tee_proof_request_str).unwrap();
+    let mut storage = db_conn_pool.connection().await.unwrap();
+    storage
+        .tee_proof_generation_dal()
+        .save_attestation(&tee_proof_request.0.pubkey, &[15, 16, 17, 18, 19])
+        .await
+        .unwrap();
+
+    let uri = format!("/tee/submit_proofs/{}", batch_number.0);
+    let app = create_proof_processing_router(
+        blob_store,
+        db_conn_pool.clone(),
+        ProofDataHandlerConfig {
+            http_port: 1337,
+            proof_generation_timeout_in_secs: 10,
+            tee_support: true,
+        },
+        L1BatchCommitmentMode::Rollup,
+    );
+
+    // A key for another batch must be rejected.
+    let wrong_key = tee_proof_request.idempotency_key(L1BatchNumber(2));
+    let response =
+        send_submit_tee_proof_request(&app, &uri, &tee_proof_request, Some(&wrong_key)).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let key = tee_proof_request.idempotency_key(batch_number);
+    let response = send_submit_tee_proof_request(&app, &uri, &tee_proof_request, Some(&key)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let proofs = storage
+        .tee_proof_generation_dal()
+        .get_tee_proofs(batch_number, Some(TeeType::Sgx))
+        .await
+        .unwrap();
+    assert_eq!(proofs.len(), 1);
+    let updated_at = proofs[0].updated_at;
+
+    // A retried submission must succeed without re-saving the proof.
+    let response = send_submit_tee_proof_request(&app, &uri, &tee_proof_request, Some(&key)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let proofs = storage
+        .tee_proof_generation_dal()
+        .get_tee_proofs(batch_number, Some(TeeType::Sgx))
+        .await
+        .unwrap();
+    assert_eq!(proofs.len(), 1);
+    assert_eq!(proofs[0].updated_at, updated_at);
+}
+
 // Mock SQL db with information about the status of the TEE proof generation
 async fn mock_tee_batch_status(
     db_conn_pool: ConnectionPool<zksync_dal::Core>,
@@ -253,17 +318,18 @@ async fn send_submit_tee_proof_request(
     app: &Router,
     uri: &str,
     tee_proof_request: &SubmitTeeProofRequest,
+    idempotency_key: Option<&str>,
 ) -> Response {
     let req_body = Body::from(serde_json::to_vec(tee_proof_request).unwrap());
+    let mut request = Request::builder()
+        .method(Method::POST)
+        .uri(uri)
+        .header(http::header::CONTENT_TYPE, "application/json");
+    if let Some(idempotency_key) = idempotency_key {
+        request = request.header(IDEMPOTENCY_KEY_HEADER, idempotency_key);
+    }
     app.clone()
-        .oneshot(
-            Request::builder()
-                .method(Method::POST)
-                .uri(uri)
-                .header(http::header::CONTENT_TYPE, "application/json")
-                .body(req_body)
-                .unwrap(),
-        )
+        .oneshot(request.body(req_body).unwrap())
         .await
         .unwrap()
 }
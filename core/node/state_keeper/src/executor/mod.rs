@@ -2,8 +2,9 @@ use zksync_multivm::interface::{
     BatchTransactionExecutionResult, Call, CompressedBytecodeInfo, ExecutionResult, Halt,
     VmExecutionResultAndLogs,
 };
-use zksync_types::Transaction;
+use zksync_types::{Transaction, U256};
 pub use zksync_vm_executor::batch::MainBatchExecutorFactory;
+use zksync_vm_executor::batch::MAX_TXS_PER_BATCH_HALT_REASON;
 
 use crate::ExecutionMetricsForCriteria;
 
@@ -25,18 +26,54 @@ pub enum TxExecutionResult {
         gas_remaining: u32,
     },
     /// The VM rejected the tx for some reason.
-    RejectedByVm { reason: Halt },
+    RejectedByVm {
+        reason: Halt,
+        /// Gas prices at the time of the rejection; can be used to explain the rejection to the user.
+        gas_prices: TxGasPrices,
+    },
     /// Bootloader gas limit is not enough to execute the tx.
     BootloaderOutOfGasForTx,
+    /// The tx was not executed because the batch has reached the limit on the number of transactions
+    /// set via [`MainBatchExecutorFactory::set_max_txs_per_batch()`].
+    BatchTxLimitReached,
+}
+
+/// Gas prices relevant for a transaction rejected by the VM.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TxGasPrices {
+    /// Base fee per gas of the L1 batch in which the transaction was executed.
+    pub batch_base_fee: u64,
+    /// Max fee per gas specified by the transaction.
+    pub max_fee_per_gas: U256,
+}
+
+impl TxGasPrices {
+    /// Checks whether the transaction max fee per gas is below the batch base fee, which is a common cause of rejections.
+    pub fn is_max_fee_below_base_fee(&self) -> bool {
+        self.max_fee_per_gas < U256::from(self.batch_base_fee)
+    }
 }
 
 impl TxExecutionResult {
-    pub(crate) fn new(res: BatchTransactionExecutionResult, tx: &Transaction) -> Self {
+    pub(crate) fn new(
+        res: BatchTransactionExecutionResult,
+        tx: &Transaction,
+        batch_base_fee: u64,
+    ) -> Self {
         match res.tx_result.result {
             ExecutionResult::Halt {
                 reason: Halt::BootloaderOutOfGas,
             } => Self::BootloaderOutOfGasForTx,
-            ExecutionResult::Halt { reason } => Self::RejectedByVm { reason },
+            ExecutionResult::Halt {
+                reason: Halt::TracerCustom(reason),
+            } if reason == MAX_TXS_PER_BATCH_HALT_REASON => Self::BatchTxLimitReached,
+            ExecutionResult::Halt { reason } => Self::RejectedByVm {
+                reason,
+                gas_prices: TxGasPrices {
+                    batch_base_fee,
+                    max_fee_per_gas: tx.max_fee_per_gas(),
+                },
+            },
             _ => Self::Success {
                 tx_metrics: Box::new(ExecutionMetricsForCriteria::new(Some(tx), &res.tx_result)),
                 gas_remaining: res.tx_result.statistics.gas_remaining,
@@ -53,8 +90,10 @@ impl TxExecutionResult {
             Self::Success { .. } => None,
             Self::RejectedByVm {
                 reason: rejection_reason,
+                ..
             } => Some(rejection_reason),
             Self::BootloaderOutOfGasForTx => Some(&Halt::BootloaderOutOfGas),
+            Self::BatchTxLimitReached => None,
         }
     }
 }
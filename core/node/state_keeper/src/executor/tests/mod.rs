@@ -1,14 +1,21 @@
 // FIXME: move storage-agnostic tests to VM executor crate
 
+use std::sync::Arc;
+
 use assert_matches::assert_matches;
 use rand::{thread_rng, Rng};
 use test_casing::{test_casing, Product};
+use tokio::sync::mpsc;
 use zksync_dal::{ConnectionPool, Core};
-use zksync_multivm::interface::{BatchTransactionExecutionResult, ExecutionResult, Halt};
+use zksync_multivm::interface::{
+    BatchTransactionExecutionResult, ExecutionResult, Halt, IntermediateBatchOutputs,
+};
 use zksync_test_account::Account;
 use zksync_types::{
-    get_nonce_key, utils::storage_key_for_eth_balance, vm::FastVmMode, PriorityOpId,
+    get_nonce_key, utils::storage_key_for_eth_balance, vm::FastVmMode, Address, PriorityOpId,
+    Transaction,
 };
+use zksync_vm_executor::batch::{TxOutcome, TxPreCheck, MAX_TXS_PER_BATCH_HALT_REASON};
 
 use self::tester::{
     AccountFailedCall, AccountLoadNextExecutable, StorageSnapshot, TestConfig, Tester,
@@ -208,6 +215,50 @@ async fn rollback(vm_mode: FastVmMode) {
     executor.finish_batch().await.unwrap();
 }
 
+/// Checks that intermediate batch outputs account for executed transactions and are reverted on rollback.
+#[test_casing(3, FAST_VM_MODES)]
+#[tokio::test]
+async fn current_outputs(vm_mode: FastVmMode) {
+    let connection_pool = ConnectionPool::<Core>::constrained_test_pool(1).await;
+    let mut alice = Account::random();
+
+    let mut tester = Tester::new(connection_pool, vm_mode);
+
+    tester.genesis().await;
+    tester.fund(&[alice.address()]).await;
+    let mut executor = tester
+        .create_batch_executor(StorageType::AsyncRocksdbCache)
+        .await;
+
+    let outputs = executor.current_outputs().await.unwrap().unwrap();
+    assert_eq!(outputs, IntermediateBatchOutputs::default());
+
+    let res = executor.execute_tx(alice.execute()).await.unwrap();
+    assert_executed(&res);
+    let outputs_after_first_tx = executor.current_outputs().await.unwrap().unwrap();
+    assert_eq!(outputs_after_first_tx.executed_tx_count, 1);
+    assert_eq!(
+        outputs_after_first_tx.gas_used,
+        res.tx_result.statistics.gas_used
+    );
+    assert_eq!(
+        outputs_after_first_tx.pubdata_published,
+        u64::from(res.tx_result.statistics.pubdata_published)
+    );
+
+    let res = executor.execute_tx(alice.execute()).await.unwrap();
+    assert_executed(&res);
+    let outputs = executor.current_outputs().await.unwrap().unwrap();
+    assert_eq!(outputs.executed_tx_count, 2);
+    assert!(outputs.gas_used > outputs_after_first_tx.gas_used);
+
+    executor.rollback_last_tx().await.unwrap();
+    let outputs = executor.current_outputs().await.unwrap().unwrap();
+    assert_eq!(outputs, outputs_after_first_tx);
+
+    executor.finish_batch().await.unwrap();
+}
+
 /// Checks that incorrect transactions are marked as rejected.
 #[test_casing(3, FAST_VM_MODES)]
 #[tokio::test]
@@ -431,6 +482,9 @@ async fn bootloader_out_of_gas_for_any_tx(vm_mode: FastVmMode) {
             vm_gas_limit: Some(10),
             validation_computational_gas_limit: u32::MAX,
             fast_vm_mode: vm_mode,
+            tx_pre_check: None,
+            max_txs_per_batch: None,
+            tx_outcome_sender: None,
         },
     );
 
@@ -482,6 +536,9 @@ async fn bootloader_tip_out_of_gas() {
         ),
         validation_computational_gas_limit: u32::MAX,
         fast_vm_mode: FastVmMode::Old,
+        tx_pre_check: None,
+        max_txs_per_batch: None,
+        tx_outcome_sender: None,
     });
 
     let mut second_executor = tester
@@ -574,6 +631,147 @@ async fn execute_tx_with_large_packable_bytecode(vm_mode: FastVmMode) {
     executor.finish_batch().await.unwrap();
 }
 
+/// Pre-check rejecting all transactions initiated by the specified account.
+#[derive(Debug)]
+struct DenyInitiator(Address);
+
+impl TxPreCheck for DenyInitiator {
+    fn check(&self, tx: &Transaction) -> Result<(), String> {
+        if tx.initiator_account() == self.0 {
+            Err(format!("initiator {:?} is denied", self.0))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Checks that transactions rejected by a pre-check are not executed in the VM.
+#[test_casing(3, FAST_VM_MODES)]
+#[tokio::test]
+async fn tx_rejected_by_pre_check(vm_mode: FastVmMode) {
+    let connection_pool = ConnectionPool::<Core>::constrained_test_pool(1).await;
+    let mut alice = Account::random();
+    let mut bob = Account::random();
+    let mut tester = Tester::with_config(
+        connection_pool,
+        TestConfig {
+            tx_pre_check: Some(Arc::new(DenyInitiator(bob.address()))),
+            ..TestConfig::new(vm_mode)
+        },
+    );
+
+    tester.genesis().await;
+    tester.fund(&[alice.address(), bob.address()]).await;
+    let mut executor = tester
+        .create_batch_executor(StorageType::AsyncRocksdbCache)
+        .await;
+
+    let res = executor.execute_tx(bob.execute()).await.unwrap();
+    assert_rejected(&res);
+    assert_matches!(
+        &res.tx_result.result,
+        ExecutionResult::Halt { reason: Halt::TracerCustom(reason) } if reason.contains("denied")
+    );
+    executor.rollback_last_tx().await.unwrap();
+
+    let res = executor.execute_tx(alice.execute()).await.unwrap();
+    assert_executed(&res);
+    executor.finish_batch().await.unwrap();
+}
+
+/// Checks that transactions exceeding the per-batch limit are rejected, and that rolled back transactions don't count
+/// towards the limit.
+#[test_casing(3, FAST_VM_MODES)]
+#[tokio::test]
+async fn tx_rejected_by_max_txs_per_batch(vm_mode: FastVmMode) {
+    let connection_pool = ConnectionPool::<Core>::constrained_test_pool(1).await;
+    let mut alice = Account::random();
+    let mut tester = Tester::with_config(
+        connection_pool,
+        TestConfig {
+            max_txs_per_batch: Some(2),
+            ..TestConfig::new(vm_mode)
+        },
+    );
+
+    tester.genesis().await;
+    tester.fund(&[alice.address()]).await;
+    let mut executor = tester
+        .create_batch_executor(StorageType::AsyncRocksdbCache)
+        .await;
+
+    let res = executor.execute_tx(alice.execute()).await.unwrap();
+    assert_executed(&res);
+    let tx = alice.execute();
+    let res = executor.execute_tx(tx.clone()).await.unwrap();
+    assert_executed(&res);
+    executor.rollback_last_tx().await.unwrap();
+    let res = executor.execute_tx(tx).await.unwrap();
+    assert_executed(&res);
+
+    let res = executor.execute_tx(alice.execute()).await.unwrap();
+    assert_rejected(&res);
+    assert_matches!(
+        &res.tx_result.result,
+        ExecutionResult::Halt { reason: Halt::TracerCustom(reason) }
+            if reason == MAX_TXS_PER_BATCH_HALT_REASON
+    );
+    executor.rollback_last_tx().await.unwrap();
+    executor.finish_batch().await.unwrap();
+}
+
+/// Checks that transaction outcome events are emitted after each executed transaction, and are dropped
+/// if the channel is full.
+#[test_casing(3, FAST_VM_MODES)]
+#[tokio::test]
+async fn tx_outcome_events(vm_mode: FastVmMode) {
+    let connection_pool = ConnectionPool::<Core>::constrained_test_pool(1).await;
+    let mut alice = Account::random();
+    let (events_sender, mut events_receiver) = mpsc::channel(1);
+    let mut tester = Tester::with_config(
+        connection_pool,
+        TestConfig {
+            max_txs_per_batch: Some(1),
+            tx_outcome_sender: Some(events_sender),
+            ..TestConfig::new(vm_mode)
+        },
+    );
+
+    tester.genesis().await;
+    tester.fund(&[alice.address()]).await;
+    let mut executor = tester
+        .create_batch_executor(StorageType::AsyncRocksdbCache)
+        .await;
+
+    let tx = alice.execute();
+    let tx_hash = tx.hash();
+    let res = executor.execute_tx(tx).await.unwrap();
+    assert_executed(&res);
+    let gas_used = res.tx_result.statistics.gas_used;
+    // The event for this transaction is dropped since the channel is full.
+    let res = executor.execute_tx(alice.execute()).await.unwrap();
+    assert_rejected(&res);
+    executor.rollback_last_tx().await.unwrap();
+
+    let event = events_receiver.try_recv().unwrap();
+    assert_eq!(event.tx_hash, tx_hash);
+    assert_eq!(event.outcome, TxOutcome::Success);
+    assert_eq!(event.gas_used, gas_used);
+    assert!(!event.duration.is_zero());
+    events_receiver.try_recv().unwrap_err();
+
+    let tx = alice.execute();
+    let tx_hash = tx.hash();
+    let res = executor.execute_tx(tx).await.unwrap();
+    assert_rejected(&res);
+    let event = events_receiver.try_recv().unwrap();
+    assert_eq!(event.tx_hash, tx_hash);
+    assert_eq!(event.outcome, TxOutcome::Halted);
+    assert!(event.duration.is_zero());
+    executor.rollback_last_tx().await.unwrap();
+    executor.finish_batch().await.unwrap();
+}
+
 #[test_casing(2, [FastVmMode::Old, FastVmMode::Shadow])] // new VM doesn't support call tracing yet
 #[tokio::test]
 async fn execute_tx_with_call_traces(vm_mode: FastVmMode) {
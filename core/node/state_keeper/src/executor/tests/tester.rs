@@ -4,7 +4,10 @@
 use std::{collections::HashMap, fmt::Debug, sync::Arc};
 
 use tempfile::TempDir;
-use tokio::{sync::watch, task::JoinHandle};
+use tokio::{
+    sync::{mpsc, watch},
+    task::JoinHandle,
+};
 use zksync_config::configs::chain::StateKeeperConfig;
 use zksync_contracts::{
     get_loadnext_contract, load_contract, read_bytecode,
@@ -35,7 +38,7 @@ use zksync_types::{
     StorageLog, Transaction, H256, L2_BASE_TOKEN_ADDRESS, U256,
 };
 use zksync_utils::u256_to_h256;
-use zksync_vm_executor::batch::{MainBatchExecutorFactory, TraceCalls};
+use zksync_vm_executor::batch::{MainBatchExecutorFactory, TraceCalls, TxOutcomeEvent, TxPreCheck};
 
 use super::{read_storage_factory::RocksdbStorageFactory, StorageType};
 use crate::{
@@ -53,6 +56,9 @@ pub(super) struct TestConfig {
     pub(super) vm_gas_limit: Option<u32>,
     pub(super) validation_computational_gas_limit: u32,
     pub(super) fast_vm_mode: FastVmMode,
+    pub(super) tx_pre_check: Option<Arc<dyn TxPreCheck>>,
+    pub(super) max_txs_per_batch: Option<usize>,
+    pub(super) tx_outcome_sender: Option<mpsc::Sender<TxOutcomeEvent>>,
 }
 
 impl TestConfig {
@@ -64,6 +70,9 @@ impl TestConfig {
             vm_gas_limit: None,
             validation_computational_gas_limit: config.validation_computational_gas_limit,
             fast_vm_mode,
+            tx_pre_check: None,
+            max_txs_per_batch: None,
+            tx_outcome_sender: None,
         }
     }
 }
@@ -158,10 +167,28 @@ impl Tester {
         if self.config.trace_calls {
             let mut executor = MainBatchExecutorFactory::<TraceCalls>::new(false);
             executor.set_fast_vm_mode(self.config.fast_vm_mode);
+            if let Some(pre_check) = &self.config.tx_pre_check {
+                executor.set_tx_pre_check(pre_check.clone());
+            }
+            if let Some(limit) = self.config.max_txs_per_batch {
+                executor.set_max_txs_per_batch(limit);
+            }
+            if let Some(sender) = &self.config.tx_outcome_sender {
+                executor.set_tx_outcome_sender(sender.clone());
+            }
             executor.init_batch(storage, l1_batch_env, system_env)
         } else {
             let mut executor = MainBatchExecutorFactory::<()>::new(false);
             executor.set_fast_vm_mode(self.config.fast_vm_mode);
+            if let Some(pre_check) = &self.config.tx_pre_check {
+                executor.set_tx_pre_check(pre_check.clone());
+            }
+            if let Some(limit) = self.config.max_txs_per_batch {
+                executor.set_max_txs_per_batch(limit);
+            }
+            if let Some(sender) = &self.config.tx_outcome_sender {
+                executor.set_tx_outcome_sender(sender.clone());
+            }
             executor.init_batch(storage, l1_batch_env, system_env)
         }
     }
@@ -462,7 +462,8 @@ impl ZkSyncStateKeeper {
                     .execute_tx(tx.clone())
                     .await
                     .with_context(|| format!("failed re-executing transaction {:?}", tx.hash()))?;
-                let result = TxExecutionResult::new(result, &tx);
+                let result =
+                    TxExecutionResult::new(result, &tx, updates_manager.base_fee_per_gas());
 
                 APP_METRICS.processed_txs[&TxStage::StateKeeper].inc();
                 APP_METRICS.processed_l1_txs[&TxStage::StateKeeper].inc_by(tx.is_l1().into());
@@ -743,7 +744,8 @@ impl ZkSyncStateKeeper {
             .execute_tx(tx.clone())
             .await
             .with_context(|| format!("failed executing transaction {:?}", tx.hash()))?;
-        let exec_result = TxExecutionResult::new(exec_result, &tx);
+        let exec_result =
+            TxExecutionResult::new(exec_result, &tx, updates_manager.base_fee_per_gas());
         latency.observe();
 
         APP_METRICS.processed_txs[&TxStage::StateKeeper].inc();
@@ -764,6 +766,7 @@ impl ZkSyncStateKeeper {
             TxExecutionResult::BootloaderOutOfGasForTx
             | TxExecutionResult::RejectedByVm {
                 reason: Halt::NotEnoughGasProvided,
+                ..
             } => {
                 let (reason, criterion) = match &exec_result {
                     TxExecutionResult::BootloaderOutOfGasForTx => (
@@ -772,6 +775,7 @@ impl ZkSyncStateKeeper {
                     ),
                     TxExecutionResult::RejectedByVm {
                         reason: Halt::NotEnoughGasProvided,
+                        ..
                     } => (
                         UnexecutableReason::NotEnoughGasProvided,
                         "not_enough_gas_provided_to_start_tx",
@@ -786,8 +790,26 @@ impl ZkSyncStateKeeper {
                 AGGREGATION_METRICS.l1_batch_reason_inc(criterion, &resolution);
                 resolution
             }
-            TxExecutionResult::RejectedByVm { reason } => {
-                UnexecutableReason::Halt(reason.clone()).into()
+            TxExecutionResult::BatchTxLimitReached => {
+                // The transaction wasn't executed at all, so it's valid to retry it in the next batch. The limit
+                // is positive, so this cannot happen for the first transaction in the batch.
+                let resolution = SealResolution::ExcludeAndSeal;
+                AGGREGATION_METRICS.l1_batch_reason_inc("max_txs_per_batch", &resolution);
+                resolution
+            }
+            TxExecutionResult::RejectedByVm { reason, gas_prices } => {
+                // Gas prices are included into the reason (and thus into the rejection error persisted for the transaction)
+                // only if they are the likely cause of the rejection.
+                let reason = if gas_prices.is_max_fee_below_base_fee() {
+                    UnexecutableReason::MaxFeeBelowBaseFee {
+                        halt: reason.clone(),
+                        batch_base_fee: gas_prices.batch_base_fee,
+                        max_fee_per_gas: gas_prices.max_fee_per_gas,
+                    }
+                } else {
+                    UnexecutableReason::Halt(reason.clone())
+                };
+                reason.into()
             }
             TxExecutionResult::Success {
                 tx_result,
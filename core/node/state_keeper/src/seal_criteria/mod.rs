@@ -18,7 +18,7 @@ use zksync_multivm::{
     vm_latest::TransactionVmExt,
 };
 use zksync_types::{
-    block::BlockGasCount, utils::display_timestamp, ProtocolVersionId, Transaction,
+    block::BlockGasCount, utils::display_timestamp, ProtocolVersionId, Transaction, U256,
 };
 use zksync_utils::time::millis_since;
 
@@ -68,6 +68,13 @@ pub enum UnexecutableReason {
     OutOfGasForBatchTip,
     BootloaderOutOfGas,
     NotEnoughGasProvided,
+    /// VM halt for a transaction with the max fee per gas below the batch base fee, which is the likely cause
+    /// of the halt. Contains gas prices so that the rejection can be explained to the user.
+    MaxFeeBelowBaseFee {
+        halt: Halt,
+        batch_base_fee: u64,
+        max_fee_per_gas: U256,
+    },
 }
 
 impl UnexecutableReason {
@@ -82,6 +89,7 @@ impl UnexecutableReason {
             UnexecutableReason::OutOfGasForBatchTip => "OutOfGasForBatchTip",
             UnexecutableReason::BootloaderOutOfGas => "BootloaderOutOfGas",
             UnexecutableReason::NotEnoughGasProvided => "NotEnoughGasProvided",
+            UnexecutableReason::MaxFeeBelowBaseFee { .. } => "MaxFeeBelowBaseFee",
         }
     }
 }
@@ -106,6 +114,14 @@ impl fmt::Display for UnexecutableReason {
             UnexecutableReason::OutOfGasForBatchTip => write!(f, "Out of gas for batch tip"),
             UnexecutableReason::BootloaderOutOfGas => write!(f, "Bootloader out of gas"),
             UnexecutableReason::NotEnoughGasProvided => write!(f, "Not enough gas provided"),
+            UnexecutableReason::MaxFeeBelowBaseFee {
+                halt,
+                batch_base_fee,
+                max_fee_per_gas,
+            } => write!(
+                f,
+                "{halt}; max fee per gas {max_fee_per_gas} is below batch base fee {batch_base_fee}"
+            ),
         }
     }
 }
@@ -25,6 +25,7 @@ use zksync_types::{
     ZKPORTER_IS_AVAILABLE,
 };
 use zksync_utils::u256_to_h256;
+use zksync_vm_executor::batch::MAX_TXS_PER_BATCH_HALT_REASON;
 
 use crate::{
     io::PendingBatchData,
@@ -363,6 +364,37 @@ async fn bootloader_tip_out_of_gas_flow() {
         .await;
 }
 
+#[tokio::test]
+async fn batch_tx_limit_flow() {
+    let config = StateKeeperConfig {
+        transaction_slots: 2,
+        ..StateKeeperConfig::default()
+    };
+    let sealer = SequencerSealer::with_sealers(config, vec![Box::new(SlotsCriterion)]);
+
+    let first_tx = random_tx(1);
+    let limited_tx = random_tx(2);
+    let third_tx = random_tx(3);
+    TestScenario::new()
+        .seal_l2_block_when(|updates| updates.l2_block.executed_transactions.len() == 1)
+        .next_tx("First tx", first_tx, successful_exec())
+        .l2_block_sealed("L2 block with 1st tx")
+        .next_tx(
+            "Tx -> Batch tx limit reached",
+            limited_tx.clone(),
+            rejected_exec(Halt::TracerCustom(MAX_TXS_PER_BATCH_HALT_REASON.to_owned())),
+        )
+        .tx_rollback("Last tx rolled back to seal the block", limited_tx.clone())
+        .batch_sealed("Batch sealed with 1 tx")
+        .next_tx("Same tx now succeeds", limited_tx, successful_exec())
+        .l2_block_sealed("L2 block with this tx sealed")
+        .next_tx("Second tx of the 2nd batch", third_tx, successful_exec())
+        .l2_block_sealed("L2 block with 2nd tx")
+        .batch_sealed("2nd batch sealed")
+        .run(sealer)
+        .await;
+}
+
 #[tokio::test]
 async fn pending_batch_is_applied() {
     let config = StateKeeperConfig {
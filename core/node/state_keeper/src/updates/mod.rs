@@ -68,6 +68,10 @@ impl UpdatesManager {
         self.batch_timestamp
     }
 
+    pub(crate) fn base_fee_per_gas(&self) -> u64 {
+        self.base_fee_per_gas
+    }
+
     pub fn base_system_contract_hashes(&self) -> BaseSystemContractsHashes {
         self.base_system_contract_hashes
     }
@@ -0,0 +1,160 @@
+//! Processing of L1 batch ranges (e.g., for backfills).
+
+use std::{
+    ops::RangeInclusive,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context as _;
+use serde::{Serialize, Serializer};
+use zksync_dal::{tee_verifier_input_producer_dal::TeeVerifierInputProducerJobClaim, CoreDal};
+use zksync_types::L1BatchNumber;
+
+use crate::{SaveOutcome, TeeVerifierInputProducer};
+
+fn serialize_secs<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64())
+}
+
+/// Status of an L1 batch processed as a part of [a range](TeeVerifierInputProducer::process_l1_batch_range()).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum BatchStatus {
+    /// Batch was successfully processed, and its artifacts were saved.
+    Success {
+        #[serde(rename = "duration_secs", serialize_with = "serialize_secs")]
+        duration: Duration,
+    },
+    /// Batch processing failed.
+    Failed { error: String },
+    /// Batch was not processed.
+    Skipped { reason: String },
+}
+
+/// Outcome of processing an L1 batch as a part of [a range](TeeVerifierInputProducer::process_l1_batch_range()).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BatchOutcome {
+    pub l1_batch_number: L1BatchNumber,
+    #[serde(flatten)]
+    pub status: BatchStatus,
+}
+
+impl TeeVerifierInputProducer {
+    /// Sequentially processes all L1 batches in the specified range. Each batch is processed only if its job
+    /// can be claimed; batches without a job, with a job in progress by another worker, or already processed
+    /// successfully are skipped, as well as all remaining batches once a stop signal is received. Failure to process
+    /// a batch marks its job as failed, but doesn't stop processing the range.
+    ///
+    /// If `report_path` is specified, the returned outcomes are additionally written to this path as a JSON array.
+    pub async fn process_l1_batch_range(
+        &self,
+        range: RangeInclusive<L1BatchNumber>,
+        report_path: Option<&Path>,
+    ) -> anyhow::Result<Vec<BatchOutcome>> {
+        let mut outcomes = vec![];
+        for number in range.start().0..=range.end().0 {
+            let l1_batch_number = L1BatchNumber(number);
+            let status = self.process_l1_batch_in_range(l1_batch_number).await?;
+            match &status {
+                BatchStatus::Success { duration } => {
+                    tracing::info!("Processed L1 batch #{l1_batch_number} in {duration:?}");
+                }
+                BatchStatus::Failed { error } => {
+                    tracing::warn!("Failed processing L1 batch #{l1_batch_number}: {error}");
+                }
+                BatchStatus::Skipped { reason } => {
+                    tracing::info!("Skipped L1 batch #{l1_batch_number}: {reason}");
+                }
+            }
+            outcomes.push(BatchOutcome {
+                l1_batch_number,
+                status,
+            });
+        }
+
+        if let Some(path) = report_path {
+            let report = serde_json::to_vec_pretty(&outcomes)
+                .context("failed serializing L1 batch range report")?;
+            tokio::fs::write(path, report).await.with_context(|| {
+                format!(
+                    "failed writing L1 batch range report to `{}`",
+                    path.display()
+                )
+            })?;
+        }
+        Ok(outcomes)
+    }
+
+    async fn process_l1_batch_in_range(
+        &self,
+        l1_batch_number: L1BatchNumber,
+    ) -> anyhow::Result<BatchStatus> {
+        if *self.stop_receiver.borrow() {
+            return Ok(BatchStatus::Skipped {
+                reason: "stop signal received".to_owned(),
+            });
+        }
+
+        let claim = self
+            .connection_pool
+            .connection()
+            .await
+            .context("failed to acquire DB connection for TeeVerifierInputProducer")?
+            .tee_verifier_input_producer_dal()
+            .claim_job(l1_batch_number)
+            .await
+            .context("failed to claim job for TeeVerifierInputProducer")?;
+        let reason = match claim {
+            TeeVerifierInputProducerJobClaim::Claimed => None,
+            TeeVerifierInputProducerJobClaim::Missing => Some("no job for the batch"),
+            TeeVerifierInputProducerJobClaim::InProgress => {
+                Some("being processed by another worker")
+            }
+            TeeVerifierInputProducerJobClaim::Successful => Some("already processed"),
+        };
+        if let Some(reason) = reason {
+            return Ok(BatchStatus::Skipped {
+                reason: reason.to_owned(),
+            });
+        }
+
+        let started_at = Instant::now();
+        let result = async {
+            let artifacts = self
+                .clone()
+                .process_job_impl(l1_batch_number, started_at)
+                .await?;
+            self.save_artifacts(l1_batch_number, started_at, artifacts)
+                .await
+        }
+        .await;
+        Ok(match result {
+            Ok(SaveOutcome::Saved) => BatchStatus::Success {
+                duration: started_at.elapsed(),
+            },
+            Ok(SaveOutcome::AlreadySaved) => BatchStatus::Skipped {
+                reason: "concurrently processed by another worker".to_owned(),
+            },
+            // The batch will be processed by the producer once its job is picked up again, but as a part
+            // of the range, it wasn't processed.
+            Ok(SaveOutcome::ReturnedToQueue(err)) => BatchStatus::Failed {
+                error: format!("transient error uploading artifacts: {err:#}"),
+            },
+            Err(err) => {
+                let error = format!("{err:#}");
+                // The claimed job must not stay in progress. If it's not in progress already (e.g., it was
+                // returned to the queue on a stop signal), this is a no-op.
+                self.connection_pool
+                    .connection()
+                    .await
+                    .context("failed to acquire DB connection for TeeVerifierInputProducer")?
+                    .tee_verifier_input_producer_dal()
+                    .mark_job_as_failed(l1_batch_number, started_at, error.clone())
+                    .await
+                    .context("failed to mark job as failed for TeeVerifierInputProducer")?;
+                BatchStatus::Failed { error }
+            }
+        })
+    }
+}
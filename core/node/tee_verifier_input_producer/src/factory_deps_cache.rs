@@ -0,0 +1,42 @@
+//! Factory deps cache shared across producer jobs.
+
+use zksync_types::H256;
+
+use crate::metrics::{StorageCacheOutcome, METRICS};
+
+/// LRU cache of factory deps (contract bytecodes) keyed by the bytecode hash. The cache is cheaply cloneable;
+/// all clones share the same underlying storage, so a single cache can be reused across jobs processed by the same worker.
+#[derive(Debug, Clone)]
+pub struct FactoryDepsCache {
+    inner: mini_moka::sync::Cache<H256, Vec<u8>>,
+}
+
+impl FactoryDepsCache {
+    /// Creates a cache with the specified capacity in bytes of cached bytecodes.
+    pub fn new(capacity: u64) -> Self {
+        tracing::info!("Configured factory deps cache with capacity {capacity}B");
+        let inner = mini_moka::sync::Cache::builder()
+            .weigher(|_, bytecode: &Vec<u8>| bytecode.len().try_into().unwrap_or(u32::MAX))
+            .max_capacity(capacity)
+            .build();
+        Self { inner }
+    }
+
+    pub(crate) fn get(&self, hash: &H256) -> Option<Vec<u8>> {
+        let entry = self.inner.get(hash);
+        let outcome = if entry.is_some() {
+            StorageCacheOutcome::Hit
+        } else {
+            StorageCacheOutcome::Miss
+        };
+        METRICS.factory_deps_cache_requests[&outcome].inc();
+        entry
+    }
+
+    pub(crate) fn insert(&self, hash: H256, bytecode: Vec<u8>) {
+        self.inner.insert(hash, bytecode);
+        METRICS
+            .factory_deps_cache_size
+            .set(self.inner.weighted_size());
+    }
+}
@@ -0,0 +1,49 @@
+//! Snapshots of L1 batches failing verification, which allow reproducing failures offline.
+
+use anyhow::Context as _;
+use zksync_object_store::{ObjectStore, StoredObject};
+use zksync_prover_interface::inputs::TeeVerifierInput;
+use zksync_tee_verifier::{VerificationResult, Verify};
+use zksync_types::L1BatchNumber;
+
+/// Returns the key of the snapshot for the specified L1 batch in the [`TeeVerifierInput`] bucket. Snapshots are stored
+/// separately from regular artifacts so that they are never consumed by TEE provers.
+pub fn failed_snapshot_key(key_prefix: &str, l1_batch_number: L1BatchNumber) -> String {
+    format!(
+        "{key_prefix}failed/{}",
+        TeeVerifierInput::encode_key(l1_batch_number)
+    )
+}
+
+/// Loads the snapshot [captured](crate::TeeVerifierInputProducer::set_capture_failed_snapshots()) for the specified
+/// L1 batch and re-runs verification on it.
+///
+/// A snapshot is a self-contained [`TeeVerifierInput`], so this doesn't require Postgres access; e.g., `object_store`
+/// may be a file-backed store with snapshots downloaded from the production bucket.
+pub async fn replay_failed_snapshot(
+    object_store: &dyn ObjectStore,
+    key_prefix: &str,
+    l1_batch_number: L1BatchNumber,
+) -> anyhow::Result<VerificationResult> {
+    let key = failed_snapshot_key(key_prefix, l1_batch_number);
+    let bytes = object_store
+        .get_raw(TeeVerifierInput::BUCKET, &key)
+        .await
+        .with_context(|| format!("failed to get snapshot `{key}` from object store"))?;
+    let snapshot = TeeVerifierInput::deserialize(bytes)
+        .map_err(|err| anyhow::anyhow!(err))
+        .with_context(|| format!("failed to deserialize snapshot `{key}`"))?;
+    let TeeVerifierInput::V1(input) = snapshot else {
+        anyhow::bail!("snapshot `{key}` has unsupported version");
+    };
+    anyhow::ensure!(
+        input.l1_batch_env.number == l1_batch_number,
+        "snapshot `{key}` is for unexpected L1 batch #{}",
+        input.l1_batch_env.number
+    );
+
+    tracing::info!("Replaying snapshot `{key}` for L1 batch #{l1_batch_number}");
+    tokio::task::spawn_blocking(move || input.verify())
+        .await
+        .context("replay panicked")?
+}
@@ -7,32 +7,239 @@
 //! Eventually, this component will only extract the inputs and send them to another
 //! machine over a "to be defined" channel, e.g., save them to an object store.
 
-use std::{sync::Arc, time::Instant};
+use std::{
+    collections::HashSet,
+    fmt,
+    io::{self, Read},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
 
 use anyhow::Context;
 use async_trait::async_trait;
-use tokio::task::JoinHandle;
-use zksync_dal::{tee_verifier_input_producer_dal::JOB_MAX_ATTEMPT, ConnectionPool, Core, CoreDal};
-use zksync_object_store::ObjectStore;
+use flate2::read::GzDecoder;
+use futures::future;
+use tokio::{sync::watch, task::JoinHandle};
+pub use zksync_dal::tee_verifier_input_producer_dal::TeeVerifierInputProducerJobOrder;
+use zksync_dal::{
+    tee_verifier_input_producer_dal::JOB_MAX_ATTEMPT, Connection, ConnectionPool, Core, CoreDal,
+};
+use zksync_multivm::interface::{L1BatchEnv, SystemEnv};
+use zksync_object_store::{ObjectStore, ObjectStoreError, StoredObject};
 use zksync_prover_interface::inputs::{
     TeeVerifierInput, V1TeeVerifierInput, WitnessInputMerklePaths,
 };
 use zksync_queued_job_processor::JobProcessor;
-use zksync_tee_verifier::Verify;
-use zksync_types::{tee_types::TeeType, L1BatchNumber, L2ChainId};
-use zksync_utils::u256_to_h256;
+use zksync_tee_verifier::{
+    replay_up_to_l2_block, PartialReplayResult, VerificationCancellation, VerificationResult,
+    Verify,
+};
+use zksync_types::{
+    tee_types::TeeType, web3::keccak256, L1BatchNumber, L2BlockNumber, L2ChainId, H256,
+};
+use zksync_utils::{time::seconds_since_epoch, u256_to_h256};
 use zksync_vm_executor::storage::L1BatchParamsProvider;
 
-use self::metrics::METRICS;
+use self::metrics::{Artifact, FactoryDepsLoadMode, InputLoadMode, StorageCacheOutcome, METRICS};
+pub use self::{
+    backfill::{BatchOutcome, BatchStatus},
+    factory_deps_cache::FactoryDepsCache,
+    failed_snapshot::{failed_snapshot_key, replay_failed_snapshot},
+};
 
+mod backfill;
+mod factory_deps_cache;
+mod failed_snapshot;
 mod metrics;
+#[cfg(test)]
+mod tests;
 
-/// Component that extracts all data (from DB) necessary to run a TEE Verifier.
+/// Parts of the verifier input for an L1 batch derived from its header.
+#[derive(Debug)]
+struct BatchParams {
+    system_env: SystemEnv,
+    l1_batch_env: L1BatchEnv,
+    used_contract_hashes: HashSet<H256>,
+    used_contracts: Vec<(H256, Vec<u8>)>,
+    /// Batch timestamp in seconds since UNIX epoch.
+    timestamp: u64,
+}
+
+/// Outcome of saving artifacts produced for an L1 batch.
+#[derive(Debug)]
+enum SaveOutcome {
+    /// Artifacts were uploaded, and the job was marked as successful.
+    Saved,
+    /// Artifacts were already saved by another worker.
+    AlreadySaved,
+    /// Artifacts weren't uploaded because of a transient object store error. The job was returned to the queue.
+    ReturnedToQueue(anyhow::Error),
+}
+
+/// Writer counting the number of written bytes.
+struct ByteCountingWriter<'a> {
+    inner: &'a mut dyn io::Write,
+    count: usize,
+}
+
+impl<'a> ByteCountingWriter<'a> {
+    fn new(inner: &'a mut dyn io::Write) -> Self {
+        Self { inner, count: 0 }
+    }
+}
+
+impl io::Write for ByteCountingWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.count += written;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Action taken if contracts loaded when re-executing an L1 batch differ from `used_contract_hashes`
+/// in the batch header.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UsedContractsMismatchMode {
+    /// Log a warning and continue processing the batch.
+    #[default]
+    Warn,
+    /// Fail processing the batch.
+    Error,
+}
+
+/// Sliding window of the latest L1 batches kept verified by [`TeeVerifierInputProducer`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VerificationWindow {
+    /// Number of the latest L1 batches in the window.
+    pub size: u32,
+    /// If set, batches in the window are re-verified once this interval has passed since their last verification.
+    /// Re-verification only happens if there are no unverified batches in the window.
+    pub reverify_after: Option<Duration>,
+}
+
+/// Source of L1 batch root hashes committed on L1. Used by [`TeeVerifierInputProducer`] to cross-check
+/// the root hash reconstructed by re-executing a batch, which catches cases when the state root stored in the DB is wrong.
+#[async_trait]
+pub trait CommittedRootHashSource: fmt::Debug + Send + Sync {
+    /// Returns the root hash for the specified L1 batch committed on L1, or `None` if the batch is not committed yet.
+    async fn committed_root_hash(
+        &self,
+        l1_batch_number: L1BatchNumber,
+    ) -> anyhow::Result<Option<H256>>;
+}
+
+/// Source of VM environment params for L1 batches re-executed by [`TeeVerifierInputProducer`]. By default, params
+/// are loaded from Postgres using [`L1BatchParamsProvider`]; a custom source can be used e.g. to supply canned params in tests.
+#[async_trait]
+pub trait L1BatchParamsSource: fmt::Debug + Send + Sync {
+    /// Loads system and L1 batch environments for the specified L1 batch. Returns `None` if the batch is not sealed.
+    async fn load_l1_batch_env(
+        &self,
+        connection: &mut Connection<'_, Core>,
+        l1_batch_number: L1BatchNumber,
+        validation_computational_gas_limit: u32,
+        chain_id: L2ChainId,
+    ) -> anyhow::Result<Option<(SystemEnv, L1BatchEnv)>>;
+}
+
+/// Default [`L1BatchParamsSource`] loading params from Postgres.
+#[derive(Debug)]
+struct PostgresL1BatchParamsSource;
+
+#[async_trait]
+impl L1BatchParamsSource for PostgresL1BatchParamsSource {
+    async fn load_l1_batch_env(
+        &self,
+        connection: &mut Connection<'_, Core>,
+        l1_batch_number: L1BatchNumber,
+        validation_computational_gas_limit: u32,
+        chain_id: L2ChainId,
+    ) -> anyhow::Result<Option<(SystemEnv, L1BatchEnv)>> {
+        let provider = L1BatchParamsProvider::new(connection)
+            .await
+            .context("failed initializing L1 batch params provider")?;
+        provider
+            .load_l1_batch_env(
+                connection,
+                l1_batch_number,
+                validation_computational_gas_limit,
+                chain_id,
+            )
+            .await
+    }
+}
+
+/// Verification logic used by [`TeeVerifierInputProducer`] to re-execute batches. By default, batches are verified
+/// using [`Verify`]; a custom verifier can be used e.g. to experiment with alternative reconstruction algorithms,
+/// or to test job orchestration without re-executing batches.
+pub trait TeeVerifier: fmt::Debug + Send + Sync {
+    /// Verifies the input. Called on a blocking thread; implementations should periodically check `cancellation`
+    /// and return an error if it's cancelled, so that the
+    /// [verification timeout](TeeVerifierInputProducer::set_verification_timeout()) is enforced.
+    fn verify(
+        &self,
+        input: V1TeeVerifierInput,
+        cancellation: &VerificationCancellation,
+    ) -> anyhow::Result<VerificationResult>;
+}
+
+/// Default [`TeeVerifier`] re-executing batches using [`Verify`].
 #[derive(Debug)]
+struct DefaultTeeVerifier;
+
+impl TeeVerifier for DefaultTeeVerifier {
+    fn verify(
+        &self,
+        input: V1TeeVerifierInput,
+        cancellation: &VerificationCancellation,
+    ) -> anyhow::Result<VerificationResult> {
+        input.verify_with_cancellation(cancellation)
+    }
+}
+
+/// Hook invoked by [`TeeVerifierInputProducer`] after a TEE verifier input is uploaded to the object store.
+/// Can be used to trigger downstream actions, e.g. cache invalidation or notifications.
+#[async_trait]
+pub trait ArtifactHook: fmt::Debug + Send + Sync {
+    /// Called after the input for the specified L1 batch is uploaded to `object_path` in the
+    /// [`TeeVerifierInput`] bucket. Errors are logged and don't fail the job.
+    async fn on_uploaded(
+        &self,
+        l1_batch_number: L1BatchNumber,
+        object_path: &str,
+    ) -> anyhow::Result<()>;
+}
+
+/// Component that extracts all data (from DB) necessary to run a TEE Verifier.
+#[derive(Debug, Clone)]
 pub struct TeeVerifierInputProducer {
     connection_pool: ConnectionPool<Core>,
     l2_chain_id: L2ChainId,
     object_store: Arc<dyn ObjectStore>,
+    object_key_prefix: String,
+    stop_receiver: watch::Receiver<bool>,
+    used_contracts_mismatch_mode: UsedContractsMismatchMode,
+    verification_window: Option<VerificationWindow>,
+    job_order: TeeVerifierInputProducerJobOrder,
+    factory_deps_load_concurrency: usize,
+    factory_deps_cache: Option<FactoryDepsCache>,
+    validation_computational_gas_limit: u32,
+    l1_batch_params_source: Arc<dyn L1BatchParamsSource>,
+    tee_verifier: Arc<dyn TeeVerifier>,
+    verification_timeout: Option<Duration>,
+    generate_only: bool,
+    skip_if_unchanged: bool,
+    parallel_input_loading: bool,
+    capture_failed_snapshots: bool,
+    committed_root_hash_source: Option<Arc<dyn CommittedRootHashSource>>,
+    artifact_hooks: Vec<Arc<dyn ArtifactHook>>,
 }
 
 impl TeeVerifierInputProducer {
@@ -44,32 +251,500 @@ impl TeeVerifierInputProducer {
         Ok(TeeVerifierInputProducer {
             connection_pool,
             object_store,
+            object_key_prefix: String::new(),
             l2_chain_id,
+            stop_receiver: watch::channel(false).1,
+            used_contracts_mismatch_mode: UsedContractsMismatchMode::default(),
+            verification_window: None,
+            job_order: TeeVerifierInputProducerJobOrder::default(),
+            factory_deps_load_concurrency: 1,
+            factory_deps_cache: None,
+            // In the state keeper, this value is used to reject execution.
+            // All batches have already been executed by State Keeper.
+            // This means we don't want to reject any execution, therefore we're using MAX as an allow all.
+            validation_computational_gas_limit: u32::MAX,
+            l1_batch_params_source: Arc::new(PostgresL1BatchParamsSource),
+            tee_verifier: Arc::new(DefaultTeeVerifier),
+            verification_timeout: None,
+            generate_only: false,
+            skip_if_unchanged: false,
+            parallel_input_loading: false,
+            capture_failed_snapshots: false,
+            committed_root_hash_source: None,
+            artifact_hooks: vec![],
         })
     }
 
-    async fn process_job_impl(
+    /// Sets the prefix prepended to keys of all objects fetched from and uploaded to the object store,
+    /// e.g. `mainnet/tee-inputs/`. This allows multiple environments to share a bucket. By default, there's no prefix.
+    ///
+    /// Components consuming the produced artifacts must be configured with the same prefix.
+    pub fn set_object_key_prefix(&mut self, prefix: impl Into<String>) {
+        self.object_key_prefix = prefix.into();
+    }
+
+    /// Makes the producer skip uploading artifacts if an object with the same content hash is already stored
+    /// under the artifact key (e.g., uploaded by a prior run that failed before marking the job as successful).
+    /// This saves bandwidth on retries at the cost of buffering artifacts in memory and downloading the full existing
+    /// object before each upload. The object store doesn't expose object metadata (e.g., size or checksum), so there's
+    /// no cheaper way to compare the content. Disabled by default.
+    pub fn set_skip_if_unchanged(&mut self, skip_if_unchanged: bool) {
+        self.skip_if_unchanged = skip_if_unchanged;
+    }
+
+    /// Makes the producer upload a self-contained snapshot of each batch failing verification to the object store
+    /// (under the [`failed_snapshot_key()`]). A snapshot can be [replayed](replay_failed_snapshot()) offline
+    /// to reproduce the failure without Postgres access. Disabled by default.
+    pub fn set_capture_failed_snapshots(&mut self, capture_failed_snapshots: bool) {
+        self.capture_failed_snapshots = capture_failed_snapshots;
+    }
+
+    /// Sets the action taken if the contracts loaded when re-executing a batch differ from the ones listed
+    /// in the batch header. By default, a warning is logged.
+    pub fn set_used_contracts_mismatch_mode(&mut self, mode: UsedContractsMismatchMode) {
+        self.used_contracts_mismatch_mode = mode;
+    }
+
+    /// Makes the producer only process jobs for the latest L1 batches (most recent first) instead of processing
+    /// all jobs in the ascending batch order. Older jobs are not processed while the window is set.
+    pub fn set_verification_window(&mut self, window: VerificationWindow) {
+        self.verification_window = Some(window);
+    }
+
+    /// Sets the order in which jobs are processed. By default, jobs are processed oldest first.
+    /// Ignored if the [verification window](Self::set_verification_window()) is set.
+    pub fn set_job_order(&mut self, order: TeeVerifierInputProducerJobOrder) {
+        self.job_order = order;
+    }
+
+    /// Sets the factory deps cache reused across jobs processed by this producer (and, if the cache is shared,
+    /// by other producers). By default, there's no cache, and all factory deps for a batch are loaded from Postgres.
+    pub fn set_factory_deps_cache(&mut self, cache: FactoryDepsCache) {
+        self.factory_deps_cache = Some(cache);
+    }
+
+    /// Sets the maximum number of concurrent DB queries used to load factory deps for a batch. Each query uses
+    /// a separate connection from the pool, so the value should be well below the pool size. By default (and if set to 1),
+    /// all factory deps are loaded with a single query, which is preferable for backends with high per-query overhead.
+    pub fn set_factory_deps_load_concurrency(&mut self, concurrency: usize) {
+        self.factory_deps_load_concurrency = concurrency.max(1);
+    }
+
+    /// Makes the producer load independent parts of the verifier input (Merkle paths from the object store, L2 blocks
+    /// and batch params from Postgres) concurrently. This reduces input load latency for large batches at the cost
+    /// of using an additional pool connection per job. Disabled by default.
+    ///
+    /// Batch re-execution is inherently sequential and is not affected by this setting.
+    pub fn set_parallel_input_loading(&mut self, parallel_input_loading: bool) {
+        self.parallel_input_loading = parallel_input_loading;
+    }
+
+    /// Sets the computational gas limit for transaction validation used when re-executing batches. By default,
+    /// the limit is set to `u32::MAX`, i.e., validation of all transactions is allowed. Setting a lower limit allows
+    /// reproducing rejections by the state keeper.
+    pub fn set_validation_computational_gas_limit(&mut self, limit: u32) {
+        self.validation_computational_gas_limit = limit;
+    }
+
+    /// Makes the producer only generate and upload inputs without re-executing and verifying batches, so that
+    /// verification can be performed later by a separate component. Uploaded inputs are the same as with verification.
+    /// Settings related to verification (e.g., the [verification timeout](Self::set_verification_timeout())
+    /// or the [committed root hash source](Self::set_committed_root_hash_source())) are ignored in this mode.
+    pub fn set_generate_only(&mut self, generate_only: bool) {
+        self.generate_only = generate_only;
+    }
+
+    /// Sets the timeout for re-executing and verifying a batch. If verification doesn't complete in time, it's cancelled,
+    /// and the job fails (i.e., it will be retried if it has attempts remaining). By default, there's no timeout.
+    pub fn set_verification_timeout(&mut self, timeout: Duration) {
+        self.verification_timeout = Some(timeout);
+    }
+
+    /// Sets the source of VM environment params for re-executed batches. By default, params are loaded from Postgres.
+    pub fn set_l1_batch_params_source(&mut self, source: Arc<dyn L1BatchParamsSource>) {
+        self.l1_batch_params_source = source;
+    }
+
+    /// Sets the verifier used to re-execute batches. By default, batches are verified using [`Verify`].
+    /// Ignored in the [generate-only mode](Self::set_generate_only()).
+    pub fn set_tee_verifier(&mut self, verifier: Arc<dyn TeeVerifier>) {
+        self.tee_verifier = verifier;
+    }
+
+    /// Sets the source of root hashes committed on L1. If set, the root hash reconstructed for each batch is compared
+    /// to the committed one, and the job fails on a mismatch. Batches not committed on L1 yet are not checked.
+    pub fn set_committed_root_hash_source(&mut self, source: Arc<dyn CommittedRootHashSource>) {
+        self.committed_root_hash_source = Some(source);
+    }
+
+    /// Adds a hook invoked after each TEE verifier input is uploaded. Hooks are invoked sequentially
+    /// in the order they were added.
+    pub fn add_artifact_hook(&mut self, hook: Arc<dyn ArtifactHook>) {
+        self.artifact_hooks.push(hook);
+    }
+
+    /// Sets the stop signal receiver used to cooperatively cancel jobs being processed. If the stop signal is received,
+    /// the job being processed is abandoned and returned to the queue, so that it can be picked up by another worker.
+    pub fn set_stop_receiver(&mut self, stop_receiver: watch::Receiver<bool>) {
+        self.stop_receiver = stop_receiver;
+    }
+
+    /// Returns the number of processing attempts remaining for the specified job before it's considered permanently failed.
+    pub async fn attempts_remaining(&self, job_id: L1BatchNumber) -> anyhow::Result<u32> {
+        let attempts = self.get_job_attempts(&job_id).await?;
+        Ok(self.max_attempts().saturating_sub(attempts))
+    }
+
+    /// Checks whether the stop signal was received. If it was, returns the job to the queue and returns an error.
+    async fn check_cancelled(
+        stop_receiver: &watch::Receiver<bool>,
+        connection: &mut Connection<'_, Core>,
         l1_batch_number: L1BatchNumber,
-        started_at: Instant,
-        connection_pool: ConnectionPool<Core>,
-        object_store: Arc<dyn ObjectStore>,
-        l2_chain_id: L2ChainId,
-    ) -> anyhow::Result<TeeVerifierInput> {
-        let prepare_basic_circuits_job: WitnessInputMerklePaths = object_store
-            .get(l1_batch_number)
+    ) -> anyhow::Result<()> {
+        if !*stop_receiver.borrow() {
+            return Ok(());
+        }
+        Err(Self::abandon_job(connection, l1_batch_number).await)
+    }
+
+    /// Returns the job cancelled by the stop signal to the queue. Returns the error to be reported for the job.
+    async fn abandon_job(
+        connection: &mut Connection<'_, Core>,
+        l1_batch_number: L1BatchNumber,
+    ) -> anyhow::Error {
+        let unlock_result = connection
+            .tee_verifier_input_producer_dal()
+            .unlock_job(l1_batch_number)
+            .await;
+        if let Err(err) = unlock_result {
+            return anyhow::Error::from(err)
+                .context("failed to unlock cancelled job for TeeVerifierInputProducer");
+        }
+        tracing::info!("Stop signal received; abandoned processing L1 batch #{l1_batch_number}");
+        anyhow::anyhow!("processing L1 batch #{l1_batch_number} was cancelled")
+    }
+
+    fn report_verification_stats(verification_result: &VerificationResult) {
+        METRICS
+            .storage_writes
+            .observe(verification_result.storage_writes);
+        METRICS
+            .used_factory_deps
+            .observe(verification_result.used_contract_hashes.len());
+        METRICS.vm_steps.observe(verification_result.vm_steps);
+
+        let l1_batch_number = verification_result.batch_number;
+        let stats = &verification_result.storage_stats;
+        let accesses = stats.get_value_storage_invocations + stats.set_value_storage_invocations;
+        let misses = stats.storage_invocations_missed;
+        let hits = accesses.saturating_sub(misses);
+        METRICS.storage_cache_accesses[&StorageCacheOutcome::Hit].observe(hits);
+        METRICS.storage_cache_accesses[&StorageCacheOutcome::Miss].observe(misses);
+        if accesses > 0 {
+            let hit_rate = hits as f64 / accesses as f64;
+            METRICS.storage_cache_hit_rate.observe(hit_rate);
+            tracing::debug!(
+                "Storage cache for L1 batch #{l1_batch_number}: {hits} hits, {misses} misses (hit rate {:.1}%)",
+                hit_rate * 100.0
+            );
+        }
+    }
+
+    /// Checks whether `err` is caused by a transient object store failure (as opposed to, e.g., a missing object).
+    /// If it is, returns the job to the queue so that the failure doesn't count against the job attempts. The job
+    /// is no longer in progress after that, so reporting the error via [`JobProcessor::save_failure()`] doesn't mark
+    /// the job as failed.
+    async fn unlock_on_transient_error(
+        &self,
+        l1_batch_number: L1BatchNumber,
+        artifact: Artifact,
+        err: &anyhow::Error,
+    ) -> anyhow::Result<bool> {
+        let is_transient = err
+            .downcast_ref::<ObjectStoreError>()
+            .is_some_and(ObjectStoreError::is_retriable);
+        if !is_transient {
+            return Ok(false);
+        }
+
+        METRICS.transient_object_store_errors[&artifact].inc();
+        self.connection_pool
+            .connection()
             .await
-            .context("failed to get PrepareBasicCircuitsJob from object store")?;
+            .context("failed to get connection for TeeVerifierInputProducer")?
+            .tee_verifier_input_producer_dal()
+            .unlock_job(l1_batch_number)
+            .await
+            .context("failed to unlock job for TeeVerifierInputProducer")?;
+        tracing::warn!(
+            "Transient object store error for L1 batch #{l1_batch_number} ({artifact:?}); returned the job to the queue: {err:#}"
+        );
+        Ok(true)
+    }
 
-        let mut connection = connection_pool
+    /// Checks whether artifacts for the specified L1 batch were already saved by another worker, i.e., the job
+    /// is marked as successful, and its artifacts are present in the object store.
+    async fn artifacts_already_saved(
+        &self,
+        l1_batch_number: L1BatchNumber,
+    ) -> anyhow::Result<bool> {
+        let object_path = self
+            .connection_pool
             .connection()
             .await
-            .context("failed to get connection for TeeVerifierInputProducer")?;
+            .context("failed to acquire DB connection for TeeVerifierInputProducer")?
+            .tee_verifier_input_producer_dal()
+            .get_successful_job_input_blob_url(l1_batch_number)
+            .await
+            .context("failed to get input blob URL for TeeVerifierInputProducer")?;
+        let Some(object_path) = object_path else {
+            return Ok(false);
+        };
+
+        // `ObjectStore` doesn't allow checking object existence, so we have to download it. This only happens
+        // in the rare case of workers racing on the same job.
+        match self
+            .object_store
+            .get_raw(TeeVerifierInput::BUCKET, &object_path)
+            .await
+        {
+            Ok(_) => Ok(true),
+            Err(ObjectStoreError::KeyNotFound(_)) => Ok(false),
+            Err(err) => {
+                tracing::warn!(
+                    "Failed checking existing artifacts for L1 batch #{l1_batch_number} at `{object_path}`; \
+                     artifacts will be re-uploaded: {err}"
+                );
+                Ok(false)
+            }
+        }
+    }
+
+    /// Invokes artifact hooks for the uploaded input. Hook errors are logged and otherwise ignored.
+    async fn run_artifact_hooks(&self, l1_batch_number: L1BatchNumber, object_path: &str) {
+        for hook in &self.artifact_hooks {
+            if let Err(err) = hook.on_uploaded(l1_batch_number, object_path).await {
+                tracing::warn!(
+                    "Artifact hook {hook:?} failed for L1 batch #{l1_batch_number} uploaded to `{object_path}`: {err:#}"
+                );
+            }
+        }
+    }
+
+    /// Compares the reconstructed root hash with the one committed on L1.
+    async fn check_committed_root_hash(
+        source: &dyn CommittedRootHashSource,
+        verification_result: &VerificationResult,
+    ) -> anyhow::Result<()> {
+        let l1_batch_number = verification_result.batch_number;
+        let committed_root_hash = source
+            .committed_root_hash(l1_batch_number)
+            .await
+            .with_context(|| {
+                format!("failed getting committed root hash for L1 batch #{l1_batch_number}")
+            })?;
+        let Some(committed_root_hash) = committed_root_hash else {
+            tracing::info!(
+                "L1 batch #{l1_batch_number} is not committed on L1 yet; skipping root hash cross-check"
+            );
+            return Ok(());
+        };
 
-        let l2_blocks_execution_data = connection
-            .transactions_dal()
-            .get_l2_blocks_to_execute_for_l1_batch(l1_batch_number)
+        let reconstructed_root_hash = verification_result.value_hash;
+        anyhow::ensure!(
+            reconstructed_root_hash == committed_root_hash,
+            "reconstructed root hash {reconstructed_root_hash:?} for L1 batch #{l1_batch_number} differs from \
+             the root hash committed on L1: {committed_root_hash:?}"
+        );
+        tracing::debug!("Reconstructed root hash for L1 batch #{l1_batch_number} matches the one committed on L1");
+        Ok(())
+    }
+
+    /// Compares contracts listed in the L1 batch header with the contracts loaded when re-executing the batch.
+    /// A mismatch indicates drift between the stored header and actual execution.
+    fn check_used_contracts(
+        l1_batch_number: L1BatchNumber,
+        header_hashes: &HashSet<H256>,
+        loaded_hashes: &[H256],
+        mode: UsedContractsMismatchMode,
+    ) -> anyhow::Result<()> {
+        let loaded_hashes: HashSet<_> = loaded_hashes.iter().copied().collect();
+        let mut not_in_header: Vec<_> = loaded_hashes.difference(header_hashes).collect();
+        let mut not_loaded: Vec<_> = header_hashes.difference(&loaded_hashes).collect();
+        if not_in_header.is_empty() && not_loaded.is_empty() {
+            return Ok(());
+        }
+        not_in_header.sort_unstable();
+        not_loaded.sort_unstable();
+
+        let message = format!(
+            "contracts loaded when re-executing L1 batch #{l1_batch_number} differ from `used_contract_hashes` \
+             in its header; loaded but not listed: {not_in_header:?}, listed but not loaded: {not_loaded:?}"
+        );
+        match mode {
+            UsedContractsMismatchMode::Warn => {
+                tracing::warn!("{message}");
+                Ok(())
+            }
+            UsedContractsMismatchMode::Error => Err(anyhow::anyhow!(message)),
+        }
+    }
+
+    /// Loads `PrepareBasicCircuitsJob` for the specified batch. The job may be stored either as raw bincode (as produced
+    /// by older nodes) or gzip-compressed, so both formats are attempted.
+    async fn load_prepare_basic_circuits_job(
+        object_store: &dyn ObjectStore,
+        key_prefix: &str,
+        l1_batch_number: L1BatchNumber,
+    ) -> anyhow::Result<WitnessInputMerklePaths> {
+        let key = format!(
+            "{key_prefix}{}",
+            WitnessInputMerklePaths::encode_key(l1_batch_number)
+        );
+        let bytes = object_store
+            .get_raw(WitnessInputMerklePaths::BUCKET, &key)
+            .await
+            .context("failed to get PrepareBasicCircuitsJob from object store")?;
+        METRICS.artifact_size[&Artifact::PrepareBasicCircuitsJob].observe(bytes.len());
+
+        let raw_err = match WitnessInputMerklePaths::deserialize(bytes.clone()) {
+            Ok(job) => {
+                tracing::debug!("Read raw PrepareBasicCircuitsJob for L1 batch #{l1_batch_number}");
+                return Ok(job);
+            }
+            Err(err) => err,
+        };
+
+        let mut decompressed = vec![];
+        GzDecoder::new(bytes.as_slice())
+            .read_to_end(&mut decompressed)
+            .with_context(|| {
+                format!(
+                    "PrepareBasicCircuitsJob for L1 batch #{l1_batch_number} is neither raw ({raw_err}) nor gzip-compressed"
+                )
+            })?;
+        let job = WitnessInputMerklePaths::deserialize(decompressed).map_err(|err| {
+            anyhow::anyhow!(
+                "failed deserializing compressed PrepareBasicCircuitsJob for L1 batch #{l1_batch_number}: {err} \
+                 (raw deserialization error: {raw_err})"
+            )
+        })?;
+        tracing::info!(
+            "Read gzip-compressed PrepareBasicCircuitsJob for L1 batch #{l1_batch_number}"
+        );
+        Ok(job)
+    }
+
+    /// Re-executes the specified L1 batch up to (and including) the specified L2 block and returns the intermediate state.
+    /// Can be used to bisect the L2 block introducing a divergence if the batch fails verification.
+    pub async fn replay_l1_batch_up_to(
+        &self,
+        l1_batch_number: L1BatchNumber,
+        last_l2_block: L2BlockNumber,
+    ) -> anyhow::Result<PartialReplayResult> {
+        // Replay doesn't process a job, so it shouldn't be cancelled.
+        let stop_receiver = watch::channel(false).1;
+        let (input, ..) = self
+            .load_verifier_input(l1_batch_number, &stop_receiver)
             .await?;
+        tokio::task::spawn_blocking(move || replay_up_to_l2_block(input, last_l2_block))
+            .await
+            .context("partial replay panicked")?
+    }
+
+    /// Loads verifier input for the specified L1 batch together with `used_contract_hashes` and the timestamp
+    /// (in seconds since UNIX epoch) from the batch header.
+    async fn load_verifier_input(
+        &self,
+        l1_batch_number: L1BatchNumber,
+        stop_receiver: &watch::Receiver<bool>,
+    ) -> anyhow::Result<(V1TeeVerifierInput, HashSet<H256>, u64)> {
+        let started_at = Instant::now();
+        let prepare_basic_circuits_job = Self::load_prepare_basic_circuits_job(
+            self.object_store.as_ref(),
+            &self.object_key_prefix,
+            l1_batch_number,
+        );
+
+        let (mode, prepare_basic_circuits_job, l2_blocks_execution_data, params) =
+            if self.parallel_input_loading {
+                // Object store and Postgres data are independent, so they are loaded concurrently.
+                // L2 blocks are loaded on a separate connection concurrently with batch params.
+                let l2_blocks_execution_data = async {
+                    let mut connection = self
+                        .connection_pool
+                        .connection()
+                        .await
+                        .context("failed to get connection for TeeVerifierInputProducer")?;
+                    let data = connection
+                        .transactions_dal()
+                        .get_l2_blocks_to_execute_for_l1_batch(l1_batch_number)
+                        .await?;
+                    anyhow::Ok(data)
+                };
+                let params = async {
+                    let mut connection = self
+                        .connection_pool
+                        .connection()
+                        .await
+                        .context("failed to get connection for TeeVerifierInputProducer")?;
+                    Self::check_cancelled(stop_receiver, &mut connection, l1_batch_number).await?;
+                    self.load_batch_params(&mut connection, l1_batch_number, stop_receiver)
+                        .await
+                };
+                let (job, l2_blocks, params) =
+                    future::try_join3(prepare_basic_circuits_job, l2_blocks_execution_data, params)
+                        .await?;
+                (InputLoadMode::Parallel, job, l2_blocks, params)
+            } else {
+                let prepare_basic_circuits_job = prepare_basic_circuits_job.await?;
+                let mut connection = self
+                    .connection_pool
+                    .connection()
+                    .await
+                    .context("failed to get connection for TeeVerifierInputProducer")?;
+                Self::check_cancelled(stop_receiver, &mut connection, l1_batch_number).await?;
+
+                let l2_blocks_execution_data = connection
+                    .transactions_dal()
+                    .get_l2_blocks_to_execute_for_l1_batch(l1_batch_number)
+                    .await?;
+                let params = self
+                    .load_batch_params(&mut connection, l1_batch_number, stop_receiver)
+                    .await?;
+                (
+                    InputLoadMode::Sequential,
+                    prepare_basic_circuits_job,
+                    l2_blocks_execution_data,
+                    params,
+                )
+            };
+        METRICS.input_load_time[&mode].observe(started_at.elapsed());
+
+        let BatchParams {
+            system_env,
+            l1_batch_env,
+            used_contract_hashes,
+            used_contracts,
+            timestamp,
+        } = params;
+        let tee_verifier_input = V1TeeVerifierInput::new(
+            prepare_basic_circuits_job,
+            l2_blocks_execution_data,
+            l1_batch_env,
+            system_env,
+            used_contracts,
+        );
+        Ok((tee_verifier_input, used_contract_hashes, timestamp))
+    }
 
+    /// Loads VM environments and factory deps for the specified L1 batch based on its header.
+    async fn load_batch_params(
+        &self,
+        connection: &mut Connection<'_, Core>,
+        l1_batch_number: L1BatchNumber,
+        stop_receiver: &watch::Receiver<bool>,
+    ) -> anyhow::Result<BatchParams> {
         let l1_batch_header = connection
             .blocks_dal()
             .get_l1_batch_header(l1_batch_number)
@@ -77,31 +752,53 @@ impl TeeVerifierInputProducer {
             .with_context(|| format!("header is missing for L1 batch #{l1_batch_number}"))?
             .unwrap();
 
-        let l1_batch_params_provider = L1BatchParamsProvider::new(&mut connection)
-            .await
-            .context("failed initializing L1 batch params provider")?;
-
-        // In the state keeper, this value is used to reject execution.
-        // All batches have already been executed by State Keeper.
-        // This means we don't want to reject any execution, therefore we're using MAX as an allow all.
-        let validation_computational_gas_limit = u32::MAX;
-
-        let (system_env, l1_batch_env) = l1_batch_params_provider
+        let (system_env, l1_batch_env) = self
+            .l1_batch_params_source
             .load_l1_batch_env(
-                &mut connection,
+                connection,
                 l1_batch_number,
-                validation_computational_gas_limit,
-                l2_chain_id,
+                self.validation_computational_gas_limit,
+                self.l2_chain_id,
             )
             .await?
             .with_context(|| format!("expected L1 batch #{l1_batch_number} to be sealed"))?;
+        Self::check_cancelled(stop_receiver, connection, l1_batch_number).await?;
 
-        let used_contract_hashes = l1_batch_header
+        let used_contract_hashes: HashSet<_> = l1_batch_header
             .used_contract_hashes
             .into_iter()
             .map(u256_to_h256)
             .collect();
 
+        let used_contracts = Self::load_factory_deps(
+            &self.connection_pool,
+            connection,
+            &used_contract_hashes,
+            self.factory_deps_load_concurrency,
+            self.factory_deps_cache.as_ref(),
+        )
+        .await?;
+        Self::check_cancelled(stop_receiver, connection, l1_batch_number).await?;
+
+        Ok(BatchParams {
+            system_env,
+            l1_batch_env,
+            used_contract_hashes,
+            used_contracts,
+            timestamp: l1_batch_header.timestamp,
+        })
+    }
+
+    /// Loads factory deps with the specified hashes. If `concurrency` is greater than 1, hashes are split into
+    /// `concurrency` chunks, each of which is loaded using a separate pool connection. If `cache` is provided,
+    /// only factory deps missing from it are loaded from Postgres, and loaded factory deps are inserted into the cache.
+    async fn load_factory_deps(
+        connection_pool: &ConnectionPool<Core>,
+        connection: &mut Connection<'_, Core>,
+        hashes: &HashSet<H256>,
+        concurrency: usize,
+        cache: Option<&FactoryDepsCache>,
+    ) -> anyhow::Result<Vec<(H256, Vec<u8>)>> {
         // `get_factory_deps()` returns the bytecode in chunks of `Vec<[u8; 32]>`,
         // but `fn store_factory_dep(&mut self, hash: H256, bytecode: Vec<u8>)` in `InMemoryStorage` wants flat byte vecs.
         pub fn into_flattened<T: Clone, const N: usize>(data: Vec<[T; N]>) -> Vec<T> {
@@ -112,29 +809,154 @@ impl TeeVerifierInputProducer {
             new
         }
 
-        let used_contracts = connection
-            .factory_deps_dal()
-            .get_factory_deps(&used_contract_hashes)
-            .await
-            .into_iter()
-            .map(|(hash, bytes)| (u256_to_h256(hash), into_flattened(bytes)))
-            .collect();
+        let mut cached_deps = vec![];
+        let mut missing_hashes = HashSet::new();
+        let hashes = if let Some(cache) = cache {
+            for &hash in hashes {
+                match cache.get(&hash) {
+                    Some(bytecode) => cached_deps.push((hash, bytecode)),
+                    None => {
+                        missing_hashes.insert(hash);
+                    }
+                }
+            }
+            &missing_hashes
+        } else {
+            hashes
+        };
+        if hashes.is_empty() {
+            return Ok(cached_deps);
+        }
 
-        tracing::info!("Started execution of l1_batch: {l1_batch_number:?}");
+        let started_at = Instant::now();
+        let (mode, factory_deps) = if concurrency <= 1 || hashes.len() <= 1 {
+            let factory_deps = connection.factory_deps_dal().get_factory_deps(hashes).await;
+            (FactoryDepsLoadMode::Sequential, factory_deps)
+        } else {
+            let hashes: Vec<_> = hashes.iter().copied().collect();
+            let chunk_size = hashes.len().div_ceil(concurrency);
+            let chunk_futures = hashes.chunks(chunk_size).map(|chunk| async move {
+                let chunk: HashSet<_> = chunk.iter().copied().collect();
+                let mut connection = connection_pool
+                    .connection()
+                    .await
+                    .context("failed to get connection for loading factory deps")?;
+                anyhow::Ok(connection.factory_deps_dal().get_factory_deps(&chunk).await)
+            });
+            let chunks = future::try_join_all(chunk_futures).await?;
+            let factory_deps = chunks.into_iter().flatten().collect();
+            (FactoryDepsLoadMode::Concurrent, factory_deps)
+        };
+        METRICS.factory_deps_load_time[&mode].observe(started_at.elapsed());
 
-        let tee_verifier_input = V1TeeVerifierInput::new(
-            prepare_basic_circuits_job,
-            l2_blocks_execution_data,
-            l1_batch_env,
-            system_env,
-            used_contracts,
-        );
+        let loaded_deps = factory_deps.into_iter().map(|(hash, bytes)| {
+            let (hash, bytecode) = (u256_to_h256(hash), into_flattened(bytes));
+            if let Some(cache) = cache {
+                cache.insert(hash, bytecode.clone());
+            }
+            (hash, bytecode)
+        });
+        cached_deps.extend(loaded_deps);
+        Ok(cached_deps)
+    }
 
-        // TODO (SEC-263): remove these 2 lines after successful testnet runs
-        tee_verifier_input.clone().verify()?;
-        tracing::info!("Looks like we verified {l1_batch_number} correctly");
+    /// Verifies the input on a blocking thread. Verification is cancelled, and an error is returned if
+    /// the verification timeout is set and elapses, or if the stop signal is received. In the latter case,
+    /// the job is returned to the queue.
+    async fn verify_input(
+        &self,
+        l1_batch_number: L1BatchNumber,
+        input: V1TeeVerifierInput,
+    ) -> anyhow::Result<VerificationResult> {
+        let cancellation = VerificationCancellation::default();
+        // Ensures that verification is cancelled on any exit, including this future being dropped (e.g., if the job
+        // is aborted). Cancelling completed verification is a no-op.
+        let _cancel_guard = cancellation.cancel_on_drop();
+        let mut verification = tokio::task::spawn_blocking({
+            let cancellation = cancellation.clone();
+            let verifier = self.tee_verifier.clone();
+            move || verifier.verify(input, &cancellation)
+        });
+        let timeout = async {
+            match self.verification_timeout {
+                Some(timeout) => {
+                    tokio::time::sleep(timeout).await;
+                    timeout
+                }
+                None => future::pending().await,
+            }
+        };
+        let mut stop_receiver = self.stop_receiver.clone();
+        let stop_signal = async {
+            // If the stop sender is dropped, the stop signal will never be received.
+            if stop_receiver.wait_for(|&stop| stop).await.is_err() {
+                future::pending::<()>().await;
+            }
+        };
 
-        tracing::info!("Finished execution of l1_batch: {l1_batch_number:?}");
+        // Verification checks for cancellation before each transaction, so the blocking thread is released
+        // shortly after cancellation. We don't wait for it in order to not block the job on a hanging transaction.
+        tokio::select! {
+            result = &mut verification => result.context("verification panicked")?,
+            () = stop_signal => {
+                cancellation.cancel();
+                let mut connection = self
+                    .connection_pool
+                    .connection()
+                    .await
+                    .context("failed to get connection for TeeVerifierInputProducer")?;
+                Err(Self::abandon_job(&mut connection, l1_batch_number).await)
+            }
+            timeout = timeout => {
+                METRICS.verification_timeouts.inc();
+                anyhow::bail!(
+                    "verification of L1 batch #{l1_batch_number} timed out after {timeout:?}"
+                );
+            }
+        }
+    }
+
+    async fn process_job_impl(
+        self,
+        l1_batch_number: L1BatchNumber,
+        started_at: Instant,
+    ) -> anyhow::Result<TeeVerifierInput> {
+        let loaded = self
+            .load_verifier_input(l1_batch_number, &self.stop_receiver)
+            .await;
+        let (tee_verifier_input, used_contract_hashes, l1_batch_timestamp) = match loaded {
+            Ok(loaded) => loaded,
+            Err(err) => {
+                let unlocked = self
+                    .unlock_on_transient_error(
+                        l1_batch_number,
+                        Artifact::PrepareBasicCircuitsJob,
+                        &err,
+                    )
+                    .await?;
+                if unlocked {
+                    return Err(err.context("transient error; the job was returned to the queue"));
+                }
+                return Err(err);
+            }
+        };
+        let batch_age = seconds_since_epoch().saturating_sub(l1_batch_timestamp);
+        METRICS.batch_age.set(Duration::from_secs(batch_age));
+
+        if self.generate_only {
+            tracing::info!(
+                "Skipped execution of l1_batch: {l1_batch_number:?} since the producer is in generate-only mode"
+            );
+        } else if let Err(err) = self
+            .verify_loaded_input(l1_batch_number, &tee_verifier_input, &used_contract_hashes)
+            .await
+        {
+            // Verification cancelled on shutdown is not a failure worth capturing.
+            if self.capture_failed_snapshots && !*self.stop_receiver.borrow() {
+                self.capture_failed_snapshot(&tee_verifier_input).await;
+            }
+            return Err(err);
+        }
 
         METRICS.process_batch_time.observe(started_at.elapsed());
         tracing::debug!(
@@ -145,6 +967,194 @@ impl TeeVerifierInputProducer {
 
         Ok(TeeVerifierInput::new(tee_verifier_input))
     }
+
+    /// Uploads a snapshot of the batch failing verification. Errors are logged and don't affect the job.
+    async fn capture_failed_snapshot(&self, tee_verifier_input: &V1TeeVerifierInput) {
+        let l1_batch_number = tee_verifier_input.l1_batch_env.number;
+        let key = failed_snapshot_key(&self.object_key_prefix, l1_batch_number);
+        let snapshot = TeeVerifierInput::new(tee_verifier_input.clone());
+        let result = match snapshot.serialize() {
+            Ok(bytes) => self
+                .object_store
+                .put_raw(TeeVerifierInput::BUCKET, &key, bytes)
+                .await
+                .map_err(anyhow::Error::from),
+            Err(err) => Err(anyhow::anyhow!(err)),
+        };
+        match result {
+            Ok(()) => {
+                tracing::info!("Captured snapshot of L1 batch #{l1_batch_number} failing verification to `{key}`");
+                METRICS.failed_snapshots_captured.inc();
+            }
+            Err(err) => {
+                tracing::warn!(
+                    "Failed capturing snapshot of L1 batch #{l1_batch_number} failing verification: {err:#}"
+                );
+            }
+        }
+    }
+
+    /// Uploads artifacts unless an object with the same content hash is already stored at `object_path`.
+    /// Returns the serialized artifacts size.
+    async fn put_artifacts_if_changed(
+        &self,
+        object_path: &str,
+        artifacts: &TeeVerifierInput,
+    ) -> anyhow::Result<usize> {
+        let serialized = artifacts
+            .serialize()
+            .map_err(|err| anyhow::anyhow!(err))
+            .context("failed to serialize artifacts for TeeVerifierInputProducer")?;
+        let size = serialized.len();
+        let hash = H256(keccak256(&serialized));
+
+        let existing = self
+            .object_store
+            .get_raw(TeeVerifierInput::BUCKET, object_path)
+            .await;
+        match existing {
+            Ok(existing) if H256(keccak256(&existing)) == hash => {
+                tracing::info!(
+                    "Artifacts at `{object_path}` are unchanged (content hash: {hash:?}); skipping upload"
+                );
+                METRICS.unchanged_uploads_skipped.inc();
+                return Ok(size);
+            }
+            Ok(_) | Err(ObjectStoreError::KeyNotFound(_)) => { /* need to upload artifacts */ }
+            Err(err) => {
+                tracing::warn!(
+                    "Failed checking existing artifacts at `{object_path}`, uploading them unconditionally: {err}"
+                );
+            }
+        }
+
+        self.object_store
+            .put_raw(TeeVerifierInput::BUCKET, object_path, serialized)
+            .await
+            .context("failed to upload artifacts for TeeVerifierInputProducer")?;
+        Ok(size)
+    }
+
+    /// Re-executes the loaded batch and checks the verification result.
+    async fn verify_loaded_input(
+        &self,
+        l1_batch_number: L1BatchNumber,
+        tee_verifier_input: &V1TeeVerifierInput,
+        used_contract_hashes: &HashSet<H256>,
+    ) -> anyhow::Result<()> {
+        tracing::info!("Started execution of l1_batch: {l1_batch_number:?}");
+
+        // TODO (SEC-263): remove these 2 lines after successful testnet runs
+        let verification_result = self
+            .verify_input(l1_batch_number, tee_verifier_input.clone())
+            .await?;
+        tracing::info!(
+            "Looks like we verified {l1_batch_number} correctly: root hash {:?}, {} storage writes, \
+             {} used factory deps, {} VM steps",
+            verification_result.value_hash,
+            verification_result.storage_writes,
+            verification_result.used_contract_hashes.len(),
+            verification_result.vm_steps
+        );
+        Self::report_verification_stats(&verification_result);
+        Self::check_used_contracts(
+            l1_batch_number,
+            used_contract_hashes,
+            &verification_result.used_contract_hashes,
+            self.used_contracts_mismatch_mode,
+        )?;
+        if let Some(source) = &self.committed_root_hash_source {
+            Self::check_committed_root_hash(source.as_ref(), &verification_result).await?;
+        }
+
+        tracing::info!("Finished execution of l1_batch: {l1_batch_number:?}");
+        Ok(())
+    }
+
+    /// Uploads artifacts for the processed job and marks the job as successful.
+    async fn save_artifacts(
+        &self,
+        job_id: L1BatchNumber,
+        started_at: Instant,
+        artifacts: TeeVerifierInput,
+    ) -> anyhow::Result<SaveOutcome> {
+        if self.artifacts_already_saved(job_id).await? {
+            return Ok(SaveOutcome::AlreadySaved);
+        }
+
+        let included_factory_deps = artifacts.used_contract_hashes().len();
+        let observer: vise::LatencyObserver = METRICS.upload_input_time.start();
+        // Stream artifacts manually (instead of using `ObjectStore::put_streaming()`) to record their size.
+        // Artifacts are buffered in memory only if the object store doesn't support streaming.
+        let object_path = format!(
+            "{}{}",
+            self.object_key_prefix,
+            TeeVerifierInput::encode_key(job_id)
+        );
+        let artifact_size = AtomicUsize::new(0);
+        let write_artifacts = |writer: &mut dyn io::Write| {
+            let mut writer = ByteCountingWriter::new(writer);
+            let result = artifacts.serialize_into(&mut writer);
+            artifact_size.store(writer.count, Ordering::Relaxed);
+            result
+        };
+        let upload_result = if self.skip_if_unchanged {
+            self.put_artifacts_if_changed(&object_path, &artifacts)
+                .await
+                .map(|size| artifact_size.store(size, Ordering::Relaxed))
+        } else {
+            self.object_store
+                .put_raw_streaming(TeeVerifierInput::BUCKET, &object_path, &write_artifacts)
+                .await
+                .context("failed to upload artifacts for TeeVerifierInputProducer")
+        };
+        if let Err(err) = upload_result {
+            if self
+                .unlock_on_transient_error(job_id, Artifact::TeeVerifierInput, &err)
+                .await?
+            {
+                return Ok(SaveOutcome::ReturnedToQueue(err));
+            }
+            return Err(err);
+        }
+        METRICS.artifact_size[&Artifact::TeeVerifierInput]
+            .observe(artifact_size.load(Ordering::Relaxed));
+        observer.observe();
+        self.run_artifact_hooks(job_id, &object_path).await;
+        let mut connection = self
+            .connection_pool
+            .connection()
+            .await
+            .context("failed to acquire DB connection for TeeVerifierInputProducer")?;
+        let mut transaction = connection
+            .start_transaction()
+            .await
+            .context("failed to acquire DB transaction for TeeVerifierInputProducer")?;
+        let marked_as_successful = transaction
+            .tee_verifier_input_producer_dal()
+            .mark_job_as_successful(job_id, started_at, &object_path)
+            .await
+            .context("failed to mark job as successful for TeeVerifierInputProducer")?;
+        if !marked_as_successful {
+            tracing::info!(
+                "L1 batch #{job_id} was concurrently marked as successful by another worker"
+            );
+        }
+        transaction
+            .tee_proof_generation_dal()
+            .insert_tee_proof_generation_job(job_id, TeeType::Sgx)
+            .await?;
+        transaction
+            .commit()
+            .await
+            .context("failed to commit DB transaction for TeeVerifierInputProducer")?;
+        METRICS.block_number_processed.set(job_id.0 as u64);
+        METRICS.included_factory_deps.observe(included_factory_deps);
+        tracing::info!(
+            "Saved artifacts for L1 batch #{job_id} including {included_factory_deps} factory deps"
+        );
+        Ok(SaveOutcome::Saved)
+    }
 }
 
 #[async_trait]
@@ -156,11 +1166,19 @@ impl JobProcessor for TeeVerifierInputProducer {
 
     async fn get_next_job(&self) -> anyhow::Result<Option<(Self::JobId, Self::Job)>> {
         let mut connection = self.connection_pool.connection().await?;
-        let l1_batch_to_process = connection
-            .tee_verifier_input_producer_dal()
-            .get_next_tee_verifier_input_producer_job()
+        let mut dal = connection.tee_verifier_input_producer_dal();
+        let l1_batch_to_process = if let Some(window) = self.verification_window {
+            dal.get_next_tee_verifier_input_producer_job_in_window(
+                window.size,
+                window.reverify_after,
+            )
             .await
-            .context("failed to get next basic witness input producer job")?;
+        } else {
+            dal.get_next_tee_verifier_input_producer_job_in_order(self.job_order)
+                .await
+        };
+        let l1_batch_to_process =
+            l1_batch_to_process.context("failed to get next basic witness input producer job")?;
         Ok(l1_batch_to_process.map(|number| (number, number)))
     }
 
@@ -175,9 +1193,15 @@ impl JobProcessor for TeeVerifierInputProducer {
             .await
             .expect("errored whilst marking job as failed");
         if let Some(tries) = attempts {
-            tracing::warn!("Failed to process job: {job_id:?}, after {tries} tries.");
+            let attempts_remaining = self.max_attempts().saturating_sub(tries);
+            tracing::warn!(
+                "Failed to process job: {job_id:?}, after {tries} tries; {attempts_remaining} attempt(s) remaining."
+            );
         } else {
-            tracing::warn!("L1 Batch {job_id:?} was processed successfully by another worker.");
+            tracing::warn!(
+                "L1 batch #{job_id} is no longer in progress (it was either processed by another worker \
+                 or returned to the queue); not marking it as failed"
+            );
         }
     }
 
@@ -187,19 +1211,8 @@ impl JobProcessor for TeeVerifierInputProducer {
         job: Self::Job,
         started_at: Instant,
     ) -> JoinHandle<anyhow::Result<Self::JobArtifacts>> {
-        let l2_chain_id = self.l2_chain_id;
-        let connection_pool = self.connection_pool.clone();
-        let object_store = self.object_store.clone();
-        tokio::task::spawn(async move {
-            Self::process_job_impl(
-                job,
-                started_at,
-                connection_pool.clone(),
-                object_store,
-                l2_chain_id,
-            )
-            .await
-        })
+        let producer = self.clone();
+        tokio::task::spawn(async move { producer.process_job_impl(job, started_at).await })
     }
 
     async fn save_result(
@@ -208,36 +1221,17 @@ impl JobProcessor for TeeVerifierInputProducer {
         started_at: Instant,
         artifacts: Self::JobArtifacts,
     ) -> anyhow::Result<()> {
-        let observer: vise::LatencyObserver = METRICS.upload_input_time.start();
-        let object_path = self
-            .object_store
-            .put(job_id, &artifacts)
-            .await
-            .context("failed to upload artifacts for TeeVerifierInputProducer")?;
-        observer.observe();
-        let mut connection = self
-            .connection_pool
-            .connection()
-            .await
-            .context("failed to acquire DB connection for TeeVerifierInputProducer")?;
-        let mut transaction = connection
-            .start_transaction()
-            .await
-            .context("failed to acquire DB transaction for TeeVerifierInputProducer")?;
-        transaction
-            .tee_verifier_input_producer_dal()
-            .mark_job_as_successful(job_id, started_at, &object_path)
-            .await
-            .context("failed to mark job as successful for TeeVerifierInputProducer")?;
-        transaction
-            .tee_proof_generation_dal()
-            .insert_tee_proof_generation_job(job_id, TeeType::Sgx)
-            .await?;
-        transaction
-            .commit()
-            .await
-            .context("failed to commit DB transaction for TeeVerifierInputProducer")?;
-        METRICS.block_number_processed.set(job_id.0 as u64);
+        match self.save_artifacts(job_id, started_at, artifacts).await? {
+            SaveOutcome::Saved => {}
+            SaveOutcome::AlreadySaved => {
+                tracing::info!(
+                    "L1 batch #{job_id} was already processed successfully by another worker; skipped artifacts upload"
+                );
+            }
+            SaveOutcome::ReturnedToQueue(_) => {
+                // The job will be re-processed once it's picked up again.
+            }
+        }
         Ok(())
     }
 
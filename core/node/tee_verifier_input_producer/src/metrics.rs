@@ -2,16 +2,102 @@
 
 use std::time::Duration;
 
-use vise::{Buckets, Gauge, Histogram, Metrics, Unit};
+use vise::{
+    Buckets, Counter, EncodeLabelSet, EncodeLabelValue, Family, Gauge, Histogram, Metrics, Unit,
+};
+
+/// Artifact fetched from or uploaded to the object store.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EncodeLabelValue, EncodeLabelSet)]
+#[metrics(label = "artifact", rename_all = "snake_case")]
+pub(crate) enum Artifact {
+    PrepareBasicCircuitsJob,
+    TeeVerifierInput,
+}
+
+/// Strategy used to load factory deps for a batch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EncodeLabelValue, EncodeLabelSet)]
+#[metrics(label = "mode", rename_all = "snake_case")]
+pub(crate) enum FactoryDepsLoadMode {
+    Sequential,
+    Concurrent,
+}
+
+/// Strategy used to load verifier input for a batch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EncodeLabelValue, EncodeLabelSet)]
+#[metrics(label = "mode", rename_all = "snake_case")]
+pub(crate) enum InputLoadMode {
+    Sequential,
+    Parallel,
+}
+
+/// Outcome of a storage access in the `StorageView` cache during batch rerun.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EncodeLabelValue, EncodeLabelSet)]
+#[metrics(label = "outcome", rename_all = "snake_case")]
+pub(crate) enum StorageCacheOutcome {
+    Hit,
+    Miss,
+}
+
+const ARTIFACT_SIZE_BUCKETS: Buckets =
+    Buckets::exponential(1_024.0..=1_024.0 * 1_024.0 * 1_024.0, 4.0);
+
+/// Batch rerun latency varies from milliseconds for empty batches to minutes for the largest ones, so buckets
+/// span 10ms to ~11 minutes.
+const BATCH_PROCESSING_BUCKETS: Buckets = Buckets::exponential(0.01..=1_000.0, 2.0);
 
 #[derive(Debug, Metrics)]
 #[metrics(prefix = "tee_verifier_input_producer")]
 pub(crate) struct TeeVerifierInputProducerMetrics {
-    #[metrics(buckets = Buckets::LATENCIES, unit = Unit::Seconds)]
+    #[metrics(buckets = BATCH_PROCESSING_BUCKETS, unit = Unit::Seconds)]
     pub process_batch_time: Histogram<Duration>,
     #[metrics(buckets = Buckets::LATENCIES, unit = Unit::Seconds)]
     pub upload_input_time: Histogram<Duration>,
+    /// Latency of loading factory deps for a batch, split by the load strategy.
+    #[metrics(buckets = Buckets::LATENCIES, unit = Unit::Seconds)]
+    pub factory_deps_load_time: Family<FactoryDepsLoadMode, Histogram<Duration>>,
+    /// Latency of loading verifier input for a batch (from Postgres and the object store), split by the load strategy.
+    #[metrics(buckets = Buckets::LATENCIES, unit = Unit::Seconds)]
+    pub input_load_time: Family<InputLoadMode, Histogram<Duration>>,
+    /// Serialized size of artifacts fetched from or uploaded to the object store.
+    #[metrics(buckets = ARTIFACT_SIZE_BUCKETS, unit = Unit::Bytes)]
+    pub artifact_size: Family<Artifact, Histogram<usize>>,
+    /// Number of transient object store errors when fetching or uploading artifacts. Such errors don't count
+    /// against job attempts.
+    pub transient_object_store_errors: Family<Artifact, Counter>,
     pub block_number_processed: Gauge<u64>,
+    /// Age of the latest processed batch, i.e. the difference between the current time and the batch timestamp.
+    /// A growing age means that the producer doesn't keep pace with batch sealing.
+    #[metrics(unit = Unit::Seconds)]
+    pub batch_age: Gauge<Duration>,
+    /// Number of artifact uploads skipped because an identical object was already stored.
+    pub unchanged_uploads_skipped: Counter,
+    /// Number of snapshots captured for batches failing verification.
+    pub failed_snapshots_captured: Counter,
+    /// Number of batch verifications cancelled because of a timeout.
+    pub verification_timeouts: Counter,
+    /// Number of storage writes applied to the Merkle tree per verified batch.
+    #[metrics(buckets = Buckets::exponential(1.0..=1_000_000.0, 4.0))]
+    pub storage_writes: Histogram<usize>,
+    /// Number of factory deps included into the artifacts per successfully processed batch.
+    #[metrics(buckets = Buckets::exponential(1.0..=4_096.0, 4.0))]
+    pub included_factory_deps: Histogram<usize>,
+    /// Number of factory deps used per verified batch.
+    #[metrics(buckets = Buckets::exponential(1.0..=4_096.0, 4.0))]
+    pub used_factory_deps: Histogram<usize>,
+    /// Total number of VM cycles per verified batch.
+    #[metrics(buckets = Buckets::exponential(1_000.0..=1_000_000_000_000.0, 10.0))]
+    pub vm_steps: Histogram<u64>,
+    /// Number of lookups in the shared factory deps cache, split by whether the lookup was a hit.
+    pub factory_deps_cache_requests: Family<StorageCacheOutcome, Counter>,
+    /// Total size of bytecodes in the shared factory deps cache.
+    #[metrics(unit = Unit::Bytes)]
+    pub factory_deps_cache_size: Gauge<u64>,
+    /// Number of storage accesses per batch rerun, split by whether the access was served from the cache.
+    #[metrics(buckets = Buckets::exponential(1.0..=1_000_000.0, 4.0))]
+    pub storage_cache_accesses: Family<StorageCacheOutcome, Histogram<usize>>,
+    /// Share of storage accesses per batch rerun served from the cache.
+    #[metrics(buckets = Buckets::linear(0.0..=1.0, 0.1))]
+    pub storage_cache_hit_rate: Histogram<f64>,
 }
 
 #[vise::register]
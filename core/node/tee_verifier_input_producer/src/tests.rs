@@ -0,0 +1,616 @@
+//! Tests for the TEE verifier input producer.
+
+use std::{io::Write, thread};
+
+use assert_matches::assert_matches;
+use flate2::{write::GzEncoder, Compression};
+use zksync_contracts::BaseSystemContracts;
+use zksync_multivm::interface::{L2BlockEnv, TxExecutionMode};
+use zksync_node_genesis::{insert_genesis_batch, GenesisParams};
+use zksync_node_test_utils::create_l1_batch;
+use zksync_object_store::{Bucket, MockObjectStore};
+use zksync_types::{fee_model::BatchFeeInput, Address, ProtocolVersionId};
+
+use super::*;
+
+/// Params source returning canned params, so that processed L1 batches don't need L2 blocks.
+#[derive(Debug)]
+struct MockL1BatchParamsSource;
+
+#[async_trait]
+impl L1BatchParamsSource for MockL1BatchParamsSource {
+    async fn load_l1_batch_env(
+        &self,
+        _connection: &mut Connection<'_, Core>,
+        l1_batch_number: L1BatchNumber,
+        validation_computational_gas_limit: u32,
+        chain_id: L2ChainId,
+    ) -> anyhow::Result<Option<(SystemEnv, L1BatchEnv)>> {
+        let system_env = SystemEnv {
+            zk_porter_available: false,
+            version: ProtocolVersionId::latest(),
+            base_system_smart_contracts: BaseSystemContracts::load_from_disk(),
+            bootloader_gas_limit: u32::MAX,
+            execution_mode: TxExecutionMode::VerifyExecute,
+            default_validation_computational_gas_limit: validation_computational_gas_limit,
+            chain_id,
+        };
+        let timestamp = l1_batch_number.0.into();
+        let l1_batch_env = L1BatchEnv {
+            previous_batch_hash: None,
+            number: l1_batch_number,
+            timestamp,
+            fee_input: BatchFeeInput::sensible_l1_pegged_default(),
+            fee_account: Address::zero(),
+            enforced_base_fee: None,
+            first_l2_block: L2BlockEnv {
+                number: l1_batch_number.0,
+                timestamp,
+                prev_block_hash: H256::zero(),
+                max_virtual_blocks_to_create: 1,
+            },
+        };
+        Ok(Some((system_env, l1_batch_env)))
+    }
+}
+
+/// Verifier hanging until verification is cancelled.
+#[derive(Debug)]
+struct HangingTeeVerifier {
+    started_sender: watch::Sender<bool>,
+}
+
+impl TeeVerifier for HangingTeeVerifier {
+    fn verify(
+        &self,
+        _input: V1TeeVerifierInput,
+        cancellation: &VerificationCancellation,
+    ) -> anyhow::Result<VerificationResult> {
+        self.started_sender.send_replace(true);
+        while !cancellation.is_cancelled() {
+            thread::sleep(Duration::from_millis(10));
+        }
+        anyhow::bail!("verification was cancelled");
+    }
+}
+
+/// Object store failing all operations in `failing_bucket` with the error produced by `make_error`. Operations
+/// in other buckets are delegated to the mock store.
+#[derive(Debug)]
+struct FailingObjectStore {
+    inner: MockObjectStore,
+    failing_bucket: Bucket,
+    make_error: fn() -> ObjectStoreError,
+}
+
+impl FailingObjectStore {
+    fn transient_error() -> ObjectStoreError {
+        ObjectStoreError::Other {
+            source: "connection reset".into(),
+            is_retriable: true,
+        }
+    }
+
+    fn check_bucket(&self, bucket: Bucket) -> Result<(), ObjectStoreError> {
+        if bucket == self.failing_bucket {
+            Err((self.make_error)())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[async_trait]
+impl ObjectStore for FailingObjectStore {
+    async fn get_raw(&self, bucket: Bucket, key: &str) -> Result<Vec<u8>, ObjectStoreError> {
+        self.check_bucket(bucket)?;
+        self.inner.get_raw(bucket, key).await
+    }
+
+    async fn put_raw(
+        &self,
+        bucket: Bucket,
+        key: &str,
+        value: Vec<u8>,
+    ) -> Result<(), ObjectStoreError> {
+        self.check_bucket(bucket)?;
+        self.inner.put_raw(bucket, key, value).await
+    }
+
+    async fn remove_raw(&self, bucket: Bucket, key: &str) -> Result<(), ObjectStoreError> {
+        self.check_bucket(bucket)?;
+        self.inner.remove_raw(bucket, key).await
+    }
+
+    fn storage_prefix_raw(&self, bucket: Bucket) -> String {
+        self.inner.storage_prefix_raw(bucket)
+    }
+}
+
+/// Mock object store counting uploaded TEE verifier inputs. Reading existing inputs can be configured to fail.
+#[derive(Debug, Default)]
+struct InputRecordingObjectStore {
+    inner: MockObjectStore,
+    fail_input_reads: bool,
+    input_uploads: AtomicUsize,
+}
+
+#[async_trait]
+impl ObjectStore for InputRecordingObjectStore {
+    async fn get_raw(&self, bucket: Bucket, key: &str) -> Result<Vec<u8>, ObjectStoreError> {
+        if self.fail_input_reads && bucket == TeeVerifierInput::BUCKET {
+            return Err(FailingObjectStore::transient_error());
+        }
+        self.inner.get_raw(bucket, key).await
+    }
+
+    async fn put_raw(
+        &self,
+        bucket: Bucket,
+        key: &str,
+        value: Vec<u8>,
+    ) -> Result<(), ObjectStoreError> {
+        if bucket == TeeVerifierInput::BUCKET {
+            self.input_uploads.fetch_add(1, Ordering::Relaxed);
+        }
+        self.inner.put_raw(bucket, key, value).await
+    }
+
+    async fn remove_raw(&self, bucket: Bucket, key: &str) -> Result<(), ObjectStoreError> {
+        self.inner.remove_raw(bucket, key).await
+    }
+
+    fn storage_prefix_raw(&self, bucket: Bucket) -> String {
+        self.inner.storage_prefix_raw(bucket)
+    }
+}
+
+/// Inserts L1 batch #1 (with no L2 blocks or used contracts) together with its job and Merkle paths.
+async fn prepare_job(pool: &ConnectionPool<Core>, object_store: &dyn ObjectStore) {
+    let mut connection = pool.connection().await.unwrap();
+    insert_genesis_batch(&mut connection, &GenesisParams::mock())
+        .await
+        .unwrap();
+    connection
+        .blocks_dal()
+        .insert_mock_l1_batch(&create_l1_batch(1))
+        .await
+        .unwrap();
+    connection
+        .tee_verifier_input_producer_dal()
+        .create_tee_verifier_input_producer_job(L1BatchNumber(1))
+        .await
+        .unwrap();
+    object_store
+        .put(L1BatchNumber(1), &WitnessInputMerklePaths::new(1))
+        .await
+        .unwrap();
+}
+
+async fn create_jobs(pool: &ConnectionPool<Core>, numbers: impl IntoIterator<Item = u32>) {
+    let mut connection = pool.connection().await.unwrap();
+    for number in numbers {
+        connection
+            .tee_verifier_input_producer_dal()
+            .create_tee_verifier_input_producer_job(L1BatchNumber(number))
+            .await
+            .unwrap();
+    }
+}
+
+async fn create_producer(
+    pool: &ConnectionPool<Core>,
+    object_store: Arc<dyn ObjectStore>,
+) -> TeeVerifierInputProducer {
+    let mut producer =
+        TeeVerifierInputProducer::new(pool.clone(), object_store, L2ChainId::default())
+            .await
+            .unwrap();
+    producer.set_l1_batch_params_source(Arc::new(MockL1BatchParamsSource));
+    producer
+}
+
+#[tokio::test]
+async fn processing_range_only_processes_claimed_batches() {
+    let pool = ConnectionPool::<Core>::test_pool().await;
+    create_jobs(&pool, [1, 2, 4]).await;
+    let mut connection = pool.connection().await.unwrap();
+    let mut dal = connection.tee_verifier_input_producer_dal();
+    // Job #1 is being processed by another worker, and job #2 is already processed.
+    let job = dal
+        .get_next_tee_verifier_input_producer_job()
+        .await
+        .unwrap();
+    assert_eq!(job, Some(L1BatchNumber(1)));
+    let job = dal
+        .get_next_tee_verifier_input_producer_job()
+        .await
+        .unwrap();
+    assert_eq!(job, Some(L1BatchNumber(2)));
+    assert!(dal
+        .mark_job_as_successful(L1BatchNumber(2), Instant::now(), "path/2")
+        .await
+        .unwrap());
+
+    let producer = create_producer(&pool, MockObjectStore::arc()).await;
+    let range = L1BatchNumber(1)..=L1BatchNumber(4);
+    let outcomes = producer.process_l1_batch_range(range, None).await.unwrap();
+    let statuses: Vec<_> = outcomes.iter().map(|outcome| &outcome.status).collect();
+    let skipped = |reason: &str| BatchStatus::Skipped {
+        reason: reason.to_owned(),
+    };
+    assert_eq!(
+        statuses[..3],
+        [
+            &skipped("being processed by another worker"),
+            &skipped("already processed"),
+            &skipped("no job for the batch"),
+        ]
+    );
+    // Job #4 is claimed, but fails because its Merkle paths are missing from the object store.
+    assert_matches!(statuses[3], BatchStatus::Failed { .. });
+
+    // The failed job is not left in progress, and skipped jobs are not affected.
+    let mut dal = connection.tee_verifier_input_producer_dal();
+    let job = dal
+        .get_next_tee_verifier_input_producer_job()
+        .await
+        .unwrap();
+    assert_eq!(job, Some(L1BatchNumber(4)));
+    let attempts = dal
+        .get_tee_verifier_input_producer_job_attempts(L1BatchNumber(4))
+        .await
+        .unwrap();
+    assert_eq!(attempts, Some(2));
+    let attempts = dal
+        .get_tee_verifier_input_producer_job_attempts(L1BatchNumber(1))
+        .await
+        .unwrap();
+    assert_eq!(attempts, Some(1));
+}
+
+#[tokio::test]
+async fn stop_signal_returns_job_to_queue() {
+    let pool = ConnectionPool::<Core>::test_pool().await;
+    let object_store = MockObjectStore::arc();
+    prepare_job(&pool, object_store.as_ref()).await;
+    let (started_sender, mut started_receiver) = watch::channel(false);
+    let (stop_sender, stop_receiver) = watch::channel(false);
+    let mut producer = create_producer(&pool, object_store).await;
+    producer.set_tee_verifier(Arc::new(HangingTeeVerifier { started_sender }));
+    producer.set_stop_receiver(stop_receiver);
+
+    let (job_id, job) = producer.get_next_job().await.unwrap().unwrap();
+    assert_eq!(job_id, L1BatchNumber(1));
+    let started_at = Instant::now();
+    let job_handle = producer.process_job(&job_id, job, started_at).await;
+    started_receiver.wait_for(|&started| started).await.unwrap();
+    stop_sender.send_replace(true);
+    let err = job_handle.await.unwrap().unwrap_err();
+    let err = format!("{err:#}");
+    assert!(err.contains("was cancelled"), "{err}");
+
+    // The job processor reports the error, but it must not be counted as a failed attempt.
+    producer.save_failure(job_id, started_at, err).await;
+    assert_eq!(producer.get_job_attempts(&job_id).await.unwrap(), 0);
+    let next_job = producer.get_next_job().await.unwrap();
+    assert_eq!(next_job, Some((job_id, job_id)));
+}
+
+#[tokio::test]
+async fn verification_timeout_fails_job() {
+    let pool = ConnectionPool::<Core>::test_pool().await;
+    let object_store = MockObjectStore::arc();
+    prepare_job(&pool, object_store.as_ref()).await;
+    let (started_sender, _started_receiver) = watch::channel(false);
+    let mut producer = create_producer(&pool, object_store).await;
+    producer.set_tee_verifier(Arc::new(HangingTeeVerifier { started_sender }));
+    producer.set_verification_timeout(Duration::from_millis(50));
+
+    let (job_id, job) = producer.get_next_job().await.unwrap().unwrap();
+    let started_at = Instant::now();
+    let job_handle = producer.process_job(&job_id, job, started_at).await;
+    let err = job_handle.await.unwrap().unwrap_err();
+    let err = format!("{err:#}");
+    assert!(err.contains("timed out after"), "{err}");
+
+    producer.save_failure(job_id, started_at, err).await;
+    assert_eq!(producer.get_job_attempts(&job_id).await.unwrap(), 1);
+    assert_eq!(producer.attempts_remaining(job_id).await.unwrap(), 4);
+    // The failed job is retried.
+    let next_job = producer.get_next_job().await.unwrap();
+    assert_eq!(next_job, Some((job_id, job_id)));
+}
+
+async fn failing_store_with_job(
+    pool: &ConnectionPool<Core>,
+    failing_bucket: Bucket,
+    make_error: fn() -> ObjectStoreError,
+) -> Arc<dyn ObjectStore> {
+    let inner = MockObjectStore::default();
+    prepare_job(pool, &inner).await;
+    Arc::new(FailingObjectStore {
+        inner,
+        failing_bucket,
+        make_error,
+    })
+}
+
+#[tokio::test]
+async fn transient_error_loading_input_returns_job_to_queue() {
+    let pool = ConnectionPool::<Core>::test_pool().await;
+    let object_store = failing_store_with_job(
+        &pool,
+        Bucket::WitnessInput,
+        FailingObjectStore::transient_error,
+    )
+    .await;
+    let producer = create_producer(&pool, object_store).await;
+
+    let (job_id, job) = producer.get_next_job().await.unwrap().unwrap();
+    let started_at = Instant::now();
+    let job_handle = producer.process_job(&job_id, job, started_at).await;
+    let err = job_handle.await.unwrap().unwrap_err();
+    let err = format!("{err:#}");
+    assert!(err.contains("returned to the queue"), "{err}");
+
+    // The job is no longer in progress, so reporting the failure is a no-op.
+    producer.save_failure(job_id, started_at, err).await;
+    assert_eq!(producer.get_job_attempts(&job_id).await.unwrap(), 0);
+    let next_job = producer.get_next_job().await.unwrap();
+    assert_eq!(next_job, Some((job_id, job_id)));
+}
+
+#[tokio::test]
+async fn missing_input_fails_job() {
+    let pool = ConnectionPool::<Core>::test_pool().await;
+    let object_store = failing_store_with_job(&pool, Bucket::WitnessInput, || {
+        ObjectStoreError::KeyNotFound("missing".into())
+    })
+    .await;
+    let producer = create_producer(&pool, object_store).await;
+
+    let (job_id, job) = producer.get_next_job().await.unwrap().unwrap();
+    let started_at = Instant::now();
+    let job_handle = producer.process_job(&job_id, job, started_at).await;
+    let err = job_handle.await.unwrap().unwrap_err();
+    let err = format!("{err:#}");
+    assert!(!err.contains("returned to the queue"), "{err}");
+
+    producer.save_failure(job_id, started_at, err).await;
+    assert_eq!(producer.get_job_attempts(&job_id).await.unwrap(), 1);
+}
+
+#[tokio::test]
+async fn transient_error_uploading_artifacts_returns_job_to_queue() {
+    let pool = ConnectionPool::<Core>::test_pool().await;
+    let object_store = failing_store_with_job(
+        &pool,
+        Bucket::TeeVerifierInput,
+        FailingObjectStore::transient_error,
+    )
+    .await;
+    let mut producer = create_producer(&pool, object_store).await;
+    producer.set_generate_only(true);
+
+    let (job_id, job) = producer.get_next_job().await.unwrap().unwrap();
+    let started_at = Instant::now();
+    let job_handle = producer.process_job(&job_id, job, started_at).await;
+    let artifacts = job_handle.await.unwrap().unwrap();
+    producer
+        .save_result(job_id, started_at, artifacts)
+        .await
+        .unwrap();
+
+    assert_eq!(producer.get_job_attempts(&job_id).await.unwrap(), 0);
+    let mut connection = pool.connection().await.unwrap();
+    let url = connection
+        .tee_verifier_input_producer_dal()
+        .get_successful_job_input_blob_url(job_id)
+        .await
+        .unwrap();
+    assert_eq!(url, None);
+    let next_job = producer.get_next_job().await.unwrap();
+    assert_eq!(next_job, Some((job_id, job_id)));
+}
+
+fn gzip(bytes: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(vec![], Compression::default());
+    encoder.write_all(bytes).unwrap();
+    encoder.finish().unwrap()
+}
+
+#[tokio::test]
+async fn loading_prepare_basic_circuits_job_in_different_formats() {
+    let object_store = MockObjectStore::arc();
+    let key = WitnessInputMerklePaths::encode_key(L1BatchNumber(1));
+    let paths = WitnessInputMerklePaths::new(42);
+    let raw_paths = paths.serialize().unwrap();
+    for bytes in [raw_paths.clone(), gzip(&raw_paths)] {
+        object_store
+            .put_raw(WitnessInputMerklePaths::BUCKET, &key, bytes)
+            .await
+            .unwrap();
+        let loaded = TeeVerifierInputProducer::load_prepare_basic_circuits_job(
+            object_store.as_ref(),
+            "",
+            L1BatchNumber(1),
+        )
+        .await
+        .unwrap();
+        assert_eq!(loaded, paths);
+    }
+
+    object_store
+        .put_raw(WitnessInputMerklePaths::BUCKET, &key, b"garbage".to_vec())
+        .await
+        .unwrap();
+    let err = TeeVerifierInputProducer::load_prepare_basic_circuits_job(
+        object_store.as_ref(),
+        "",
+        L1BatchNumber(1),
+    )
+    .await
+    .unwrap_err();
+    let err = format!("{err:#}");
+    assert!(
+        err.contains("L1 batch #1 is neither raw (") && err.contains(") nor gzip-compressed"),
+        "{err}"
+    );
+
+    object_store
+        .put_raw(WitnessInputMerklePaths::BUCKET, &key, gzip(b"garbage"))
+        .await
+        .unwrap();
+    let err = TeeVerifierInputProducer::load_prepare_basic_circuits_job(
+        object_store.as_ref(),
+        "",
+        L1BatchNumber(1),
+    )
+    .await
+    .unwrap_err();
+    let err = format!("{err:#}");
+    assert!(
+        err.contains("failed deserializing compressed PrepareBasicCircuitsJob for L1 batch #1")
+            && err.contains("(raw deserialization error: "),
+        "{err}"
+    );
+}
+
+#[test]
+fn checking_used_contracts() {
+    let header_hashes = HashSet::from([H256::repeat_byte(1), H256::repeat_byte(2)]);
+    let modes = [
+        UsedContractsMismatchMode::Warn,
+        UsedContractsMismatchMode::Error,
+    ];
+    let check = |loaded_hashes: &[H256], mode| {
+        TeeVerifierInputProducer::check_used_contracts(
+            L1BatchNumber(1),
+            &header_hashes,
+            loaded_hashes,
+            mode,
+        )
+    };
+
+    // Duplicate and reordered loaded hashes are fine.
+    let matching = [
+        H256::repeat_byte(2),
+        H256::repeat_byte(1),
+        H256::repeat_byte(2),
+    ];
+    for mode in modes {
+        check(&matching, mode).unwrap();
+    }
+
+    let extra = [
+        H256::repeat_byte(1),
+        H256::repeat_byte(2),
+        H256::repeat_byte(3),
+    ];
+    let missing = [H256::repeat_byte(1)];
+    for loaded_hashes in [&extra[..], &missing] {
+        check(loaded_hashes, UsedContractsMismatchMode::Warn).unwrap();
+    }
+
+    let err = check(&extra, UsedContractsMismatchMode::Error)
+        .unwrap_err()
+        .to_string();
+    let expected = format!(
+        "loaded but not listed: [{:?}], listed but not loaded: []",
+        H256::repeat_byte(3)
+    );
+    assert!(err.contains(&expected), "{err}");
+
+    let err = check(&missing, UsedContractsMismatchMode::Error)
+        .unwrap_err()
+        .to_string();
+    let expected = format!(
+        "loaded but not listed: [], listed but not loaded: [{:?}]",
+        H256::repeat_byte(2)
+    );
+    assert!(err.contains(&expected), "{err}");
+}
+
+async fn load_artifacts(producer: &TeeVerifierInputProducer) -> TeeVerifierInput {
+    let stop_receiver = watch::channel(false).1;
+    let (input, ..) = producer
+        .load_verifier_input(L1BatchNumber(1), &stop_receiver)
+        .await
+        .unwrap();
+    TeeVerifierInput::new(input)
+}
+
+#[tokio::test]
+async fn skipping_upload_of_unchanged_artifacts() {
+    let pool = ConnectionPool::<Core>::test_pool().await;
+    let object_store = Arc::new(InputRecordingObjectStore::default());
+    prepare_job(&pool, object_store.as_ref()).await;
+    let producer = create_producer(&pool, object_store.clone()).await;
+    let artifacts = load_artifacts(&producer).await;
+    let serialized = artifacts.serialize().unwrap();
+    let object_path = TeeVerifierInput::encode_key(L1BatchNumber(1));
+
+    let size = producer
+        .put_artifacts_if_changed(&object_path, &artifacts)
+        .await
+        .unwrap();
+    assert_eq!(size, serialized.len());
+    assert_eq!(object_store.input_uploads.load(Ordering::Relaxed), 1);
+
+    // The stored object has the same content hash, so it's not re-uploaded.
+    let size = producer
+        .put_artifacts_if_changed(&object_path, &artifacts)
+        .await
+        .unwrap();
+    assert_eq!(size, serialized.len());
+    assert_eq!(object_store.input_uploads.load(Ordering::Relaxed), 1);
+
+    // The stored object has a different content hash, so it's overwritten.
+    object_store
+        .inner
+        .put_raw(TeeVerifierInput::BUCKET, &object_path, b"stale".to_vec())
+        .await
+        .unwrap();
+    producer
+        .put_artifacts_if_changed(&object_path, &artifacts)
+        .await
+        .unwrap();
+    assert_eq!(object_store.input_uploads.load(Ordering::Relaxed), 2);
+    let stored = object_store
+        .inner
+        .get_raw(TeeVerifierInput::BUCKET, &object_path)
+        .await
+        .unwrap();
+    assert_eq!(stored, serialized);
+}
+
+#[tokio::test]
+async fn uploading_artifacts_if_stored_object_cannot_be_read() {
+    let pool = ConnectionPool::<Core>::test_pool().await;
+    let object_store = Arc::new(InputRecordingObjectStore {
+        fail_input_reads: true,
+        ..InputRecordingObjectStore::default()
+    });
+    prepare_job(&pool, object_store.as_ref()).await;
+    let producer = create_producer(&pool, object_store.clone()).await;
+    let artifacts = load_artifacts(&producer).await;
+    let object_path = TeeVerifierInput::encode_key(L1BatchNumber(1));
+
+    for expected_uploads in [1, 2] {
+        producer
+            .put_artifacts_if_changed(&object_path, &artifacts)
+            .await
+            .unwrap();
+        assert_eq!(
+            object_store.input_uploads.load(Ordering::Relaxed),
+            expected_uploads
+        );
+    }
+    let stored = object_store
+        .inner
+        .get_raw(TeeVerifierInput::BUCKET, &object_path)
+        .await
+        .unwrap();
+    assert_eq!(stored, artifacts.serialize().unwrap());
+}
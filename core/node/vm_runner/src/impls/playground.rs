@@ -1,5 +1,4 @@
 use std::{
-    hash::{DefaultHasher, Hash, Hasher},
     io,
     num::NonZeroU32,
     path::{Path, PathBuf},
@@ -17,10 +16,10 @@ use zksync_dal::{Connection, ConnectionPool, Core, CoreDal};
 use zksync_health_check::{Health, HealthStatus, HealthUpdater, ReactiveHealthCheck};
 use zksync_object_store::{Bucket, ObjectStore};
 use zksync_state::RocksdbStorage;
-use zksync_types::{vm::FastVmMode, L1BatchNumber, L2ChainId};
+use zksync_types::{vm::FastVmMode, L1BatchNumber, L2ChainId, H256};
 use zksync_vm_executor::batch::MainBatchExecutorFactory;
 use zksync_vm_interface::{
-    utils::{DivergenceHandler, VmDump},
+    utils::{DivergenceHandler, VmDump, VmDumpCompression, VmDumpFormat},
     L1BatchEnv, L2BlockEnv, SystemEnv,
 };
 
@@ -101,6 +100,8 @@ impl VmPlayground {
     pub async fn new(
         pool: ConnectionPool<Core>,
         dumps_object_store: Option<Arc<dyn ObjectStore>>,
+        dumps_format: VmDumpFormat,
+        dumps_compression: VmDumpCompression,
         vm_mode: FastVmMode,
         storage: VmPlaygroundStorageOptions,
         chain_id: L2ChainId,
@@ -138,12 +139,15 @@ impl VmPlayground {
         batch_executor_factory.observe_storage_metrics();
         let handle = tokio::runtime::Handle::current();
         if let Some(store) = dumps_object_store {
-            tracing::info!("Using object store for VM dumps: {store:?}");
+            tracing::info!(
+                "Using object store for VM dumps: {store:?}, format: {dumps_format:?}, compression: {dumps_compression:?}"
+            );
 
             let handler = DivergenceHandler::new(move |err, dump| {
-                let err_message = err.to_string();
-                if let Err(err) = handle.block_on(Self::dump_vm_state(&*store, &err_message, &dump))
-                {
+                let err_hash = err.stable_hash();
+                let dump_future =
+                    Self::dump_vm_state(&*store, dumps_format, dumps_compression, err_hash, &dump);
+                if let Err(err) = handle.block_on(dump_future) {
                     let l1_batch_number = dump.l1_batch_number();
                     tracing::error!(
                         "Saving VM dump for L1 batch #{l1_batch_number} failed: {err:#}"
@@ -200,20 +204,20 @@ impl VmPlayground {
 
     async fn dump_vm_state(
         object_store: &dyn ObjectStore,
-        err_message: &str,
+        format: VmDumpFormat,
+        compression: VmDumpCompression,
+        err_hash: H256,
         dump: &VmDump,
     ) -> anyhow::Result<()> {
         // Deduplicate VM dumps by the error hash so that we don't create a lot of dumps for the same error.
-        let mut hasher = DefaultHasher::new();
-        err_message.hash(&mut hasher);
-        let err_hash = hasher.finish();
-        let batch_number = dump.l1_batch_number().0;
-        let dump_filename = format!("shadow_vm_dump_batch{batch_number:08}_{err_hash:x}.json");
+        // The hash is stable, so that dumps for recurring errors are grouped together.
+        let dump_filename =
+            VmDump::file_name(dump.l1_batch_number(), err_hash, format, compression);
 
         tracing::info!("Dumping diverged VM state to `{dump_filename}`");
-        let dump = serde_json::to_string(&dump).context("failed serializing VM dump")?;
+        let dump = dump.to_compressed_bytes(format, compression)?;
         object_store
-            .put_raw(Bucket::VmDumps, &dump_filename, dump.into_bytes())
+            .put_raw(Bucket::VmDumps, &dump_filename, dump)
             .await
             .context("failed putting VM dump to object store")?;
         Ok(())
@@ -6,6 +6,7 @@ use zksync_health_check::HealthStatus;
 use zksync_node_genesis::{insert_genesis_batch, GenesisParams};
 use zksync_state::RocksdbStorage;
 use zksync_types::vm::FastVmMode;
+use zksync_vm_interface::utils::{VmDumpCompression, VmDumpFormat};
 
 use super::*;
 use crate::impls::{
@@ -75,6 +76,8 @@ async fn run_playground(
     let (playground, playground_tasks) = VmPlayground::new(
         pool.clone(),
         None,
+        VmDumpFormat::default(),
+        VmDumpCompression::default(),
         FastVmMode::Shadow,
         storage,
         genesis_params.config().l2_chain_id,
@@ -257,6 +260,8 @@ async fn using_larger_window_size(window_size: u32) {
     let (playground, playground_tasks) = VmPlayground::new(
         pool.clone(),
         None,
+        VmDumpFormat::default(),
+        VmDumpCompression::default(),
         FastVmMode::Shadow,
         VmPlaygroundStorageOptions::from(&rocksdb_dir),
         genesis_params.config().l2_chain_id,
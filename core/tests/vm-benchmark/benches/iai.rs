@@ -1,13 +1,16 @@
+//! IAI benchmarks for the fast, legacy and shadowed VMs. Benchmarks for the shadowed VM (`*_shadowed`) are used
+//! to track the shadowing overhead relative to the fast VM; see `compare_iai_results`.
+
 use iai::black_box;
-use vm_benchmark::{BenchmarkingVm, BenchmarkingVmFactory, Bytecode, Fast, Legacy};
+use vm_benchmark::{BenchmarkingVm, BenchmarkingVmFactory, Bytecode, Fast, Legacy, Shadowed};
 
 fn run_bytecode<VM: BenchmarkingVmFactory>(name: &str) {
     let tx = Bytecode::get(name).deploy_tx();
-    black_box(BenchmarkingVm::<VM>::default().run_transaction(&tx));
+    black_box(BenchmarkingVm::<VM>::deterministic().run_transaction(&tx));
 }
 
 macro_rules! make_functions_and_main {
-    ($($file:ident => $legacy_name:ident,)+) => {
+    ($($file:ident => $legacy_name:ident, $shadowed_name:ident,)+) => {
         $(
         fn $file() {
             run_bytecode::<Fast>(stringify!($file));
@@ -16,19 +19,23 @@ macro_rules! make_functions_and_main {
         fn $legacy_name() {
             run_bytecode::<Legacy>(stringify!($file));
         }
+
+        fn $shadowed_name() {
+            run_bytecode::<Shadowed>(stringify!($file));
+        }
         )+
 
-        iai::main!($($file, $legacy_name,)+);
+        iai::main!($($file, $legacy_name, $shadowed_name,)+);
     };
 }
 
 make_functions_and_main!(
-    access_memory => access_memory_legacy,
-    call_far => call_far_legacy,
-    decode_shl_sub => decode_shl_sub_legacy,
-    deploy_simple_contract => deploy_simple_contract_legacy,
-    finish_eventful_frames => finish_eventful_frames_legacy,
-    write_and_decode => write_and_decode_legacy,
-    event_spam => event_spam_legacy,
-    slot_hash_collision => slot_hash_collision_legacy,
+    access_memory => access_memory_legacy, access_memory_shadowed,
+    call_far => call_far_legacy, call_far_shadowed,
+    decode_shl_sub => decode_shl_sub_legacy, decode_shl_sub_shadowed,
+    deploy_simple_contract => deploy_simple_contract_legacy, deploy_simple_contract_shadowed,
+    finish_eventful_frames => finish_eventful_frames_legacy, finish_eventful_frames_shadowed,
+    write_and_decode => write_and_decode_legacy, write_and_decode_shadowed,
+    event_spam => event_spam_legacy, event_spam_shadowed,
+    slot_hash_collision => slot_hash_collision_legacy, slot_hash_collision_shadowed,
 );
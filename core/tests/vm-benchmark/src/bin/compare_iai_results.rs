@@ -1,40 +1,183 @@
 use std::{
-    collections::{HashMap, HashSet},
-    fs::File,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt::Write as _,
+    fs::{self, File},
     io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+    thread,
 };
 
 pub use crate::common::parse_iai;
 
 mod common;
 
-fn main() {
-    let [iai_before, iai_after, opcodes_before, opcodes_after] = std::env::args()
-        .skip(1)
-        .take(4)
-        .collect::<Vec<_>>()
-        .try_into()
-        .expect("expected four arguments");
-
-    let iai_before = get_name_to_cycles(&iai_before);
-    let iai_after = get_name_to_cycles(&iai_after);
-    let opcodes_before = get_name_to_opcodes(&opcodes_before);
-    let opcodes_after = get_name_to_opcodes(&opcodes_after);
-
-    let perf_changes = iai_before
-        .keys()
-        .collect::<HashSet<_>>()
-        .intersection(&iai_after.keys().collect())
-        .filter_map(|&name| {
-            let diff = percent_difference(iai_before[name], iai_after[name]);
-            if diff.abs() > 2. {
-                Some((name, format!("{:+.1}%", diff)))
-            } else {
-                None
+/// Minimum relative change in estimated runtime (in percent) that is considered significant.
+const SIGNIFICANT_PERCENT_DIFFERENCE: f64 = 2.;
+/// Suffix of benchmarks running the shadowed VM. Such a benchmark is compared with the benchmark without the suffix
+/// (i.e., running the fast VM without shadowing) to estimate shadowing overhead.
+const SHADOWED_SUFFIX: &str = "_shadowed";
+/// Output for relative changes with a zero baseline, for which the change in percent is not meaningful.
+const ZERO_BASELINE_CHANGE: &str = "new";
+
+#[derive(Debug, Default)]
+struct Args {
+    /// If set, IAI results may contain repeated samples per benchmark, and changes are only reported if they exceed
+    /// the confidence interval with the specified half-width (measured in standard errors).
+    confidence: Option<f64>,
+    /// Git ref of the baseline results looked up in `baseline_root`, or a path to the directory with baseline results
+    /// (`iai` and `opcodes` files). If set, results for the `before` side are loaded from the baseline, and only `after`
+    /// files are provided as positional args.
+    baseline: Option<String>,
+    /// Root directory with baseline results stored in a directory per git ref (e.g., `<root>/main` or `<root>/<commit>`),
+    /// e.g. synced by CI from the artifact storage.
+    baseline_root: Option<PathBuf>,
+    /// If set, input files are parsed on the main thread. By default, IAI outputs and opcode counts
+    /// for both sides are parsed on separate threads.
+    sequential_parsing: bool,
+    /// If set, absolute cycle and opcode counts before and after the change are output in addition to relative changes.
+    verbose: bool,
+    /// If set, the comparison output is additionally written to the specified file (e.g., to be posted as a PR comment).
+    /// Parent directories are created if necessary, and the existing file contents are overwritten.
+    output: Option<PathBuf>,
+    /// Labeled candidate result sets specified via `--after <label> <iai> <opcodes>`. If non-empty, a single `before` set
+    /// is compared against all candidates, and the output is a wide table with columns for each candidate.
+    after: Vec<Candidate>,
+    positional: Vec<String>,
+}
+
+/// Labeled `after` result set in the matrix comparison mode.
+#[derive(Debug)]
+struct Candidate {
+    label: String,
+    /// Comma-separated IAI output files.
+    iai: String,
+    opcodes: String,
+}
+
+impl Args {
+    fn parse() -> Self {
+        let mut args = Self::default();
+        let mut raw_args = std::env::args().skip(1);
+        while let Some(arg) = raw_args.next() {
+            match arg.as_str() {
+                "--confidence" => {
+                    let value = raw_args.next().expect("`--confidence` requires a value");
+                    let value = value
+                        .parse()
+                        .expect("`--confidence` value must be a number");
+                    args.confidence = Some(value);
+                }
+                "--baseline" => {
+                    let value = raw_args.next().expect("`--baseline` requires a value");
+                    args.baseline = Some(value);
+                }
+                "--baseline-root" => {
+                    let value = raw_args.next().expect("`--baseline-root` requires a value");
+                    args.baseline_root = Some(value.into());
+                }
+                "--sequential-parsing" => args.sequential_parsing = true,
+                "--verbose" => args.verbose = true,
+                "--output" => {
+                    let value = raw_args.next().expect("`--output` requires a value");
+                    args.output = Some(value.into());
+                }
+                "--after" => {
+                    let mut next_value = || {
+                        raw_args
+                            .next()
+                            .expect("`--after` requires label, IAI and opcodes values")
+                    };
+                    let (label, iai, opcodes) = (next_value(), next_value(), next_value());
+                    args.after.push(Candidate {
+                        label,
+                        iai,
+                        opcodes,
+                    });
+                }
+                _ => args.positional.push(arg),
             }
-        })
-        .collect::<HashMap<_, _>>();
+        }
+        args
+    }
+
+    /// Resolves the directory with baseline results. If the baseline root is set and contains a directory
+    /// for the baseline ref, this directory is used; otherwise, the baseline is treated as a path.
+    fn baseline_dir(&self) -> Option<PathBuf> {
+        let baseline = self.baseline.as_ref()?;
+        if let Some(root) = &self.baseline_root {
+            let ref_dir = root.join(baseline);
+            if ref_dir.is_dir() {
+                return Some(ref_dir);
+            }
+        }
+        Some(PathBuf::from(baseline))
+    }
+}
+
+fn main() {
+    let args = Args::parse();
+    if !args.after.is_empty() {
+        compare_matrix(args);
+        return;
+    }
+
+    let (before, after) = if let Some(baseline) = &args.baseline_dir() {
+        let [iai_after, opcodes_after] = args
+            .positional
+            .into_iter()
+            .take(2)
+            .collect::<Vec<_>>()
+            .try_into()
+            .expect("expected two arguments with `--baseline`");
+        let before = BenchmarkResults::from_baseline(baseline);
+        (
+            before,
+            BenchmarkResults::from_files(&iai_after, &opcodes_after),
+        )
+    } else {
+        let [iai_before, iai_after, opcodes_before, opcodes_after] = args
+            .positional
+            .into_iter()
+            .take(4)
+            .collect::<Vec<_>>()
+            .try_into()
+            .expect("expected four arguments");
+        let before = BenchmarkResults::from_files(&iai_before, &opcodes_before);
+        (
+            before,
+            BenchmarkResults::from_files(&iai_after, &opcodes_after),
+        )
+    };
 
+    let (before, after) = if args.sequential_parsing {
+        (before.parse(), after.parse())
+    } else {
+        thread::scope(|scope| {
+            let before = before.parse_in_parallel(scope);
+            let after = after.parse_in_parallel(scope);
+            (before.join(), after.join())
+        })
+    };
+    let ParsedResults {
+        iai: iai_before,
+        opcodes: opcodes_before,
+    } = before;
+    let ParsedResults {
+        iai: iai_after,
+        opcodes: opcodes_after,
+    } = after;
+    let shadow_overhead_before = get_shadow_overheads(&iai_before);
+    let shadow_overhead_after = get_shadow_overheads(&iai_after);
+    let use_mean_cycles = args.confidence.is_some();
+    let cycles_before = get_displayed_cycles(&iai_before, use_mean_cycles);
+    let cycles_after = get_displayed_cycles(&iai_after, use_mean_cycles);
+    let perf_changes = if let Some(confidence) = args.confidence {
+        get_significant_sample_changes(&iai_before, &iai_after, confidence)
+    } else {
+        let iai_before = get_name_to_cycles(&iai_before);
+        let iai_after = get_name_to_cycles(&iai_after);
+        get_significant_changes(&iai_before, &iai_after)
+    };
     let duration_changes = opcodes_before
         .keys()
         .collect::<HashSet<_>>()
@@ -45,55 +188,525 @@ fn main() {
         })
         .collect::<HashMap<_, _>>();
 
+    let mut output = String::new();
     let mut nonzero_diff = false;
 
     for name in perf_changes.keys().collect::<HashSet<_>>().union(
         &duration_changes
             .iter()
-            .filter_map(|(key, value)| (*value != 0).then_some(key))
+            .filter_map(|(key, value)| (*value != 0).then_some(*key))
             .collect(),
     ) {
         // write the header before writing the first line of diff
         if !nonzero_diff {
-            println!("Benchmark name | change in estimated runtime | change in number of opcodes executed \n--- | --- | ---");
+            if args.verbose {
+                writeln!(output, "Benchmark name | change in estimated runtime | cycles before | cycles after | change in number of opcodes executed | opcodes before | opcodes after \n--- | --- | --- | --- | --- | --- | ---").unwrap();
+            } else {
+                writeln!(output, "Benchmark name | change in estimated runtime | change in number of opcodes executed \n--- | --- | ---").unwrap();
+            }
             nonzero_diff = true;
         }
 
         let n_a = "N/A".to_string();
-        println!(
-            "{} | {} | {}",
-            name,
-            perf_changes.get(**name).unwrap_or(&n_a.clone()),
-            duration_changes
-                .get(**name)
-                .map(|abs_diff| format!(
-                    "{:+} ({:+.1}%)",
-                    abs_diff,
-                    percent_difference(opcodes_before[**name], opcodes_after[**name])
-                ))
-                .unwrap_or(n_a),
-        );
+        let perf_change = perf_changes.get(*name).unwrap_or(&n_a);
+        let opcodes_change = if duration_changes.contains_key(name) {
+            format_opcodes_change(opcodes_before[*name], opcodes_after[*name])
+        } else {
+            n_a.clone()
+        };
+        if args.verbose {
+            let opcodes_before = opcodes_before.get(*name).map(u64::to_string);
+            let opcodes_after = opcodes_after.get(*name).map(u64::to_string);
+            writeln!(
+                output,
+                "{name} | {perf_change} | {} | {} | {opcodes_change} | {} | {}",
+                cycles_before.get(*name).unwrap_or(&n_a),
+                cycles_after.get(*name).unwrap_or(&n_a),
+                opcodes_before.as_ref().unwrap_or(&n_a),
+                opcodes_after.as_ref().unwrap_or(&n_a),
+            )
+            .unwrap();
+        } else {
+            writeln!(output, "{name} | {perf_change} | {opcodes_change}").unwrap();
+        }
     }
 
+    report_shadow_overhead_changes(&mut output, &shadow_overhead_before, &shadow_overhead_after);
+
     if nonzero_diff {
-        println!("\n Changes in number of opcodes executed indicate that the gas price of the benchmark has changed, which causes it run out of gas at a different time. Or that it is behaving completely differently.");
+        writeln!(output, "\n Changes in number of opcodes executed indicate that the gas price of the benchmark has changed, which causes it run out of gas at a different time. Or that it is behaving completely differently.").unwrap();
+    }
+
+    print!("{output}");
+    if let Some(path) = &args.output {
+        write_output(path, &output);
     }
 }
 
-fn percent_difference(a: u64, b: u64) -> f64 {
-    ((b as f64) - (a as f64)) / (a as f64) * 100.0
+/// Compares a single `before` set against several labeled `after` sets, outputting a table with a column group
+/// per candidate. A benchmark is output if it has a significant change for at least one of the candidates.
+fn compare_matrix(args: Args) {
+    let before = if let Some(baseline) = &args.baseline_dir() {
+        BenchmarkResults::from_baseline(baseline)
+    } else {
+        let [iai_before, opcodes_before] = args
+            .positional
+            .into_iter()
+            .take(2)
+            .collect::<Vec<_>>()
+            .try_into()
+            .expect("expected two `before` arguments with `--after`");
+        BenchmarkResults::from_files(&iai_before, &opcodes_before)
+    };
+    let (labels, candidates): (Vec<_>, Vec<_>) = args
+        .after
+        .into_iter()
+        .map(|candidate| {
+            let results = BenchmarkResults::from_files(&candidate.iai, &candidate.opcodes);
+            (candidate.label, results)
+        })
+        .unzip();
+
+    let (before, candidates) = if args.sequential_parsing {
+        let candidates = candidates.into_iter().map(BenchmarkResults::parse);
+        (before.parse(), candidates.collect::<Vec<_>>())
+    } else {
+        thread::scope(|scope| {
+            let before = before.parse_in_parallel(scope);
+            let candidates: Vec<_> = candidates
+                .into_iter()
+                .map(|results| results.parse_in_parallel(scope))
+                .collect();
+            let candidates = candidates.into_iter().map(ParsingResults::join);
+            (before.join(), candidates.collect())
+        })
+    };
+
+    let output = format_matrix(&before, &labels, &candidates, args.confidence, args.verbose);
+    print!("{output}");
+    if let Some(path) = &args.output {
+        write_output(path, &output);
+    }
 }
 
-fn get_name_to_cycles(filename: &str) -> HashMap<String, u64> {
-    parse_iai(BufReader::new(
-        File::open(filename).expect("failed to open file"),
-    ))
-    .map(|x| (x.name, x.cycles))
-    .collect()
+/// Formats the output of the matrix comparison mode (see [`compare_matrix()`]).
+fn format_matrix(
+    before: &ParsedResults,
+    labels: &[String],
+    candidates: &[ParsedResults],
+    confidence: Option<f64>,
+    verbose: bool,
+) -> String {
+    let use_mean_cycles = confidence.is_some();
+    let cycles_before = get_displayed_cycles(&before.iai, use_mean_cycles);
+    let name_to_cycles_before = get_name_to_cycles(&before.iai);
+    let columns: Vec<_> = candidates
+        .iter()
+        .map(|after| {
+            let perf_changes = if let Some(confidence) = confidence {
+                get_significant_sample_changes(&before.iai, &after.iai, confidence)
+            } else {
+                get_significant_changes(&name_to_cycles_before, &get_name_to_cycles(&after.iai))
+            };
+            let opcodes_changes: HashMap<_, _> = after
+                .opcodes
+                .iter()
+                .filter_map(|(name, &opcodes_after)| {
+                    let opcodes_before = *before.opcodes.get(name)?;
+                    (opcodes_before != opcodes_after).then(|| {
+                        let change = format_opcodes_change(opcodes_before, opcodes_after);
+                        (name.clone(), change)
+                    })
+                })
+                .collect();
+            let cycles = get_displayed_cycles(&after.iai, use_mean_cycles);
+            (perf_changes, opcodes_changes, cycles)
+        })
+        .collect();
+
+    let names: BTreeSet<_> = columns
+        .iter()
+        .flat_map(|(perf_changes, opcodes_changes, _)| {
+            perf_changes.keys().chain(opcodes_changes.keys())
+        })
+        .collect();
+
+    let mut output = String::new();
+    if !names.is_empty() {
+        let mut header = "Benchmark name".to_owned();
+        let mut column_count = 1;
+        if verbose {
+            header.push_str(" | cycles before | opcodes before");
+            column_count += 2;
+        }
+        for label in labels {
+            write!(
+                header,
+                " | {label}: change in estimated runtime | {label}: change in number of opcodes executed"
+            )
+            .unwrap();
+            column_count += 2;
+            if verbose {
+                write!(header, " | {label}: cycles | {label}: opcodes").unwrap();
+                column_count += 2;
+            }
+        }
+        writeln!(
+            output,
+            "{header}\n{}",
+            vec!["---"; column_count].join(" | ")
+        )
+        .unwrap();
+    }
+
+    let n_a = "N/A".to_string();
+    for name in &names {
+        let mut row = (*name).clone();
+        if verbose {
+            let opcodes_before = before.opcodes.get(*name).map(u64::to_string);
+            write!(
+                row,
+                " | {} | {}",
+                cycles_before.get(*name).unwrap_or(&n_a),
+                opcodes_before.as_ref().unwrap_or(&n_a)
+            )
+            .unwrap();
+        }
+        for ((perf_changes, opcodes_changes, cycles), after) in columns.iter().zip(candidates) {
+            write!(
+                row,
+                " | {} | {}",
+                perf_changes.get(*name).unwrap_or(&n_a),
+                opcodes_changes.get(*name).unwrap_or(&n_a)
+            )
+            .unwrap();
+            if verbose {
+                let opcodes_after = after.opcodes.get(*name).map(u64::to_string);
+                write!(
+                    row,
+                    " | {} | {}",
+                    cycles.get(*name).unwrap_or(&n_a),
+                    opcodes_after.as_ref().unwrap_or(&n_a)
+                )
+                .unwrap();
+            }
+        }
+        writeln!(output, "{row}").unwrap();
+    }
+
+    let shadow_overhead_before = get_shadow_overheads(&before.iai);
+    for (label, after) in labels.iter().zip(candidates) {
+        let shadow_overhead_after = get_shadow_overheads(&after.iai);
+        let mut candidate_output = String::new();
+        report_shadow_overhead_changes(
+            &mut candidate_output,
+            &shadow_overhead_before,
+            &shadow_overhead_after,
+        );
+        if !candidate_output.is_empty() {
+            write!(
+                output,
+                "\nShadowing overhead changes for `{label}`:\n{candidate_output}"
+            )
+            .unwrap();
+        }
+    }
+
+    if !names.is_empty() {
+        writeln!(output, "\n Changes in number of opcodes executed indicate that the gas price of the benchmark has changed, which causes it run out of gas at a different time. Or that it is behaving completely differently.").unwrap();
+    }
+
+    output
+}
+
+/// Writes the comparison output to the specified file, creating parent directories if necessary.
+fn write_output(path: &Path, output: &str) {
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        fs::create_dir_all(parent)
+            .unwrap_or_else(|err| panic!("failed to create directory {parent:?}: {err}"));
+    }
+    fs::write(path, output).unwrap_or_else(|err| panic!("failed to write {path:?}: {err}"));
+}
+
+/// Returns shadowing overhead (in percent) for each benchmark having a shadowed counterpart.
+fn get_shadow_overheads(samples: &HashMap<String, Samples>) -> BTreeMap<String, f64> {
+    samples
+        .iter()
+        .filter_map(|(name, shadowed)| {
+            let base_name = name.strip_suffix(SHADOWED_SUFFIX)?;
+            let base = samples.get(base_name)?;
+            let overhead = percent_difference(base.mean(), shadowed.mean())?;
+            Some((base_name.to_owned(), overhead))
+        })
+        .collect()
 }
 
-fn get_name_to_opcodes(filename: &str) -> HashMap<String, u64> {
-    BufReader::new(File::open(filename).expect("failed to open file"))
+/// Reports benchmarks for which shadowing overhead has changed by more than [`SIGNIFICANT_PERCENT_DIFFERENCE`]
+/// percentage points, or which didn't have a shadowed counterpart before.
+fn report_shadow_overhead_changes(
+    output: &mut String,
+    before: &BTreeMap<String, f64>,
+    after: &BTreeMap<String, f64>,
+) {
+    let mut has_changes = false;
+    for (name, &overhead_after) in after {
+        let overhead_before = before.get(name).copied();
+        let is_significant = match overhead_before {
+            Some(overhead_before) => {
+                (overhead_after - overhead_before).abs() > SIGNIFICANT_PERCENT_DIFFERENCE
+            }
+            None => true,
+        };
+        if !is_significant {
+            continue;
+        }
+
+        // write the header before writing the first line of diff
+        if !has_changes {
+            writeln!(output, "\nBenchmark name | shadowing overhead before | shadowing overhead after \n--- | --- | ---").unwrap();
+            has_changes = true;
+        }
+        let overhead_before = overhead_before
+            .map(|overhead| format!("{overhead:+.1}%"))
+            .unwrap_or_else(|| "N/A".to_owned());
+        writeln!(output, "{name} | {overhead_before} | {overhead_after:+.1}%").unwrap();
+    }
+}
+
+/// Returns the relative difference between `a` and `b` in percent. Returns `None` if the difference is not meaningful
+/// because `a` is zero (or close to zero) and `b` is not. Equal near-zero values are considered to have zero difference.
+fn percent_difference(a: f64, b: f64) -> Option<f64> {
+    if a.abs() < f64::EPSILON {
+        return ((b - a).abs() < f64::EPSILON).then_some(0.0);
+    }
+    let diff = (b - a) / a * 100.0;
+    diff.is_finite().then_some(diff)
+}
+
+/// Formats an absolute and relative change in the number of executed opcodes.
+fn format_opcodes_change(before: u64, after: u64) -> String {
+    let abs_diff = (after as i64) - (before as i64);
+    let diff = percent_difference(before as f64, after as f64);
+    format!("{abs_diff:+} ({})", format_percent_difference(diff))
+}
+
+fn format_percent_difference(diff: Option<f64>) -> String {
+    diff.map_or_else(
+        || ZERO_BASELINE_CHANGE.to_owned(),
+        |diff| format!("{diff:+.1}%"),
+    )
+}
+
+fn get_significant_changes(
+    before: &HashMap<String, u64>,
+    after: &HashMap<String, u64>,
+) -> HashMap<String, String> {
+    before
+        .keys()
+        .collect::<HashSet<_>>()
+        .intersection(&after.keys().collect())
+        .filter_map(|&name| {
+            let diff = percent_difference(before[name] as f64, after[name] as f64);
+            // Changes with a zero baseline are always considered significant.
+            let is_significant = match diff {
+                Some(diff) => diff.abs() > SIGNIFICANT_PERCENT_DIFFERENCE,
+                None => true,
+            };
+            if is_significant {
+                Some((name.clone(), format_percent_difference(diff)))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Compares means of the repeated samples. A change is significant if it's significant in the default mode,
+/// and the difference between means exceeds the confidence interval.
+fn get_significant_sample_changes(
+    before: &HashMap<String, Samples>,
+    after: &HashMap<String, Samples>,
+    confidence: f64,
+) -> HashMap<String, String> {
+    before
+        .keys()
+        .collect::<HashSet<_>>()
+        .intersection(&after.keys().collect())
+        .filter_map(|&name| {
+            let (before, after) = (&before[name], &after[name]);
+            let Some(diff) = percent_difference(before.mean(), after.mean()) else {
+                // Changes with a zero baseline are always considered significant.
+                return Some((name.clone(), ZERO_BASELINE_CHANGE.to_owned()));
+            };
+            let abs_diff = (after.mean() - before.mean()).abs();
+            let interval = confidence * before.std_error_of_difference(after);
+            if diff.abs() > SIGNIFICANT_PERCENT_DIFFERENCE && abs_diff > interval {
+                let interval_percent = interval / before.mean() * 100.0;
+                Some((
+                    name.clone(),
+                    format!("{diff:+.1}% (±{interval_percent:.1}%)"),
+                ))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Repeated measurements of a single benchmark.
+#[derive(Debug, Default)]
+struct Samples(Vec<u64>);
+
+impl Samples {
+    fn mean(&self) -> f64 {
+        self.0.iter().map(|&x| x as f64).sum::<f64>() / self.0.len() as f64
+    }
+
+    /// Returns the sample standard deviation, or 0 if there are less than 2 samples.
+    fn std_dev(&self) -> f64 {
+        if self.0.len() < 2 {
+            return 0.0;
+        }
+        let mean = self.mean();
+        let sum_of_squares: f64 = self.0.iter().map(|&x| (x as f64 - mean).powi(2)).sum();
+        (sum_of_squares / (self.0.len() - 1) as f64).sqrt()
+    }
+
+    /// Returns the standard error of the difference between means of `self` and `other`.
+    fn std_error_of_difference(&self, other: &Self) -> f64 {
+        let self_variance = self.std_dev().powi(2) / self.0.len() as f64;
+        let other_variance = other.std_dev().powi(2) / other.0.len() as f64;
+        (self_variance + other_variance).sqrt()
+    }
+}
+
+/// Benchmark results for one side of the comparison.
+struct BenchmarkResults {
+    /// IAI outputs; may contain repeated measurements.
+    iai: Vec<Box<dyn BufRead + Send>>,
+    /// Opcode counts.
+    opcodes: Box<dyn BufRead + Send>,
+}
+
+/// Parsed [`BenchmarkResults`].
+#[derive(Debug)]
+struct ParsedResults {
+    iai: HashMap<String, Samples>,
+    opcodes: HashMap<String, u64>,
+}
+
+/// [`ParsedResults`] being parsed on scoped threads.
+struct ParsingResults<'scope> {
+    iai: thread::ScopedJoinHandle<'scope, HashMap<String, Samples>>,
+    opcodes: thread::ScopedJoinHandle<'scope, HashMap<String, u64>>,
+}
+
+impl ParsingResults<'_> {
+    fn join(self) -> ParsedResults {
+        ParsedResults {
+            iai: self.iai.join().expect("parsing IAI outputs panicked"),
+            opcodes: self.opcodes.join().expect("parsing opcodes panicked"),
+        }
+    }
+}
+
+impl BenchmarkResults {
+    /// Opens results from files. `iai` may list several comma-separated files with repeated measurements.
+    fn from_files(iai: &str, opcodes: &str) -> Self {
+        Self {
+            iai: iai.split(',').map(open_file).collect(),
+            opcodes: open_file(opcodes),
+        }
+    }
+
+    fn parse(self) -> ParsedResults {
+        ParsedResults {
+            iai: get_name_to_cycle_samples(self.iai),
+            opcodes: get_name_to_opcodes(self.opcodes),
+        }
+    }
+
+    /// Parses IAI outputs and opcode counts on separate threads.
+    fn parse_in_parallel<'scope>(
+        self,
+        scope: &'scope thread::Scope<'scope, '_>,
+    ) -> ParsingResults<'scope> {
+        let Self { iai, opcodes } = self;
+        ParsingResults {
+            iai: scope.spawn(move || get_name_to_cycle_samples(iai)),
+            opcodes: scope.spawn(move || get_name_to_opcodes(opcodes)),
+        }
+    }
+
+    /// Loads results from the baseline directory containing `iai` and `opcodes` files.
+    fn from_baseline(baseline: &Path) -> Self {
+        assert!(
+            baseline.is_dir(),
+            "baseline {baseline:?} is not a directory; it must contain `iai` and `opcodes` files \
+             with the baseline results, or be a git ref with such a directory in `--baseline-root`"
+        );
+        Self {
+            iai: vec![open_file(baseline.join("iai"))],
+            opcodes: open_file(baseline.join("opcodes")),
+        }
+    }
+}
+
+fn open_file(path: impl AsRef<Path>) -> Box<dyn BufRead + Send> {
+    let path = path.as_ref();
+    let file = File::open(path).unwrap_or_else(|err| panic!("failed to open {path:?}: {err}"));
+    Box::new(BufReader::new(file))
+}
+
+/// Returns cycle counts for benchmarks formatted for output. If `use_mean` is set, the mean of samples is used
+/// (consistent with [`get_significant_sample_changes()`]); otherwise, the last sample is used (consistent with
+/// [`get_significant_changes()`]).
+fn get_displayed_cycles(
+    samples: &HashMap<String, Samples>,
+    use_mean: bool,
+) -> HashMap<String, String> {
+    samples
+        .iter()
+        .filter_map(|(name, samples)| {
+            let cycles = if use_mean {
+                format!("{:.0}", samples.mean())
+            } else {
+                samples.0.last()?.to_string()
+            };
+            Some((name.clone(), cycles))
+        })
+        .collect()
+}
+
+/// Uses the last sample for each benchmark.
+fn get_name_to_cycles(samples: &HashMap<String, Samples>) -> HashMap<String, u64> {
+    samples
+        .iter()
+        .filter_map(|(name, samples)| Some((name.clone(), *samples.0.last()?)))
+        .collect()
+}
+
+/// Reads cycle samples from IAI outputs. Each output may contain multiple results for the same benchmark
+/// (e.g., concatenated outputs of several runs).
+fn get_name_to_cycle_samples(iai: Vec<Box<dyn BufRead + Send>>) -> HashMap<String, Samples> {
+    let mut samples = HashMap::<_, Samples>::new();
+    for reader in iai {
+        for result in parse_iai(reader) {
+            samples
+                .entry(result.name)
+                .or_default()
+                .0
+                .push(result.cycles);
+        }
+    }
+    samples
+}
+
+fn get_name_to_opcodes(reader: Box<dyn BufRead + Send>) -> HashMap<String, u64> {
+    reader
         .lines()
         .map(|line| {
             let line = line.unwrap();
@@ -105,3 +718,218 @@ fn get_name_to_opcodes(filename: &str) -> HashMap<String, u64> {
         })
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolving_baseline_dir() {
+        let root = std::env::temp_dir().join(format!("iai_baselines_{}", std::process::id()));
+        fs::create_dir_all(root.join("main")).unwrap();
+        fs::create_dir_all(root.join("feature/test")).unwrap();
+
+        let args = |baseline: &str, root: Option<&Path>| Args {
+            baseline: Some(baseline.to_owned()),
+            baseline_root: root.map(Path::to_owned),
+            ..Args::default()
+        };
+        assert_eq!(
+            args("main", Some(&root)).baseline_dir(),
+            Some(root.join("main"))
+        );
+        assert_eq!(
+            args("feature/test", Some(&root)).baseline_dir(),
+            Some(root.join("feature/test"))
+        );
+        // Unknown refs and baselines without a root are treated as paths.
+        assert_eq!(
+            args("other", Some(&root)).baseline_dir(),
+            Some(PathBuf::from("other"))
+        );
+        assert_eq!(
+            args("main", None).baseline_dir(),
+            Some(PathBuf::from("main"))
+        );
+        assert_eq!(Args::default().baseline_dir(), None);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    fn samples(iai: &[(&str, &[u64])]) -> HashMap<String, Samples> {
+        iai.iter()
+            .map(|&(name, samples)| (name.to_owned(), Samples(samples.to_vec())))
+            .collect()
+    }
+
+    fn parsed_results(iai: &[(&str, &[u64])], opcodes: &[(&str, u64)]) -> ParsedResults {
+        ParsedResults {
+            iai: samples(iai),
+            opcodes: opcodes
+                .iter()
+                .map(|&(name, count)| (name.to_owned(), count))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn samples_statistics() {
+        let samples = Samples(vec![10, 12, 14]);
+        assert_eq!(samples.mean(), 12.0);
+        assert_eq!(samples.std_dev(), 2.0);
+        let expected_error = (4.0_f64 / 3.0 * 2.0).sqrt();
+        assert!((samples.std_error_of_difference(&samples) - expected_error).abs() < 1e-9);
+
+        let single_sample = Samples(vec![5]);
+        assert_eq!(single_sample.mean(), 5.0);
+        assert_eq!(single_sample.std_dev(), 0.0);
+        assert_eq!(single_sample.std_error_of_difference(&single_sample), 0.0);
+    }
+
+    #[test]
+    fn percent_difference_with_zero_baseline() {
+        assert_eq!(percent_difference(100.0, 110.0), Some(10.0));
+        assert_eq!(percent_difference(100.0, 50.0), Some(-50.0));
+        assert_eq!(percent_difference(0.0, 0.0), Some(0.0));
+        assert_eq!(percent_difference(0.0, 5.0), None);
+
+        assert_eq!(format_opcodes_change(100, 90), "-10 (-10.0%)");
+        assert_eq!(format_opcodes_change(0, 5), "+5 (new)");
+        assert_eq!(format_opcodes_change(0, 0), "+0 (+0.0%)");
+    }
+
+    #[test]
+    fn significant_changes() {
+        let before = HashMap::from([
+            ("small".to_owned(), 100),
+            ("large".to_owned(), 100),
+            ("zero".to_owned(), 0),
+            ("removed".to_owned(), 100),
+        ]);
+        let after = HashMap::from([
+            ("small".to_owned(), 101),
+            ("large".to_owned(), 110),
+            ("zero".to_owned(), 5),
+            ("added".to_owned(), 100),
+        ]);
+        let changes = get_significant_changes(&before, &after);
+        assert_eq!(
+            changes,
+            HashMap::from([
+                ("large".to_owned(), "+10.0%".to_owned()),
+                ("zero".to_owned(), "new".to_owned()),
+            ])
+        );
+    }
+
+    #[test]
+    fn significant_sample_changes() {
+        let before = samples(&[
+            ("noisy", &[100, 80, 120]),
+            ("stable", &[100, 100, 100]),
+            ("zero", &[0, 0]),
+        ]);
+        let after = samples(&[
+            ("noisy", &[110, 90, 130]),
+            ("stable", &[110, 110, 110]),
+            ("zero", &[1, 1]),
+        ]);
+        let changes = get_significant_sample_changes(&before, &after, 2.0);
+        assert_eq!(
+            changes,
+            HashMap::from([
+                ("stable".to_owned(), "+10.0% (±0.0%)".to_owned()),
+                ("zero".to_owned(), "new".to_owned()),
+            ])
+        );
+
+        // With zero confidence, only the relative change threshold applies.
+        let changes = get_significant_sample_changes(&before, &after, 0.0);
+        assert_eq!(changes["noisy"], "+10.0% (±0.0%)");
+    }
+
+    #[test]
+    fn matrix_comparison() {
+        let before = parsed_results(
+            &[("changed", &[100]), ("unchanged", &[100])],
+            &[("changed", 10), ("unchanged", 10)],
+        );
+        let candidates = [
+            parsed_results(
+                &[("changed", &[90]), ("unchanged", &[100])],
+                &[("changed", 10), ("unchanged", 10)],
+            ),
+            parsed_results(
+                &[("changed", &[101]), ("unchanged", &[100])],
+                &[("changed", 12), ("unchanged", 10)],
+            ),
+        ];
+        let labels = ["fast".to_owned(), "slow".to_owned()];
+
+        let output = format_matrix(&before, &labels, &candidates, None, false);
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(
+            lines[0],
+            "Benchmark name | fast: change in estimated runtime | fast: change in number of opcodes executed \
+             | slow: change in estimated runtime | slow: change in number of opcodes executed"
+        );
+        assert_eq!(lines[1], "--- | --- | --- | --- | ---");
+        assert_eq!(lines[2], "changed | -10.0% | N/A | N/A | +2 (+20.0%)");
+        assert!(!output.contains("unchanged"), "{output}");
+
+        let output = format_matrix(&before, &labels, &candidates, None, true);
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(lines[1], ["---"; 11].join(" | "));
+        assert_eq!(
+            lines[2],
+            "changed | 100 | 10 | -10.0% | N/A | 90 | 10 | N/A | +2 (+20.0%) | 101 | 12"
+        );
+
+        // No changes at all.
+        let same_results = parsed_results(
+            &[("changed", &[100]), ("unchanged", &[100])],
+            &[("changed", 10), ("unchanged", 10)],
+        );
+        let output = format_matrix(&before, &labels[..1], &[same_results], None, false);
+        assert_eq!(output, "");
+    }
+
+    #[test]
+    fn shadow_overhead_report() {
+        let iai = samples(&[
+            ("a", &[100]),
+            ("a_shadowed", &[150]),
+            ("b", &[100]),
+            ("b_shadowed", &[101]),
+            ("orphan_shadowed", &[100]),
+        ]);
+        let overheads = get_shadow_overheads(&iai);
+        assert_eq!(
+            overheads,
+            BTreeMap::from([("a".to_owned(), 50.0), ("b".to_owned(), 1.0)])
+        );
+
+        let before = BTreeMap::from([("a".to_owned(), 49.0), ("b".to_owned(), 10.0)]);
+        let after = BTreeMap::from([
+            ("a".to_owned(), 50.0),
+            ("b".to_owned(), 1.0),
+            ("c".to_owned(), 5.0),
+        ]);
+        let mut output = String::new();
+        report_shadow_overhead_changes(&mut output, &before, &after);
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(
+            lines[1..],
+            [
+                "Benchmark name | shadowing overhead before | shadowing overhead after ",
+                "--- | --- | ---",
+                "b | +10.0% | +1.0%",
+                "c | N/A | +5.0%",
+            ]
+        );
+
+        let mut output = String::new();
+        report_shadow_overhead_changes(&mut output, &after, &after);
+        assert_eq!(output, "");
+    }
+}
@@ -12,6 +12,7 @@ use zksync_multivm::{
     vm_fast, vm_latest,
     vm_latest::{constants::BATCH_COMPUTATIONAL_GAS_LIMIT, HistoryEnabled},
     zk_evm_latest::ethereum_types::{Address, U256},
+    ShadowedFastVm,
 };
 use zksync_types::{
     block::L2BlockHasher, fee_model::BatchFeeInput, helpers::unix_timestamp_ms,
@@ -38,6 +39,7 @@ static STORAGE: Lazy<InMemoryStorage> = Lazy::new(|| {
 pub enum VmLabel {
     Fast,
     Legacy,
+    Shadowed,
 }
 
 impl VmLabel {
@@ -46,6 +48,7 @@ impl VmLabel {
         match self {
             Self::Fast => "fast",
             Self::Legacy => "legacy",
+            Self::Shadowed => "shadowed",
         }
     }
 
@@ -54,6 +57,7 @@ impl VmLabel {
         match self {
             Self::Fast => "",
             Self::Legacy => "/legacy",
+            Self::Shadowed => "/shadowed",
         }
     }
 }
@@ -111,12 +115,44 @@ impl BenchmarkingVmFactory for Legacy {
     }
 }
 
+/// Factory for the new / fast VM shadowed by the legacy VM. Used to measure shadowing overhead.
+#[derive(Debug)]
+pub struct Shadowed;
+
+impl BenchmarkingVmFactory for Shadowed {
+    const LABEL: VmLabel = VmLabel::Shadowed;
+
+    type Instance = ShadowedFastVm<&'static InMemoryStorage>;
+
+    fn create(
+        batch_env: L1BatchEnv,
+        system_env: SystemEnv,
+        storage: &'static InMemoryStorage,
+    ) -> Self::Instance {
+        let storage = StorageView::new(storage).to_rc_ptr();
+        ShadowedFastVm::new(batch_env, system_env, storage)
+    }
+}
+
+/// Batch timestamp used by [`BenchmarkingVm::deterministic()`].
+const DETERMINISTIC_TIMESTAMP: u64 = 1_700_000_000_000;
+
 #[derive(Debug)]
 pub struct BenchmarkingVm<VM: BenchmarkingVmFactory>(VM::Instance);
 
 impl<VM: BenchmarkingVmFactory> Default for BenchmarkingVm<VM> {
     fn default() -> Self {
-        let timestamp = unix_timestamp_ms();
+        Self::with_timestamp(unix_timestamp_ms())
+    }
+}
+
+impl<VM: BenchmarkingVmFactory> BenchmarkingVm<VM> {
+    /// Creates a VM with a fixed batch timestamp, so that execution doesn't depend on the wall clock.
+    pub fn deterministic() -> Self {
+        Self::with_timestamp(DETERMINISTIC_TIMESTAMP)
+    }
+
+    fn with_timestamp(timestamp: u64) -> Self {
         Self(VM::create(
             L1BatchEnv {
                 previous_batch_hash: None,
@@ -147,9 +183,7 @@ impl<VM: BenchmarkingVmFactory> Default for BenchmarkingVm<VM> {
             &STORAGE,
         ))
     }
-}
 
-impl<VM: BenchmarkingVmFactory> BenchmarkingVm<VM> {
     pub fn run_transaction(&mut self, tx: &Transaction) -> VmExecutionResultAndLogs {
         self.0.push_transaction(tx.clone());
         self.0.execute(VmExecutionMode::OneTx)
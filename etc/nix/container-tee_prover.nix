@@ -36,6 +36,16 @@ nixsgxLib.mkSGXContainer {
         TEE_PROVER_INITIAL_RETRY_BACKOFF_SEC.passthrough = true;
         TEE_PROVER_RETRY_BACKOFF_MULTIPLIER.passthrough = true;
         TEE_PROVER_MAX_BACKOFF_SEC.passthrough = true;
+        TEE_PROVER_SUBMIT_PROOF_ENDPOINTS.passthrough = true;
+        TEE_PROVER_ADDITIONAL_SIGNING_KEYS.passthrough = true;
+        TEE_PROVER_KEY_ROTATION_POLICY.passthrough = true;
+        TEE_PROVER_PROXY_URL.passthrough = true;
+        TEE_PROVER_PROXY_USERNAME.passthrough = true;
+        TEE_PROVER_PROXY_PASSWORD.passthrough = true;
+        TEE_PROVER_DRY_RUN.passthrough = true;
+        TEE_PROVER_IDLE_BACKOFF_MULTIPLIER.passthrough = true;
+        TEE_PROVER_MAX_IDLE_BACKOFF_SEC.passthrough = true;
+        TEE_PROVER_VERIFICATION_CACHE_SIZE.passthrough = true;
         API_PROMETHEUS_LISTENER_PORT.passthrough = true;
         API_PROMETHEUS_PUSHGATEWAY_URL.passthrough = true;
         API_PROMETHEUS_PUSH_INTERVAL_MS.passthrough = true;
@@ -1,9 +1,84 @@
-use std::sync::Arc;
+use std::{error, fmt, future::Future, sync::Arc, time::Duration};
 
+use anyhow::Context as _;
 use serde::{de::DeserializeOwned, Serialize};
+use tokio::sync::watch;
 use zksync_object_store::ObjectStore;
 use zksync_prover_dal::{ConnectionPool, Prover};
 
+/// Maximum length of the response body snippet included into [`ApiError::Deserialization`].
+const MAX_BODY_SNIPPET_LEN: usize = 512;
+
+/// Errors that can occur when sending requests to the prover API.
+#[derive(Debug)]
+pub(crate) enum ApiError {
+    /// Error sending the request or receiving the response, or an error HTTP status.
+    Http(reqwest::Error),
+    /// Successful response has an unexpected body.
+    Deserialization {
+        err: serde_json::Error,
+        /// Response body, truncated to a reasonable length.
+        body_snippet: String,
+    },
+    /// Request was aborted because the stop signal was received.
+    Cancelled,
+}
+
+impl From<reqwest::Error> for ApiError {
+    fn from(err: reqwest::Error) -> Self {
+        Self::Http(err)
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Http(err) => fmt::Display::fmt(err, formatter),
+            Self::Deserialization { err, body_snippet } => {
+                write!(
+                    formatter,
+                    "failed deserializing response: {err}; response body: {body_snippet}"
+                )
+            }
+            Self::Cancelled => formatter.write_str("request was cancelled by the stop signal"),
+        }
+    }
+}
+
+impl error::Error for ApiError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Http(err) => Some(err),
+            Self::Deserialization { err, .. } => Some(err),
+            Self::Cancelled => None,
+        }
+    }
+}
+
+/// Drives the `request` future to completion unless the stop signal is received first, in which case
+/// the request is dropped and [`ApiError::Cancelled`] is returned.
+pub(crate) async fn cancel_on_stop<T>(
+    stop_receiver: &mut watch::Receiver<bool>,
+    request: impl Future<Output = Result<T, ApiError>>,
+) -> Result<T, ApiError> {
+    tokio::select! {
+        res = request => res,
+        // If the stop sender is dropped, `wait_for()` errors; in this case, the request is never cancelled.
+        Ok(_) = stop_receiver.wait_for(|&stop| stop) => Err(ApiError::Cancelled),
+    }
+}
+
+fn body_snippet(body: &str) -> String {
+    if body.len() <= MAX_BODY_SNIPPET_LEN {
+        return body.to_owned();
+    }
+    let mut snippet_len = MAX_BODY_SNIPPET_LEN;
+    while !body.is_char_boundary(snippet_len) {
+        snippet_len -= 1;
+    }
+    format!("{}... ({} bytes total)", &body[..snippet_len], body.len())
+}
+
 /// A tiny wrapper over the reqwest client that also stores
 /// the objects commonly needed when interacting with prover API.
 #[derive(Debug)]
@@ -14,38 +89,136 @@ pub(crate) struct ProverApiClient {
     pub(crate) client: reqwest::Client,
 }
 
-impl ProverApiClient {
-    pub(crate) fn new(
+/// Basic authentication credentials for an HTTP proxy.
+#[derive(Clone)]
+pub(crate) struct ProxyCredentials {
+    pub(crate) username: String,
+    pub(crate) password: String,
+}
+
+impl fmt::Debug for ProxyCredentials {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("ProxyCredentials")
+            .field("username", &self.username)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Builder for [`ProverApiClient`]s. Validates the configuration and configures the underlying HTTP client,
+/// so that misconfiguration is caught on startup.
+#[derive(Debug, Clone)]
+pub(crate) struct ProverApiClientBuilder {
+    base_url: String,
+    request_timeout: Option<Duration>,
+    proxy: Option<(String, Option<ProxyCredentials>)>,
+}
+
+impl ProverApiClientBuilder {
+    pub(crate) fn new(base_url: String) -> Self {
+        Self {
+            base_url,
+            request_timeout: None,
+            proxy: None,
+        }
+    }
+
+    /// Sets the timeout for each request to the prover API. By default, requests don't time out.
+    pub(crate) fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = Some(timeout);
+        self
+    }
+
+    /// Sends all requests to the prover API through the HTTP / HTTPS proxy at `proxy_url`, optionally authenticating
+    /// with basic auth. By default, requests are sent directly.
+    pub(crate) fn with_proxy(
+        mut self,
+        proxy_url: String,
+        credentials: Option<ProxyCredentials>,
+    ) -> Self {
+        self.proxy = Some((proxy_url, credentials));
+        self
+    }
+
+    /// Builds a client for the specified endpoint (e.g., `/submit_proof`) of the prover API.
+    pub(crate) fn build(
+        &self,
         blob_store: Arc<dyn ObjectStore>,
         pool: ConnectionPool<Prover>,
-        api_url: String,
-    ) -> Self {
-        Self {
+        endpoint_path: &str,
+    ) -> anyhow::Result<ProverApiClient> {
+        let base_url = reqwest::Url::parse(&self.base_url)
+            .with_context(|| format!("prover API URL `{}` is invalid", self.base_url))?;
+        anyhow::ensure!(
+            matches!(base_url.scheme(), "http" | "https"),
+            "prover API URL `{}` has unsupported scheme; expected http or https",
+            self.base_url
+        );
+
+        let mut client = reqwest::Client::builder();
+        if let Some(timeout) = self.request_timeout {
+            anyhow::ensure!(!timeout.is_zero(), "prover API request timeout is zero");
+            client = client.timeout(timeout);
+        }
+        if let Some((proxy_url, credentials)) = &self.proxy {
+            client = client.proxy(Self::build_proxy(proxy_url, credentials.as_ref())?);
+        }
+        let client = client.build().context("failed building HTTP client")?;
+
+        let base_url = self.base_url.trim_end_matches('/');
+        Ok(ProverApiClient {
             blob_store,
             pool,
-            api_url,
-            client: reqwest::Client::new(),
+            api_url: format!("{base_url}{endpoint_path}"),
+            client,
+        })
+    }
+
+    fn build_proxy(
+        proxy_url: &str,
+        credentials: Option<&ProxyCredentials>,
+    ) -> anyhow::Result<reqwest::Proxy> {
+        let parsed_url = reqwest::Url::parse(proxy_url)
+            .with_context(|| format!("proxy URL `{proxy_url}` is invalid"))?;
+        anyhow::ensure!(
+            matches!(parsed_url.scheme(), "http" | "https"),
+            "proxy URL `{proxy_url}` has unsupported scheme; expected http or https"
+        );
+        let mut proxy = reqwest::Proxy::all(parsed_url)
+            .with_context(|| format!("proxy URL `{proxy_url}` is invalid"))?;
+        if let Some(credentials) = credentials {
+            proxy = proxy.basic_auth(&credentials.username, &credentials.password);
         }
+        Ok(proxy)
     }
+}
 
+impl ProverApiClient {
+    /// Sends a request to the prover API. If the response body cannot be deserialized, the returned error
+    /// contains a snippet of the body.
     pub(crate) async fn send_http_request<Req, Resp>(
         &self,
         request: Req,
         endpoint: &str,
-    ) -> Result<Resp, reqwest::Error>
+    ) -> Result<Resp, ApiError>
     where
         Req: Serialize,
         Resp: DeserializeOwned,
     {
         tracing::info!("Sending request to {}", endpoint);
 
-        self.client
+        let body = self
+            .client
             .post(endpoint)
             .json(&request)
             .send()
             .await?
             .error_for_status()?
-            .json::<Resp>()
-            .await
+            .text()
+            .await?;
+        serde_json::from_str(&body).map_err(|err| ApiError::Deserialization {
+            err,
+            body_snippet: body_snippet(&body),
+        })
     }
 }
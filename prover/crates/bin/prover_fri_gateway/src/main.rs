@@ -2,10 +2,11 @@ use std::time::Duration;
 
 use anyhow::Context as _;
 use clap::Parser;
+use client::{ProverApiClientBuilder, ProxyCredentials};
 use proof_gen_data_fetcher::ProofGenDataFetcher;
 use proof_submitter::ProofSubmitter;
 use tokio::sync::{oneshot, watch};
-use traits::PeriodicApi as _;
+use traits::{CircuitBreakerConfig, PeriodicApi as _};
 use zksync_core_leftovers::temp_config_store::{load_database_secrets, load_general_config};
 use zksync_env_config::object_store::ProverObjectStoreConfig;
 use zksync_object_store::ObjectStoreFactory;
@@ -19,6 +20,9 @@ mod proof_gen_data_fetcher;
 mod proof_submitter;
 mod traits;
 
+/// Environment variable containing the password for the proxy basic authentication (see `--proxy-username`).
+const PROXY_PASSWORD_ENV_VAR: &str = "FRI_PROVER_GATEWAY_PROXY_PASSWORD";
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let opt = Cli::parse();
@@ -52,16 +56,47 @@ async fn main() -> anyhow::Result<()> {
     );
     let store_factory = ObjectStoreFactory::new(object_store_config.0);
 
+    let poll_duration = config.api_poll_duration();
+    anyhow::ensure!(!poll_duration.is_zero(), "prover API poll duration is zero");
+    let mut client_builder = ProverApiClientBuilder::new(config.api_url.clone());
+    if let Some(timeout_secs) = opt.request_timeout_secs {
+        client_builder = client_builder.with_request_timeout(Duration::from_secs(timeout_secs));
+    }
+    if let Some(proxy_url) = opt.proxy_url {
+        let credentials = opt.proxy_username.map(|username| ProxyCredentials {
+            username,
+            // The password is read from the environment so that it doesn't leak via the command line.
+            password: std::env::var(PROXY_PASSWORD_ENV_VAR).unwrap_or_default(),
+        });
+        client_builder = client_builder.with_proxy(proxy_url, credentials);
+    }
+
     let proof_submitter = ProofSubmitter::new(
+        &client_builder,
         store_factory.create_store().await?,
-        config.api_url.clone(),
         pool.clone(),
-    );
-    let proof_gen_data_fetcher = ProofGenDataFetcher::new(
-        store_factory.create_store().await?,
-        config.api_url.clone(),
-        pool,
-    );
+    )
+    .context("failed creating proof submitter")?;
+    let proof_gen_data_fetcher =
+        ProofGenDataFetcher::new(&client_builder, store_factory.create_store().await?, pool)
+            .context("failed creating proof generation data fetcher")?;
+
+    if opt.run_once {
+        tracing::info!("Running a single Fri Prover Gateway cycle");
+        // The sender is dropped immediately, so the one-shot cycle is never cancelled.
+        let mut stop_receiver = watch::channel(false).1;
+        let fetcher_outcome = proof_gen_data_fetcher
+            .run_once(&mut stop_receiver)
+            .await
+            .context("failed fetching proof generation data")?;
+        tracing::info!("Proof generation data fetcher: {fetcher_outcome:?}");
+        let submitter_outcome = proof_submitter
+            .run_once(&mut stop_receiver)
+            .await
+            .context("failed submitting proof")?;
+        tracing::info!("Proof submitter: {submitter_outcome:?}");
+        return Ok(());
+    }
 
     let (stop_sender, stop_receiver) = watch::channel(false);
 
@@ -75,14 +110,22 @@ async fn main() -> anyhow::Result<()> {
     .context("Error setting Ctrl+C handler")?;
 
     tracing::info!("Starting Fri Prover Gateway");
+    let circuit_breaker = CircuitBreakerConfig {
+        failure_threshold: opt.circuit_breaker_failure_threshold,
+        open_interval: Duration::from_secs(opt.circuit_breaker_open_interval_secs),
+    };
 
     let tasks = vec![
         tokio::spawn(
             PrometheusExporterConfig::pull(config.prometheus_listener_port)
                 .run(stop_receiver.clone()),
         ),
-        tokio::spawn(proof_gen_data_fetcher.run(config.api_poll_duration(), stop_receiver.clone())),
-        tokio::spawn(proof_submitter.run(config.api_poll_duration(), stop_receiver)),
+        tokio::spawn(proof_gen_data_fetcher.run(
+            poll_duration,
+            circuit_breaker,
+            stop_receiver.clone(),
+        )),
+        tokio::spawn(proof_submitter.run(poll_duration, circuit_breaker, stop_receiver)),
     ];
 
     let mut tasks = ManagedTasks::new(tasks);
@@ -104,4 +147,25 @@ pub(crate) struct Cli {
     pub(crate) config_path: Option<std::path::PathBuf>,
     #[arg(long)]
     pub(crate) secrets_path: Option<std::path::PathBuf>,
+    /// Fetch proof generation data and submit a proof at most once, and then exit instead of polling the API
+    /// periodically. Useful for testing and manual recovery.
+    #[arg(long)]
+    pub(crate) run_once: bool,
+    /// Number of consecutive failed requests after which the API is polled with a longer interval
+    /// (`--circuit-breaker-open-interval-secs`) until a request succeeds.
+    #[arg(long, default_value_t = 10)]
+    pub(crate) circuit_breaker_failure_threshold: usize,
+    /// Interval between API requests used after `--circuit-breaker-failure-threshold` consecutive failures.
+    #[arg(long, default_value_t = 300)]
+    pub(crate) circuit_breaker_open_interval_secs: u64,
+    /// Timeout for each request to the prover API. If not specified, requests don't time out.
+    #[arg(long)]
+    pub(crate) request_timeout_secs: Option<u64>,
+    /// URL of the HTTP / HTTPS proxy to send prover API requests through. If not specified, requests are sent directly.
+    #[arg(long)]
+    pub(crate) proxy_url: Option<String>,
+    /// Username for the proxy basic authentication. The password is read from
+    /// the `FRI_PROVER_GATEWAY_PROXY_PASSWORD` env variable. Ignored if `--proxy-url` is not specified.
+    #[arg(long)]
+    pub(crate) proxy_username: Option<String>,
 }
@@ -1,10 +1,32 @@
-use vise::{Counter, LabeledFamily, Metrics};
+use vise::{Counter, EncodeLabelValue, Gauge, LabeledFamily, Metrics};
+
+/// Kind of the gateway service shutdown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EncodeLabelValue)]
+#[metrics(rename_all = "snake_case")]
+pub(crate) enum ShutdownKind {
+    /// Shutdown between request cycles; no work was abandoned.
+    Clean,
+    /// Shutdown interrupted an in-flight request.
+    Forced,
+}
 
 #[derive(Debug, Metrics)]
 #[metrics(prefix = "prover_fri_prover_fri_gateway")]
 pub(crate) struct ProverFriGatewayMetrics {
     #[metrics(labels = ["service_name"])]
     pub http_error: LabeledFamily<&'static str, Counter>,
+    /// Number of consecutive failed request cycles.
+    #[metrics(labels = ["service_name"])]
+    pub consecutive_failures: LabeledFamily<&'static str, Gauge<u64>>,
+    /// Whether the circuit breaker is open (1) or closed (0).
+    #[metrics(labels = ["service_name"])]
+    pub circuit_open: LabeledFamily<&'static str, Gauge<u64>>,
+    /// Number of times the circuit breaker was opened.
+    #[metrics(labels = ["service_name"])]
+    pub circuit_opened: LabeledFamily<&'static str, Counter>,
+    /// Number of service shutdowns, split by whether an in-flight request was interrupted.
+    #[metrics(labels = ["service_name", "kind"])]
+    pub shutdowns: LabeledFamily<(&'static str, ShutdownKind), Counter, 2>,
 }
 
 #[vise::register]
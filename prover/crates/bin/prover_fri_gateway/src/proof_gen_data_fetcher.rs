@@ -1,13 +1,17 @@
 use std::sync::Arc;
 
 use async_trait::async_trait;
+use tokio::sync::watch;
 use zksync_object_store::ObjectStore;
 use zksync_prover_dal::{ConnectionPool, Prover, ProverDal};
 use zksync_prover_interface::api::{
     ProofGenerationData, ProofGenerationDataRequest, ProofGenerationDataResponse,
 };
 
-use crate::{client::ProverApiClient, traits::PeriodicApi};
+use crate::{
+    client::{cancel_on_stop, ApiError, ProverApiClient, ProverApiClientBuilder},
+    traits::PeriodicApi,
+};
 
 /// Poller structure that will periodically check the prover API for new proof generation data.
 /// Fetched data is stored to the database/object store for further processing.
@@ -19,13 +23,12 @@ const PROOF_GENERATION_DATA_PATH: &str = "/proof_generation_data";
 
 impl ProofGenDataFetcher {
     pub(crate) fn new(
+        client_builder: &ProverApiClientBuilder,
         blob_store: Arc<dyn ObjectStore>,
-        base_url: String,
         pool: ConnectionPool<Prover>,
-    ) -> Self {
-        let api_url = format!("{base_url}{PROOF_GENERATION_DATA_PATH}");
-        let inner = ProverApiClient::new(blob_store, pool, api_url);
-        Self(inner)
+    ) -> anyhow::Result<Self> {
+        let inner = client_builder.build(blob_store, pool, PROOF_GENERATION_DATA_PATH)?;
+        Ok(Self(inner))
     }
 }
 
@@ -71,8 +74,10 @@ impl PeriodicApi for ProofGenDataFetcher {
         &self,
         _: (),
         request: ProofGenerationDataRequest,
-    ) -> reqwest::Result<Self::Response> {
-        self.0.send_http_request(request, &self.0.api_url).await
+        stop_receiver: &mut watch::Receiver<bool>,
+    ) -> Result<Self::Response, ApiError> {
+        let request = self.0.send_http_request(request, &self.0.api_url);
+        cancel_on_stop(stop_receiver, request).await
     }
 
     async fn handle_response(&self, _: (), response: Self::Response) {
@@ -1,12 +1,16 @@
 use std::sync::Arc;
 
 use async_trait::async_trait;
+use tokio::sync::watch;
 use zksync_object_store::ObjectStore;
 use zksync_prover_dal::{ConnectionPool, Prover, ProverDal};
 use zksync_prover_interface::api::{SubmitProofRequest, SubmitProofResponse};
 use zksync_types::{prover_dal::ProofCompressionJobStatus, L1BatchNumber};
 
-use crate::{client::ProverApiClient, traits::PeriodicApi};
+use crate::{
+    client::{cancel_on_stop, ApiError, ProverApiClient, ProverApiClientBuilder},
+    traits::PeriodicApi,
+};
 
 /// The path to the API endpoint that submits the proof.
 const SUBMIT_PROOF_PATH: &str = "/submit_proof";
@@ -18,13 +22,12 @@ pub struct ProofSubmitter(ProverApiClient);
 
 impl ProofSubmitter {
     pub(crate) fn new(
+        client_builder: &ProverApiClientBuilder,
         blob_store: Arc<dyn ObjectStore>,
-        base_url: String,
         pool: ConnectionPool<Prover>,
-    ) -> Self {
-        let api_url = format!("{base_url}{SUBMIT_PROOF_PATH}");
-        let inner = ProverApiClient::new(blob_store, pool, api_url);
-        Self(inner)
+    ) -> anyhow::Result<Self> {
+        let inner = client_builder.build(blob_store, pool, SUBMIT_PROOF_PATH)?;
+        Ok(Self(inner))
     }
 }
 
@@ -88,9 +91,11 @@ impl PeriodicApi for ProofSubmitter {
         &self,
         job_id: Self::JobId,
         request: SubmitProofRequest,
-    ) -> reqwest::Result<Self::Response> {
+        stop_receiver: &mut watch::Receiver<bool>,
+    ) -> Result<Self::Response, ApiError> {
         let endpoint = format!("{}/{job_id}", self.0.api_url);
-        self.0.send_http_request(request, &endpoint).await
+        let request = self.0.send_http_request(request, &endpoint);
+        cancel_on_stop(stop_receiver, request).await
     }
 
     async fn handle_response(&self, job_id: L1BatchNumber, response: Self::Response) {
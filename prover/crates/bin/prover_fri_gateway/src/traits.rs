@@ -2,7 +2,104 @@ use std::time::Duration;
 
 use tokio::sync::watch;
 
-use crate::metrics::METRICS;
+use crate::{
+    client::ApiError,
+    metrics::{ShutdownKind, METRICS},
+};
+
+/// Outcome of a single [`PeriodicApi::run_once()`] cycle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CycleOutcome<JobId> {
+    /// There was no request to send.
+    NoRequest,
+    /// A request for the specified job was sent, and its response was handled.
+    Handled(JobId),
+}
+
+/// Configuration of the circuit breaker used by [`PeriodicApi::run()`].
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct CircuitBreakerConfig {
+    /// Number of consecutive failed cycles after which the circuit is opened.
+    pub failure_threshold: usize,
+    /// Polling interval used while the circuit is open. If it's shorter than the regular polling interval,
+    /// the regular interval is used instead.
+    pub open_interval: Duration,
+}
+
+/// Circuit breaker tracking consecutive failures of [`PeriodicApi`] cycles. While the circuit is open, the API is polled
+/// with a longer interval; the circuit is closed after the first successful request. Cycles without a request
+/// don't affect the breaker state. The state is exposed via metrics and logs.
+#[derive(Debug)]
+struct CircuitBreaker {
+    config: CircuitBreakerConfig,
+    /// Polling interval used while the circuit is closed.
+    poll_duration: Duration,
+    service_name: &'static str,
+    consecutive_failures: usize,
+}
+
+impl CircuitBreaker {
+    fn new(
+        config: CircuitBreakerConfig,
+        poll_duration: Duration,
+        service_name: &'static str,
+    ) -> Self {
+        let this = Self {
+            config,
+            poll_duration,
+            service_name,
+            consecutive_failures: 0,
+        };
+        this.report_state();
+        this
+    }
+
+    fn is_open(&self) -> bool {
+        self.consecutive_failures >= self.config.failure_threshold.max(1)
+    }
+
+    fn poll_interval(&self) -> Duration {
+        if self.is_open() {
+            self.config.open_interval.max(self.poll_duration)
+        } else {
+            self.poll_duration
+        }
+    }
+
+    fn record_success(&mut self) {
+        if self.is_open() {
+            tracing::info!(
+                "Circuit closed for {} after {} consecutive failures",
+                self.service_name,
+                self.consecutive_failures
+            );
+        }
+        self.consecutive_failures = 0;
+        self.report_state();
+    }
+
+    fn record_failure(&mut self) {
+        let was_open = self.is_open();
+        self.consecutive_failures += 1;
+        if self.is_open() {
+            if !was_open {
+                METRICS.circuit_opened[&self.service_name].inc();
+            }
+            tracing::error!(
+                "Circuit open for {}: {} consecutive failures; polling every {:?} until a request succeeds",
+                self.service_name,
+                self.consecutive_failures,
+                self.poll_interval()
+            );
+        }
+        self.report_state();
+    }
+
+    fn report_state(&self) {
+        METRICS.consecutive_failures[&self.service_name].set(self.consecutive_failures as u64);
+        METRICS.circuit_open[&self.service_name].set(self.is_open().into());
+    }
+}
 
 /// Trait for fetching data from an API periodically.
 #[async_trait::async_trait]
@@ -16,49 +113,150 @@ pub(crate) trait PeriodicApi: Sync + Send + 'static + Sized {
     /// Returns the next request to be sent to the API and the endpoint to send it to.
     async fn get_next_request(&self) -> Option<(Self::JobId, Self::Request)>;
 
-    /// Submits a request to the API.
+    /// Submits a request to the API. Implementations should abort the request with [`ApiError::Cancelled`]
+    /// once the stop signal is received, so that in-flight requests don't delay shutdown.
     async fn send_request(
         &self,
         job_id: Self::JobId,
         request: Self::Request,
-    ) -> reqwest::Result<Self::Response>;
+        stop_receiver: &mut watch::Receiver<bool>,
+    ) -> Result<Self::Response, ApiError>;
 
     /// Handles the response from the API.
     async fn handle_response(&self, job_id: Self::JobId, response: Self::Response);
 
-    /// Runs `get_next_request` -> `send_request` -> `handle_response` in a loop.
+    /// Runs a single `get_next_request` -> `send_request` -> `handle_response` cycle.
+    async fn run_once(
+        &self,
+        stop_receiver: &mut watch::Receiver<bool>,
+    ) -> Result<CycleOutcome<Self::JobId>, ApiError> {
+        let Some((job_id, request)) = self.get_next_request().await else {
+            return Ok(CycleOutcome::NoRequest);
+        };
+        let response = self.send_request(job_id, request, stop_receiver).await?;
+        self.handle_response(job_id, response).await;
+        Ok(CycleOutcome::Handled(job_id))
+    }
+
+    /// Runs `get_next_request` -> `send_request` -> `handle_response` in a loop. After `circuit_breaker.failure_threshold`
+    /// consecutive failures, the API is polled every `circuit_breaker.open_interval` (but not more often than every
+    /// `poll_duration`) until a request succeeds.
     async fn run(
         self,
         poll_duration: Duration,
+        circuit_breaker: CircuitBreakerConfig,
         mut stop_receiver: watch::Receiver<bool>,
     ) -> anyhow::Result<()> {
         tracing::info!(
-            "Starting periodic job: {} with frequency: {:?}",
+            "Starting periodic job: {} with frequency: {:?}, circuit breaker: {:?}",
             Self::SERVICE_NAME,
-            poll_duration
+            poll_duration,
+            circuit_breaker
         );
+        let mut circuit_breaker =
+            CircuitBreaker::new(circuit_breaker, poll_duration, Self::SERVICE_NAME);
+        // Set if the last request cycle was interrupted by the stop signal.
+        let mut interrupted_request = false;
 
         loop {
             if *stop_receiver.borrow() {
-                tracing::warn!("Stop signal received, shutting down {}", Self::SERVICE_NAME);
+                let kind = if interrupted_request {
+                    tracing::warn!(
+                        "Stop signal received, shutting down {}; in-flight request was abandoned",
+                        Self::SERVICE_NAME
+                    );
+                    ShutdownKind::Forced
+                } else {
+                    tracing::info!(
+                        "Stop signal received, shutting down {} between request cycles",
+                        Self::SERVICE_NAME
+                    );
+                    ShutdownKind::Clean
+                };
+                METRICS.shutdowns[&(Self::SERVICE_NAME, kind)].inc();
                 return Ok(());
             }
 
-            if let Some((job_id, request)) = self.get_next_request().await {
-                match self.send_request(job_id, request).await {
-                    Ok(response) => {
-                        self.handle_response(job_id, response).await;
-                    }
-                    Err(err) => {
-                        METRICS.http_error[&Self::SERVICE_NAME].inc();
-                        tracing::error!("HTTP request failed due to error: {}", err);
-                    }
+            match self.run_once(&mut stop_receiver).await {
+                Ok(CycleOutcome::NoRequest) => {}
+                Ok(CycleOutcome::Handled(_)) => circuit_breaker.record_success(),
+                Err(ApiError::Cancelled) => {
+                    tracing::info!("In-flight request for {} was cancelled", Self::SERVICE_NAME);
+                    interrupted_request = true;
+                    continue;
+                }
+                Err(err) => {
+                    METRICS.http_error[&Self::SERVICE_NAME].inc();
+                    tracing::error!("HTTP request failed due to error: {}", err);
+                    circuit_breaker.record_failure();
                 }
             }
             // Exit condition will be checked on the next iteration.
-            tokio::time::timeout(poll_duration, stop_receiver.changed())
+            let poll_interval = circuit_breaker.poll_interval();
+            tokio::time::timeout(poll_interval, stop_receiver.changed())
                 .await
                 .ok();
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POLL_DURATION: Duration = Duration::from_secs(1);
+
+    fn circuit_breaker(failure_threshold: usize, open_interval: Duration) -> CircuitBreaker {
+        let config = CircuitBreakerConfig {
+            failure_threshold,
+            open_interval,
+        };
+        CircuitBreaker::new(config, POLL_DURATION, "test")
+    }
+
+    #[test]
+    fn circuit_breaker_state_transitions() {
+        let open_interval = Duration::from_secs(30);
+        let mut breaker = circuit_breaker(3, open_interval);
+        assert!(!breaker.is_open());
+        assert_eq!(breaker.poll_interval(), POLL_DURATION);
+
+        breaker.record_failure();
+        breaker.record_failure();
+        assert!(!breaker.is_open());
+        assert_eq!(breaker.poll_interval(), POLL_DURATION);
+
+        breaker.record_failure();
+        assert!(breaker.is_open());
+        assert_eq!(breaker.poll_interval(), open_interval);
+        breaker.record_failure();
+        assert!(breaker.is_open());
+        assert_eq!(breaker.consecutive_failures, 4);
+
+        breaker.record_success();
+        assert!(!breaker.is_open());
+        assert_eq!(breaker.consecutive_failures, 0);
+        assert_eq!(breaker.poll_interval(), POLL_DURATION);
+
+        // Failures are counted from scratch after a success.
+        breaker.record_failure();
+        breaker.record_failure();
+        assert!(!breaker.is_open());
+    }
+
+    #[test]
+    fn circuit_breaker_with_zero_threshold() {
+        let mut breaker = circuit_breaker(0, Duration::from_secs(30));
+        assert!(!breaker.is_open());
+        breaker.record_failure();
+        assert!(breaker.is_open());
+    }
+
+    #[test]
+    fn open_interval_is_not_shorter_than_poll_duration() {
+        let mut breaker = circuit_breaker(1, Duration::from_millis(100));
+        breaker.record_failure();
+        assert!(breaker.is_open());
+        assert_eq!(breaker.poll_interval(), POLL_DURATION);
+    }
+}